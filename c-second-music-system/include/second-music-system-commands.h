@@ -45,6 +45,48 @@ void SMS_Command(replace_soundtrack)(
     struct SMS_Soundtrack* new_soundtrack
 );
 
+// Replace the active soundtrack with the given one, crossfading every
+// playing flow into its counterpart in the new soundtrack. This is meant for
+// hot-reloading a soundtrack while it's playing, e.g. while iterating on it.
+//
+// Each flow that is currently playing will keep playing, from the old
+// soundtrack, while it fades out over `fade_length` seconds. If the new
+// soundtrack has a flow with the same name, it will be started and faded up
+// to the volume the old one was playing at, over the same period. Flows that
+// only exist in the old soundtrack are simply faded out.
+//
+// Note: Passing an `SMS_Soundtrack*` to this function transfers ownership of
+// that object to SMS. You can no longer use that same object again. If you
+// want to retain a copy for yourself, `clone` your soundtrack and submit the
+// clone instead. Do *not* call `SMS_Soundtrack_free` on this pointer!
+void SMS_Command(crossfade_soundtrack)(
+    struct SMS_Target*,
+    struct SMS_Soundtrack* new_soundtrack,
+    float fade_length,
+    int fade_type
+);
+
+// Replace the active soundtrack with the given one, and switch every playing
+// node over to its edited counterpart in the new soundtrack, without
+// restarting anything. This is meant for live-editing a soundtrack while
+// listening to it.
+//
+// Each node that is currently playing, and whose flow still has a node by the
+// same name in the new soundtrack, will carry on from the same instruction in
+// the new version of that node. If the new version is too short for that, it
+// will start again from its beginning instead (and a warning will be issued).
+// Nodes that don't exist in the new soundtrack keep playing their old
+// version, as with `replace_soundtrack`.
+//
+// Note: Passing an `SMS_Soundtrack*` to this function transfers ownership of
+// that object to SMS. You can no longer use that same object again. If you
+// want to retain a copy for yourself, `clone` your soundtrack and submit the
+// clone instead. Do *not* call `SMS_Soundtrack_free` on this pointer!
+void SMS_Command(reconcile_soundtrack)(
+    struct SMS_Target*,
+    struct SMS_Soundtrack* new_soundtrack
+);
+
 // Requests that the given flow be precached for playback. The engine
 // will attempt to load/preroll all requested sounds and streams in the
 // background. Use `is_flow_ready` to determine when the loading is complete.
@@ -92,6 +134,44 @@ void SMS_Command(unprecache_all)(
     struct SMS_Target*
 );
 
+// Requests that the given sound be loaded into memory in its entirety and
+// kept there, even if the soundtrack says it should be streamed. This is
+// useful for a streamed sound that turns out to be played very often, such as
+// a stinger. The sound will stay in memory until `uncache_sound` is called.
+//
+// A buffered sound takes up a lot more memory than a streamed one; about 4
+// bytes per sample per channel, for the whole length of the sound. Don't do
+// this to long sounds unless you have the memory to spare.
+//
+// Instances of the sound that are already streaming will keep streaming. Only
+// instances that are loaded after all of those have finished will use the
+// cached copy.
+//
+// This is *not* recursive. If you call `cache_sound` twice, then call
+// `uncache_sound` once, the sound will no longer be forcibly cached.
+void SMS_Command(cache_sound)(
+    struct SMS_Target*,
+    const char* sound_name,
+    size_t sound_name_len
+);
+void SMS_Command(cache_sound_cstr)(
+    struct SMS_Target*,
+    const char* sound_name
+);
+
+// Undoes a previous request that the given sound be forcibly cached. The
+// sound will be let go once nothing is using it, and will go back to being
+// streamed the next time it's loaded (if the soundtrack says it should be).
+void SMS_Command(uncache_sound)(
+    struct SMS_Target*,
+    const char* sound_name,
+    size_t sound_name_len
+);
+void SMS_Command(uncache_sound_cstr)(
+    struct SMS_Target*,
+    const char* sound_name
+);
+
 // Sets a given FlowControl to the given value.
 void SMS_Command(set_flow_control_to_number)(
     struct SMS_Target*,
@@ -145,6 +225,40 @@ void SMS_Command(clear_all_flow_controls)(
     struct SMS_Target*
 );
 
+// Sets all the FlowControls listed in the given preset from the current
+// soundtrack, all at once. The values are computed before any of the
+// FlowControls are changed, so a preset's expressions see the values the
+// FlowControls had *before* the preset was applied. FlowControls not
+// mentioned in the preset are left alone.
+void SMS_Command(apply_preset)(
+    struct SMS_Target*,
+    const char* preset_name,
+    size_t preset_name_len
+);
+void SMS_Command(apply_preset_cstr)(
+    struct SMS_Target*,
+    const char* preset_name
+);
+
+// Restarts the sequence of numbers returned by `random()` in the
+// soundtrack's expressions, based on the given seed. The same seed will
+// always result in the same sequence. Every engine starts out as if it was
+// seeded with 0.
+void SMS_Command(seed_random)(
+    struct SMS_Target*,
+    uint64_t seed
+);
+
+// Stretches all musical timing (waits, and the timing of sequences) by the
+// given factor, without changing the pitch or speed of the sounds themselves.
+// At 0.5, the music plays at half speed, rhythmically. Every engine starts out
+// at 1.0. Factors below 1/64 are treated as 1/64. Sounds that are already
+// queued keep their original start times and durations.
+void SMS_Command(set_time_scale)(
+    struct SMS_Target*,
+    float factor
+);
+
 // Fades a given MixControl to the given volume (0.0 to 1.0), using the
 // given fading curve, over the given time period (in seconds).
 //
@@ -190,6 +304,27 @@ void SMS_Command(fade_prefixed_mix_controls_to_cstr)(
     int fade_type
 );
 
+// Fades every mix control in the given channel group (declared in the
+// soundtrack with `group NAME includes CHANNEL...`) to the given volume (0.0
+// to 1.0), using the given fading curve, over the given time period (in
+// seconds). The group's members are looked up when the command is carried
+// out. Members that don't exist yet are created, with a warning.
+void SMS_Command(fade_group_to)(
+    struct SMS_Target*,
+    const char* group_name,
+    size_t group_name_len,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_group_to_cstr)(
+    struct SMS_Target*,
+    const char* group_name,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+
 // Fades all *currently existing* mix controls, *including* `main`, to
 // the given volume (0.0 to 1.0), using the given fading curve, over the
 // given time period (in seconds).
@@ -204,9 +339,9 @@ void SMS_Command(fade_all_mix_controls_to)(
     int fade_type
 );
 
-// Fades all *currently existing* mix controls, *except* `main`, to the
-// given volume (0.0 to 1.0), using the given fading curve, over the given
-// time period (in seconds).
+// Fades all *currently existing* mix controls, *except* the default channel
+// (usually `main`), to the given volume (0.0 to 1.0), using the given fading
+// curve, over the given time period (in seconds).
 //
 // Use `SMS_FADE_TYPE_EXPONENTIAL` unless you are doing intermixing of
 // correlated signals. Don't give a volume above 1.0 unless you are sure
@@ -278,9 +413,9 @@ void SMS_Command(fade_all_mix_controls_out)(
     int fade_type
 );
 
-// Fades all *currently existing* mix controls, *except* `main`,
-// to zero volume, using the given fading curve, over the given time
-// period (in seconds). When the fade is complete, the MixControl will be
+// Fades all *currently existing* mix controls, *except* the default channel
+// (usually `main`), to zero volume, using the given fading curve, over the
+// given time period (in seconds). When the fade is complete, the MixControl will be
 // removed from existence rather than simply zeroed; future commands to
 // "prefixed" and "all" will not resuscitate it (unless it is the target
 // of a future, specific command).
@@ -337,8 +472,8 @@ void SMS_Command(kill_all_mix_controls)(
     struct SMS_Target*
 );
 
-// Kills all MixControls, *except* `main`, as if you yanked an audio
-// cable.
+// Kills all MixControls, *except* the default channel (usually `main`), as if
+// you yanked an audio cable.
 //
 // This is similar to fading that MixControl out over zero seconds, except
 // that the MixControl in question is immediately removed (and therefore
@@ -348,6 +483,296 @@ void SMS_Command(kill_all_mix_controls_except_main)(
     struct SMS_Target*
 );
 
+// Limits the number of sounds that can play on a given MixControl at once.
+// When a sound is about to start on a MixControl that already has
+// `max_voices` sounds playing, `stealing` decides whether the oldest one is
+// cut off to make room (`SMS_VOICE_STEALING_STEAL_OLDEST`), or the new one is
+// never started (`SMS_VOICE_STEALING_DROP_NEWEST`).
+//
+// In debug builds, every stolen or dropped voice is reported as a warning.
+//
+// `max_voices` must not be zero.
+void SMS_Command(set_mix_control_voice_limit)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    size_t max_voices,
+    int stealing
+);
+void SMS_Command(set_mix_control_voice_limit_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t max_voices,
+    int stealing
+);
+
+// Removes the voice limit from a given MixControl, allowing any number of
+// sounds to play on it at once. (This is the default.)
+void SMS_Command(clear_mix_control_voice_limit)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len
+);
+void SMS_Command(clear_mix_control_voice_limit_cstr)(
+    struct SMS_Target*,
+    const char* control_name
+);
+
+// Solos a given MixControl: every MixControl that isn't soloed is silenced,
+// until `clear_solo`. Soloing more than one MixControl solos all of them
+// together. Sounds `send`ing to a soloed MixControl are still heard through
+// it. Soloing doesn't touch any MixControl's volume or fades, and takes effect
+// immediately, without fading.
+void SMS_Command(solo_mix_control)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len
+);
+void SMS_Command(solo_mix_control_cstr)(
+    struct SMS_Target*,
+    const char* control_name
+);
+
+// Un-solos every MixControl, so that they can all be heard again.
+void SMS_Command(clear_solo)(
+    struct SMS_Target*
+);
+
+// Mutes (if `muted` is nonzero) or unmutes a given MixControl. A muted
+// MixControl is silent, even if it's soloed, but its volume and fades are left
+// alone, so unmuting it brings it right back where it was. Takes effect
+// immediately, without fading.
+void SMS_Command(set_mix_control_muted)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    int muted
+);
+void SMS_Command(set_mix_control_muted_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    int muted
+);
+
+// Puts a built-in effect on a given MixControl, replacing any effect that was
+// already there. Only one effect per MixControl is supported. The effect
+// processes everything playing on that MixControl (including sends), after
+// the MixControl's volume is applied, so fading a MixControl out lets its
+// echoes or reverb tail ring out naturally.
+//
+// The delay is an echo, `time` seconds after the original sound. The reverb
+// is a simple room; `time` is roughly its size, in seconds (0.05 is a small
+// room, 0.1 a large hall). `feedback` is how much of the effect's output is
+// fed back into it (values of 1.0 and up are treated as 0.99). `mix` is how
+// much of the output is the effect, from 0.0 (none) to 1.0 (all).
+//
+// Effects cost CPU time whenever they're attached, even if nothing is playing
+// on their MixControl. A delay costs a few operations per sample; a reverb
+// costs about six times that. Like voice limits, effects apply to the
+// MixControl's *name*, and survive it being killed.
+void SMS_Command(set_mix_control_delay)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    float time,
+    float feedback,
+    float mix
+);
+void SMS_Command(set_mix_control_delay_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    float time,
+    float feedback,
+    float mix
+);
+void SMS_Command(set_mix_control_reverb)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    float time,
+    float feedback,
+    float mix
+);
+void SMS_Command(set_mix_control_reverb_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    float time,
+    float feedback,
+    float mix
+);
+
+// Removes the effect from a given MixControl. Any echoes or reverb tail still
+// ringing will be cut off.
+void SMS_Command(clear_mix_control_effect)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len
+);
+void SMS_Command(clear_mix_control_effect_cstr)(
+    struct SMS_Target*,
+    const char* control_name
+);
+
+// Fades the cutoff frequency of a low-pass filter on a given MixControl to
+// `cutoff_hz`, using the given fading curve, over the given time period (in
+// seconds). Good for making music sound muffled, as if it's coming from
+// underwater or behind a wall.
+//
+// Every MixControl starts with its cutoff at the Nyquist frequency (half the
+// Engine's sample rate), where the filter is bypassed entirely. Fading the
+// cutoff back up to (or above) Nyquist bypasses it again. If the MixControl
+// also has an effect, the filter comes after it. Like effects, the cutoff
+// applies to the MixControl's *name*, and survives it being killed.
+void SMS_Command(fade_mix_control_cutoff_to)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    float cutoff_hz,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_mix_control_cutoff_to_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    float cutoff_hz,
+    float fade_length,
+    int fade_type
+);
+
+// Fades the stereo width of a given MixControl to `width`, using the given
+// fading curve, over the given time period (in seconds). The side (left minus
+// right) part of the MixControl's output is scaled by the width: 0.0 is mono,
+// 1.0 (where every MixControl starts) leaves the sound alone, and more than
+// 1.0 is wider.
+//
+// On stereo and headphone output, this affects the whole MixControl. On
+// surround output, only the front left and right speakers are affected. On
+// mono output, this does nothing. Width comes after any effect or filter, and
+// like them, applies to the MixControl's *name*, and survives it being
+// killed.
+void SMS_Command(fade_mix_control_width_to)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    float width,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_mix_control_width_to_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    float width,
+    float fade_length,
+    int fade_type
+);
+
+// Moves the listener, for sounds that have a `position`. `position`,
+// `forward`, and `up` each point to three floats (X, Y, Z). `forward` is the
+// way the listener is facing, and `up` is up from their point of view;
+// neither needs to be normalized, but they must not be parallel. The default
+// listener is at the origin, facing -Z, with +Y up.
+//
+// Positional sounds get quieter with distance from the listener, and are
+// panned between the speakers according to their horizontal direction from
+// the listener. Elevation is ignored. Sounds within `reference_distance` of
+// the listener are at full volume; each doubling of distance beyond that
+// halves the volume.
+//
+// A positional sound's speaker gains are worked out once per mix, so moving
+// the listener a long way at once can cause audible clicks.
+void SMS_Command(set_listener)(
+    struct SMS_Target*,
+    const float* position,
+    const float* forward,
+    const float* up,
+    float reference_distance
+);
+
+// Bends the pitch of every sound playing (or that will play) on a given
+// MixControl or Flow, by fading its playback rate to the given rate over the
+// given time period (in seconds). 1.0 is normal, 2.0 is twice as fast (an
+// octave up), 0.5 is half as fast (an octave down), and 0.0 stops the sound in
+// its tracks. A sound on a bent MixControl *and* a bent Flow plays at the
+// product of the two rates.
+//
+// Bent sounds are resampled on the sound thread, which costs a little CPU per
+// sound. Sounds that have never been bent cost (almost) nothing extra.
+void SMS_Command(fade_mix_control_pitch_to)(
+    struct SMS_Target*,
+    const char* control_name,
+    size_t control_name_len,
+    float rate,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_mix_control_pitch_to_cstr)(
+    struct SMS_Target*,
+    const char* control_name,
+    float rate,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_flow_pitch_to)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    float rate,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_flow_pitch_to_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    float rate,
+    float fade_length,
+    int fade_type
+);
+
+// Plays a sound once, right away, on the given mix control, without needing a
+// flow to play it. Useful for sound effects, e.g. UI clicks. The sound is
+// played at the given volume (on top of its mix control's) and at the given
+// pitch (1.0 is normal, 2.0 is an octave up, 0.5 is an octave down).
+//
+// The sound should already be cached (see `cache_sound`) if it needs to start
+// without delay. It plays until it ends, is stopped with `stop_one_shots`, or
+// its mix control is killed; fading or killing flows doesn't affect it.
+//
+// Returns a response that will arrive once the sound is done: "yes" if it
+// played (whether it ran to its end or was cut off early), "no" if it never
+// got to play at all (e.g. the sound is missing or couldn't be loaded, or it
+// was stopped before it started). You must free it with
+// `SMS_BooleanResponse_free`, even if you don't care about the answer. (It's
+// fine to free it right away.)
+struct SMS_BooleanResponse* SMS_Command(play_one_shot)(
+    struct SMS_Target*,
+    const char* sound_name,
+    size_t sound_name_len,
+    const char* channel,
+    size_t channel_len,
+    float volume,
+    float pitch
+);
+struct SMS_BooleanResponse* SMS_Command(play_one_shot_cstr)(
+    struct SMS_Target*,
+    const char* sound_name,
+    const char* channel,
+    float volume,
+    float pitch
+);
+
+// Stops every sound started with `play_one_shot` on the given mix control,
+// immediately. Pass a NULL `channel` to stop every such sound on every mix
+// control.
+void SMS_Command(stop_one_shots)(
+    struct SMS_Target*,
+    const char* channel,
+    size_t channel_len
+);
+void SMS_Command(stop_one_shots_cstr)(
+    struct SMS_Target*,
+    const char* channel
+);
+
 // Starts a given flow if it's not already playing. If the flow
 // is being newly started, it will be faded up from zero volume to the
 // target volume, with the given fade curve. If the flow was
@@ -372,6 +797,32 @@ void SMS_Command(start_flow_cstr)(
     int fade_type
 );
 
+// As `start_flow`, but if the flow is being newly started, it starts at the
+// given node (NULL for the starting node) instead of the starting node, as if
+// that node had already been playing for `offset` seconds. Sounds that would
+// already have started by then are skipped, rather than starting partway
+// through. If the flow was already playing, acts just like `fade_flow_to`.
+void SMS_Command(start_flow_at)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    const char* node_name,
+    size_t node_name_len,
+    float offset,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(start_flow_at_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    const char* node_name,
+    float offset,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+
 // Fades a given flow to the given volume (0.0 to 1.0), using the
 // given fading curve, over the given time period (in seconds). Does
 // nothing if the flow is not currently playing.
@@ -399,6 +850,158 @@ void SMS_Command(fade_flow_to_cstr)(
     int fade_type
 );
 
+// Fades a single node of a given flow to the given volume (0.0 to 1.0), using
+// the given fading curve, over the given time period (in seconds). Pass a
+// NULL `node_name` for the flow's starting node. This is on top of the flow's
+// own volume, and applies to every sound the node plays, including those
+// already playing. Nodes start out at full volume.
+//
+// This is meant for layered arrangements, where each node of a flow is one
+// instrument (or stem) that can be mixed in and out while the flow plays.
+//
+// A warning is issued if the node isn't currently playing, but the volume
+// will still apply if the node starts later. Node volumes are forgotten when
+// their flow stops.
+//
+// Use `SMS_FADE_TYPE_EXPONENTIAL` unless you are doing intermixing of
+// correlated signals. Don't give a volume above 1.0 unless you are sure
+// it won't cause clipping. Don't give negative volumes.
+void SMS_Command(fade_node_to)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    const char* node_name,
+    size_t node_name_len,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_node_to_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    const char* node_name,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+
+// Fades a single element of a sequence, as played by a given flow, to the
+// given volume (0.0 to 1.0), using the given fading curve, over the given time
+// period (in seconds). `element` counts from 0, in the order the sequence's
+// elements appear in the soundtrack. This is on top of the flow's (and
+// node's) own volume, and applies to every sound that element plays,
+// including those already playing and those it plays later. Elements start
+// out at full volume.
+//
+// This is meant for sequences that play several things at once, e.g. an
+// ambient sequence where one element is birdsong, so that the game can
+// silence just the birds. If the same sequence is played by more than one
+// node of the flow, the element is faded in all of them.
+//
+// Element volumes are forgotten when their flow stops.
+//
+// Use `SMS_FADE_TYPE_EXPONENTIAL` unless you are doing intermixing of
+// correlated signals. Don't give a volume above 1.0 unless you are sure
+// it won't cause clipping. Don't give negative volumes.
+void SMS_Command(fade_sequence_element_to)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    const char* sequence_name,
+    size_t sequence_name_len,
+    size_t element,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(fade_sequence_element_to_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    const char* sequence_name,
+    size_t element,
+    float target_volume,
+    float fade_length,
+    int fade_type
+);
+
+// Immediately stops every sound that a single element of a sequence, as
+// played by a given flow, is playing or has queued up to play. (See
+// `fade_sequence_element_to` for how elements are counted.) This doesn't stop
+// the element from playing again, the next time the sequence is played; to
+// keep it quiet, fade it to zero instead.
+void SMS_Command(stop_sequence_element)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    const char* sequence_name,
+    size_t sequence_name_len,
+    size_t element
+);
+void SMS_Command(stop_sequence_element_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    const char* sequence_name,
+    size_t element
+);
+
+// Sets the layer intensity of a given flow, fading each of its layer nodes to
+// the volume that intensity calls for, using the given fading curve, over the
+// given time period (in seconds).
+//
+// A layer node is one declared with an intensity curve in the soundtrack,
+// e.g. `node bass active above 0.3` (full volume at intensity 0.3 or more,
+// silent below), `node pad active below 0.5`, or `node lead active from 0.4
+// to 0.8` (silent at 0.4, full volume at 0.8, in between in between). Other
+// nodes aren't affected. Intensity is usually from 0.0 to 1.0, but any number
+// will do.
+//
+// The intensity is remembered, and applied (instantly) whenever the flow is
+// started. Until a flow's intensity is first set, its layer nodes play at
+// full volume. This works by way of the same node volumes as `fade_node_to`,
+// so using both on the same node isn't a good idea.
+void SMS_Command(set_layer_intensity)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    float intensity,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(set_layer_intensity_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    float intensity,
+    float fade_length,
+    int fade_type
+);
+
+// Chains one flow to another: when the given flow finishes on its own (all of
+// its nodes have run out of things to do, and it doesn't `autoloop`), the
+// successor flow is started, and the finished flow is faded out, both over the
+// given time period (in seconds) with the given fading curve. Pass a NULL
+// `successor` to remove the chain.
+//
+// Flows that were faded out, killed, or replaced by a soundtrack crossfade
+// don't start their successors. A chain that would lead back to the flow it
+// starts from is refused, with a warning; use `autoloop` in the soundtrack for
+// a flow that should repeat.
+void SMS_Command(set_flow_successor)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    const char* successor,
+    size_t successor_len,
+    float fade_length,
+    int fade_type
+);
+void SMS_Command(set_flow_successor_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    const char* successor,
+    float fade_length,
+    int fade_type
+);
+
 // Fades all *currently playing* flows whose names strictly start with
 // the given prefix to the given volume (0.0 to 1.0), using the given
 // fading curve, over the given time period (in seconds). Does nothing to
@@ -467,6 +1070,35 @@ void SMS_Command(fade_flow_out_cstr)(
     int fade_type
 );
 
+// Fades a given flow to the given volume over `fade_length` seconds,
+// holds it there for `hold` more seconds, and then fades it out over
+// `fade_out` seconds, stopping it, as `fade_flow_out` would. The same
+// fading curve is used for both fades. Does nothing if the flow is not
+// currently playing.
+//
+// Any other command that fades, starts, or kills the flow before its
+// fade out begins (including another `fade_flow_to_then_out`) replaces
+// this one, and the scheduled fade out is forgotten.
+void SMS_Command(fade_flow_to_then_out)(
+    struct SMS_Target*,
+    const char* flow_name,
+    size_t flow_name_len,
+    float target_volume,
+    float fade_length,
+    float hold,
+    float fade_out,
+    int fade_type
+);
+void SMS_Command(fade_flow_to_then_out_cstr)(
+    struct SMS_Target*,
+    const char* flow_name,
+    float target_volume,
+    float fade_length,
+    float hold,
+    float fade_out,
+    int fade_type
+);
+
 // Fades all *currently playing* flows whose names strictly start with
 // the given prefix to zero volume, using the given fading curve, over the
 // given time period (in seconds). Does nothing to flows that haven't
@@ -53,9 +53,119 @@ uint32_t SMS_get_version_number();
 // a fade that "hangs out" at the louder side. Arguably more
 // aesthetically pleasing than a logarithmic fade.
 #define SMS_FADE_TYPE_EXPONENTIAL 0
+// Fades between the given amplification factors on a "smoothstep" curve,
+// which starts and ends gently, with no abrupt change in speed at either end.
+// Good for things like volume sliders in a settings menu.
+#define SMS_FADE_TYPE_S_CURVE 3
+// Fades between the given amplification factors on a quarter of a sine (or
+// cosine) wave, such that a fade up and a fade down of the same length, played
+// over each other, keep the same total power throughout. This is the usual
+// crossfade for uncorrelated signals.
+#define SMS_FADE_TYPE_EQUAL_POWER 4
 
 #define SMS_FADE_TYPE_DEFAULT SMS_FADE_TYPE_EXPONENTIAL
 
+// Stop the sound that has been playing on that MixControl the longest, and
+// start the new sound in its place.
+#define SMS_VOICE_STEALING_STEAL_OLDEST 0
+// Leave the sounds that are already playing alone, and don't start the new
+// sound at all.
+#define SMS_VOICE_STEALING_DROP_NEWEST 1
+
+// Diagnostic levels: how serious a diagnostic is. (See
+// `SMS_SoundDelegate_new_with_diagnostics`.)
+// Something worth knowing about while debugging, but not a problem in
+// itself. (For example, a voice being stolen.) Only issued in debug builds.
+#define SMS_DIAG_LEVEL_DEBUG 0
+// Something doesn't make sense. Usually a mistake in the soundtrack, or in
+// the commands the game is issuing. SMS will ignore it and carry on.
+#define SMS_DIAG_LEVEL_WARNING 1
+// Something failed at runtime, e.g. a sound file couldn't be opened. SMS
+// will play silence in its place.
+#define SMS_DIAG_LEVEL_ERROR 2
+// SMS's internal bookkeeping has become inconsistent. THIS IS A BUG IN SMS,
+// and should be reported as such.
+#define SMS_DIAG_LEVEL_BUG 3
+
+// Diagnostic codes: what a diagnostic is about. More codes may be added in
+// the future, so be ready for ones you don't know about.
+// A flow was referred to that doesn't exist.
+#define SMS_DIAG_CODE_MISSING_FLOW 0
+// A node was referred to that doesn't exist in its flow.
+#define SMS_DIAG_CODE_MISSING_NODE 1
+// A sound was referred to that doesn't exist.
+#define SMS_DIAG_CODE_MISSING_SOUND 2
+// A sequence was referred to that doesn't exist.
+#define SMS_DIAG_CODE_MISSING_SEQUENCE 3
+// A preset was referred to that doesn't exist.
+#define SMS_DIAG_CODE_MISSING_PRESET 4
+// A channel group was referred to that doesn't exist.
+#define SMS_DIAG_CODE_MISSING_GROUP 5
+// A MixControl was referred to that doesn't exist yet.
+#define SMS_DIAG_CODE_MISSING_MIX_CONTROL 6
+// An attempt was made to start a node that was already playing.
+#define SMS_DIAG_CODE_NODE_ALREADY_PLAYING 7
+// A node was referred to that isn't playing.
+#define SMS_DIAG_CODE_NODE_NOT_PLAYING 8
+// A playing node was replaced by an edited version too short to carry on where
+// it left off, so it was restarted from its beginning.
+#define SMS_DIAG_CODE_NODE_REWOUND 9
+// A chain of flow successors would have looped back on itself.
+#define SMS_DIAG_CODE_SUCCESSOR_LOOP 10
+// A flow was precached more than once.
+#define SMS_DIAG_CODE_REDUNDANT_PRECACHE 11
+// A flow was unprecached that wasn't precached.
+#define SMS_DIAG_CODE_UNBALANCED_UNPRECACHE 12
+// A channel's voice limit was reached, and an older sound was stopped to make
+// room for a newer one.
+#define SMS_DIAG_CODE_VOICE_STOLEN 13
+// A channel's voice limit was reached, and a new sound was not played.
+#define SMS_DIAG_CODE_VOICE_DROPPED 14
+// The same sound file is used both streamed and buffered.
+#define SMS_DIAG_CODE_STREAMED_AND_BUFFERED 15
+// A sound's length was needed, but couldn't be determined.
+#define SMS_DIAG_CODE_UNKNOWN_LENGTH 16
+// Buffered sounds are using more memory than the budget allows.
+#define SMS_DIAG_CODE_OVER_BUDGET 17
+// The delegate couldn't open a sound file.
+#define SMS_DIAG_CODE_OPEN_FAILED 18
+// Loading a sound in the background failed.
+#define SMS_DIAG_CODE_LOAD_FAILED 19
+// A sound file couldn't be decoded all the way through (e.g. because it's
+// corrupt).
+#define SMS_DIAG_CODE_DECODE_FAILED 20
+// The delegate opened a sound file, but the stream it returned didn't make
+// sense: its sample rate was zero, or it didn't come out in whole sample
+// frames for its speaker layout.
+#define SMS_DIAG_CODE_BAD_STREAM_FORMAT 21
+// A resampler couldn't be set up.
+#define SMS_DIAG_CODE_RESAMPLER_FAILED 22
+// A sound was unloaded more times than it was loaded.
+#define SMS_DIAG_CODE_UNBALANCED_UNLOAD 23
+// Flows ran too many instructions without any time passing, and were held back
+// until the next block of audio. See `SMS_Engine_set_instruction_limit`.
+#define SMS_DIAG_CODE_RUNAWAY_FLOW 24
+// A flow waited longer than the load timeout for its sounds to load. See
+// `SMS_Engine_set_load_timeout`.
+#define SMS_DIAG_CODE_LOAD_TIMED_OUT 25
+
+// Warnings:
+//
+// Warnings from an Engine go to the `diagnostic_handler` of its SoundDelegate,
+// if it has one, or to its `warning_handler` if not.
+// Warnings that have nowhere else to go (those from a SoundDelegate whose
+// `warning_handler` is NULL, and those caused by invalid arguments to SMS
+// functions) go to the callback installed here, or to stderr if there is
+// none. Pass NULL to go back to stderr.
+//
+// The callback may be called from any thread, including more than one at
+// once. Usually this will be whichever thread is calling
+// `SMS_Engine_turn_handle` (i.e. your sound thread, so don't block!), but
+// problems found while loading sounds in the background will be reported from
+// the background loading threads. `message` is only valid until the callback
+// returns.
+void SMS_set_warning_callback(void(*callback)(void* callback_data, const char* message), void* callback_data);
+
 // Strings:
 //
 // Any function that takes strings comes in two variants. In the regular
@@ -99,6 +209,12 @@ struct SMS_Soundtrack* SMS_Soundtrack_parse_new_cstr(const char* src, char** err
 // If parsing fails, leaves the existing soundtrack alone.
 int SMS_Soundtrack_parse(struct SMS_Soundtrack*, const char* src, size_t src_len, char** error_out, size_t* error_len_out);
 int SMS_Soundtrack_parse_cstr(struct SMS_Soundtrack*, const char* src, char** error_out, size_t* error_len_out);
+// Changes the name of the channel that sounds play on when the soundtrack
+// doesn't say otherwise (`main` by default). Sequence elements get their
+// channel when they're parsed, so call this *before* parsing for it to apply
+// to them. See also `SMS_Engine_set_default_channel`.
+void SMS_Soundtrack_set_default_channel(struct SMS_Soundtrack*, const char* channel, size_t channel_len);
+void SMS_Soundtrack_set_default_channel_cstr(struct SMS_Soundtrack*, const char* channel);
 
 ///////////////////////////////////////////////////////////////////////////////
 // FormattedSoundStream
@@ -135,6 +251,11 @@ struct SMS_FormattedSoundStream* SMS_FormattedSoundStream_new(
     // Note: The given size_t value is the number of samples in the buffer,
     // *not* the number of sample *frames*, nor the number of *bytes*!
     //
+    // If decoding fails (e.g. the file is corrupt), return `SIZE_MAX`
+    // instead. SMS will report the failure through the delegate, and stop
+    // using the stream. (A buffered sound that fails is replaced with
+    // silence.)
+    //
     // Note 2: While all other handlers may be NULL, this one *must* be
     // non-NULL. (You almost certainly want `free_handler` to be non-NULL too.)
     size_t (*read_handler)(void*, void* buf, size_t num_samples_in_buf),
@@ -238,12 +359,28 @@ struct SMS_SoundDelegate* SMS_SoundDelegate_new(
     // mechanism, then return NULL.
     struct SMS_FormattedSoundStream*(*file_open_handler)(void*, const char* name),
     // Present and/or log a warning in some application-specific way.
-    // May be NULL, in which case warnings will be printed directly to stderr.
+    // May be NULL, in which case warnings will go to the callback installed
+    // with `SMS_set_warning_callback`, or to stderr if there is none. (See
+    // "Warnings" near the top of this file for threading details.)
     void(*warning_handler)(void*, const char* message),
     // Called when this SMS_SoundDelegate is no longer referenced anymore. May
     // be NULL.
     void(*free_handler)(void*)
 );
+// As `SMS_SoundDelegate_new`, but with a `diagnostic_handler` as well. Every
+// warning from an Engine goes to it, along with how serious it is (one of the
+// `SMS_DIAG_LEVEL_*` constants) and what it's about (one of the
+// `SMS_DIAG_CODE_*` constants), so that you can filter or sort them without
+// picking the message apart. If `diagnostic_handler` is NULL, they go to
+// `warning_handler` instead, as with `SMS_SoundDelegate_new`. The same
+// threading caveats apply. (See "Warnings" near the top of this file.)
+struct SMS_SoundDelegate* SMS_SoundDelegate_new_with_diagnostics(
+    void* callback_data,
+    struct SMS_FormattedSoundStream*(*file_open_handler)(void*, const char* name),
+    void(*warning_handler)(void*, const char* message),
+    void(*diagnostic_handler)(void*, int level, int code, const char* message),
+    void(*free_handler)(void*)
+);
 // Call this when you are done with *your copy* of the pointer to this
 // SoundDelegate, i.e. when you know you are not going to pass it to any more
 // new Engines.
@@ -300,23 +437,224 @@ struct SMS_Soundtrack* SMS_Engine_copy_live_soundtrack(
 
 // TODO: Engine::copy_all_flow_controls
 
+// Gets the current value of a FlowControl, right now. Returns 1 if the
+// FlowControl is set, 0 if it is not. (Commands that haven't been processed
+// yet, by a call to `SMS_Engine_turn_handle`, are not reflected.)
+//
+// The `_number` variant interprets the value as a number, the same way the
+// soundtrack language would (a string that isn't a valid number becomes NaN),
+// and writes it to `*number_out` if `number_out` is not NULL.
+//
+// The `_string` variant interprets the value as a string. If `string_out` is
+// not NULL, it is filled in with a newly malloc'd C string containing the
+// value (including a null terminator). You must free this when you're done
+// with it. If `string_out_len` is not NULL, it is filled in with the length of
+// the value (not including the null terminator). Nothing is allocated if the
+// FlowControl is not set.
+int SMS_Engine_get_flow_control_number(struct SMS_Engine*, const char* control_name, size_t control_name_len, float* number_out);
+int SMS_Engine_get_flow_control_number_cstr(struct SMS_Engine*, const char* control_name, float* number_out);
+int SMS_Engine_get_flow_control_string(struct SMS_Engine*, const char* control_name, size_t control_name_len, char** string_out, size_t* string_out_len);
+int SMS_Engine_get_flow_control_string_cstr(struct SMS_Engine*, const char* control_name, char** string_out, size_t* string_out_len);
+
 // Returns the `SpeakerLayout` this `Engine` was initialized for.
 int SMS_Engine_get_speaker_layout(struct SMS_Engine*);
 // Returns the sample rate this `Engine` was initialized for.
 float SMS_Engine_get_sample_rate(struct SMS_Engine*);
+// Returns the sample rate `SMS_Engine_turn_handle` is currently producing.
+float SMS_Engine_get_output_sample_rate(struct SMS_Engine*);
+// Changes the sample rate `SMS_Engine_turn_handle` produces, e.g. because
+// the output device changed. Everything else, including all timing, still
+// runs at the sample rate this `Engine` was initialized for; the mixed output
+// is resampled (cheaply, with linear interpolation) on its way out. Setting it
+// back to the original rate turns the resampling off again, which may drop up
+// to a few milliseconds of audio.
+void SMS_Engine_set_output_sample_rate(struct SMS_Engine*, float sample_rate);
+// Applies every command that has been issued so far, right now, without
+// mixing any audio or advancing time. (Normally, commands wait for the next
+// `SMS_Engine_turn_handle`.) Useful at shutdown, to make sure a final
+// `kill_all_flows` takes effect.
+void SMS_Engine_drain_commands(struct SMS_Engine*);
+// Sets the most flow node instructions that may run in a single call to
+// `SMS_Engine_turn_handle` (etc.), or 0 for no limit. The default is 10,000.
+// When the limit is reached, every playing node is held back until the next
+// call, and a warning is issued naming the flow that was running at the time.
+// This keeps a soundtrack that loops without ever waiting from hanging the
+// audio thread.
+void SMS_Engine_set_instruction_limit(struct SMS_Engine*, size_t limit);
+// Sets how long (in seconds) a starting flow may wait for its sounds to load
+// before something is done about it, or 0 (the default) to wait forever. When
+// a flow has waited longer than that, a warning is issued, and if
+// `start_anyway` is nonzero, the flow is started without waiting any longer.
+// Any sound it plays that still isn't loaded is skipped, and later plays of
+// that sound will be heard once it finishes loading.
+void SMS_Engine_set_load_timeout(struct SMS_Engine*, float timeout,
+                                 int start_anyway);
+// Sets the most audio (in seconds) that will be mixed at once before checking
+// for new commands again, or 0 (the default) for no limit. With a limit (e.g.
+// 0.005 for five milliseconds), commands that arrive while a very large
+// buffer is being mixed take effect close to when they were issued, instead
+// of up to the whole buffer late. This costs a little performance.
+void SMS_Engine_set_max_chunk_length(struct SMS_Engine*, float length);
+// Starts using a different SMS_SoundDelegate, e.g. to load sounds from a
+// downloaded bundle instead of from disk. Every load from now on opens its
+// files with the new delegate, and every warning goes to it. Sounds that are
+// already loaded aren't loaded again, and loads that were already underway
+// finish with the old delegate. As with `SMS_Engine_new`, you still need to
+// `SMS_SoundDelegate_free` your own copy of the pointer when you're done
+// with it.
+void SMS_Engine_replace_delegate(struct SMS_Engine*,
+                                 struct SMS_SoundDelegate* delegate);
+// Changes the name of the default channel from `main` (or whatever it was
+// changed to before). Best done right after creating the `Engine`, before
+// anything has played. The default channel's mix control is renamed, keeping
+// its volume, unless a mix control by the new name already exists. From then
+// on, the "all except main" commands spare the new default channel instead.
+//
+// This only decides which *mix control* is the default. Which channel sounds
+// play on when the soundtrack doesn't say is up to the soundtrack; see
+// `SMS_Soundtrack_set_default_channel`. You will usually want to give both
+// the same name.
+void SMS_Engine_set_default_channel(struct SMS_Engine*, const char* channel, size_t channel_len);
+void SMS_Engine_set_default_channel_cstr(struct SMS_Engine*, const char* channel);
+
+// Adds a tap: an extra output, mixed from the same music as the main output,
+// but with its own speaker layout, master volume, and mix control gains (e.g.
+// one per player in a split-screen game). Returns an ID to refer to it by.
+// Taps start out at full volume, with every mix control gain at 1.0.
+//
+// Taps get the same sounds, at the same volumes, as the main output, except
+// that mix control effects and filters only apply to the main output. Taps
+// are always at the sample rate the `Engine` was initialized for.
+//
+// Whichever output is pulled from first mixes for all of them, and the others
+// keep what it mixed until they're pulled from, so you should pull the same
+// amount of audio from each one. (An output that isn't pulled from at all
+// will only keep a second or so of audio.)
+uint32_t SMS_Engine_add_tap(struct SMS_Engine*, int speaker_layout);
+// Removes a tap. Does nothing if there is no such tap.
+void SMS_Engine_remove_tap(struct SMS_Engine*, uint32_t tap);
+// Changes a tap's master volume. Does nothing if there is no such tap.
+void SMS_Engine_set_tap_volume(struct SMS_Engine*, uint32_t tap, float volume);
+// Changes how loud a mix control is on a tap, on top of its own volume. Does
+// nothing if there is no such tap.
+void SMS_Engine_set_tap_channel_gain(struct SMS_Engine*, uint32_t tap, const char* channel, size_t channel_len, float gain);
+void SMS_Engine_set_tap_channel_gain_cstr(struct SMS_Engine*, uint32_t tap, const char* channel, float gain);
+// As `SMS_Engine_turn_handle`, but for a tap. `out_len` must be divisible by
+// the number of speaker channels *in the tap's speaker layout*. Any existing
+// data in `out` is overwritten. Panics if there is no such tap.
+void SMS_Engine_turn_handle_tap(struct SMS_Engine*, uint32_t tap, float* out, size_t out_len);
+
+// Gets some statistics about what the `Engine` is doing right now, for
+// profiling. Each one is written to the corresponding pointer, if it is not
+// NULL. (Commands that haven't been processed yet, by a call to
+// `SMS_Engine_turn_handle`, are not reflected.)
+//
+// - `active_voices`: How many sounds the mixer is currently playing.
+// - `cached_sound_bytes`: Roughly how many bytes of sample data are being
+//   kept in memory for buffered (non-streamed) sounds.
+// - `streaming_sounds`: How many different sound files are currently loaded
+//   for streaming.
+// - `active_flows`: How many flows are playing, including any old ones that
+//   are fading out after a crossfade.
+void SMS_Engine_get_stats(struct SMS_Engine*, size_t* active_voices, size_t* cached_sound_bytes, size_t* streaming_sounds, size_t* active_flows);
+
+// Finds out how far along the loading of a flow's sounds is. Returns 0 if the
+// flow isn't being precached or played, in which case nothing is written.
+// Otherwise, returns 1, and writes each value to the corresponding pointer,
+// if it is not NULL. (Commands that haven't been processed yet are not
+// reflected.)
+//
+// - `sounds_ready`: How many of the flow's sounds are ready to play.
+// - `total_sounds`: How many sounds the flow uses, in total.
+// - `seconds_remaining`: Roughly how many seconds of audio still have to be
+//   decoded before the flow is ready. This is only an *estimate*, and
+//   doesn't include sounds of unknown length.
+// - `sounds_of_unknown_length`: How many of the sounds that aren't ready yet
+//   are of unknown length, because their loading hasn't started yet or
+//   because their decoder can't tell.
+int SMS_Engine_get_flow_load_progress(struct SMS_Engine*, const char* flow_name, size_t flow_name_len, size_t* sounds_ready, size_t* total_sounds, float* seconds_remaining, size_t* sounds_of_unknown_length);
+int SMS_Engine_get_flow_load_progress_cstr(struct SMS_Engine*, const char* flow_name, size_t* sounds_ready, size_t* total_sounds, float* seconds_remaining, size_t* sounds_of_unknown_length);
+
+// Plays a single sound or sequence from the given soundtrack, which doesn't
+// have to be the live one, right away, without needing a flow to play it.
+// This is meant for soundtrack editors, e.g. for an "audition" button. The
+// soundtrack is not freed or taken over; you still own it.
+//
+// The audition plays at unity gain, ignoring every mix control, flow, and
+// pitch bend, so it doesn't interfere with (and isn't interfered with by)
+// whatever else is playing. (A sequence's own pans, positions, and fades are
+// kept.) Its sounds are loaded when this is called, and it starts as soon as
+// they're ready. Only one audition plays at a time; starting another one
+// stops this one. It plays until it ends, or until `SMS_Engine_stop_audition`
+// is called.
+void SMS_Engine_audition_sound(struct SMS_Engine*, struct SMS_Soundtrack* soundtrack, const char* sound_name, size_t sound_name_len);
+void SMS_Engine_audition_sound_cstr(struct SMS_Engine*, struct SMS_Soundtrack* soundtrack, const char* sound_name);
+void SMS_Engine_audition_sequence(struct SMS_Engine*, struct SMS_Soundtrack* soundtrack, const char* sequence_name, size_t sequence_name_len);
+void SMS_Engine_audition_sequence_cstr(struct SMS_Engine*, struct SMS_Soundtrack* soundtrack, const char* sequence_name);
+// Stops the sound or sequence being auditioned, if any, immediately.
+void SMS_Engine_stop_audition(struct SMS_Engine*);
+// Returns 1 if a sound or sequence is being auditioned (including if it's
+// still loading), 0 otherwise.
+int SMS_Engine_is_auditioning(struct SMS_Engine*);
 
 // Mix some audio, advance time! `out` must have a number of elements
 // divisible by the number of speaker channels. Any existing data in `out`
-// is mixed with the active music data. You may or may not want to zero
-// `out` before this call.
+// is overwritten; there's no need to zero it first.
 //
 // `out_len` is the number of ELEMENTS, i.e. SAMPLES, in the output buffer.
-// It is NOT the number of bytes, and it is NOT the number of frames.
+// It is NOT the number of bytes, and it is NOT the number of frames. If it
+// isn't divisible by the number of speaker channels, as many whole frames as
+// fit are mixed, the leftover samples are left alone, and a warning is issued.
 void SMS_Engine_turn_handle(
     struct SMS_Engine*,
     float* out,
     size_t out_len
 );
+// As `SMS_Engine_turn_handle`, but with a separate output buffer for each
+// speaker channel, instead of a single interleaved buffer. `outs` points to
+// `num_outs` buffers, which must be exactly the number of speaker channels.
+// Any existing data in the buffers is overwritten.
+//
+// `out_len` is the number of samples in EACH buffer.
+void SMS_Engine_turn_handle_planar(
+    struct SMS_Engine*,
+    float* const* outs,
+    size_t num_outs,
+    size_t out_len
+);
+// As `SMS_Engine_turn_handle`, but with some mix controls mixed to their own
+// output buffers instead of to `out`. `channels` and `route_outs` each point
+// to `num_routes` elements: the name of a mix control, and the buffer it goes
+// to. Every sound playing on that mix control is mixed into that buffer, and
+// not into `out`; mix controls that aren't listed are mixed into `out` as
+// usual. (A sound that `send`s to a routed mix control is mixed into that mix
+// control's buffer at the send's level.)
+//
+// Every buffer is `out_len` samples long, in the engine's speaker layout.
+// Any existing data in the buffers (including `out`) is overwritten. A
+// routed mix control keeps its volume, effect, filter, and stereo width.
+// List each mix control at most once.
+//
+// Routing only applies to audio that's mixed during this call. Audio that was
+// already mixed for a tap comes out in `out`, with every mix control in it.
+// This can't be used once the output sample rate has been changed.
+void SMS_Engine_turn_handle_routed(
+    struct SMS_Engine*,
+    float* out,
+    size_t out_len,
+    const char* const* channels,
+    const size_t* channel_lens,
+    float* const* route_outs,
+    size_t num_routes
+);
+void SMS_Engine_turn_handle_routed_cstr(
+    struct SMS_Engine*,
+    float* out,
+    size_t out_len,
+    const char* const* channels,
+    float* const* route_outs,
+    size_t num_routes
+);
 
 #define SMS_Target SMS_Engine
 #include "second-music-system-commands.h"
@@ -369,7 +707,8 @@ void SMS_Transaction_commit(struct SMS_Commander*);
 // BooleanResponse
 ///////////////////////////////////////////////////////////////////////////////
 // A delayed answer to a yes-or-no question. Create it by calling one of the
-// `SMS_*_is_*` functions. (See `second-music-system-commands.h`)
+// `SMS_*_is_*` functions, or `SMS_*_play_one_shot`. (See
+// `second-music-system-commands.h`)
 struct SMS_BooleanResponse;
 
 // Free the response. We're done with it.
@@ -24,6 +24,41 @@ macro_rules! fade_type {
     };
 }
 
+macro_rules! voice_stealing {
+    ($stealing:expr, $function_name:expr) => {
+        match voice_stealing_from_int($stealing) {
+            Some(x) => x,
+            None => panic!(
+                "{}: stealing must be a valid SMS_VOICE_STEALING_* constant!",
+                $function_name
+            ),
+        }
+    };
+}
+
+macro_rules! voice_limit {
+    ($max_voices:expr, $function_name:expr) => {
+        match std::num::NonZeroUsize::new($max_voices as usize) {
+            Some(x) => x,
+            None => panic!("{}: max_voices cannot be zero!", $function_name),
+        }
+    };
+}
+
+macro_rules! vector3 {
+    ($vector:expr, $function_name:expr) => {
+        if $vector.is_null() {
+            panic!(
+                "{}: {} cannot be NULL!",
+                $function_name,
+                stringify!($vector)
+            );
+        } else {
+            unsafe { std::ptr::read($vector as *const [f32; 3]) }
+        }
+    };
+}
+
 macro_rules! implement_commands {
 ($c_target:ty, $rust_target:ty) => { paste::paste!{
 
@@ -49,6 +84,29 @@ extern "C" fn [<$c_target _ replace_soundtrack>](
     target.replace_soundtrack(new_soundtrack);
 }
 
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ crossfade_soundtrack>](
+    target: *mut $rust_target,
+    new_soundtrack: *mut Soundtrack,
+    fade_length: c_float,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let new_soundtrack = *unsafe { Box::from_raw(new_soundtrack) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.crossfade_soundtrack(new_soundtrack, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ reconcile_soundtrack>](
+    target: *mut $rust_target,
+    new_soundtrack: *mut Soundtrack,
+) {
+    let target = target!(target, function_name!());
+    let new_soundtrack = *unsafe { Box::from_raw(new_soundtrack) };
+    target.reconcile_soundtrack(new_soundtrack);
+}
+
 #[no_mangle] #[named]
 extern "C" fn [<$c_target _ precache>](
     target: *mut $rust_target,
@@ -99,6 +157,48 @@ extern "C" fn [<$c_target _ unprecache_all>](
     target.unprecache_all();
 }
 
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ cache_sound>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+    sound_name_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let sound_name = input(sound_name, sound_name_len).unwrap();
+    target.cache_sound(sound_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ cache_sound_cstr>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let sound_name = input_cstr(sound_name).unwrap();
+    target.cache_sound(sound_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ uncache_sound>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+    sound_name_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let sound_name = input(sound_name, sound_name_len).unwrap();
+    target.uncache_sound(sound_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ uncache_sound_cstr>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let sound_name = input_cstr(sound_name).unwrap();
+    target.uncache_sound(sound_name);
+}
+
 #[no_mangle] #[named]
 extern "C" fn [<$c_target _ set_flow_control_to_number>](
     target: *mut $rust_target,
@@ -198,6 +298,45 @@ extern "C" fn [<$c_target _ clear_all_flow_controls>](
     target.clear_all_flow_controls();
 }
 
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ apply_preset>](
+    target: *mut $rust_target,
+    preset_name: *const c_char,
+    preset_name_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let preset_name = input(preset_name, preset_name_len).unwrap();
+    target.apply_preset(preset_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ apply_preset_cstr>](
+    target: *mut $rust_target,
+    preset_name: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let preset_name = input_cstr(preset_name).unwrap();
+    target.apply_preset(preset_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ seed_random>](
+    target: *mut $rust_target,
+    seed: u64,
+) {
+    let target = target!(target, function_name!());
+    target.seed_random(seed);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_time_scale>](
+    target: *mut $rust_target,
+    factor: f32,
+) {
+    let target = target!(target, function_name!());
+    target.set_time_scale(positive(factor));
+}
+
 // mix controls
 
 #[no_mangle] #[named]
@@ -258,6 +397,35 @@ extern "C" fn [<$c_target _ fade_prefixed_mix_controls_to_cstr>](
     target.fade_prefixed_mix_controls_to(control_prefix, positive(target_volume), positive(fade_length), fade_type);
 }
 
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_group_to>](
+    target: *mut $rust_target,
+    group_name: *const c_char,
+    group_name_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let group_name = input(group_name, group_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_group_to(group_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_group_to_cstr>](
+    target: *mut $rust_target,
+    group_name: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let group_name = input_cstr(group_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_group_to(group_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
 #[no_mangle] #[named]
 extern "C" fn [<$c_target _ fade_all_mix_controls_to>](
     target: *mut $rust_target,
@@ -387,7 +555,7 @@ extern "C" fn [<$c_target _ kill_prefixed_mix_controls>](
 ) {
     let target = target!(target, function_name!());
     let control_prefix = input(control_prefix, control_prefix_len).unwrap();
-    target.kill_mix_control(control_prefix);
+    target.kill_prefixed_mix_controls(control_prefix);
 }
 
 #[no_mangle] #[named]
@@ -397,7 +565,7 @@ extern "C" fn [<$c_target _ kill_prefixed_mix_controls_cstr>](
 ) {
     let target = target!(target, function_name!());
     let control_prefix = input_cstr(control_prefix).unwrap();
-    target.kill_mix_control(control_prefix);
+    target.kill_prefixed_mix_controls(control_prefix);
 }
 
 #[no_mangle] #[named]
@@ -416,212 +584,825 @@ extern "C" fn [<$c_target _ kill_all_mix_controls_except_main>](
     target.kill_all_mix_controls_except_main();
 }
 
-// flows
-
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ start_flow>](
+extern "C" fn [<$c_target _ set_mix_control_voice_limit>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    flow_name_len: size_t,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    max_voices: size_t,
+    stealing: c_int,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input(flow_name, flow_name_len).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.start_flow(flow_name, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    let max_voices = voice_limit!(max_voices, function_name!());
+    let stealing = voice_stealing!(stealing, function_name!());
+    target.set_mix_control_voice_limit(control_name, max_voices, stealing);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ start_flow_cstr>](
+extern "C" fn [<$c_target _ set_mix_control_voice_limit_cstr>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    max_voices: size_t,
+    stealing: c_int,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input_cstr(flow_name).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.start_flow(flow_name, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input_cstr(control_name).unwrap();
+    let max_voices = voice_limit!(max_voices, function_name!());
+    let stealing = voice_stealing!(stealing, function_name!());
+    target.set_mix_control_voice_limit(control_name, max_voices, stealing);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_flow_to>](
+extern "C" fn [<$c_target _ clear_mix_control_voice_limit>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    flow_name_len: size_t,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input(flow_name, flow_name_len).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_flow_to(flow_name, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.clear_mix_control_voice_limit(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_flow_to_cstr>](
+extern "C" fn [<$c_target _ clear_mix_control_voice_limit_cstr>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input_cstr(flow_name).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_flow_to(flow_name, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input_cstr(control_name).unwrap();
+    target.clear_mix_control_voice_limit(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_prefixed_flows_to>](
+extern "C" fn [<$c_target _ solo_mix_control>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
-    flow_prefix_len: size_t,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_prefixed_flows_to(flow_prefix, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.solo_mix_control(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_prefixed_flows_to_cstr>](
+extern "C" fn [<$c_target _ solo_mix_control_cstr>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input_cstr(flow_prefix).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_prefixed_flows_to(flow_prefix, positive(target_volume), positive(fade_length), fade_type);
+    let control_name = input_cstr(control_name).unwrap();
+    target.solo_mix_control(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_all_flows_to>](
+extern "C" fn [<$c_target _ clear_solo>](
     target: *mut $rust_target,
-    target_volume: f32,
-    fade_length: f32,
-    fade_type: c_int,
 ) {
     let target = target!(target, function_name!());
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_all_flows_to(positive(target_volume), positive(fade_length), fade_type);
+    target.clear_solo();
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_flow_out>](
+extern "C" fn [<$c_target _ set_mix_control_muted>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    flow_name_len: size_t,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    muted: c_int,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input(flow_name, flow_name_len).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_flow_out(flow_name, positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.set_mix_control_muted(control_name, muted != 0);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_flow_out_cstr>](
+extern "C" fn [<$c_target _ set_mix_control_muted_cstr>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    muted: c_int,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input_cstr(flow_name).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_flow_out(flow_name, positive(fade_length), fade_type);
+    let control_name = input_cstr(control_name).unwrap();
+    target.set_mix_control_muted(control_name, muted != 0);
 }
 
+// effects
+
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_prefixed_flows_out>](
+extern "C" fn [<$c_target _ set_mix_control_delay>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
-    flow_prefix_len: size_t,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    time: f32,
+    feedback: f32,
+    mix: f32,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_prefixed_flows_out(flow_prefix, positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.set_mix_control_effect(control_name, ChannelEffect::Delay {
+        time: positive(time),
+        feedback: positive(feedback),
+        mix: positive(mix),
+    });
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_prefixed_flows_out_cstr>](
+extern "C" fn [<$c_target _ set_mix_control_delay_cstr>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    time: f32,
+    feedback: f32,
+    mix: f32,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input_cstr(flow_prefix).unwrap();
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_prefixed_flows_out(flow_prefix, positive(fade_length), fade_type);
+    let control_name = input_cstr(control_name).unwrap();
+    target.set_mix_control_effect(control_name, ChannelEffect::Delay {
+        time: positive(time),
+        feedback: positive(feedback),
+        mix: positive(mix),
+    });
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ fade_all_flows_out>](
+extern "C" fn [<$c_target _ set_mix_control_reverb>](
     target: *mut $rust_target,
-    fade_length: f32,
-    fade_type: c_int,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    time: f32,
+    feedback: f32,
+    mix: f32,
 ) {
     let target = target!(target, function_name!());
-    let fade_type = fade_type!(fade_type, function_name!());
-    target.fade_all_flows_out(positive(fade_length), fade_type);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.set_mix_control_effect(control_name, ChannelEffect::Reverb {
+        time: positive(time),
+        feedback: positive(feedback),
+        mix: positive(mix),
+    });
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ kill_flow>](
+extern "C" fn [<$c_target _ set_mix_control_reverb_cstr>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
-    flow_name_len: size_t,
+    control_name: *const c_char,
+    time: f32,
+    feedback: f32,
+    mix: f32,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input(flow_name, flow_name_len).unwrap();
-    target.kill_flow(flow_name);
+    let control_name = input_cstr(control_name).unwrap();
+    target.set_mix_control_effect(control_name, ChannelEffect::Reverb {
+        time: positive(time),
+        feedback: positive(feedback),
+        mix: positive(mix),
+    });
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ kill_flow_cstr>](
+extern "C" fn [<$c_target _ clear_mix_control_effect>](
     target: *mut $rust_target,
-    flow_name: *const c_char,
+    control_name: *const c_char,
+    control_name_len: size_t,
 ) {
     let target = target!(target, function_name!());
-    let flow_name = input_cstr(flow_name).unwrap();
-    target.kill_flow(flow_name);
+    let control_name = input(control_name, control_name_len).unwrap();
+    target.clear_mix_control_effect(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ kill_prefixed_flows>](
+extern "C" fn [<$c_target _ clear_mix_control_effect_cstr>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
-    flow_prefix_len: size_t,
+    control_name: *const c_char,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
-    target.kill_flow(flow_prefix);
+    let control_name = input_cstr(control_name).unwrap();
+    target.clear_mix_control_effect(control_name);
 }
 
 #[no_mangle] #[named]
-extern "C" fn [<$c_target _ kill_prefixed_flows_cstr>](
+extern "C" fn [<$c_target _ fade_mix_control_cutoff_to>](
     target: *mut $rust_target,
-    flow_prefix: *const c_char,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    cutoff_hz: f32,
+    fade_length: f32,
+    fade_type: c_int,
 ) {
     let target = target!(target, function_name!());
-    let flow_prefix = input_cstr(flow_prefix).unwrap();
-    target.kill_flow(flow_prefix);
+    let control_name = input(control_name, control_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_mix_control_cutoff_to(control_name, positive(cutoff_hz), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_mix_control_cutoff_to_cstr>](
+    target: *mut $rust_target,
+    control_name: *const c_char,
+    cutoff_hz: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let control_name = input_cstr(control_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_mix_control_cutoff_to(control_name, positive(cutoff_hz), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_mix_control_width_to>](
+    target: *mut $rust_target,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    width: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let control_name = input(control_name, control_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_mix_control_width_to(control_name, positive(width), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_mix_control_width_to_cstr>](
+    target: *mut $rust_target,
+    control_name: *const c_char,
+    width: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let control_name = input_cstr(control_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_mix_control_width_to(control_name, positive(width), positive(fade_length), fade_type);
+}
+
+// positional audio
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_listener>](
+    target: *mut $rust_target,
+    position: *const f32,
+    forward: *const f32,
+    up: *const f32,
+    reference_distance: f32,
+) {
+    let target = target!(target, function_name!());
+    target.set_listener(Listener {
+        position: vector3!(position, function_name!()),
+        forward: vector3!(forward, function_name!()),
+        up: vector3!(up, function_name!()),
+        reference_distance: positive(reference_distance),
+    });
+}
+
+// pitch bends
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_mix_control_pitch_to>](
+    target: *mut $rust_target,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    rate: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let control_name = input(control_name, control_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_pitch_to(ChannelOrFlow::Channel(control_name), positive(rate), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_mix_control_pitch_to_cstr>](
+    target: *mut $rust_target,
+    control_name: *const c_char,
+    rate: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let control_name = input_cstr(control_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_pitch_to(ChannelOrFlow::Channel(control_name), positive(rate), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_pitch_to>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    rate: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_pitch_to(ChannelOrFlow::Flow(flow_name), positive(rate), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_pitch_to_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    rate: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_pitch_to(ChannelOrFlow::Flow(flow_name), positive(rate), positive(fade_length), fade_type);
+}
+
+// one-shots
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ play_one_shot>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+    sound_name_len: size_t,
+    channel: *const c_char,
+    channel_len: size_t,
+    volume: f32,
+    pitch: f32,
+) -> *mut query::Response<bool> {
+    let target = target!(target, function_name!());
+    let sound_name = input(sound_name, sound_name_len).unwrap();
+    let channel = input(channel, channel_len).unwrap();
+    Box::into_raw(Box::new(target.play_one_shot(sound_name, channel, positive(volume), positive(pitch))))
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ play_one_shot_cstr>](
+    target: *mut $rust_target,
+    sound_name: *const c_char,
+    channel: *const c_char,
+    volume: f32,
+    pitch: f32,
+) -> *mut query::Response<bool> {
+    let target = target!(target, function_name!());
+    let sound_name = input_cstr(sound_name).unwrap();
+    let channel = input_cstr(channel).unwrap();
+    Box::into_raw(Box::new(target.play_one_shot(sound_name, channel, positive(volume), positive(pitch))))
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ stop_one_shots>](
+    target: *mut $rust_target,
+    channel: *const c_char,
+    channel_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let channel = if channel.is_null() { None } else { Some(input(channel, channel_len).unwrap()) };
+    target.stop_one_shots(channel);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ stop_one_shots_cstr>](
+    target: *mut $rust_target,
+    channel: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let channel = if channel.is_null() { None } else { Some(input_cstr(channel).unwrap()) };
+    target.stop_one_shots(channel);
+}
+
+// flows
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ start_flow>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.start_flow(flow_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ start_flow_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.start_flow(flow_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ start_flow_at>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    node_name: *const c_char,
+    node_name_len: size_t,
+    offset: f32,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let node_name = if node_name.is_null() { None } else { Some(input(node_name, node_name_len).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.start_flow_at(flow_name, node_name, positive(offset), positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ start_flow_at_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    node_name: *const c_char,
+    offset: f32,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let node_name = if node_name.is_null() { None } else { Some(input_cstr(node_name).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.start_flow_at(flow_name, node_name, positive(offset), positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_to>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_to(flow_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_to_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_to(flow_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_node_to>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    node_name: *const c_char,
+    node_name_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let node_name = if node_name.is_null() { None } else { Some(input(node_name, node_name_len).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_node_to(flow_name, node_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_node_to_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    node_name: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let node_name = if node_name.is_null() { None } else { Some(input_cstr(node_name).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_node_to(flow_name, node_name, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_sequence_element_to>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    sequence_name: *const c_char,
+    sequence_name_len: size_t,
+    element: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let sequence_name = input(sequence_name, sequence_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_sequence_element_to(flow_name, sequence_name, element, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_sequence_element_to_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    sequence_name: *const c_char,
+    element: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let sequence_name = input_cstr(sequence_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_sequence_element_to(flow_name, sequence_name, element, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ stop_sequence_element>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    sequence_name: *const c_char,
+    sequence_name_len: size_t,
+    element: size_t,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let sequence_name = input(sequence_name, sequence_name_len).unwrap();
+    target.stop_sequence_element(flow_name, sequence_name, element);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ stop_sequence_element_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    sequence_name: *const c_char,
+    element: size_t,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let sequence_name = input_cstr(sequence_name).unwrap();
+    target.stop_sequence_element(flow_name, sequence_name, element);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_layer_intensity>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    intensity: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.set_layer_intensity(flow_name, intensity, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_layer_intensity_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    intensity: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.set_layer_intensity(flow_name, intensity, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_flow_successor>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    successor: *const c_char,
+    successor_len: size_t,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let successor = if successor.is_null() { None } else { Some(input(successor, successor_len).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.set_flow_successor(flow_name, successor, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ set_flow_successor_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    successor: *const c_char,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let successor = if successor.is_null() { None } else { Some(input_cstr(successor).unwrap()) };
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.set_flow_successor(flow_name, successor, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_prefixed_flows_to>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+    flow_prefix_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_prefixed_flows_to(flow_prefix, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_prefixed_flows_to_cstr>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input_cstr(flow_prefix).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_prefixed_flows_to(flow_prefix, positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_all_flows_to>](
+    target: *mut $rust_target,
+    target_volume: f32,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_all_flows_to(positive(target_volume), positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_out>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_out(flow_name, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_out_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_out(flow_name, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_to_then_out>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    target_volume: f32,
+    fade_length: f32,
+    hold: f32,
+    fade_out: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_to_then_out(flow_name, positive(target_volume), positive(fade_length), positive(hold), positive(fade_out), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_flow_to_then_out_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    target_volume: f32,
+    fade_length: f32,
+    hold: f32,
+    fade_out: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_flow_to_then_out(flow_name, positive(target_volume), positive(fade_length), positive(hold), positive(fade_out), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_prefixed_flows_out>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+    flow_prefix_len: size_t,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_prefixed_flows_out(flow_prefix, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_prefixed_flows_out_cstr>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input_cstr(flow_prefix).unwrap();
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_prefixed_flows_out(flow_prefix, positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ fade_all_flows_out>](
+    target: *mut $rust_target,
+    fade_length: f32,
+    fade_type: c_int,
+) {
+    let target = target!(target, function_name!());
+    let fade_type = fade_type!(fade_type, function_name!());
+    target.fade_all_flows_out(positive(fade_length), fade_type);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ kill_flow>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    target.kill_flow(flow_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ kill_flow_cstr>](
+    target: *mut $rust_target,
+    flow_name: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let flow_name = input_cstr(flow_name).unwrap();
+    target.kill_flow(flow_name);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ kill_prefixed_flows>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+    flow_prefix_len: size_t,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input(flow_prefix, flow_prefix_len).unwrap();
+    target.kill_prefixed_flows(flow_prefix);
+}
+
+#[no_mangle] #[named]
+extern "C" fn [<$c_target _ kill_prefixed_flows_cstr>](
+    target: *mut $rust_target,
+    flow_prefix: *const c_char,
+) {
+    let target = target!(target, function_name!());
+    let flow_prefix = input_cstr(flow_prefix).unwrap();
+    target.kill_prefixed_flows(flow_prefix);
 }
 
 #[no_mangle] #[named]
@@ -640,3 +1421,89 @@ implement_commands!(
     SMS_Transaction,
     Transaction<'static, dyn EngineCommandIssuer>
 );
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mix_control_names(engine: &mut Engine) -> Vec<CompactString> {
+        let mut response = engine.get_all_mix_controls();
+        engine.turn_handle(&mut [0.0; 2]);
+        let mut names: Vec<CompactString> = response
+            .take()
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn kill_prefixed_mix_controls() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Stereo,
+            PosFloat::new(48000.0).unwrap(),
+        );
+        for name in ["music_a", "music_b", "music", "sfx"] {
+            engine.fade_mix_control_to(
+                name.to_compact_string(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        SMS_Engine_kill_prefixed_mix_controls_cstr(
+            &mut engine,
+            c"music_".as_ptr(),
+        );
+        assert_eq!(mix_control_names(&mut engine), ["main", "music", "sfx"]);
+        SMS_Engine_kill_prefixed_mix_controls(
+            &mut engine,
+            c"musicsfx".as_ptr(),
+            5,
+        );
+        assert_eq!(mix_control_names(&mut engine), ["main", "sfx"]);
+    }
+
+    fn flow_names(engine: &mut Engine) -> Vec<CompactString> {
+        engine.step_frames(1);
+        let mut names: Vec<CompactString> = engine
+            .inspect_active_nodes()
+            .into_iter()
+            .map(|x| x.flow)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn kill_prefixed_flows() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Stereo,
+            PosFloat::new(48000.0).unwrap(),
+        );
+        let names = ["music_a", "music_b", "music", "sfx"];
+        let source: String = names
+            .iter()
+            .map(|name| format!("flow {name}\n  wait 10\n"))
+            .collect();
+        engine.replace_soundtrack(Soundtrack::from_source(&source).unwrap());
+        for name in names {
+            engine.start_flow(
+                name.to_compact_string(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        assert_eq!(
+            flow_names(&mut engine),
+            ["music", "music_a", "music_b", "sfx"]
+        );
+        SMS_Engine_kill_prefixed_flows_cstr(&mut engine, c"music_".as_ptr());
+        assert_eq!(flow_names(&mut engine), ["music", "sfx"]);
+        SMS_Engine_kill_prefixed_flows(&mut engine, c"musicsfx".as_ptr(), 5);
+        assert_eq!(flow_names(&mut engine), ["sfx"]);
+    }
+}
@@ -20,22 +20,21 @@ extern "C" fn SMS_Engine_new(
     );
     let background_loading = background_loading != 0;
     let delegate = unsafe { delegate.as_ref().unwrap() }.clone();
-    if background_loading {
-        Box::into_raw(Box::new(Engine::new(
-            delegate,
-            speaker_layout,
-            positive(sample_rate),
-            NonZeroUsize::new(num_threads.clamp(0, c_int::MAX) as usize),
-            affinity as usize,
-        )))
+    let builder = Engine::builder()
+        .delegate(delegate)
+        .speaker_layout(speaker_layout)
+        .sample_rate(positive(sample_rate));
+    let engine = if background_loading {
+        builder
+            .num_threads(NonZeroUsize::new(
+                num_threads.clamp(0, c_int::MAX) as usize
+            ))
+            .affinity(affinity as usize)
+            .build()
     } else {
-        Box::into_raw(Box::new(Engine::new_with_runtime(
-            delegate,
-            speaker_layout,
-            positive(sample_rate),
-            Arc::new(ForegroundTaskRuntime),
-        )))
-    }
+        builder.runtime(Arc::new(ForegroundTaskRuntime)).build()
+    };
+    Box::into_raw(Box::new(engine))
 }
 
 #[no_mangle]
@@ -96,5 +95,609 @@ unsafe extern "C" fn SMS_Engine_turn_handle(
     }
     let engine = unsafe { engine.as_mut().unwrap() };
     let out = unsafe { std::slice::from_raw_parts_mut(out, out_len) };
-    engine.turn_handle(out);
+    if let Err(x) = engine.try_turn_handle(out) {
+        sound_delegate::global_warning(&format!(
+            "SMS_Engine_turn_handle: {x}"
+        ));
+    }
+}
+
+/// Common code for the `get_flow_control_number` functions.
+fn output_flow_control_number(
+    value: Option<StringOrNumber>,
+    number_out: *mut c_float,
+) -> c_int {
+    match value {
+        None => 0,
+        Some(value) => {
+            if let Some(number_out) = unsafe { number_out.as_mut() } {
+                *number_out = value.as_number();
+            }
+            1
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_control_number(
+    engine: *mut Engine,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    number_out: *mut c_float,
+) -> c_int {
+    if engine.is_null() {
+        panic!("SMS_Engine_get_flow_control_number: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    let control_name = input(control_name, control_name_len).unwrap();
+    output_flow_control_number(
+        engine.copy_flow_control(&control_name),
+        number_out,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_control_number_cstr(
+    engine: *mut Engine,
+    control_name: *const c_char,
+    number_out: *mut c_float,
+) -> c_int {
+    if engine.is_null() {
+        panic!(
+            "SMS_Engine_get_flow_control_number_cstr: engine cannot be NULL!"
+        );
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    let control_name = input_cstr(control_name).unwrap();
+    output_flow_control_number(
+        engine.copy_flow_control(&control_name),
+        number_out,
+    )
+}
+
+/// Common code for the `get_flow_control_string` functions.
+fn output_flow_control_string(
+    value: Option<StringOrNumber>,
+    string_out: *mut *mut c_char,
+    string_out_len: *mut size_t,
+) -> c_int {
+    match value {
+        None => 0,
+        Some(value) => {
+            output_string(&value.as_string(), string_out, string_out_len);
+            1
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_control_string(
+    engine: *mut Engine,
+    control_name: *const c_char,
+    control_name_len: size_t,
+    string_out: *mut *mut c_char,
+    string_out_len: *mut size_t,
+) -> c_int {
+    if engine.is_null() {
+        panic!("SMS_Engine_get_flow_control_string: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    let control_name = input(control_name, control_name_len).unwrap();
+    output_flow_control_string(
+        engine.copy_flow_control(&control_name),
+        string_out,
+        string_out_len,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_control_string_cstr(
+    engine: *mut Engine,
+    control_name: *const c_char,
+    string_out: *mut *mut c_char,
+    string_out_len: *mut size_t,
+) -> c_int {
+    if engine.is_null() {
+        panic!(
+            "SMS_Engine_get_flow_control_string_cstr: engine cannot be NULL!"
+        );
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    let control_name = input_cstr(control_name).unwrap();
+    output_flow_control_string(
+        engine.copy_flow_control(&control_name),
+        string_out,
+        string_out_len,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_turn_handle_planar(
+    engine: *mut Engine,
+    outs: *const *mut f32,
+    num_outs: size_t,
+    out_len: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_turn_handle_planar: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let outs = unsafe { std::slice::from_raw_parts(outs, num_outs) };
+    let mut outs: Vec<&mut [f32]> = outs
+        .iter()
+        .map(|out| unsafe { std::slice::from_raw_parts_mut(*out, out_len) })
+        .collect();
+    engine.turn_handle_planar(&mut outs);
+}
+
+/// Common code for the `turn_handle_routed` functions.
+unsafe fn turn_handle_routed(
+    engine: &mut Engine,
+    out: *mut f32,
+    out_len: size_t,
+    channels: Vec<CompactString>,
+    route_outs: *const *mut f32,
+) {
+    let out = unsafe { std::slice::from_raw_parts_mut(out, out_len) };
+    let route_outs =
+        unsafe { std::slice::from_raw_parts(route_outs, channels.len()) };
+    let mut routes: Vec<(&str, &mut [f32])> = channels
+        .iter()
+        .zip(route_outs.iter())
+        .map(|(channel, route_out)| {
+            (channel.as_str(), unsafe {
+                std::slice::from_raw_parts_mut(*route_out, out_len)
+            })
+        })
+        .collect();
+    engine.turn_handle_routed(out, &mut routes);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_turn_handle_routed(
+    engine: *mut Engine,
+    out: *mut f32,
+    out_len: size_t,
+    channels: *const *const c_char,
+    channel_lens: *const size_t,
+    route_outs: *const *mut f32,
+    num_routes: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_turn_handle_routed: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let channels = unsafe { std::slice::from_raw_parts(channels, num_routes) };
+    let channel_lens =
+        unsafe { std::slice::from_raw_parts(channel_lens, num_routes) };
+    let channels = channels
+        .iter()
+        .zip(channel_lens.iter())
+        .map(|(channel, len)| input(*channel, *len).unwrap())
+        .collect();
+    unsafe { turn_handle_routed(engine, out, out_len, channels, route_outs) }
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_turn_handle_routed_cstr(
+    engine: *mut Engine,
+    out: *mut f32,
+    out_len: size_t,
+    channels: *const *const c_char,
+    route_outs: *const *mut f32,
+    num_routes: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_turn_handle_routed_cstr: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let channels = unsafe { std::slice::from_raw_parts(channels, num_routes) };
+    let channels = channels
+        .iter()
+        .map(|channel| input_cstr(*channel).unwrap())
+        .collect();
+    unsafe { turn_handle_routed(engine, out, out_len, channels, route_outs) }
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_output_sample_rate(
+    engine: *mut Engine,
+) -> f32 {
+    if engine.is_null() {
+        panic!("SMS_Engine_get_output_sample_rate: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    *engine.get_output_sample_rate()
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_stats(
+    engine: *mut Engine,
+    active_voices_out: *mut size_t,
+    cached_sound_bytes_out: *mut size_t,
+    streaming_sounds_out: *mut size_t,
+    active_flows_out: *mut size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_get_stats: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    let stats = engine.stats();
+    for (out, value) in [
+        (active_voices_out, stats.active_voices),
+        (cached_sound_bytes_out, stats.cached_sound_bytes),
+        (streaming_sounds_out, stats.streaming_sounds),
+        (active_flows_out, stats.active_flows),
+    ] {
+        if let Some(out) = unsafe { out.as_mut() } {
+            *out = value;
+        }
+    }
+}
+
+/// Common code for the `get_flow_load_progress` functions.
+fn output_flow_load_progress(
+    progress: Option<FlowLoadProgress>,
+    sounds_ready_out: *mut size_t,
+    total_sounds_out: *mut size_t,
+    seconds_remaining_out: *mut c_float,
+    sounds_of_unknown_length_out: *mut size_t,
+) -> c_int {
+    let Some(progress) = progress else { return 0 };
+    for (out, value) in [
+        (sounds_ready_out, progress.sounds_ready),
+        (total_sounds_out, progress.total_sounds),
+        (
+            sounds_of_unknown_length_out,
+            progress.sounds_of_unknown_length,
+        ),
+    ] {
+        if let Some(out) = unsafe { out.as_mut() } {
+            *out = value;
+        }
+    }
+    if let Some(out) = unsafe { seconds_remaining_out.as_mut() } {
+        *out = progress.seconds_remaining;
+    }
+    1
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_load_progress(
+    engine: *mut Engine,
+    flow_name: *const c_char,
+    flow_name_len: size_t,
+    sounds_ready_out: *mut size_t,
+    total_sounds_out: *mut size_t,
+    seconds_remaining_out: *mut c_float,
+    sounds_of_unknown_length_out: *mut size_t,
+) -> c_int {
+    if engine.is_null() {
+        panic!("SMS_Engine_get_flow_load_progress: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let flow_name = input(flow_name, flow_name_len).unwrap();
+    output_flow_load_progress(
+        engine.flow_load_progress(&flow_name),
+        sounds_ready_out,
+        total_sounds_out,
+        seconds_remaining_out,
+        sounds_of_unknown_length_out,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_get_flow_load_progress_cstr(
+    engine: *mut Engine,
+    flow_name: *const c_char,
+    sounds_ready_out: *mut size_t,
+    total_sounds_out: *mut size_t,
+    seconds_remaining_out: *mut c_float,
+    sounds_of_unknown_length_out: *mut size_t,
+) -> c_int {
+    if engine.is_null() {
+        panic!(
+            "SMS_Engine_get_flow_load_progress_cstr: engine cannot be NULL!"
+        );
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let flow_name = input_cstr(flow_name).unwrap();
+    output_flow_load_progress(
+        engine.flow_load_progress(&flow_name),
+        sounds_ready_out,
+        total_sounds_out,
+        seconds_remaining_out,
+        sounds_of_unknown_length_out,
+    )
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_audition_sound(
+    engine: *mut Engine,
+    soundtrack: *mut Soundtrack,
+    sound_name: *const c_char,
+    sound_name_len: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_audition_sound: engine cannot be NULL!");
+    }
+    if soundtrack.is_null() {
+        panic!("SMS_Engine_audition_sound: soundtrack cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let soundtrack = unsafe { soundtrack.as_ref().unwrap() };
+    let sound_name = input(sound_name, sound_name_len).unwrap();
+    engine.audition_sound(soundtrack, &sound_name);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_audition_sound_cstr(
+    engine: *mut Engine,
+    soundtrack: *mut Soundtrack,
+    sound_name: *const c_char,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_audition_sound_cstr: engine cannot be NULL!");
+    }
+    if soundtrack.is_null() {
+        panic!("SMS_Engine_audition_sound_cstr: soundtrack cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let soundtrack = unsafe { soundtrack.as_ref().unwrap() };
+    let sound_name = input_cstr(sound_name).unwrap();
+    engine.audition_sound(soundtrack, &sound_name);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_audition_sequence(
+    engine: *mut Engine,
+    soundtrack: *mut Soundtrack,
+    sequence_name: *const c_char,
+    sequence_name_len: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_audition_sequence: engine cannot be NULL!");
+    }
+    if soundtrack.is_null() {
+        panic!("SMS_Engine_audition_sequence: soundtrack cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let soundtrack = unsafe { soundtrack.as_ref().unwrap() };
+    let sequence_name = input(sequence_name, sequence_name_len).unwrap();
+    engine.audition_sequence(soundtrack, &sequence_name);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_audition_sequence_cstr(
+    engine: *mut Engine,
+    soundtrack: *mut Soundtrack,
+    sequence_name: *const c_char,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_audition_sequence_cstr: engine cannot be NULL!");
+    }
+    if soundtrack.is_null() {
+        panic!(
+            "SMS_Engine_audition_sequence_cstr: soundtrack cannot be NULL!"
+        );
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let soundtrack = unsafe { soundtrack.as_ref().unwrap() };
+    let sequence_name = input_cstr(sequence_name).unwrap();
+    engine.audition_sequence(soundtrack, &sequence_name);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_stop_audition(engine: *mut Engine) {
+    if engine.is_null() {
+        panic!("SMS_Engine_stop_audition: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.stop_audition();
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_is_auditioning(engine: *mut Engine) -> c_int {
+    if engine.is_null() {
+        panic!("SMS_Engine_is_auditioning: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_ref().unwrap() };
+    engine.is_auditioning() as c_int
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_output_sample_rate(
+    engine: *mut Engine,
+    sample_rate: f32,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_output_sample_rate: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let sample_rate = PosFloat::new(sample_rate)
+        .ok()
+        .filter(|x| *x > PosFloat::ZERO)
+        .expect(
+            "SMS_Engine_set_output_sample_rate: sample_rate must be positive!",
+        );
+    engine.set_output_sample_rate(sample_rate);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_drain_commands(engine: *mut Engine) {
+    if engine.is_null() {
+        panic!("SMS_Engine_drain_commands: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.drain_commands();
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_instruction_limit(
+    engine: *mut Engine,
+    limit: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_instruction_limit: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_instruction_limit(NonZeroUsize::new(limit));
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_load_timeout(
+    engine: *mut Engine,
+    timeout: f32,
+    start_anyway: c_int,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_load_timeout: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let timeout = PosFloat::new(timeout).ok().filter(|x| *x > PosFloat::ZERO);
+    engine.set_load_timeout(timeout, start_anyway != 0);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_max_chunk_length(
+    engine: *mut Engine,
+    length: f32,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_max_chunk_length: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let length = PosFloat::new(length).ok().filter(|x| *x > PosFloat::ZERO);
+    engine.set_max_chunk_length(length);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_replace_delegate(
+    engine: *mut Engine,
+    delegate: *mut Arc<dyn SoundDelegate>,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_replace_delegate: engine cannot be NULL!");
+    }
+    if delegate.is_null() {
+        panic!("SMS_Engine_replace_delegate: delegate cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let delegate = unsafe { delegate.as_ref().unwrap() }.clone();
+    engine.replace_delegate(delegate);
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_default_channel(
+    engine: *mut Engine,
+    channel: *const c_char,
+    channel_len: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_default_channel: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_default_channel(input(channel, channel_len).unwrap());
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_default_channel_cstr(
+    engine: *mut Engine,
+    channel: *const c_char,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_default_channel_cstr: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_default_channel(input_cstr(channel).unwrap());
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_add_tap(
+    engine: *mut Engine,
+    speaker_layout: c_int,
+) -> u32 {
+    if engine.is_null() {
+        panic!("SMS_Engine_add_tap: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let speaker_layout = speaker_layout_from_int(speaker_layout).expect(
+        "SMS_Engine_add_tap: speaker_layout was not a valid \
+                 SMS_SPEAKER_LAYOUT_* constant",
+    );
+    engine.add_tap(speaker_layout).0
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_remove_tap(engine: *mut Engine, tap: u32) {
+    if engine.is_null() {
+        panic!("SMS_Engine_remove_tap: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.remove_tap(TapId(tap));
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_tap_volume(
+    engine: *mut Engine,
+    tap: u32,
+    volume: f32,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_tap_volume: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_tap_volume(TapId(tap), positive(volume));
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_tap_channel_gain(
+    engine: *mut Engine,
+    tap: u32,
+    channel: *const c_char,
+    channel_len: size_t,
+    gain: f32,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_tap_channel_gain: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_tap_channel_gain(
+        TapId(tap),
+        input(channel, channel_len).unwrap(),
+        positive(gain),
+    );
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_set_tap_channel_gain_cstr(
+    engine: *mut Engine,
+    tap: u32,
+    channel: *const c_char,
+    gain: f32,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_set_tap_channel_gain_cstr: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    engine.set_tap_channel_gain(
+        TapId(tap),
+        input_cstr(channel).unwrap(),
+        positive(gain),
+    );
+}
+
+#[no_mangle]
+unsafe extern "C" fn SMS_Engine_turn_handle_tap(
+    engine: *mut Engine,
+    tap: u32,
+    out: *mut f32,
+    out_len: size_t,
+) {
+    if engine.is_null() {
+        panic!("SMS_Engine_turn_handle_tap: engine cannot be NULL!");
+    }
+    let engine = unsafe { engine.as_mut().unwrap() };
+    let out = unsafe { std::slice::from_raw_parts_mut(out, out_len) };
+    engine.turn_handle_tap(TapId(tap), out);
 }
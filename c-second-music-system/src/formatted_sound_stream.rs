@@ -22,6 +22,8 @@ struct ForeignSoundReader<T: Sample> {
         ) -> *mut FormattedSoundStream,
     >,
     estimate_len_handler: Option<unsafe extern "C" fn(*mut c_void) -> u64>,
+    /// Set when `read_handler` returns `SIZE_MAX`.
+    failed: bool,
     _phantom_data: PhantomData<T>,
 }
 
@@ -39,14 +41,24 @@ unsafe impl<T: Sample> Send for ForeignSoundReader<T> {}
 
 impl<T: Sample> SoundReader<T> for ForeignSoundReader<T> {
     fn read(&mut self, buf: &mut [std::mem::MaybeUninit<T>]) -> usize {
-        unsafe {
+        let ret = unsafe {
             (self.read_handler)(
                 self.callback_data,
                 buf.as_mut_ptr() as *mut c_void,
                 buf.len(),
             )
+        };
+        if ret == size_t::MAX {
+            self.failed = true;
+            0
+        } else {
+            ret
         }
     }
+    fn take_error(&mut self) -> Option<String> {
+        std::mem::take(&mut self.failed)
+            .then(|| "the read_handler reported an error".to_string())
+    }
     fn seek(&mut self, pos: u64) -> Option<u64> {
         self.seek_handler.and_then(|x| {
             let ret = unsafe { x(self.callback_data, pos) };
@@ -186,6 +198,7 @@ unsafe extern "C" fn SMS_FormattedSoundStream_new(
                     skip_coarse_handler,
                     clone_handler,
                     estimate_len_handler,
+                    failed: false,
                     _phantom_data: PhantomData,
                 },
             ))
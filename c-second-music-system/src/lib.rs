@@ -35,6 +35,43 @@ const SMS_SOUND_FORMAT_FLOAT_32: c_int = 4;
 const SMS_FADE_TYPE_LOGARITHMIC: c_int = 1;
 const SMS_FADE_TYPE_LINEAR: c_int = 2;
 const SMS_FADE_TYPE_EXPONENTIAL: c_int = 0;
+const SMS_FADE_TYPE_S_CURVE: c_int = 3;
+const SMS_FADE_TYPE_EQUAL_POWER: c_int = 4;
+
+const SMS_VOICE_STEALING_STEAL_OLDEST: c_int = 0;
+const SMS_VOICE_STEALING_DROP_NEWEST: c_int = 1;
+
+const SMS_DIAG_LEVEL_DEBUG: c_int = 0;
+const SMS_DIAG_LEVEL_WARNING: c_int = 1;
+const SMS_DIAG_LEVEL_ERROR: c_int = 2;
+const SMS_DIAG_LEVEL_BUG: c_int = 3;
+
+const SMS_DIAG_CODE_MISSING_FLOW: c_int = 0;
+const SMS_DIAG_CODE_MISSING_NODE: c_int = 1;
+const SMS_DIAG_CODE_MISSING_SOUND: c_int = 2;
+const SMS_DIAG_CODE_MISSING_SEQUENCE: c_int = 3;
+const SMS_DIAG_CODE_MISSING_PRESET: c_int = 4;
+const SMS_DIAG_CODE_MISSING_GROUP: c_int = 5;
+const SMS_DIAG_CODE_MISSING_MIX_CONTROL: c_int = 6;
+const SMS_DIAG_CODE_NODE_ALREADY_PLAYING: c_int = 7;
+const SMS_DIAG_CODE_NODE_NOT_PLAYING: c_int = 8;
+const SMS_DIAG_CODE_NODE_REWOUND: c_int = 9;
+const SMS_DIAG_CODE_SUCCESSOR_LOOP: c_int = 10;
+const SMS_DIAG_CODE_REDUNDANT_PRECACHE: c_int = 11;
+const SMS_DIAG_CODE_UNBALANCED_UNPRECACHE: c_int = 12;
+const SMS_DIAG_CODE_VOICE_STOLEN: c_int = 13;
+const SMS_DIAG_CODE_VOICE_DROPPED: c_int = 14;
+const SMS_DIAG_CODE_STREAMED_AND_BUFFERED: c_int = 15;
+const SMS_DIAG_CODE_UNKNOWN_LENGTH: c_int = 16;
+const SMS_DIAG_CODE_OVER_BUDGET: c_int = 17;
+const SMS_DIAG_CODE_OPEN_FAILED: c_int = 18;
+const SMS_DIAG_CODE_LOAD_FAILED: c_int = 19;
+const SMS_DIAG_CODE_DECODE_FAILED: c_int = 20;
+const SMS_DIAG_CODE_BAD_STREAM_FORMAT: c_int = 21;
+const SMS_DIAG_CODE_RESAMPLER_FAILED: c_int = 22;
+const SMS_DIAG_CODE_UNBALANCED_UNLOAD: c_int = 23;
+const SMS_DIAG_CODE_RUNAWAY_FLOW: c_int = 24;
+const SMS_DIAG_CODE_LOAD_TIMED_OUT: c_int = 25;
 
 fn source_input(
     src: *const c_char,
@@ -67,18 +104,20 @@ fn input_cstr(src: *const c_char) -> Result<CompactString, String> {
     input(src, len)
 }
 
-fn output_error(
+/// Fill in a newly malloc'd, null-terminated copy of `text`, and/or its
+/// length, whichever of the two pointers are non-NULL.
+fn output_string(
     text: &str,
-    error_out: *mut *mut c_char,
-    error_out_len: *mut size_t,
+    text_out: *mut *mut c_char,
+    text_out_len: *mut size_t,
 ) {
     unsafe {
-        if let Some(error_out_len) = error_out_len.as_mut() {
-            *error_out_len = text.len() as size_t;
+        if let Some(text_out_len) = text_out_len.as_mut() {
+            *text_out_len = text.len() as size_t;
         }
-        if let Some(error_out) = error_out.as_mut() {
+        if let Some(text_out) = text_out.as_mut() {
             let ptr = malloc(text.len() + 1);
-            *error_out = transmute(ptr);
+            *text_out = transmute(ptr);
             if !ptr.is_null() {
                 let slice = std::slice::from_raw_parts_mut(
                     transmute(ptr),
@@ -95,7 +134,9 @@ fn output_error(
 /// to stderr. (Ew.)
 fn positive(x: f32) -> PosFloat {
     PosFloat::new(x).unwrap_or_else(|e| {
-        eprintln!("THIS IS A BUG IN THE PROGRAM USING SMS: {e}");
+        sound_delegate::global_warning(&format!(
+            "THIS IS A BUG IN THE PROGRAM USING SMS: {e}"
+        ));
         PosFloat::ZERO
     })
 }
@@ -131,6 +172,57 @@ fn fade_type_from_int(int: c_int) -> Option<FadeType> {
         SMS_FADE_TYPE_LOGARITHMIC => FadeType::Logarithmic,
         SMS_FADE_TYPE_LINEAR => FadeType::Linear,
         SMS_FADE_TYPE_EXPONENTIAL => FadeType::Exponential,
+        SMS_FADE_TYPE_S_CURVE => FadeType::SCurve,
+        SMS_FADE_TYPE_EQUAL_POWER => FadeType::EqualPower,
+        _ => return None,
+    })
+}
+
+fn diag_level_to_int(level: DiagLevel) -> c_int {
+    match level {
+        DiagLevel::Debug => SMS_DIAG_LEVEL_DEBUG,
+        DiagLevel::Warning => SMS_DIAG_LEVEL_WARNING,
+        DiagLevel::Error => SMS_DIAG_LEVEL_ERROR,
+        DiagLevel::Bug => SMS_DIAG_LEVEL_BUG,
+    }
+}
+
+fn diag_code_to_int(code: DiagCode) -> c_int {
+    match code {
+        DiagCode::MissingFlow => SMS_DIAG_CODE_MISSING_FLOW,
+        DiagCode::MissingNode => SMS_DIAG_CODE_MISSING_NODE,
+        DiagCode::MissingSound => SMS_DIAG_CODE_MISSING_SOUND,
+        DiagCode::MissingSequence => SMS_DIAG_CODE_MISSING_SEQUENCE,
+        DiagCode::MissingPreset => SMS_DIAG_CODE_MISSING_PRESET,
+        DiagCode::MissingGroup => SMS_DIAG_CODE_MISSING_GROUP,
+        DiagCode::MissingMixControl => SMS_DIAG_CODE_MISSING_MIX_CONTROL,
+        DiagCode::NodeAlreadyPlaying => SMS_DIAG_CODE_NODE_ALREADY_PLAYING,
+        DiagCode::NodeNotPlaying => SMS_DIAG_CODE_NODE_NOT_PLAYING,
+        DiagCode::NodeRewound => SMS_DIAG_CODE_NODE_REWOUND,
+        DiagCode::SuccessorLoop => SMS_DIAG_CODE_SUCCESSOR_LOOP,
+        DiagCode::RedundantPrecache => SMS_DIAG_CODE_REDUNDANT_PRECACHE,
+        DiagCode::UnbalancedUnprecache => SMS_DIAG_CODE_UNBALANCED_UNPRECACHE,
+        DiagCode::VoiceStolen => SMS_DIAG_CODE_VOICE_STOLEN,
+        DiagCode::VoiceDropped => SMS_DIAG_CODE_VOICE_DROPPED,
+        DiagCode::StreamedAndBuffered => SMS_DIAG_CODE_STREAMED_AND_BUFFERED,
+        DiagCode::UnknownLength => SMS_DIAG_CODE_UNKNOWN_LENGTH,
+        DiagCode::OverBudget => SMS_DIAG_CODE_OVER_BUDGET,
+        DiagCode::OpenFailed => SMS_DIAG_CODE_OPEN_FAILED,
+        DiagCode::LoadFailed => SMS_DIAG_CODE_LOAD_FAILED,
+        DiagCode::DecodeFailed => SMS_DIAG_CODE_DECODE_FAILED,
+        DiagCode::BadStreamFormat => SMS_DIAG_CODE_BAD_STREAM_FORMAT,
+        DiagCode::ResamplerFailed => SMS_DIAG_CODE_RESAMPLER_FAILED,
+        DiagCode::UnbalancedUnload => SMS_DIAG_CODE_UNBALANCED_UNLOAD,
+        DiagCode::RunawayFlow => SMS_DIAG_CODE_RUNAWAY_FLOW,
+        DiagCode::LoadTimedOut => SMS_DIAG_CODE_LOAD_TIMED_OUT,
+        _ => panic!("DiagCode was expanded, but diag_code_to_int was not!"),
+    }
+}
+
+fn voice_stealing_from_int(int: c_int) -> Option<VoiceStealing> {
+    Some(match int {
+        SMS_VOICE_STEALING_STEAL_OLDEST => VoiceStealing::StealOldest,
+        SMS_VOICE_STEALING_DROP_NEWEST => VoiceStealing::DropNewest,
         _ => return None,
     })
 }
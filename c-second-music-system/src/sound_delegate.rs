@@ -1,6 +1,52 @@
 use super::*;
 
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+type WarningCallback = unsafe extern "C" fn(*mut c_void, *const c_char);
+type DiagnosticHandler =
+    unsafe extern "C" fn(*mut c_void, c_int, c_int, *const c_char);
+
+struct GlobalWarningCallback {
+    callback: WarningCallback,
+    callback_data: *mut c_void,
+}
+unsafe impl Send for GlobalWarningCallback {}
+unsafe impl Sync for GlobalWarningCallback {}
+
+static GLOBAL_WARNING_CALLBACK: RwLock<Option<GlobalWarningCallback>> =
+    RwLock::new(None);
+
+/// Present a warning that doesn't have a delegate-specific handler to go to:
+/// via the callback installed with `SMS_set_warning_callback` if there is one,
+/// or to stderr if not.
+pub(crate) fn global_warning(message: &str) {
+    let installed = GLOBAL_WARNING_CALLBACK
+        .read()
+        .unwrap_or_else(|x| x.into_inner())
+        .as_ref()
+        .map(|x| (x.callback, x.callback_data));
+    match installed {
+        Some((callback, callback_data)) => {
+            let message = CString::new(message).unwrap();
+            unsafe { callback(callback_data, message.as_ptr()) }
+        }
+        None => eprintln!("SMS warning: {}", message),
+    }
+}
+
+#[no_mangle]
+extern "C" fn SMS_set_warning_callback(
+    callback: Option<WarningCallback>,
+    callback_data: *mut c_void,
+) {
+    *GLOBAL_WARNING_CALLBACK
+        .write()
+        .unwrap_or_else(|x| x.into_inner()) =
+        callback.map(|callback| GlobalWarningCallback {
+            callback,
+            callback_data,
+        });
+}
 
 struct ForeignSoundDelegate {
     callback_data: *mut c_void,
@@ -9,6 +55,7 @@ struct ForeignSoundDelegate {
         *const c_char,
     ) -> *mut FormattedSoundStream,
     warning_handler: Option<unsafe extern "C" fn(*mut c_void, *const c_char)>,
+    diagnostic_handler: Option<DiagnosticHandler>,
     free_handler: Option<unsafe extern "C" fn(*mut c_void)>,
 }
 unsafe impl Send for ForeignSoundDelegate {}
@@ -43,9 +90,23 @@ impl SoundDelegate for ForeignSoundDelegate {
                     (warning_handler)(self.callback_data, message.as_ptr());
                 }
             }
-            None => {
-                eprintln!("SMS warning: {}", message);
+            None => global_warning(message),
+        }
+    }
+    fn diagnostic(&self, level: DiagLevel, code: DiagCode, message: &str) {
+        match self.diagnostic_handler {
+            Some(diagnostic_handler) => {
+                let message = CString::new(message).unwrap();
+                unsafe {
+                    (diagnostic_handler)(
+                        self.callback_data,
+                        diag_level_to_int(level),
+                        diag_code_to_int(code),
+                        message.as_ptr(),
+                    );
+                }
             }
+            None => self.warning(message),
         }
     }
 }
@@ -68,6 +129,33 @@ extern "C" fn SMS_SoundDelegate_new(
         callback_data,
         file_open_handler,
         warning_handler,
+        diagnostic_handler: None,
+        free_handler,
+    })))
+}
+
+#[no_mangle]
+extern "C" fn SMS_SoundDelegate_new_with_diagnostics(
+    callback_data: *mut c_void,
+    file_open_handler: Option<
+        unsafe extern "C" fn(
+            *mut c_void,
+            *const c_char,
+        ) -> *mut FormattedSoundStream,
+    >,
+    warning_handler: Option<unsafe extern "C" fn(*mut c_void, *const c_char)>,
+    diagnostic_handler: Option<DiagnosticHandler>,
+    free_handler: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> *mut Arc<dyn SoundDelegate> {
+    let file_open_handler = file_open_handler.expect(
+        "SMS_SoundDelegate_new_with_diagnostics: file_open_handler cannot be \
+         NULL!",
+    );
+    Box::into_raw(Box::new(Arc::new(ForeignSoundDelegate {
+        callback_data,
+        file_open_handler,
+        warning_handler,
+        diagnostic_handler,
         free_handler,
     })))
 }
@@ -76,3 +164,80 @@ extern "C" fn SMS_SoundDelegate_new(
 extern "C" fn SMS_SoundDelegate_free(p: *mut Arc<dyn SoundDelegate>) {
     drop(unsafe { Box::from_raw(p) })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    type Heard = Mutex<Vec<(c_int, c_int, String)>>;
+
+    unsafe extern "C" fn open_nothing(
+        _: *mut c_void,
+        _: *const c_char,
+    ) -> *mut FormattedSoundStream {
+        null_mut()
+    }
+    unsafe extern "C" fn hear_warning(data: *mut c_void, msg: *const c_char) {
+        let heard = &*(data as *const Heard);
+        let msg = CStr::from_ptr(msg).to_string_lossy().into_owned();
+        heard.lock().unwrap().push((-1, -1, msg));
+    }
+    unsafe extern "C" fn hear_diagnostic(
+        data: *mut c_void,
+        level: c_int,
+        code: c_int,
+        msg: *const c_char,
+    ) {
+        let heard = &*(data as *const Heard);
+        let msg = CStr::from_ptr(msg).to_string_lossy().into_owned();
+        heard.lock().unwrap().push((level, code, msg));
+    }
+
+    /// Starts a flow that doesn't exist, with a delegate made by
+    /// `SMS_SoundDelegate_new_with_diagnostics`, and returns what it heard.
+    fn missing_flow(
+        diagnostic_handler: Option<DiagnosticHandler>,
+    ) -> Vec<(c_int, c_int, String)> {
+        let heard = Heard::default();
+        let delegate = SMS_SoundDelegate_new_with_diagnostics(
+            &heard as *const Heard as *mut c_void,
+            Some(open_nothing),
+            Some(hear_warning),
+            diagnostic_handler,
+            None,
+        );
+        let mut engine = Engine::new_with_runtime(
+            unsafe { (*delegate).clone() },
+            SpeakerLayout::Mono,
+            PosFloat::new(48000.0).unwrap(),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        );
+        SMS_SoundDelegate_free(delegate);
+        engine.start_flow(
+            "nowhere".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut [0.0; 1]);
+        drop(engine);
+        heard.into_inner().unwrap()
+    }
+
+    #[test]
+    fn diagnostics_reach_the_diagnostic_handler() {
+        let heard = missing_flow(Some(hear_diagnostic));
+        assert_eq!(heard.len(), 1, "{heard:?}");
+        assert_eq!(heard[0].0, SMS_DIAG_LEVEL_WARNING);
+        assert_eq!(heard[0].1, SMS_DIAG_CODE_MISSING_FLOW);
+        assert!(heard[0].2.contains("\"nowhere\""), "{heard:?}");
+        // without one, they go to the warning handler as before
+        let heard = missing_flow(None);
+        assert_eq!(heard.len(), 1, "{heard:?}");
+        assert_eq!(heard[0].0, -1);
+        assert!(heard[0].2.contains("\"nowhere\""), "{heard:?}");
+    }
+}
@@ -15,7 +15,7 @@ pub extern "C" fn SMS_Soundtrack_parse_new(
     match source_input(src, src_len).and_then(Soundtrack::from_source) {
         Ok(x) => Box::into_raw(Box::new(x)),
         Err(x) => {
-            output_error(&x, error_out, error_out_len);
+            output_string(&x, error_out, error_out_len);
             null_mut()
         }
     }
@@ -30,7 +30,7 @@ pub extern "C" fn SMS_Soundtrack_parse_new_str(
     match source_input_cstr(src).and_then(Soundtrack::from_source) {
         Ok(x) => Box::into_raw(Box::new(x)),
         Err(x) => {
-            output_error(&x, error_out, error_out_len);
+            output_string(&x, error_out, error_out_len);
             null_mut()
         }
     }
@@ -51,7 +51,7 @@ pub extern "C" fn SMS_Soundtrack_parse(
     {
         Ok(_) => 1,
         Err(x) => {
-            output_error(&x, error_out, error_out_len);
+            output_string(&x, error_out, error_out_len);
             0
         }
     }
@@ -71,12 +71,31 @@ pub extern "C" fn SMS_Soundtrack_parse_str(
     {
         Ok(_) => 1,
         Err(x) => {
-            output_error(&x, error_out, error_out_len);
+            output_string(&x, error_out, error_out_len);
             0
         }
     }
 }
 
+#[no_mangle]
+pub extern "C" fn SMS_Soundtrack_set_default_channel(
+    soundtrack: *mut Soundtrack,
+    channel: *const c_char,
+    channel_len: size_t,
+) {
+    let soundtrack = unsafe { soundtrack.as_mut() }.unwrap();
+    soundtrack.set_default_channel(input(channel, channel_len).unwrap());
+}
+
+#[no_mangle]
+pub extern "C" fn SMS_Soundtrack_set_default_channel_cstr(
+    soundtrack: *mut Soundtrack,
+    channel: *const c_char,
+) {
+    let soundtrack = unsafe { soundtrack.as_mut() }.unwrap();
+    soundtrack.set_default_channel(input_cstr(channel).unwrap());
+}
+
 #[no_mangle]
 pub extern "C" fn SMS_Soundtrack_free(p: *mut Soundtrack) {
     drop(unsafe { Box::from_raw(p) })
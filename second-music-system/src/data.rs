@@ -46,6 +46,44 @@ impl StringOrNumber {
             StringOrNumber::Number(n) => *n,
         }
     }
+    /// As `as_number`, except that a string that is an invalid number
+    /// results in `None` instead of NaN.
+    pub fn try_as_number(&self) -> Option<f32> {
+        match self {
+            StringOrNumber::String(s) => {
+                if s.is_empty() {
+                    Some(0.0)
+                } else {
+                    s.parse().ok()
+                }
+            }
+            StringOrNumber::Number(n) => Some(*n),
+        }
+    }
+    /// When interpreting this as an integer:
+    /// - Empty string: zero
+    /// - String or number that is exactly an integer (that fits in an `i64`):
+    ///   that integer
+    /// - Anything else (fractions, NaN, infinities, non-numbers): `None`
+    pub fn as_i64(&self) -> Option<i64> {
+        if let StringOrNumber::String(s) = self {
+            // (this way, big integers don't get rounded off by `f32`)
+            if let Ok(x) = s.parse() {
+                return Some(x);
+            }
+        }
+        let n = self.try_as_number()?;
+        // `i64::MAX as f32` rounds up to 2^63, which is out of range
+        if n.fract() == 0.0 && n >= i64::MIN as f32 && n < i64::MAX as f32 {
+            Some(n as i64)
+        } else {
+            None
+        }
+    }
+    /// When interpreting this as a boolean. Same as `is_truthy`.
+    pub fn as_bool(&self) -> bool {
+        self.is_truthy()
+    }
     /// When interpreting this as a string:
     /// - Any string: that string
     /// - Any number: that number, rendered with default formatting, as a string
@@ -131,12 +169,62 @@ pub(crate) struct Sound {
     /// cached. (If some sounds request that it be streamed and others request
     /// that it be cached, whether it is streamed or cached is undefined.)
     pub(crate) stream: bool,
+    /// How fast to play the sound back. 1.0 is as authored, 2.0 is twice as
+    /// fast (an octave up), 0.5 is half as fast (an octave down). `start` and
+    /// `end` are in the sound's own time, so the sound takes
+    /// `(end - start) / pitch` seconds to play.
+    pub(crate) pitch: PosFloat,
+    /// A fixed volume the sound is always played at, e.g. to bring a sound
+    /// that was recorded too loud in line with the others. 1.0 is as
+    /// authored.
+    pub(crate) gain: PosFloat,
+    /// When the sound is repeated (see `SequenceElement::PlaySound`), how
+    /// many seconds each repetition overlaps the next by, crossfading from
+    /// one into the other to hide the seam.
+    pub(crate) loop_crossfade: PosFloat,
+    /// Named parts of the sound (e.g. an intro, a loop, and an outro), each
+    /// with its own start and (if known) end, in the same terms as `start`
+    /// and `end`. Each one is also in the soundtrack as a sound of its own;
+    /// see `region_sound_name`.
+    pub(crate) regions: HashMap<CompactString, (PosFloat, Option<PosFloat>)>,
+    /// Editor annotations. See `Soundtrack::parse_source_with_metadata`.
+    pub(crate) metadata: HashMap<String, String>,
+}
+
+/// Returns the name of the sound that plays the given region of the given
+/// sound. (`play sound X region Y` plays the sound named `X[Y]`.)
+pub(crate) fn region_sound_name(sound: &str, region: &str) -> CompactString {
+    format!("{sound}[{region}]").to_compact_string()
 }
 
 impl Sound {
+    /// Returns a copy of this sound that only plays the given region of it.
+    pub(crate) fn region_sound(
+        &self,
+        region: &str,
+        start: PosFloat,
+        end: Option<PosFloat>,
+    ) -> Sound {
+        let end_lock = OnceLock::new();
+        if let Some(end) = end {
+            end_lock.set(end).unwrap();
+        }
+        Sound {
+            name: region_sound_name(&self.name, region),
+            path: self.path.clone(),
+            start,
+            end: end_lock,
+            stream: self.stream,
+            pitch: self.pitch,
+            gain: self.gain,
+            loop_crossfade: self.loop_crossfade,
+            regions: HashMap::new(),
+            metadata: HashMap::new(),
+        }
+    }
     pub(crate) fn get_end(&self, delegate: &dyn SoundDelegate) -> PosFloat {
         *self.end.get_or_init(|| {
-            delegate.warning(&format!("The length of sound {:?} is needed, but was not specified in the soundtrack, and could not be retrieved because the sound is marked for streaming. Set the length manually or disable streaming.", self.name));
+            delegate.diagnostic(DiagLevel::Warning, DiagCode::UnknownLength, &format!("The length of sound {:?} is needed, but was not specified in the soundtrack, and could not be retrieved because the sound is marked for streaming. Set the length manually or disable streaming.", self.name));
             PosFloat::ONE
         })
     }
@@ -147,6 +235,17 @@ pub(crate) enum SequenceElement {
     PlaySound {
         sound: CompactString,
         channel: CompactString, // default is `main`
+        /// Other channels this sound is also sent to, and at what level. The
+        /// send level is independent of the volume of `channel`. (A send to
+        /// a channel with no effect on it just adds to the output.)
+        sends: Vec<(CompactString, PosFloat)>,
+        /// Where to put the sound between the left and right speakers, from
+        /// -1.0 (full left) to 1.0 (full right). Only applies to stereo (and
+        /// headphone) output.
+        pan: f32,
+        /// Where the sound is in the world, if it's positional. See
+        /// `EngineCommands::set_listener`.
+        position: Option<[f32; 3]>,
         /// How many seconds of fade-in between starting and becoming full
         /// volume
         fade_in: PosFloat,
@@ -156,21 +255,58 @@ pub(crate) enum SequenceElement {
         /// (whereas in the format, this is how long before `end` that the fade
         /// will *start*)
         fade_out: PosFloat,
+        /// How many seconds early to start the sound, so that a slow attack
+        /// can land on the beat. (Never earlier than the sequence itself
+        /// starts.)
+        lead: PosFloat,
+        /// Up to how many seconds, early or late, to randomly move the sound
+        /// by each time it's played. (Also never earlier than the sequence
+        /// itself starts.)
+        humanize_time: PosFloat,
+        /// Up to how much (from 0.0 to 1.0) to randomly lower the sound's
+        /// volume by each time it's played.
+        humanize_volume: PosFloat,
+        /// How many times to play the sound, back to back. (At least 1.) Each
+        /// time lasts for `length` plus `fade_out`, if there's a `length`, or
+        /// for the whole sound, if not. Only the first time fades in, and
+        /// only the last time fades out. If the sound has a `loop_crossfade`,
+        /// each time after the first starts that much earlier, and they
+        /// crossfade into each other with an equal-power curve (which is then
+        /// used for `fade_in` and `fade_out` too).
+        repeat: u32,
+        /// If present, `sound` is only played if this condition is true when
+        /// the sequence is played. Otherwise, the `else` sound (if any) is
+        /// played in its place.
+        condition: Option<SoundCondition>,
     },
     PlaySequence {
         sequence: CompactString,
     },
 }
 
+/// The `if` part of a `play sound if CONDITION then SOUND else SOUND`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SoundCondition {
+    pub(crate) condition: Vec<PredicateOp>,
+    pub(crate) else_sound: Option<CompactString>,
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct Sequence {
     // unique within a soundtrack
     pub(crate) name: CompactString,
     pub(crate) length: PosFloat,
     pub(crate) elements: Vec<(PosFloat, SequenceElement)>,
+    /// Editor annotations. See `Soundtrack::parse_source_with_metadata`.
+    pub(crate) metadata: HashMap<String, String>,
 }
 
 impl Sequence {
+    /// Returns how long this Sequence lasts, in seconds, i.e. its `length`.
+    /// (Sounds it plays may ring on past the end.)
+    pub fn duration(&self) -> PosFloat {
+        self.length
+    }
     /// Call the given handlers at least once with every sound or sequence
     /// directly used by this Sequence.
     pub fn find_all_direct_dependencies<A, B>(
@@ -183,7 +319,16 @@ impl Sequence {
     {
         for (_time, element) in self.elements.iter() {
             match element {
-                SequenceElement::PlaySound { sound, .. } => found_sound(sound),
+                SequenceElement::PlaySound {
+                    sound, condition, ..
+                } => {
+                    found_sound(sound);
+                    if let Some(else_sound) =
+                        condition.as_ref().and_then(|x| x.else_sound.as_ref())
+                    {
+                        found_sound(else_sound);
+                    }
+                }
                 SequenceElement::PlaySequence { sequence } => {
                     found_sequence(sequence)
                 }
@@ -198,13 +343,18 @@ pub(crate) enum Command {
     Done,
     /// Wait a certain number of seconds.
     Wait(PosFloat),
+    /// Wait until the given condition is true (`wait until $door_open`). It's
+    /// checked again whenever a FlowControl changes.
+    WaitForCondition(Vec<PredicateOp>),
     /// Start a Sound playing (even if another instance of that sound is
-    /// already playing)
-    PlaySound(CompactString),
+    /// already playing), at the given gain (`play sound X at 0.7`, 1.0 if not
+    /// given). The gain is multiplied with the sound's flow, node, and
+    /// MixControl volumes, in that order; none of them affect each other.
+    PlaySound(CompactString, PosFloat),
     /// Acts like `PlaySound` followed by `Wait`, but the amount of waiting
     /// depends on the length of the named sound (information about which may
     /// not be available at parse time).
-    PlaySoundAndWait(CompactString),
+    PlaySoundAndWait(CompactString, PosFloat),
     /// Start a Sequence playing (even if another instance of that sequence
     /// is already playing)
     PlaySequence(CompactString),
@@ -219,6 +369,15 @@ pub(crate) enum Command {
     RestartNode(CompactString),
     /// As `RestartNode(the starting node)`
     RestartFlow,
+    /// Fade a Node of this Flow (`None` for the starting node) to the given
+    /// volume, over the given number of seconds, on the given curve. (See
+    /// `EngineCommands::fade_node_to`.)
+    FadeNode {
+        node: Option<CompactString>,
+        volume: PosFloat,
+        length: PosFloat,
+        fade_type: FadeType,
+    },
     /// Change a FlowControl to a new value.
     Set(CompactString, Vec<PredicateOp>),
     /// If/else chain. **INTERMEDIATE PARSING STEP ONLY, MUST NOT OCCUR IN THE
@@ -237,10 +396,48 @@ pub(crate) enum Command {
     Placeholder,
 }
 
+/// How a node's volume follows its flow's layer intensity. (See
+/// `EngineCommands::set_layer_intensity`.)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LayerCurve {
+    /// Full volume at or above the given intensity, silent below it.
+    Above(f32),
+    /// Full volume below the given intensity, silent at or above it.
+    Below(f32),
+    /// Silent at the first intensity, full volume at the second, and a
+    /// straight line in between.
+    Ramp(f32, f32),
+}
+
+impl LayerCurve {
+    /// Returns the volume a node with this curve should have at the given
+    /// intensity.
+    pub fn volume_at(&self, intensity: f32) -> PosFloat {
+        match *self {
+            LayerCurve::Above(threshold) if intensity >= threshold => {
+                PosFloat::ONE
+            }
+            LayerCurve::Below(threshold) if intensity < threshold => {
+                PosFloat::ONE
+            }
+            LayerCurve::Above(_) | LayerCurve::Below(_) => PosFloat::ZERO,
+            LayerCurve::Ramp(silent, full) => PosFloat::new_clamped(
+                ((intensity - silent) / (full - silent)).min(1.0),
+            ),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub(crate) struct Node {
     pub(crate) name: Option<CompactString>,
     pub(crate) commands: Vec<Command>,
+    /// If this node is a layer, how its volume follows its flow's layer
+    /// intensity.
+    pub(crate) layer: Option<LayerCurve>,
+    /// Editor annotations. See `Soundtrack::parse_source_with_metadata`.
+    /// (Always empty for a starting node.)
+    pub(crate) metadata: HashMap<String, String>,
 }
 
 impl Node {
@@ -248,6 +445,85 @@ impl Node {
         Node {
             name: None,
             commands: vec![],
+            layer: None,
+            metadata: HashMap::new(),
+        }
+    }
+    /// Returns the indices of the commands in this node that can never run,
+    /// because every way to them is cut off by a `Done` or an unconditional
+    /// `Goto`. The `Goto`s left behind by `flatten_commands`, and the `Done`
+    /// it puts on the end, don't count.
+    pub fn unreachable_commands(&self) -> Vec<usize> {
+        let mut reachable = vec![false; self.commands.len()];
+        let mut to_visit = vec![0];
+        while let Some(n) = to_visit.pop() {
+            if n >= self.commands.len() || reachable[n] {
+                continue;
+            }
+            reachable[n] = true;
+            match &self.commands[n] {
+                Command::Done => (),
+                Command::Goto(condition, true, target)
+                    if condition.is_empty() =>
+                {
+                    to_visit.push(*target)
+                }
+                Command::Goto(_, _, target) => {
+                    to_visit.push(*target);
+                    to_visit.push(n + 1);
+                }
+                _ => to_visit.push(n + 1),
+            }
+        }
+        let last = self.commands.len().saturating_sub(1);
+        reachable
+            .into_iter()
+            .enumerate()
+            .filter(|&(n, reachable)| {
+                !reachable
+                    && n != last
+                    && !matches!(self.commands[n], Command::Goto(..))
+            })
+            .map(|(n, _)| n)
+            .collect()
+    }
+    /// Append a human-readable listing of this node's (flattened) commands to
+    /// `out`, one per line, prefixed with their indices. Every index that a
+    /// `Goto` jumps to gets a label line (`L<index>:`) above it, and each
+    /// `Goto` names its target by that label.
+    pub fn dump(&self, out: &mut String) {
+        use std::fmt::Write;
+        let targets: HashSet<usize> = self
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                Command::Goto(_, _, target) => Some(*target),
+                _ => None,
+            })
+            .collect();
+        for index in 0..=self.commands.len() {
+            if targets.contains(&index) {
+                writeln!(out, "  L{index}:").unwrap();
+            }
+            let Some(command) = self.commands.get(index) else {
+                break;
+            };
+            write!(out, "    {index:>3}  ").unwrap();
+            match command {
+                Command::Goto(condition, _, target)
+                    if condition.is_empty() =>
+                {
+                    writeln!(out, "goto L{target}")
+                }
+                Command::Goto(condition, true, target) => {
+                    writeln!(out, "goto L{target} if {condition:?}")
+                }
+                Command::Goto(condition, false, target) => {
+                    writeln!(out, "goto L{target} unless {condition:?}")
+                }
+                other => writeln!(out, "{other:?}"),
+            }
+            .unwrap();
         }
     }
 }
@@ -259,6 +535,8 @@ pub(crate) struct Flow {
     pub(crate) start_node: Arc<Node>,
     pub(crate) nodes: HashMap<CompactString, Arc<Node>>,
     pub(crate) autoloop: bool,
+    /// Editor annotations. See `Soundtrack::parse_source_with_metadata`.
+    pub(crate) metadata: HashMap<String, String>,
 }
 
 impl Flow {
@@ -279,7 +557,7 @@ impl Flow {
             for command in node.commands.iter() {
                 use Command::*;
                 match command {
-                    PlaySound(x) | PlaySoundAndWait(x) => {
+                    PlaySound(x, _) | PlaySoundAndWait(x, _) => {
                         found_sound(x)
                     },
                     PlaySequence(x) | PlaySequenceAndWait(x) => {
@@ -292,71 +570,238 @@ impl Flow {
             }
         }
     }
-    /// Return a Vec containing every Sound used by this Flow, directly or
-    /// indirectly. Calls the `missing_sound` and `missing_sequence` functions
-    /// exactly once for each sound or sequence that is referred to, but not
-    /// (currently) present within the Soundtrack.
-    pub fn find_all_sounds<A, B>(
+    /// Call the given handlers exactly once with the name of every Sound and
+    /// Sequence used by this Flow, directly or indirectly. `found_sequence`
+    /// is also given the Sequence itself, or `None` if it is not (currently)
+    /// present within the Soundtrack.
+    pub fn find_all_dependencies<A, B>(
         &self,
         soundtrack: &Soundtrack,
-        mut missing_sound: A,
-        mut missing_sequence: B,
-    ) -> Vec<Arc<Sound>>
-    where
+        mut found_sound: A,
+        mut found_sequence: B,
+    ) where
         A: FnMut(&str),
-        B: FnMut(&str),
+        B: FnMut(&str, Option<&Sequence>),
     {
         let mut found_sounds = HashSet::new();
         let mut found_sequences = HashSet::new();
-        let mut found_sound;
-        let mut found_sequence;
         let mut indirects = Vec::with_capacity(soundtrack.sequences.len());
-        found_sound = |sound_name: &str| {
+        let mut found_sound = |sound_name: &str| {
             if !found_sounds.contains(sound_name) {
                 found_sounds.insert(sound_name.to_compact_string());
-                if !soundtrack.sounds.contains_key(sound_name) {
-                    missing_sound(sound_name);
-                }
+                found_sound(sound_name);
             }
         };
-        found_sequence = |sequence_name: &str| {
+        let mut found_sequence_name = |sequence_name: &str| {
             if !found_sequences.contains(sequence_name) {
                 found_sequences.insert(sequence_name.to_compact_string());
                 indirects.push(sequence_name.to_compact_string());
-                if !soundtrack.sequences.contains_key(sequence_name) {
-                    missing_sequence(sequence_name);
-                }
             }
         };
         self.find_all_direct_dependencies(
             &mut found_sound,
-            &mut found_sequence,
+            &mut found_sequence_name,
         );
         let mut n = 0;
         while n < indirects.len() {
-            if let Some(sequence) = soundtrack.sequences.get(&indirects[n]) {
-                let mut found_sequence = |sequence_name: &str| {
+            let sequence = soundtrack.sequences.get(&indirects[n]);
+            found_sequence(&indirects[n], sequence.map(Arc::as_ref));
+            if let Some(sequence) = sequence {
+                let mut found_sequence_name = |sequence_name: &str| {
                     if !found_sequences.contains(sequence_name) {
                         found_sequences
                             .insert(sequence_name.to_compact_string());
                         indirects.push(sequence_name.to_compact_string());
-                        if !soundtrack.sequences.contains_key(sequence_name) {
-                            missing_sequence(sequence_name);
-                        }
                     }
                 };
                 sequence.find_all_direct_dependencies(
                     &mut found_sound,
-                    &mut found_sequence,
+                    &mut found_sequence_name,
                 )
             }
             n += 1;
         }
-        found_sounds
-            .into_iter()
-            .filter_map(|k| soundtrack.sounds.get(&k).cloned())
-            .collect()
     }
+    /// Return a Vec containing every Sound used by this Flow, directly or
+    /// indirectly. Calls the `missing_sound` and `missing_sequence` functions
+    /// exactly once for each sound or sequence that is referred to, but not
+    /// (currently) present within the Soundtrack.
+    pub fn find_all_sounds<A, B>(
+        &self,
+        soundtrack: &Soundtrack,
+        mut missing_sound: A,
+        mut missing_sequence: B,
+    ) -> Vec<Arc<Sound>>
+    where
+        A: FnMut(&str),
+        B: FnMut(&str),
+    {
+        let mut sounds = vec![];
+        self.find_all_dependencies(
+            soundtrack,
+            |sound_name| match soundtrack.sounds.get(sound_name) {
+                Some(sound) => sounds.push(sound.clone()),
+                None => missing_sound(sound_name),
+            },
+            |sequence_name, sequence| {
+                if sequence.is_none() {
+                    missing_sequence(sequence_name);
+                }
+            },
+        );
+        sounds
+    }
+    /// Return the names of every channel (i.e. MixControl) that sounds used
+    /// by this Flow, directly or indirectly, will play on or be sent to.
+    /// Sounds played directly by nodes, and sequence elements that don't
+    /// specify a channel, play on the soundtrack's default channel.
+    pub fn referenced_channels(
+        &self,
+        soundtrack: &Soundtrack,
+    ) -> HashSet<CompactString> {
+        let mut channels = HashSet::new();
+        self.find_all_direct_dependencies(
+            |_| {
+                channels.insert(soundtrack.default_channel.clone());
+            },
+            |_| (),
+        );
+        self.find_all_dependencies(
+            soundtrack,
+            |_| (),
+            |_, sequence| {
+                let Some(sequence) = sequence else { return };
+                for (_time, element) in sequence.elements.iter() {
+                    if let SequenceElement::PlaySound {
+                        channel, sends, ..
+                    } = element
+                    {
+                        channels.insert(channel.clone());
+                        channels.extend(
+                            sends.iter().map(|(channel, _)| channel.clone()),
+                        );
+                    }
+                }
+            },
+        );
+        channels
+    }
+    /// Estimates the longest this Flow can run for, in seconds: from when it
+    /// starts, to when the last of its nodes finishes, taking the longest way
+    /// through every `if`. This counts `wait`s, `play ... and wait`s (using
+    /// the sound's length, if it's known yet), and nodes started by other
+    /// nodes. It doesn't count sounds or sequences left playing after the
+    /// last node finishes, or time scaling.
+    ///
+    /// This is a best-effort estimate, for things like drawing timelines.
+    /// Returns `None` if there's no telling: if the flow loops (by
+    /// `with loop`, `restart flow`, jumping back, or nodes that start each
+    /// other), waits for a condition, or waits for a sound whose length
+    /// isn't known.
+    pub fn max_duration(&self, soundtrack: &Soundtrack) -> Option<PosFloat> {
+        if self.autoloop {
+            return None;
+        }
+        self.node_max_duration(
+            &self.start_node,
+            soundtrack,
+            &mut HashMap::new(),
+        )
+    }
+    /// `max_duration` for one node, and the nodes it starts. `durations`
+    /// holds the nodes already worked out, and `None` for those being worked
+    /// out right now (which, if we see them again, are starting themselves).
+    fn node_max_duration<'a>(
+        &'a self,
+        node: &'a Node,
+        soundtrack: &Soundtrack,
+        durations: &mut HashMap<Option<&'a str>, Option<PosFloat>>,
+    ) -> Option<PosFloat> {
+        let key = node.name.as_deref();
+        if let Some(duration) = durations.get(&key) {
+            return *duration;
+        }
+        durations.insert(key, None);
+        // How long it'll be from each command until this node, and any node
+        // it starts from there on, is finished. Worked out back to front,
+        // which works because flattened `if`s only ever jump forward.
+        let len = node.commands.len();
+        let mut from = vec![PosFloat::ZERO; len + 1];
+        for (index, command) in node.commands.iter().enumerate().rev() {
+            let next = from[index + 1];
+            from[index] = match command {
+                Command::Done => PosFloat::ZERO,
+                Command::Wait(time) => *time + next,
+                Command::PlaySoundAndWait(sound, _) => {
+                    match soundtrack.sounds.get(sound) {
+                        Some(sound) => {
+                            sound.end.get()?.saturating_sub(sound.start)
+                                / sound.pitch
+                                + next
+                        }
+                        // (nothing to wait for)
+                        None => next,
+                    }
+                }
+                Command::PlaySequenceAndWait(sequence) => {
+                    match soundtrack.sequences.get(sequence) {
+                        Some(sequence) => sequence.duration() + next,
+                        None => next,
+                    }
+                }
+                Command::StartNode(name) | Command::RestartNode(name) => {
+                    match self.nodes.get(name) {
+                        Some(started) => self
+                            .node_max_duration(started, soundtrack, durations)?
+                            .max(next),
+                        None => next,
+                    }
+                }
+                Command::WaitForCondition(_) | Command::RestartFlow => {
+                    return None
+                }
+                Command::Goto(_, _, target) if *target <= index => {
+                    return None
+                }
+                Command::Goto(condition, true, target)
+                    if condition.is_empty() =>
+                {
+                    from[(*target).min(len)]
+                }
+                Command::Goto(_, _, target) => {
+                    from[(*target).min(len)].max(next)
+                }
+                _ => next,
+            };
+        }
+        durations.insert(key, Some(from[0]));
+        Some(from[0])
+    }
+    /// Returns a human-readable listing of every node in this flow, starting
+    /// node first and the rest in alphabetical order. See `Node::dump`.
+    pub fn dump(&self) -> String {
+        let mut out = format!("flow {:?}\n", self.name);
+        out.push_str("start node:\n");
+        self.start_node.dump(&mut out);
+        let mut names: Vec<&CompactString> = self.nodes.keys().collect();
+        names.sort();
+        for name in names {
+            out.push_str(&format!("node {:?}:\n", name));
+            self.nodes[name].dump(&mut out);
+        }
+        out
+    }
+}
+
+/// A named set of FlowControl values, which can be applied all at once.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Preset {
+    // unique within a soundtrack
+    pub(crate) name: CompactString,
+    /// The FlowControls to set, and the expressions that give their new
+    /// values. The expressions are all evaluated before any of the
+    /// FlowControls are changed.
+    pub(crate) sets: Vec<(CompactString, Vec<PredicateOp>)>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -435,6 +880,29 @@ pub(crate) enum PredicateOp {
     Sign,
     /// Pop one element, push its negation.
     Negate,
+    /// Pop three elements, push the third from the top, limited to be no less
+    /// than the second from the top and no greater than the top.
+    Clamp,
+    /// Pop three elements, push the interpolation between the third from the
+    /// top (at 0) and the second from the top (at 1), with the top as the
+    /// interpolation factor.
+    Lerp,
+    /// Push a random number, at least 0 and less than 1.
+    Random,
+    /// Pop two elements, push the second from the top with the top appended
+    /// to it, as strings.
+    Concat,
+    /// Pop two elements, push whether the second from the top, as a string,
+    /// starts with the top, as a string.
+    StartsWith,
+    /// Pop two elements, push whether the second from the top, as a string,
+    /// contains the top, as a string.
+    Contains,
+    /// Pop one element, push the number of characters in it, as a string.
+    Length,
+    /// Pop three elements, push the second from the top if the third from the
+    /// top is truthy, or the top otherwise.
+    Select,
 }
 
 #[cfg(test)]
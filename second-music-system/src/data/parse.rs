@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, mem::take};
 
 use super::*;
 
@@ -13,7 +13,9 @@ use timebase::*;
 #[cfg(test)]
 mod test;
 
-const SOUND_TIME_KEYWORDS: &[&str] = &["timebase", "start", "end", "length"];
+const SOUND_TIME_KEYWORDS: &[&str] =
+    &["timebase", "start", "end", "length", "loop_crossfade"];
+const REGION_TIME_KEYWORDS: &[&str] = &["start", "end", "length"];
 
 impl Sound {
     /// Parse a `Sound` from a `DinNode`. This `DinNode` might be an "outline
@@ -63,6 +65,38 @@ impl Sound {
             }
         }
         let offset = offset.unwrap_or(PosFloat::ZERO);
+        let mut pitch = PosFloat::ONE;
+        if let Some(child) = node.consume_optional_prefixed_child("pitch")? {
+            match child
+                .items
+                .get(1)
+                .and_then(|x| x.parse().ok())
+                .and_then(|x| PosFloat::new(x).ok())
+            {
+                Some(value) if value > PosFloat::ZERO && value.is_finite() => {
+                    pitch = value
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}: pitch must be a positive number",
+                        child.lineno
+                    ))
+                }
+            }
+        }
+        let mut gain = PosFloat::ONE;
+        if let Some(child) = node.consume_optional_prefixed_child("gain")? {
+            match child.items.get(1).and_then(|x| parse_volume(x)) {
+                Some(value) => gain = value,
+                None => {
+                    return Err(format!(
+                        "line {}: gain must be a number, 0 or more, or a \
+                         number of decibels (e.g. \"-6db\")",
+                        child.lineno
+                    ))
+                }
+            }
+        }
         let start = match time_data.get("start") {
             Some(x) => *x + offset,
             None => PosFloat::ZERO,
@@ -79,6 +113,57 @@ impl Sound {
             (None, Some(x)) => Some(start + *x),
             (None, None) => None,
         };
+        let loop_crossfade = time_data
+            .get("loop_crossfade")
+            .copied()
+            .unwrap_or(PosFloat::ZERO);
+        let mut regions = HashMap::new();
+        for mut child in node.consume_prefixed_children("region") {
+            let lineno = child.lineno;
+            let mut region_name = None;
+            parse_din_node!(child, "region" region_name=*)?;
+            let mut region_data = HashMap::new();
+            for child in
+                child.consume_designated_children(REGION_TIME_KEYWORDS)
+            {
+                let time = timebases.parse_time_node(&child)?;
+                if region_data.insert(child.items[0].clone(), time).is_some() {
+                    return Err(format!(
+                        "line {}: only one {:?} parameter allowed",
+                        child.lineno, child.items[0]
+                    ));
+                }
+            }
+            child.finish_parsing_children()?;
+            let region_start = match region_data.get("start") {
+                Some(x) => *x + offset,
+                None => PosFloat::ZERO,
+            };
+            let region_end =
+                match (region_data.get("end"), region_data.get("length")) {
+                    (Some(_), Some(_)) => {
+                        return Err(format!(
+                        "line {lineno}: only one of \"end\" and \"length\" \
+                             may be specified, not both"
+                    ))
+                    }
+                    (Some(x), None) => Some(*x + offset),
+                    (None, Some(x)) => Some(region_start + *x),
+                    // (the rest of the sound)
+                    (None, None) => end,
+                };
+            let region_name = region_name.unwrap();
+            if regions
+                .insert(region_name.clone(), (region_start, region_end))
+                .is_some()
+            {
+                return Err(format!(
+                    "line {lineno}: there is already a region named \
+                     {region_name:?} in this sound"
+                ));
+            }
+        }
+        let metadata = take(&mut node.metadata);
         node.finish_parsing_children()?;
         let path = match path {
             Some(path) => path.to_compact_string(),
@@ -99,6 +184,11 @@ impl Sound {
             start,
             end: end_lock,
             stream,
+            pitch,
+            gain,
+            loop_crossfade,
+            regions,
+            metadata,
         })
     }
 }
@@ -134,18 +224,21 @@ impl Sequence {
                 _ => unreachable!(),
             }
         }
+        let metadata = take(&mut node.metadata);
         node.finish_parsing_children()?;
         elements.sort_by(|a, b| a.0.cmp(&b.0));
         Ok(Sequence {
             name,
             length,
             elements,
+            metadata,
         })
     }
 }
 
-const SOUND_ELEMENT_TIME_KEYWORDS: &[&str] =
-    &["timebase", "at", "for", "until", "fade_in", "fade_out"];
+const SOUND_ELEMENT_TIME_KEYWORDS: &[&str] = &[
+    "timebase", "at", "for", "until", "fade_in", "fade_out", "lead",
+];
 
 const SEQUENCE_ELEMENT_TIME_KEYWORDS: &[&str] = &["timebase", "at"];
 
@@ -157,10 +250,44 @@ impl SequenceElement {
         sequence_name: &str,
     ) -> Result<(PosFloat, SequenceElement), String> {
         let lineno = node.lineno;
+        let mut condition = None;
+        if node.items.get(1).map(String::as_str) == Some("sound")
+            && node.items.get(2).map(String::as_str) == Some("if")
+        {
+            let (ops, rest) = parse_condition(&node.items[3..])
+                .map_err(|x| format!("line {lineno}: {x}"))?;
+            let (then_sound, else_sound) = match rest {
+                [then_sound] => (then_sound.clone(), None),
+                [then_sound, else_, else_sound] if else_ == "else" => {
+                    (then_sound.clone(), Some(else_sound.to_compact_string()))
+                }
+                _ => {
+                    return Err(format!(
+                        "line {lineno}: a conditional sound must look like \
+                         `play sound if CONDITION then SOUND else SOUND` \
+                         (the `else` part is optional)"
+                    ))
+                }
+            };
+            condition = Some(SoundCondition {
+                condition: ops,
+                else_sound,
+            });
+            // (the rest is parsed as if it were a plain `play sound`)
+            node.items =
+                vec!["play".to_string(), "sound".to_string(), then_sound];
+        }
         let mut element_type = None;
         let mut name = None;
-        parse_din_node!(node, "play" element_type=("sound"|"sequence") [name=*])?;
+        let mut region: Option<CompactString> = None;
+        parse_din_node!(node, "play" element_type=("sound"|"sequence") [name=*] ["region" region=*])?;
         let element_type = element_type.unwrap();
+        if region.is_some() && (element_type != "sound" || name.is_none()) {
+            return Err(format!(
+                "line {}: only named sounds can have a region played",
+                node.lineno
+            ));
+        }
         let time_keywords = match element_type.as_str() {
             "sound" => SOUND_ELEMENT_TIME_KEYWORDS,
             "sequence" => SEQUENCE_ELEMENT_TIME_KEYWORDS,
@@ -169,8 +296,126 @@ impl SequenceElement {
         let mut timebases = timebases.make_child();
         let mut data = HashMap::new();
         let mut channel = None;
+        let mut sends = vec![];
+        let mut pan = 0.0;
+        let mut position = None;
+        let mut humanize_time = PosFloat::ZERO;
+        let mut humanize_volume = PosFloat::ZERO;
+        let mut repeat = 1;
         if element_type == "sound" {
             parse_optional_prefixed_child!(node, "channel" channel=*)?;
+            for child in node.consume_prefixed_children("send") {
+                let level = match child.items.as_slice() {
+                    [_, _, level] => level
+                        .parse()
+                        .ok()
+                        .and_then(|x| PosFloat::new(x).ok())
+                        .filter(|x| x.is_finite()),
+                    _ => {
+                        return Err(format!(
+                            "line {}: \"send\" must be followed by a channel \
+                             name and a level",
+                            child.lineno
+                        ))
+                    }
+                };
+                let Some(level) = level else {
+                    return Err(format!(
+                        "line {}: send level must be a non-negative number",
+                        child.lineno
+                    ));
+                };
+                sends.push((child.items[1].to_compact_string(), level));
+            }
+            if let Some(child) = node.consume_optional_prefixed_child("pan")? {
+                match child.items.get(1).and_then(|x| x.parse::<f32>().ok()) {
+                    Some(value) if (-1.0..=1.0).contains(&value) => {
+                        pan = value
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {}: pan must be a number from -1 to 1",
+                            child.lineno
+                        ))
+                    }
+                }
+            }
+            if let Some(child) =
+                node.consume_optional_prefixed_child("position")?
+            {
+                let coordinates: Option<Vec<f32>> = child.items[1..]
+                    .iter()
+                    .map(|x| x.parse().ok().filter(|x: &f32| x.is_finite()))
+                    .collect();
+                match coordinates.as_deref() {
+                    Some(&[x, y, z]) => position = Some([x, y, z]),
+                    _ => {
+                        return Err(format!(
+                            "line {}: position must be three numbers (X, Y, \
+                             and Z)",
+                            child.lineno
+                        ))
+                    }
+                }
+                if pan != 0.0 {
+                    return Err(format!(
+                        "line {}: a sound can't have both a pan and a \
+                         position",
+                        child.lineno
+                    ));
+                }
+            }
+            if let Some(child) =
+                node.consume_optional_prefixed_child("humanize")?
+            {
+                let amounts: Option<Vec<PosFloat>> = child.items[1..]
+                    .iter()
+                    .map(|x| {
+                        x.parse()
+                            .ok()
+                            .and_then(|x| PosFloat::new(x).ok())
+                            .filter(|x| x.is_finite())
+                    })
+                    .collect();
+                match amounts.as_deref() {
+                    Some(&[time]) => humanize_time = time,
+                    Some(&[time, volume]) if volume <= PosFloat::ONE => {
+                        humanize_time = time;
+                        humanize_volume = volume;
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {}: \"humanize\" must be followed by a \
+                             number of seconds, and optionally a volume from \
+                             0 to 1",
+                            child.lineno
+                        ))
+                    }
+                }
+            }
+            if let Some(child) =
+                node.consume_optional_prefixed_child("repeat")?
+            {
+                match child.items.as_slice() {
+                    [_, count] => match count.parse::<u32>() {
+                        Ok(count) if count > 0 => repeat = count,
+                        _ => {
+                            return Err(format!(
+                                "line {}: \"repeat\" must be followed by a \
+                                 whole number of times, 1 or more",
+                                child.lineno
+                            ))
+                        }
+                    },
+                    _ => {
+                        return Err(format!(
+                            "line {}: \"repeat\" must be followed by a \
+                             number of times",
+                            child.lineno
+                        ))
+                    }
+                }
+            }
         }
         for child in node.consume_designated_children(time_keywords) {
             if child.items[0] == "timebase" {
@@ -204,7 +449,7 @@ impl SequenceElement {
                 "sound" => {
                     let sound =
                         Sound::parse_din_node(node, &timebases, name.clone())?;
-                    soundtrack.sounds.insert(name.clone(), Arc::new(sound));
+                    soundtrack.insert_sound(sound);
                 }
                 "sequence" => {
                     let sequence = Sequence::parse_din_node(
@@ -225,7 +470,7 @@ impl SequenceElement {
         let channel = channel
             .as_ref()
             .map(CompactString::as_str)
-            .unwrap_or("main")
+            .unwrap_or(&soundtrack.default_channel)
             .to_compact_string();
         let start = match data.get("at") {
             Some(x) => *x,
@@ -235,6 +480,10 @@ impl SequenceElement {
             Some(x) => *x,
             None => PosFloat::ZERO,
         };
+        let lead = match data.get("lead") {
+            Some(x) => *x,
+            None => PosFloat::ZERO,
+        };
         let length = match (data.get("for"), data.get("until")) {
             (Some(_), Some(_)) => {
                 return Err(format!(
@@ -256,11 +505,22 @@ impl SequenceElement {
             "sound" => Ok((
                 start,
                 SequenceElement::PlaySound {
-                    sound: name,
+                    sound: match region {
+                        Some(region) => region_sound_name(&name, &region),
+                        None => name,
+                    },
                     channel,
+                    sends,
+                    pan,
+                    position,
                     fade_in,
                     length,
                     fade_out,
+                    lead,
+                    humanize_time,
+                    humanize_volume,
+                    repeat,
+                    condition,
                 },
             )),
             "sequence" => {
@@ -271,6 +531,29 @@ impl SequenceElement {
     }
 }
 
+/// Parses a volume or gain, either linear (`0.5`) or in decibels (`-6db`, or
+/// `-infdb` for silence).
+fn parse_volume(text: &str) -> Option<PosFloat> {
+    match text
+        .len()
+        .checked_sub(2)
+        .filter(|&n| text.is_char_boundary(n))
+        .map(|n| text.split_at(n))
+    {
+        Some((db, suffix)) if suffix.eq_ignore_ascii_case("db") => db
+            .parse::<f32>()
+            .ok()
+            // (rejecting NaN, and gains too loud to be finite)
+            .filter(|db| 10f32.powf(db / 20.0).is_finite())
+            .map(PosFloat::from_db),
+        _ => text
+            .parse()
+            .ok()
+            .and_then(|x| PosFloat::new(x).ok())
+            .filter(|x| x.is_finite()),
+    }
+}
+
 fn parse_flow_command_tokens(
     soundtrack: &mut Soundtrack,
     flow_name: &str,
@@ -298,6 +581,12 @@ fn parse_flow_command_tokens(
             }
             Ok(Some(Command::Done))
         }
+        "wait" if tokens.get(1).map(String::as_str) == Some("until") => {
+            if tokens.len() < 3 {
+                return Err("\"wait until\" needs a condition (e.g. `wait until $door_open`)".to_string());
+            }
+            Ok(Some(Command::WaitForCondition(parse_expression(&tokens[2..])?)))
+        }
         "wait" => {
             let how_long = timebases.parse_time(tokens)?;
             Ok(Some(Command::Wait(how_long)))
@@ -314,6 +603,28 @@ fn parse_flow_command_tokens(
             } else {
                 (false, tokens)
             };
+            let (gain, tokens) = match tokens {
+                [rest @ .., at, gain] if rest.len() >= 2 && at == "at" => {
+                    if element_type != "sound" {
+                        return Err("only sounds can be played \"at\" a gain".to_string());
+                    }
+                    let gain = parse_volume(gain).ok_or_else(|| {
+                        "next element after \"at\" must be a gain (a number, 0 or more, or a number of decibels, e.g. \"-6db\")"
+                            .to_string()
+                    })?;
+                    (gain, rest)
+                }
+                _ => (PosFloat::ONE, tokens),
+            };
+            let (region, tokens) = match tokens {
+                [rest @ .., keyword, region] if rest.len() >= 3 && keyword == "region" => {
+                    if element_type != "sound" {
+                        return Err("only sounds have regions".to_string());
+                    }
+                    (Some(region), rest)
+                }
+                _ => (None, tokens),
+            };
             let name = tokens.get(2);
             if tokens.get(3).is_some() {
                 return Err("too many elements after the name of the {element_type} to play (do you need quotation marks?)".to_string());
@@ -346,7 +657,7 @@ fn parse_flow_command_tokens(
                             "sound" => {
                                 let sound =
                                     Sound::parse_din_node(din_node, timebases, name.clone())?;
-                                soundtrack.sounds.insert(name.clone(), Arc::new(sound));
+                                soundtrack.insert_sound(sound);
                             }
                             "sequence" => {
                                 let sequence = Sequence::parse_din_node(
@@ -371,11 +682,15 @@ fn parse_flow_command_tokens(
                     }
                 }
             }
+            let name = match region {
+                Some(region) => region_sound_name(&name, region),
+                None => name,
+            };
             Ok(Some(match (element_type, and_wait) {
                 ("sequence", false) => Command::PlaySequence(name),
                 ("sequence", true) => Command::PlaySequenceAndWait(name),
-                ("sound", false) => Command::PlaySound(name),
-                ("sound", true) => Command::PlaySoundAndWait(name),
+                ("sound", false) => Command::PlaySound(name, gain),
+                ("sound", true) => Command::PlaySoundAndWait(name, gain),
                 _ => unreachable!(),
             }))
         }
@@ -431,6 +746,56 @@ fn parse_flow_command_tokens(
                 tokens[0]
             )),
         },
+        "fade" => {
+            let (node, rest) = match &tokens[1..] {
+                [starting, node, rest @ ..]
+                    if starting == "starting" && node == "node" =>
+                {
+                    (None, rest)
+                }
+                [node, rest @ ..] => (Some(node.to_compact_string()), rest),
+                [] => {
+                    return Err(
+                        "next element after \"fade\" must be the name of the node to fade, or \"starting node\""
+                            .to_string(),
+                    )
+                }
+            };
+            let (fade_type, rest) = match rest.split_last() {
+                Some((last, init)) => match last.as_str() {
+                    "logarithmic" => (FadeType::Logarithmic, init),
+                    "linear" => (FadeType::Linear, init),
+                    "exponential" => (FadeType::Exponential, init),
+                    "scurve" => (FadeType::SCurve, init),
+                    "equalpower" => (FadeType::EqualPower, init),
+                    _ => (FadeType::Exponential, rest),
+                },
+                None => (FadeType::Exponential, rest),
+            };
+            let (volume, rest) = match rest {
+                [to, volume, rest @ ..] if to == "to" => {
+                    let volume = parse_volume(volume).ok_or_else(|| {
+                        "next element after \"to\" must be a volume (a number, 0 or more, or a number of decibels, e.g. \"-6db\")"
+                            .to_string()
+                    })?;
+                    (volume, rest)
+                }
+                _ => (PosFloat::ZERO, rest),
+            };
+            if rest.first().map(String::as_str) != Some("over") {
+                return Err(
+                    "\"fade\" needs an \"over\" and a length of time (e.g. `fade NodeName to 0.5 over 2 exponential`)"
+                        .to_string(),
+                );
+            }
+            let length = timebases.parse_time(rest)?;
+            Ok(Some(Command::FadeNode {
+                node,
+                volume,
+                length,
+                fade_type,
+            }))
+        }
         "set" => {
             let target =
                 match tokens.get(1) {
@@ -658,34 +1023,77 @@ impl Node {
     fn parse_node(
         soundtrack: &mut Soundtrack,
         flow_name: &str,
-        din_node: DinNode,
+        mut din_node: DinNode,
         timebases: &TimebaseCollection,
     ) -> Result<Node, String> {
         assert_eq!(din_node.items[0], "node");
-        if din_node.items.len() != 2 {
+        let lineno = din_node.lineno;
+        if din_node.items.len() < 2 {
             return Err(format!(
-                "line {}: node element must have a name",
-                din_node.lineno
+                "line {lineno}: node element must have a name"
             ));
         }
+        let layer = match &din_node.items[2..] {
+            [] => None,
+            [active, rest @ ..] if active == "active" => Some(
+                parse_layer_curve(rest)
+                    .map_err(|x| format!("line {lineno}: {x}"))?,
+            ),
+            _ => {
+                return Err(format!(
+                    "line {lineno}: node element must have only a name, \
+                     optionally followed by \"active ...\""
+                ))
+            }
+        };
         let name = din_node.items[1].to_compact_string();
+        let metadata = take(&mut din_node.metadata);
         let commands = parse_node_child_code(
             soundtrack, flow_name, &name, din_node, timebases,
         )?;
         Ok(Node {
             name: Some(name),
             commands,
+            layer,
+            metadata,
         })
     }
 }
 
+/// Parse the part of a node element after `active`: `above X`, `below X`, or
+/// `from X to Y`.
+fn parse_layer_curve(tokens: &[String]) -> Result<LayerCurve, String> {
+    let number = |x: &String| {
+        x.parse::<f32>()
+            .ok()
+            .filter(|x| x.is_finite())
+            .ok_or_else(|| format!("{x:?} is not a valid intensity"))
+    };
+    match tokens {
+        [above, x] if above == "above" => Ok(LayerCurve::Above(number(x)?)),
+        [below, x] if below == "below" => Ok(LayerCurve::Below(number(x)?)),
+        [from, x, to, y] if from == "from" && to == "to" => {
+            let (x, y) = (number(x)?, number(y)?);
+            if x == y {
+                return Err(
+                    "the two intensities of \"from ... to\" must differ"
+                        .to_string(),
+                );
+            }
+            Ok(LayerCurve::Ramp(x, y))
+        }
+        _ => Err("\"active\" must be followed by \"above X\", \"below X\", \
+                  or \"from X to Y\""
+            .to_string()),
+    }
+}
+
 impl Flow {
     fn parse_din_node(
         soundtrack: &mut Soundtrack,
         mut node: DinNode,
         timebases: &TimebaseCollection,
     ) -> Result<Flow, String> {
-        let lineno = node.lineno;
         let mut name = None;
         let mut autoloop = false;
         parse_din_node!(node, "flow" name=* autoloop=["with" "loop"])?;
@@ -695,6 +1103,7 @@ impl Flow {
         let mut start_node = Node::new();
         for child in node.consume_children() {
             debug_assert!(!child.items.is_empty());
+            let lineno = child.lineno;
             if child.items[0] == "timebase" {
                 timebases.parse_timebase_node(&child)?;
             } else if child.items[0] == "node" {
@@ -726,60 +1135,253 @@ impl Flow {
             start_node: Arc::new(start_node),
             nodes,
             autoloop,
+            metadata: take(&mut node.metadata),
         };
         Ok(new_flow)
     }
 }
 
+impl Preset {
+    fn parse_din_node(
+        mut node: DinNode,
+        name: CompactString,
+    ) -> Result<Preset, String> {
+        let mut sets = Vec::new();
+        for child in node.consume_children() {
+            let lineno = child.lineno;
+            if !child.children.is_empty() {
+                return Err(format!(
+                    "line {lineno}: preset elements are not allowed to have children"
+                ));
+            }
+            if child.items[0] != "set" {
+                return Err(format!(
+                    "line {lineno}: presets may only contain \"set\" elements"
+                ));
+            }
+            let target = match child.items.get(1) {
+                Some(x) => x.to_compact_string(),
+                None => return Err(format!(
+                    "line {lineno}: next element after \"set\" must be the name of the flow control to set"
+                )),
+            };
+            if child.items.get(2).map(String::as_str) != Some("to") {
+                return Err(format!(
+                    "line {lineno}: next element after flow control name must be \"to\""
+                ));
+            }
+            let expression = parse_expression(&child.items[3..])
+                .map_err(|x| format!("line {lineno}: {x}"))?;
+            sets.push((target, expression));
+        }
+        Ok(Preset { name, sets })
+    }
+}
+
+/// What `parse_source_leniently` made of a soundtrack's source code.
+pub(crate) struct LenientParse {
+    /// Everything that could be parsed.
+    pub soundtrack: Soundtrack,
+    /// Everything that couldn't, in the order it was found, along with the
+    /// line the element with the problem started on.
+    pub errors: Vec<(usize, String)>,
+    /// The line each top-level element that was parsed started on, by type
+    /// (e.g. `"sound"`) and name.
+    pub definition_lines: HashMap<(CompactString, CompactString), usize>,
+    /// The type and name of each top-level element that couldn't be parsed.
+    pub broken_names: Vec<(CompactString, CompactString)>,
+}
+
 impl Soundtrack {
-    pub fn parse_source(mut self, source: &str) -> Result<Soundtrack, String> {
-        let document = parse_din(source)?;
+    /// Adds a sound, and a sound for each of its regions, replacing any
+    /// sound (and regions) that had the same name.
+    fn insert_sound(&mut self, sound: Sound) {
+        if let Some(old) = self.sounds.get(&sound.name).cloned() {
+            for region in old.regions.keys() {
+                self.sounds.remove(&region_sound_name(&old.name, region));
+            }
+        }
+        for (region, (start, end)) in sound.regions.iter() {
+            let region_sound = sound.region_sound(region, *start, *end);
+            self.sounds
+                .insert(region_sound.name.clone(), Arc::new(region_sound));
+        }
+        self.sounds.insert(sound.name.clone(), Arc::new(sound));
+    }
+    pub fn parse_source(self, source: &str) -> Result<Soundtrack, String> {
+        self.parse_source_impl(source, false)
+    }
+    /// Like `parse_source`, but instead of ignoring `editor:` elements, keeps
+    /// them on the flows, nodes, sequences, and sounds they're inside of, for
+    /// an editor to get back with `flow_metadata` and friends. An element
+    /// like `editor:color dark red` becomes the entry `"color"` →
+    /// `"dark red"`. (Comments are still thrown away.)
+    pub fn parse_source_with_metadata(
+        self,
+        source: &str,
+    ) -> Result<Soundtrack, String> {
+        self.parse_source_impl(source, true)
+    }
+    fn parse_source_impl(
+        mut self,
+        source: &str,
+        keep_metadata: bool,
+    ) -> Result<Soundtrack, String> {
+        let document = parse_din(source, keep_metadata)?;
+        let mut timebases = TimebaseCollection::new();
+        for node in document.into_iter() {
+            self.parse_top_level_node(node, &mut timebases)?;
+        }
+        Ok(self)
+    }
+    /// As `parse_source`, but doesn't stop at the first element that has a
+    /// problem. Elements with problems are left out, and their errors are
+    /// returned along with everything else that was parsed, alongside the
+    /// line numbers of the top-level elements that were. Only gives up
+    /// early if the source isn't valid DIN at all (e.g. bad indentation).
+    pub(crate) fn parse_source_leniently(
+        mut self,
+        source: &str,
+    ) -> LenientParse {
+        let mut errors = vec![];
+        let mut definition_lines = HashMap::new();
+        let mut broken_names = vec![];
+        let document = match parse_din(source, false) {
+            Ok(document) => document,
+            Err(error) => {
+                errors.push((1, error));
+                vec![]
+            }
+        };
         let mut timebases = TimebaseCollection::new();
         for node in document.into_iter() {
             assert!(!node.items.is_empty());
-            match node.items[0].as_str() {
-                "timebase" => timebases.parse_timebase_node(&node)?,
-                "sound" => {
-                    let mut name = None;
-                    parse_din_node!(node, "sound" name=*)?;
-                    let name = name.unwrap().to_compact_string();
-                    let sound = Sound::parse_din_node(node, &timebases, name.clone())?;
-                    debug_assert_eq!(sound.name, name);
-                    self.sounds.insert(name, Arc::new(sound));
+            let kind = node.items[0].to_compact_string();
+            let name = node.items.get(1).map(|x| x.to_compact_string());
+            let lineno = node.lineno;
+            match self.parse_top_level_node(node, &mut timebases) {
+                Ok(()) => {
+                    if let Some(name) = name {
+                        definition_lines.insert((kind, name), lineno);
+                    }
                 }
-                "sequence" => {
-                    let mut name = None;
-                    parse_din_node!(node, "sequence" name=*)?;
-                    let name = name.unwrap().to_compact_string();
-                    let sequence = Sequence::parse_din_node(&mut self, node, &timebases, name.clone())?;
-                    debug_assert_eq!(sequence.name, name);
-                    self.sequences.insert(name, Arc::new(sequence));
-                }
-                "flow" => {
-                    let flow = Flow::parse_din_node(&mut self, node, &timebases)?;
-                    self.flows.insert(flow.name.clone(), Arc::new(flow));
-                }
-                "region" => {
-                    return Err(format!(
-                        "line {}: regions may only exist inside sequences (check indentation)",
-                        node.lineno
-                    ))
+                Err(error) => {
+                    errors.push((lineno, error));
+                    if let Some(name) = name {
+                        broken_names.push((kind, name));
+                    }
                 }
-                "node" => {
+            }
+        }
+        LenientParse {
+            soundtrack: self,
+            errors,
+            definition_lines,
+            broken_names,
+        }
+    }
+    fn parse_top_level_node(
+        &mut self,
+        node: DinNode,
+        timebases: &mut TimebaseCollection,
+    ) -> Result<(), String> {
+        assert!(!node.items.is_empty());
+        match node.items[0].as_str() {
+            "timebase" => timebases.parse_timebase_node(&node)?,
+            "sound" => {
+                let mut name = None;
+                parse_din_node!(node, "sound" name=*)?;
+                let name = name.unwrap().to_compact_string();
+                let sound = Sound::parse_din_node(node, timebases, name.clone())?;
+                debug_assert_eq!(sound.name, name);
+                self.insert_sound(sound);
+            }
+            "sequence" => {
+                let mut name = None;
+                parse_din_node!(node, "sequence" name=*)?;
+                let name = name.unwrap().to_compact_string();
+                let sequence = Sequence::parse_din_node(self, node, timebases, name.clone())?;
+                debug_assert_eq!(sequence.name, name);
+                self.sequences.insert(name, Arc::new(sequence));
+            }
+            "flow" => {
+                let flow = Flow::parse_din_node(self, node, timebases)?;
+                self.flows.insert(flow.name.clone(), Arc::new(flow));
+            }
+            "preset" => {
+                let mut name = None;
+                parse_din_node!(node, "preset" name=*)?;
+                let name = name.unwrap().to_compact_string();
+                let preset = Preset::parse_din_node(node, name.clone())?;
+                self.presets.insert(name, Arc::new(preset));
+            }
+            "channel" => {
+                let lineno = node.lineno;
+                let mut name = None;
+                let mut max_volume = None;
+                parse_din_node!(node, "channel" name=* "maxgain" max_volume=*)?;
+                if !node.children.is_empty() {
                     return Err(format!(
-                        "line {}: nodes may only exist inside flows (check indentation)",
-                        node.lineno
-                    ))
+                        "line {lineno}: \"channel\" must not have children (check indentation)"
+                    ));
                 }
-                x => {
+                let max_volume = max_volume
+                    .unwrap()
+                    .parse()
+                    .ok()
+                    .and_then(|x| PosFloat::new(x).ok())
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| {
+                        format!(
+                            "line {lineno}: \"maxgain\" must be followed by a volume"
+                        )
+                    })?;
+                self.channel_max_volumes
+                    .insert(name.unwrap(), max_volume);
+            }
+            "group" => {
+                let lineno = node.lineno;
+                if !node.children.is_empty() {
                     return Err(format!(
-                        "line {}: unknown top-level element {:?}",
-                        node.lineno, x
-                    ))
+                        "line {lineno}: \"group\" must not have children (check indentation)"
+                    ));
                 }
+                match node.items.as_slice() {
+                    [_, name, includes, members @ ..]
+                        if includes == "includes" && !members.is_empty() =>
+                    {
+                        self.channel_groups.insert(
+                            name.to_compact_string(),
+                            members.iter().map(|x| x.to_compact_string()).collect(),
+                        );
+                    }
+                    _ => {
+                        return Err(format!(
+                            "line {lineno}: expected something like: group \"<name>\" includes \"<channel>\" ..."
+                        ))
+                    }
+                }
+            }
+            "region" => {
+                return Err(format!(
+                    "line {}: regions may only exist inside sounds (check indentation)",
+                    node.lineno
+                ))
+            }
+            "node" => {
+                return Err(format!(
+                    "line {}: nodes may only exist inside flows (check indentation)",
+                    node.lineno
+                ))
+            }
+            x => {
+                return Err(format!(
+                    "line {}: unknown top-level element {:?}",
+                    node.lineno, x
+                ))
             }
         }
-        Ok(self)
+        Ok(())
     }
 }
 
@@ -27,36 +27,106 @@ impl ExprNode<'_> {
 // 6: and
 // 7: or
 
-fn parse_partial(
+/// What kind of thing `parse_partial` is parsing.
+#[derive(Clone, Copy, PartialEq)]
+enum Nesting {
+    /// A whole expression, which ends at the end of the tokens.
+    TopLevel,
+    /// A parenthesized subexpression, which ends at a `)`.
+    Parenthesized,
+    /// A function argument, which ends at a `,` or a `)`.
+    Argument,
+}
+
+/// Parses an n-ary function call, starting just after the function's name.
+fn parse_function_call(
     it: &mut std::vec::IntoIter<&str>,
-    top_level: bool,
+    name: &str,
 ) -> Result<Vec<PredicateOp>, String> {
+    let (arity, op) = match name {
+        "clamp" => (3, PredicateOp::Clamp),
+        "lerp" => (3, PredicateOp::Lerp),
+        "random" => (0, PredicateOp::Random),
+        _ => panic!("internal error: unknown function {:?}", name),
+    };
+    if it.next() != Some("(") {
+        return Err(format!(
+            "{:?} must be followed by its arguments, in parentheses",
+            name
+        ));
+    }
+    let mut num_args = 0;
+    let mut ops = Vec::new();
+    if it.as_slice().first() == Some(&")") {
+        it.next();
+    } else {
+        loop {
+            let (mut arg, more) = parse_partial(it, Nesting::Argument)?;
+            ops.append(&mut arg);
+            num_args += 1;
+            if !more {
+                break;
+            }
+        }
+    }
+    if num_args != arity {
+        return Err(format!(
+            "{:?} takes {} argument{}, but was given {}",
+            name,
+            arity,
+            if arity == 1 { "" } else { "s" },
+            num_args
+        ));
+    }
+    ops.push(op);
+    Ok(ops)
+}
+
+/// Parses an expression or subexpression. Returns the parsed operations, and
+/// (if parsing a function argument) whether there are more arguments to come.
+fn parse_partial(
+    it: &mut std::vec::IntoIter<&str>,
+    nesting: Nesting,
+) -> Result<(Vec<PredicateOp>, bool), String> {
     let mut partial = Vec::new();
+    let mut more_arguments = false;
     loop {
         let x = it.next();
         let x = match x {
             None => {
-                if top_level {
+                if nesting == Nesting::TopLevel {
                     break;
                 } else {
                     return Err("unbalanced parentheses in expression (not enough \")\")".to_string());
                 }
             }
             Some(")") => {
-                if !top_level {
+                if nesting != Nesting::TopLevel {
                     break;
                 } else {
                     return Err("unbalanced parentheses in expression (too many \")\")".to_string());
                 }
             }
+            Some(",") => {
+                if nesting == Nesting::Argument {
+                    more_arguments = true;
+                    break;
+                } else {
+                    return Err("\",\" is only allowed between the arguments of a function".to_string());
+                }
+            }
             Some(x) => x,
         };
         match x {
             "(" => {
-                let subops = parse_partial(it, false)?;
+                let (subops, _) = parse_partial(it, Nesting::Parenthesized)?;
+                partial.push(ExprNode::Subexpression(subops));
+            },
+            "clamp" | "lerp" | "random" => {
+                let subops = parse_function_call(it, x)?;
                 partial.push(ExprNode::Subexpression(subops));
             },
-            "=" | "≠" | ">" | "≥" | "<" | "≤" => {
+            "=" | "≠" | ">" | "≥" | "<" | "≤" | "startswith" | "contains" => {
                 partial.push(ExprNode::BinOp { op: x, precedence: 5 });
             },
             "min" | "max" => {
@@ -68,7 +138,7 @@ fn parse_partial(
                     _ => partial.push(ExprNode::BinOp { op: "-", precedence: 3 }),
                 }
             },
-            "+" => {
+            "+" | "concat" => {
                 partial.push(ExprNode::BinOp { op: x, precedence: 3 });
             },
             "*" | "/" | "//" | "%" => {
@@ -78,9 +148,13 @@ fn parse_partial(
                 partial.push(ExprNode::BinOp { op: x, precedence: 1 });
             },
             "$" | "not" | "sin" | "cos" | "tan" | "asin" | "acos"
-            | "atan" | "ln" | "exp" | "floor" | "ceil" | "abs" | "sign" => {
+            | "atan" | "ln" | "exp" | "floor" | "ceil" | "abs" | "sign"
+            | "length" => {
                 partial.push(ExprNode::UnOp { op: x });
             },
+            "?" | ":" => {
+                partial.push(ExprNode::BinOp { op: x, precedence: 8 });
+            },
             "&" | "|" | "!" | "~" => {
                 return Err(format!("there is no {:?} operator in the current version (please use \"and\", \"or\", \"not\", and \"xor\" instead of C-like operators)", x))
             },
@@ -153,6 +227,7 @@ fn parse_partial(
                         "ceil" => PredicateOp::Ceil,
                         "abs" => PredicateOp::Abs,
                         "sign" => PredicateOp::Sign,
+                        "length" => PredicateOp::Length,
                         "-" => PredicateOp::Negate,
                         _ => panic!("Unknown unary op: {:?}", unop),
                     });
@@ -180,6 +255,52 @@ fn parse_partial(
     if partial.len() % 2 != 1 {
         return Err("binary operator missing second operand".to_string());
     }
+    Ok((reduce_binary_ops(partial)?, more_arguments))
+}
+
+/// Given a partial that alternates between operands and binary operators
+/// (starting and ending with an operand), combine it into a single list of
+/// operations.
+fn reduce_binary_ops(
+    mut partial: Vec<ExprNode>,
+) -> Result<Vec<PredicateOp>, String> {
+    // The conditional operator has lower precedence than everything else, and
+    // (uniquely) is right associative, so it's handled separately.
+    if let Some(question_index) = partial
+        .iter()
+        .position(|x| matches!(x, ExprNode::BinOp { op: "?", .. }))
+    {
+        let mut depth = 0;
+        let mut colon_index = None;
+        for (n, node) in partial.iter().enumerate().skip(question_index + 1) {
+            match node {
+                ExprNode::BinOp { op: "?", .. } => depth += 1,
+                ExprNode::BinOp { op: ":", .. } if depth == 0 => {
+                    colon_index = Some(n);
+                    break;
+                }
+                ExprNode::BinOp { op: ":", .. } => depth -= 1,
+                _ => (),
+            }
+        }
+        let colon_index =
+            colon_index.ok_or("\"?\" must be followed by a matching \":\"")?;
+        let if_false = partial.split_off(colon_index + 1);
+        partial.pop();
+        let if_true = partial.split_off(question_index + 1);
+        partial.pop();
+        let mut ops = reduce_binary_ops(partial)?;
+        ops.append(&mut reduce_binary_ops(if_true)?);
+        ops.append(&mut reduce_binary_ops(if_false)?);
+        ops.push(PredicateOp::Select);
+        return Ok(ops);
+    }
+    if partial
+        .iter()
+        .any(|x| matches!(x, ExprNode::BinOp { op: ":", .. }))
+    {
+        return Err("\":\" must be preceded by a matching \"?\"".to_string());
+    }
     while partial.len() > 1 {
         debug_assert!(partial.len() % 2 == 1);
         let old_len = partial.len();
@@ -227,6 +348,9 @@ fn parse_partial(
             "atan2" => PredicateOp::ATan2,
             "min" => PredicateOp::Min,
             "max" => PredicateOp::Max,
+            "concat" => PredicateOp::Concat,
+            "startswith" => PredicateOp::StartsWith,
+            "contains" => PredicateOp::Contains,
             _ => panic!("internal error: unknown binary operation {:?}", op),
         };
         let mut ops = Vec::new();
@@ -274,6 +398,16 @@ pub(super) fn parse_condition(
     Ok((parse_expression(&tokens[..then_pos])?, rest))
 }
 
+/// Returns true if the character at the given position is a `.` that's part
+/// of a number (like the one in `0.2`), and so shouldn't be split off.
+fn is_decimal_point(s: &str, pos: usize) -> bool {
+    let (before, after) = s.split_at(pos);
+    let mut after = after.chars();
+    after.next() == Some('.')
+        && after.next().is_some_and(|x| x.is_ascii_digit())
+        && !before.ends_with(|x: char| !x.is_ascii_digit())
+}
+
 /// Parses any expression.
 pub(super) fn parse_expression(
     tokens: &[String],
@@ -284,9 +418,12 @@ pub(super) fn parse_expression(
     // quote-escape operators but this seems like a small price to pay.
     for token in (tokens[..tokens.len()]).iter() {
         let mut rest = token.as_str();
-        while let Some((split_pos, _)) = rest.char_indices().find(|(_, ch)| {
-            data::EXPRESSION_SPLIT_CHARS.contains(|x| x == *ch)
-        }) {
+        while let Some((split_pos, _)) =
+            rest.char_indices().find(|(pos, ch)| {
+                data::EXPRESSION_SPLIT_CHARS.contains(|x| x == *ch)
+                    && !is_decimal_point(rest, *pos)
+            })
+        {
             let before = &rest[..split_pos];
             let during = &rest[split_pos..];
             let split_len = during
@@ -339,5 +476,5 @@ pub(super) fn parse_expression(
         }
     }
     let mut it = pieces.into_iter();
-    parse_partial(&mut it, true)
+    parse_partial(&mut it, Nesting::TopLevel).map(|(ops, _)| ops)
 }
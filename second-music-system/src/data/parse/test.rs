@@ -19,6 +19,11 @@ fn new_sound_parse() {
             start: PosFloat::ZERO,
             end,
             stream: false,
+            pitch: PosFloat::ONE,
+            gain: PosFloat::ONE,
+            loop_crossfade: PosFloat::ZERO,
+            regions: HashMap::new(),
+            metadata: HashMap::new(),
         }
     );
 }
@@ -42,12 +47,71 @@ sound test1.mp3
             start: PosFloat::ZERO,
             end,
             stream: false,
+            pitch: PosFloat::ONE,
+            gain: PosFloat::ONE,
+            loop_crossfade: PosFloat::ZERO,
+            regions: HashMap::new(),
+            metadata: HashMap::new(),
         }
     );
     assert_eq!(soundtrack.sequences.len(), 0);
     assert_eq!(soundtrack.flows.len(), 0);
 }
 
+#[test]
+fn sound_region_parse() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound music
+  file music.ogg
+  length 12
+  region loop
+    start 2
+    length 8
+  region outro
+    start 10
+sequence s
+  length 8
+  play sound music region loop
+flow test
+  play sequence s
+  play sound music region outro and wait
+"#,
+    )
+    .unwrap();
+    let region = &soundtrack.sounds["music[loop]"];
+    assert_eq!(region.path, "music.ogg");
+    assert_eq!(region.start, PosFloat::new_clamped(2.0));
+    assert_eq!(region.end.get(), Some(&PosFloat::new_clamped(10.0)));
+    let region = &soundtrack.sounds["music[outro]"];
+    assert_eq!(region.end.get(), Some(&PosFloat::new_clamped(12.0)));
+    let SequenceElement::PlaySound { sound, .. } =
+        &soundtrack.sequences["s"].elements[0].1
+    else {
+        panic!()
+    };
+    assert_eq!(sound.as_str(), "music[loop]");
+    assert_eq!(
+        soundtrack.flows["test"].start_node.commands[1],
+        Command::PlaySoundAndWait("music[outro]".into(), PosFloat::ONE)
+    );
+    // redefining the sound takes its old regions with it
+    let soundtrack = soundtrack
+        .parse_source("sound music\n  region intro\n    end 2\n")
+        .unwrap();
+    let mut names: Vec<&str> = soundtrack
+        .sounds
+        .keys()
+        .map(CompactString::as_str)
+        .collect();
+    names.sort();
+    assert_eq!(names, ["music", "music[intro]"]);
+    assert!(Soundtrack::from_source(
+        "sequence s\n  length 1\n  play sequence t region loop\n"
+    )
+    .is_err());
+}
+
 #[test]
 #[should_panic]
 fn sound_with_null_implicit_path_parse() {
@@ -98,6 +162,7 @@ fn new_sequence_parse() {
             name: "test1".to_compact_string(),
             length: PosFloat::new_clamped(32.0),
             elements: vec![],
+            metadata: HashMap::new(),
         }
     );
 }
@@ -148,6 +213,7 @@ fn new_sequence_element_parse() {
             name: "test1".to_compact_string(),
             length: PosFloat::new_clamped(32.0),
             elements: vec![],
+            metadata: HashMap::new(),
         }
     );
     assert_eq!(
@@ -161,6 +227,7 @@ fn new_sequence_element_parse() {
                     sequence: "test1".to_compact_string()
                 }
             ),],
+            metadata: HashMap::new(),
         }
     );
 }
@@ -185,6 +252,7 @@ sequence test2
             name: "test1".to_compact_string(),
             length: PosFloat::new_clamped(32.0),
             elements: vec![],
+            metadata: HashMap::new(),
         }
     );
     assert_eq!(
@@ -198,6 +266,7 @@ sequence test2
                     sequence: "test1".to_compact_string()
                 }
             )],
+            metadata: HashMap::new(),
         }
     );
     assert_eq!(soundtrack.flows.len(), 0);
@@ -249,6 +318,7 @@ flow test_flow1
             start_node,
             nodes,
             autoloop: false,
+            metadata: HashMap::new(),
         }
     );
 }
@@ -286,6 +356,8 @@ flow test_flow1
     let start_node = Arc::new(Node {
         name: None,
         commands: vec![Command::Done],
+        layer: None,
+        metadata: HashMap::new(),
     });
     let mut nodes = HashMap::new();
     nodes.insert(
@@ -296,6 +368,8 @@ flow test_flow1
                 Command::PlaySequence("test_sequence1".to_compact_string()),
                 Command::Done,
             ],
+            layer: None,
+            metadata: HashMap::new(),
         }),
     );
     assert_eq!(
@@ -305,6 +379,7 @@ flow test_flow1
             start_node,
             nodes,
             autoloop: false,
+            metadata: HashMap::new(),
         }
     );
 }
@@ -344,11 +419,20 @@ fn fade_out_parse() {
                 SequenceElement::PlaySound {
                     sound: CompactString::new("test_sound"),
                     channel: CompactString::new("main"),
+                    sends: vec![],
+                    pan: 0.0,
+                    position: None,
                     fade_in: PosFloat::ZERO,
                     length: Some(PosFloat::new_clamped(12.0)),
                     fade_out: PosFloat::new_clamped(4.0),
+                    lead: PosFloat::ZERO,
+                    humanize_time: PosFloat::ZERO,
+                    humanize_volume: PosFloat::ZERO,
+                    repeat: 1,
+                    condition: None,
                 }
             )],
+            metadata: HashMap::new(),
         }
     );
 }
@@ -466,6 +550,269 @@ fn expression_parsing() {
         panic!("Some lines that should have parsed did not! (See output)");
     }
 }
+#[test]
+fn function_call_parsing() {
+    let parse = |src: &str| {
+        let toks =
+            shellish_parse::parse(src, shellish_parse::ParseOptions::new())
+                .unwrap();
+        parse_expression(&toks)
+    };
+    assert_eq!(
+        parse("clamp($x, 0, 1)").unwrap(),
+        vec![
+            PredicateOp::PushVar("x".into()),
+            PredicateOp::PushConst(StringOrNumber::Number(0.0)),
+            PredicateOp::PushConst(StringOrNumber::Number(1.0)),
+            PredicateOp::Clamp,
+        ]
+    );
+    assert_eq!(
+        parse("lerp(1, 2 + 3, random()) * 2").unwrap(),
+        vec![
+            PredicateOp::PushConst(StringOrNumber::Number(1.0)),
+            PredicateOp::PushConst(StringOrNumber::Number(2.0)),
+            PredicateOp::PushConst(StringOrNumber::Number(3.0)),
+            PredicateOp::Add,
+            PredicateOp::Random,
+            PredicateOp::Lerp,
+            PredicateOp::PushConst(StringOrNumber::Number(2.0)),
+            PredicateOp::Mul,
+        ]
+    );
+    for bad in [
+        "clamp(1, 2)",
+        "clamp()",
+        "lerp(1, 2, 3, 4)",
+        "random(1)",
+        "random",
+        "clamp 1 2 3",
+        "clamp(1, , 3)",
+        "clamp(1, 2, 3",
+        "1, 2",
+        "(1, 2)",
+    ] {
+        assert!(parse(bad).is_err(), "{:?} should not have parsed", bad);
+    }
+    assert_eq!(
+        parse("clamp(1, 2)").unwrap_err(),
+        "\"clamp\" takes 3 arguments, but was given 2"
+    );
+    assert_eq!(
+        parse("random(1)").unwrap_err(),
+        "\"random\" takes 0 arguments, but was given 1"
+    );
+}
+#[test]
+fn string_function_parsing() {
+    let goods = [
+        "$region startswith forest",
+        "$region startswith \"forest\" and $combat",
+        "$name contains \"boss\"",
+        "$a concat $b",
+        "$a concat \"_\" concat $b = \"x_y\"",
+        "length $name > 3",
+        "length ($a concat $b)",
+        "1 concat 2",
+    ];
+    let bads = [
+        "startswith",
+        "startswith forest",
+        "$region startswith",
+        "$a concat",
+        "concat $b",
+        "$a concat concat $b",
+        "$a contains contains $b",
+        "length",
+        "$name length",
+        "length length",
+    ];
+    for good in goods {
+        let toks =
+            shellish_parse::parse(good, shellish_parse::ParseOptions::new())
+                .unwrap();
+        if let Err(x) = parse_expression(&toks) {
+            panic!("{:?} should have parsed, but: {}", good, x);
+        }
+    }
+    for bad in bads {
+        let toks =
+            shellish_parse::parse(bad, shellish_parse::ParseOptions::new())
+                .unwrap();
+        if let Ok(x) = parse_expression(&toks) {
+            panic!("{:?} should not have parsed, but parsed as {:?}", bad, x);
+        }
+    }
+    let toks = shellish_parse::parse(
+        "$region startswith forest and length $region > 6",
+        shellish_parse::ParseOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(
+        parse_expression(&toks).unwrap(),
+        vec![
+            PredicateOp::PushVar("region".into()),
+            PredicateOp::PushConst(StringOrNumber::String("forest".into())),
+            PredicateOp::StartsWith,
+            PredicateOp::PushVar("region".into()),
+            PredicateOp::Length,
+            PredicateOp::PushConst(StringOrNumber::Number(6.0)),
+            PredicateOp::Greater,
+            PredicateOp::And,
+        ]
+    );
+}
+#[test]
+fn decimal_parsing() {
+    let parse = |src: &str| {
+        let toks =
+            shellish_parse::parse(src, shellish_parse::ParseOptions::new())
+                .unwrap();
+        parse_expression(&toks)
+    };
+    let num = |x| PredicateOp::PushConst(StringOrNumber::Number(x));
+    assert_eq!(
+        parse("$x * 0.5 + 0.25").unwrap(),
+        vec![
+            PredicateOp::PushVar("x".into()),
+            num(0.5),
+            PredicateOp::Mul,
+            num(0.25),
+            PredicateOp::Add,
+        ]
+    );
+    // a "." that isn't part of a number is still split off
+    assert!(parse("$x . 5").is_err());
+}
+#[test]
+fn conditional_parsing() {
+    let parse = |src: &str| {
+        let toks =
+            shellish_parse::parse(src, shellish_parse::ParseOptions::new())
+                .unwrap();
+        parse_expression(&toks)
+    };
+    let num = |x| PredicateOp::PushConst(StringOrNumber::Number(x));
+    assert_eq!(
+        parse("$combat ? 1 : 0.2").unwrap(),
+        vec![
+            PredicateOp::PushVar("combat".into()),
+            num(1.0),
+            num(0.2),
+            PredicateOp::Select
+        ]
+    );
+    // lower precedence than everything else
+    assert_eq!(
+        parse("$x > 1 and $y ? 1 + 2 : 3").unwrap(),
+        vec![
+            PredicateOp::PushVar("x".into()),
+            num(1.0),
+            PredicateOp::Greater,
+            PredicateOp::PushVar("y".into()),
+            PredicateOp::And,
+            num(1.0),
+            num(2.0),
+            PredicateOp::Add,
+            num(3.0),
+            PredicateOp::Select,
+        ]
+    );
+    // right associative, and nestable in the middle
+    assert_eq!(
+        parse("1 ? 2 : 3 ? 4 : 5").unwrap(),
+        parse("1 ? 2 : (3 ? 4 : 5)").unwrap()
+    );
+    assert_eq!(
+        parse("1 ? 2 ? 3 : 4 : 5").unwrap(),
+        parse("1 ? (2 ? 3 : 4) : 5").unwrap()
+    );
+    for bad in [
+        "1 ? 2",
+        "1 : 2",
+        "? 1 : 2",
+        "1 ? : 2",
+        "1 ? 2 :",
+        "1 ? 2 : 3 : 4",
+        "(1 ? 2) : 3",
+        "1 ? 2 ? 3 : 4",
+    ] {
+        assert!(parse(bad).is_err(), "{:?} should not have parsed", bad);
+    }
+}
+#[test]
+fn sound_pitch_parse() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound test1.mp3
+    pitch 2
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        soundtrack.sounds.get("test1.mp3").unwrap().pitch,
+        PosFloat::new_clamped(2.0)
+    );
+    for bad in ["0", "-1", "fast", ""] {
+        assert!(Soundtrack::from_source(&format!(
+            "sound test1.mp3\n    pitch {bad}\n"
+        ))
+        .is_err());
+    }
+}
+#[test]
+fn sound_gain_parse() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound test1.mp3
+sound test2.mp3
+    gain 0.5
+sound test3.mp3
+    gain -20db
+    "#,
+    )
+    .unwrap();
+    let gain = |name| soundtrack.sounds.get(name).unwrap().gain;
+    assert_eq!(gain("test1.mp3"), PosFloat::ONE);
+    assert_eq!(gain("test2.mp3"), PosFloat::HALF);
+    assert!((*gain("test3.mp3") - 0.1).abs() < 0.0001);
+    for bad in ["-1", "loud", ""] {
+        assert!(Soundtrack::from_source(&format!(
+            "sound test1.mp3\n    gain {bad}\n"
+        ))
+        .is_err());
+    }
+}
+#[test]
+fn commented_soundtrack_parse() {
+    let commented = Soundtrack::from_source(
+        r##"
+# bumps x, then waits
+flow test
+  # (comments can go between commands, too)
+  set x to $x + 1 # adds one
+  wait 1 # rests
+sound take
+  file "take #2.wav" # (the first "#" is in quotes, so isn't a comment)
+"##,
+    )
+    .unwrap();
+    let uncommented = Soundtrack::from_source(
+        r##"
+flow test
+  set x to $x + 1
+  wait 1
+sound take
+  file "take #2.wav"
+"##,
+    )
+    .unwrap();
+    assert_eq!(
+        commented.flows.get("test").unwrap(),
+        uncommented.flows.get("test").unwrap()
+    );
+    assert_eq!(commented.sounds.get("take").unwrap().path, "take #2.wav");
+}
 // TODO: test anonymous sound with no path (should error)
 #[test]
 #[should_panic]
@@ -510,11 +857,20 @@ fn reject_wackadoodle_indentation() {
                 SequenceElement::PlaySound {
                     sound: "foo".to_compact_string(),
                     channel: "bar".to_compact_string(),
+                    sends: vec![],
+                    pan: 0.0,
+                    position: None,
                     fade_in: PosFloat::ZERO,
                     length: None,
                     fade_out: PosFloat::ZERO,
+                    lead: PosFloat::ZERO,
+                    humanize_time: PosFloat::ZERO,
+                    humanize_volume: PosFloat::ZERO,
+                    repeat: 1,
+                    condition: None,
                 }
             ),],
+            metadata: HashMap::new(),
         }
     );
 }
@@ -545,11 +901,406 @@ fn ignore_editor_nodes() {
                 SequenceElement::PlaySound {
                     sound: "foo".to_compact_string(),
                     channel: "main".to_compact_string(),
+                    sends: vec![],
+                    pan: 0.0,
+                    position: None,
                     fade_in: PosFloat::ZERO,
                     length: None,
                     fade_out: PosFloat::ZERO,
+                    lead: PosFloat::ZERO,
+                    humanize_time: PosFloat::ZERO,
+                    humanize_volume: PosFloat::ZERO,
+                    repeat: 1,
+                    condition: None,
                 }
             ),],
+            metadata: HashMap::new(),
         }
     );
 }
+
+#[test]
+fn keep_editor_nodes() {
+    let source = r#"sequence test
+  length 0
+  editor:color dark red
+  play sound "foo"
+    at 0
+flow main
+  editor:position 10 20
+  node verse
+    editor:collapsed
+sound foo
+  editor:note "needs remastering"
+"#;
+    let soundtrack = Soundtrack::from_source(source).unwrap();
+    assert!(soundtrack.sequence_metadata("test").unwrap().is_empty());
+    let soundtrack = Soundtrack::new()
+        .parse_source_with_metadata(source)
+        .unwrap();
+    let entry = |metadata: Option<&HashMap<String, String>>, key: &str| {
+        metadata.unwrap().get(key).cloned()
+    };
+    assert_eq!(
+        entry(soundtrack.sequence_metadata("test"), "color").as_deref(),
+        Some("dark red")
+    );
+    assert_eq!(
+        entry(soundtrack.flow_metadata("main"), "position").as_deref(),
+        Some("10 20")
+    );
+    assert_eq!(
+        entry(soundtrack.node_metadata("main", "verse"), "collapsed")
+            .as_deref(),
+        Some("")
+    );
+    assert_eq!(
+        entry(soundtrack.sound_metadata("foo"), "note").as_deref(),
+        Some("needs remastering")
+    );
+    assert!(soundtrack.node_metadata("main", "chorus").is_none());
+}
+
+#[test]
+fn send_parse() {
+    let soundtrack = Soundtrack::from_source(
+        r#"sequence test
+  length 0
+  play sound "foo"
+    at 0
+    channel "dry"
+    send "reverb" 0.5
+    send "echo" 0
+"#,
+    )
+    .unwrap();
+    let SequenceElement::PlaySound { channel, sends, .. } =
+        &soundtrack.sequences["test"].elements[0].1
+    else {
+        panic!("not a sound");
+    };
+    assert_eq!(channel.as_str(), "dry");
+    assert_eq!(
+        sends,
+        &[
+            ("reverb".to_compact_string(), PosFloat::HALF),
+            ("echo".to_compact_string(), PosFloat::ZERO),
+        ]
+    );
+    for bad in ["send reverb", "send reverb loud", "send reverb -1"] {
+        assert!(Soundtrack::from_source(&format!(
+            "sequence test\n  length 0\n  play sound foo\n    {bad}\n"
+        ))
+        .is_err());
+    }
+}
+
+#[test]
+fn lead_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "sequence test\n  length 4\n  play sound foo\n    at 2\n    lead 0.25\n",
+    )
+    .unwrap();
+    let (start, SequenceElement::PlaySound { lead, .. }) =
+        &soundtrack.sequences["test"].elements[0]
+    else {
+        panic!("not a sound");
+    };
+    // (the sound stays anchored to its beat)
+    assert_eq!(*start, PosFloat::new_clamped(2.0));
+    assert_eq!(*lead, PosFloat::new_clamped(0.25));
+}
+
+#[test]
+fn humanize_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "sequence test\n  length 0\n  play sound foo\n    humanize 0.02 0.1\n",
+    )
+    .unwrap();
+    let SequenceElement::PlaySound {
+        humanize_time,
+        humanize_volume,
+        ..
+    } = &soundtrack.sequences["test"].elements[0].1
+    else {
+        panic!("not a sound");
+    };
+    assert_eq!(*humanize_time, PosFloat::new_clamped(0.02));
+    assert_eq!(*humanize_volume, PosFloat::new_clamped(0.1));
+    for bad in [
+        "humanize",
+        "humanize -1",
+        "humanize 0.02 2",
+        "humanize a b c",
+    ] {
+        assert!(Soundtrack::from_source(&format!(
+            "sequence test\n  length 0\n  play sound foo\n    {bad}\n"
+        ))
+        .is_err());
+    }
+}
+
+#[test]
+fn pan_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "sequence test\n  length 0\n  play sound foo\n    pan -0.5\n",
+    )
+    .unwrap();
+    let SequenceElement::PlaySound { pan, .. } =
+        &soundtrack.sequences["test"].elements[0].1
+    else {
+        panic!("not a sound");
+    };
+    assert_eq!(*pan, -0.5);
+    for bad in ["pan", "pan left", "pan 1.5", "pan -2"] {
+        assert!(Soundtrack::from_source(&format!(
+            "sequence test\n  length 0\n  play sound foo\n    {bad}\n"
+        ))
+        .is_err());
+    }
+}
+
+#[test]
+fn position_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "sequence test\n  length 0\n  play sound foo\n    position 1 -2 3.5\n",
+    )
+    .unwrap();
+    let SequenceElement::PlaySound { position, .. } =
+        &soundtrack.sequences["test"].elements[0].1
+    else {
+        panic!("not a sound");
+    };
+    assert_eq!(*position, Some([1.0, -2.0, 3.5]));
+    for bad in [
+        "position",
+        "position 1 2",
+        "position 1 2 3 4",
+        "position 1 2 z",
+    ] {
+        assert!(Soundtrack::from_source(&format!(
+            "sequence test\n  length 0\n  play sound foo\n    {bad}\n"
+        ))
+        .is_err());
+    }
+    assert!(Soundtrack::from_source(
+        "sequence test\n  length 0\n  play sound foo\n    pan 1\n    position 1 2 3\n"
+    )
+    .is_err());
+}
+
+#[test]
+fn layer_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "flow test\n  node bass active above 0.3\n  node pad active below \
+         0.5\n  node lead active from 0.25 to 0.75\n  node drums\n",
+    )
+    .unwrap();
+    let flow = &soundtrack.flows["test"];
+    assert_eq!(flow.nodes["bass"].layer, Some(LayerCurve::Above(0.3)));
+    assert_eq!(flow.nodes["pad"].layer, Some(LayerCurve::Below(0.5)));
+    assert_eq!(flow.nodes["lead"].layer, Some(LayerCurve::Ramp(0.25, 0.75)));
+    assert_eq!(flow.nodes["drums"].layer, None);
+    let lead = flow.nodes["lead"].layer.unwrap();
+    assert_eq!(lead.volume_at(0.0), PosFloat::ZERO);
+    assert_eq!(lead.volume_at(0.5), PosFloat::HALF);
+    assert_eq!(lead.volume_at(1.0), PosFloat::ONE);
+    for bad in [
+        "active",
+        "active above",
+        "active above loud",
+        "active from 1 to 1",
+        "loud",
+    ] {
+        assert!(Soundtrack::from_source(&format!(
+            "flow test\n  node bass {bad}\n"
+        ))
+        .is_err());
+    }
+}
+
+#[test]
+fn soundtrack_names() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound test1.mp3
+    length 32
+sequence test_sequence1
+    length 32
+    play sound test1.mp3
+        at 0
+flow test_flow1
+flow test_flow2
+    "#,
+    )
+    .unwrap();
+    let mut flow_names: Vec<&str> = soundtrack.flow_names().collect();
+    flow_names.sort();
+    assert_eq!(flow_names, ["test_flow1", "test_flow2"]);
+    assert_eq!(
+        soundtrack.sequence_names().collect::<Vec<_>>(),
+        ["test_sequence1"]
+    );
+    assert_eq!(soundtrack.sound_names().collect::<Vec<_>>(), ["test1.mp3"]);
+    assert!(soundtrack.has_flow("test_flow2"));
+    assert!(!soundtrack.has_flow("test_sequence1"));
+    assert!(soundtrack.has_sequence("test_sequence1"));
+    assert!(soundtrack.has_sound("test1.mp3"));
+    assert_eq!(soundtrack.flow_count(), 2);
+    assert_eq!(soundtrack.sequence_count(), 1);
+    assert_eq!(soundtrack.sound_count(), 1);
+}
+
+#[test]
+fn preset_parse() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+preset calm
+    set intensity to 0
+    set mood to "peaceful"
+    "#,
+    )
+    .unwrap();
+    assert_eq!(
+        **soundtrack.presets.get("calm").unwrap(),
+        Preset {
+            name: "calm".to_compact_string(),
+            sets: vec![
+                (
+                    "intensity".to_compact_string(),
+                    vec![PredicateOp::PushConst(StringOrNumber::Number(0.0))],
+                ),
+                (
+                    "mood".to_compact_string(),
+                    vec![PredicateOp::PushConst(StringOrNumber::String(
+                        "peaceful".to_compact_string()
+                    ))],
+                ),
+            ],
+        }
+    );
+}
+
+#[test]
+#[should_panic]
+fn preset_with_command_parse() {
+    Soundtrack::from_source(
+        r#"
+preset calm
+    wait 1
+    "#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn fade_node_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "flow test\n  fade lead to 0.5 over 2 linear\n  fade starting node over 3\n",
+    )
+    .unwrap();
+    assert_eq!(
+        soundtrack.flows["test"].start_node.commands[..2],
+        [
+            Command::FadeNode {
+                node: Some("lead".to_compact_string()),
+                volume: PosFloat::HALF,
+                length: PosFloat::new_clamped(2.0),
+                fade_type: FadeType::Linear,
+            },
+            Command::FadeNode {
+                node: None,
+                volume: PosFloat::ZERO,
+                length: PosFloat::new_clamped(3.0),
+                fade_type: FadeType::Exponential,
+            },
+        ]
+    );
+    for bad in [
+        "fade",
+        "fade lead to 0.5",
+        "fade lead to loud over 2",
+        "fade lead to 1000db over 2",
+        "fade lead to nandb over 2",
+    ] {
+        assert!(
+            Soundtrack::from_source(&format!("flow test\n  {bad}\n")).is_err()
+        );
+    }
+}
+
+#[test]
+fn decibel_volume_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "flow test\n  fade lead to -6dB over 2\n  fade lead to -infdb over 2\n  play sound foo at 0db\n",
+    )
+    .unwrap();
+    match &soundtrack.flows["test"].start_node.commands[..3] {
+        [Command::FadeNode { volume: a, .. }, Command::FadeNode { volume: b, .. }, Command::PlaySound(_, c)] =>
+        {
+            assert!((**a - 0.501).abs() < 0.001);
+            assert_eq!(*b, PosFloat::ZERO);
+            assert_eq!(*c, PosFloat::ONE);
+        }
+        x => panic!("wrong commands: {x:?}"),
+    }
+    assert_eq!(PosFloat::from_db(f32::NEG_INFINITY), PosFloat::ZERO);
+}
+
+#[test]
+fn conditional_sound_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "sequence test\n  length 2\n  play sound if $x then foo else bar\n    at 1\n",
+    )
+    .unwrap();
+    match &soundtrack.sequences["test"].elements[..] {
+        [(
+            start,
+            SequenceElement::PlaySound {
+                sound,
+                condition: Some(condition),
+                ..
+            },
+        )] => {
+            assert_eq!(*start, PosFloat::ONE);
+            assert_eq!(sound.as_str(), "foo");
+            assert_eq!(condition.else_sound.as_deref(), Some("bar"));
+        }
+        x => panic!("unexpected elements: {x:?}"),
+    }
+    for bad in [
+        "play sound if $x then foo bar",
+        "play sound if $x then foo else",
+        "play sound if $x foo",
+    ] {
+        assert!(
+            Soundtrack::from_source(&format!("sequence test\n  {bad}\n"))
+                .is_err()
+        );
+    }
+}
+
+#[test]
+fn play_sound_gain_parse() {
+    let soundtrack = Soundtrack::from_source(
+        "flow test\n  play sound a at 0.7\n  play sound b at 2 and wait\n  play sound c\n",
+    )
+    .unwrap();
+    assert_eq!(
+        soundtrack.flows["test"].start_node.commands[..3],
+        [
+            Command::PlaySound("a".into(), PosFloat::new_clamped(0.7)),
+            Command::PlaySoundAndWait("b".into(), PosFloat::new_clamped(2.0)),
+            Command::PlaySound("c".into(), PosFloat::ONE),
+        ]
+    );
+    for bad in [
+        "play sound a at -1",
+        "play sound a at loud",
+        "play sequence a at 0.5",
+    ] {
+        assert!(
+            Soundtrack::from_source(&format!("flow test\n  {bad}\n")).is_err()
+        );
+    }
+}
@@ -14,13 +14,27 @@ struct TimebaseStage {
 #[derive(Debug, PartialEq)]
 pub struct Timebase {
     stages: Vec<TimebaseStage>,
+    /// How many seconds long the basis stage is. Note values are relative to
+    /// this; a quarter note (`1/4`) is one beat.
+    beat: PosFloat,
+    /// If the timebase swings: the index of the stage that gets swung (the
+    /// one right after the basis), and the swing amount. See `swing_time`.
+    swing: Option<(usize, f32)>,
 }
 
+/// The smallest and largest allowed swing amounts. 0.5 is straight, 2/3 is a
+/// triplet feel, and 0.75 is a dotted-eighth-and-sixteenth feel. Anything
+/// beyond that stops sounding like swing.
+const MIN_SWING: f32 = 0.5;
+const MAX_SWING: f32 = 0.75;
+
 static DEFAULT_TIMEBASE: Lazy<Timebase> = Lazy::new(|| Timebase {
     stages: vec![TimebaseStage {
         one_based: false,
         multiplier: PosFloat::ONE,
     }],
+    beat: PosFloat::ONE,
+    swing: None,
 });
 
 #[derive(Debug, Copy, Clone)]
@@ -154,6 +168,19 @@ impl Timebase {
         Ok((one_based, number, timespec))
     }
     pub fn parse_source(source: &[String]) -> Result<Timebase, String> {
+        let (source, swing) = match source.iter().position(|x| x == "swing") {
+            None => (source, None),
+            Some(n) => {
+                if n + 2 != source.len() {
+                    return Err("\"swing\" must be followed by exactly one number, and come last".to_string());
+                }
+                let swing = match source[n + 1].parse::<f32>() {
+                    Ok(x) if !x.is_nan() => x.clamp(MIN_SWING, MAX_SWING),
+                    _ => return Err("Invalid swing amount".to_string()),
+                };
+                (&source[..n], Some(swing))
+            }
+        };
         let mut stages: Vec<(bool, PosFloat)> =
             Vec::with_capacity(source.len());
         let mut basis = None;
@@ -189,6 +216,7 @@ impl Timebase {
                         .to_string(),
                 ),
             };
+        let stages_len = stages.len();
         let mut ret: Vec<TimebaseStage> = Vec::with_capacity(stages.len());
         let mut iter = stages.into_iter().enumerate();
         for (n, (one_based, mut multiplier)) in &mut iter {
@@ -214,7 +242,15 @@ impl Timebase {
                 break;
             }
         }
-        let mut multiplier = ret.last().unwrap().multiplier;
+        let beat = ret.last().unwrap().multiplier;
+        let swing = match swing {
+            None => None,
+            Some(_) if basis_index + 1 >= stages_len => {
+                return Err("Swing needs a resolution after the basis, to say which notes are swung".to_string());
+            }
+            Some(swing) => Some((basis_index + 1, swing)),
+        };
+        let mut multiplier = beat;
         for (_, (one_based, number)) in &mut iter {
             multiplier = multiplier / number;
             ret.push(TimebaseStage {
@@ -222,7 +258,67 @@ impl Timebase {
                 multiplier,
             });
         }
-        Ok(Timebase { stages: ret })
+        Ok(Timebase {
+            stages: ret,
+            beat,
+            swing,
+        })
+    }
+    /// Applies swing to a time, measured from the beginning of a beat, made
+    /// up only of stages at or below the swung stage.
+    ///
+    /// The swung stage's ticks are taken in pairs. Straight, the second tick
+    /// of a pair falls halfway through it; with a swing amount of `s`, it
+    /// falls `s` of the way through it instead. Times in between are
+    /// stretched (in the first half) or squashed (in the second half) to
+    /// match, so finer stages stay in order and evenly spaced within each
+    /// swung tick. Lengths are swung too, as if they started on a beat.
+    fn swing_time(&self, time: f32) -> f32 {
+        let Some((stage, swing)) = self.swing else {
+            return time;
+        };
+        let tick = *self.stages[stage].multiplier;
+        let pair = tick * 2.0;
+        let pairs = (time / pair).floor();
+        let within = time - pairs * pair;
+        let swung = if within < tick {
+            within * 2.0 * swing
+        } else {
+            pair * swing + (within - tick) * 2.0 * (1.0 - swing)
+        };
+        pairs * pair + swung
+    }
+    /// Evaluates a note value, like `1/4` (a quarter note, which is one
+    /// beat), `3/8` (three eighth notes), `1/4.` (a dotted quarter note), or
+    /// `1/8t` (an eighth note triplet). More than one dot is allowed.
+    fn eval_note_value(&self, specifier: &str) -> Result<PosFloat, String> {
+        let (numerator, mut denominator) = specifier
+            .split_once('/')
+            .ok_or_else(|| "Invalid note value".to_string())?;
+        let triplet = match denominator.strip_suffix('t') {
+            Some(x) => {
+                denominator = x;
+                true
+            }
+            None => false,
+        };
+        let without_dots = denominator.trim_end_matches('.');
+        let dots = denominator.len() - without_dots.len();
+        let numerator = match numerator.parse::<u32>() {
+            Ok(x) if x > 0 => x,
+            _ => return Err("Invalid note value".to_string()),
+        };
+        let denominator = match without_dots.parse::<u32>() {
+            Ok(x) if x > 0 => x,
+            _ => return Err("Invalid note value".to_string()),
+        };
+        // each dot adds half as much as the previous one did
+        let mut length = numerator as f32 * 4.0 / denominator as f32;
+        length *= 2.0 - 0.5f32.powi(dots as i32);
+        if triplet {
+            length *= 2.0 / 3.0;
+        }
+        Ok(PosFloat::new(length)? * self.beat)
     }
     fn eval(
         &self,
@@ -233,6 +329,8 @@ impl Timebase {
         // largest... but if your Segment is long enough for that to matter
         // you should rethink your life choices
         let mut ret = PosFloat::ZERO;
+        let swing_stage = self.swing.map(|(x, _)| x).unwrap_or(usize::MAX);
+        let mut within_beat = PosFloat::ZERO;
         for (i, stage) in self.stages.iter().enumerate() {
             let last = i + 1 == self.stages.len();
             let raw = if last {
@@ -256,7 +354,14 @@ impl Timebase {
             if be_one_based && stage.one_based {
                 raw.saturating_sub(PosFloat::ONE);
             }
-            ret = ret + raw * stage.multiplier;
+            if i >= swing_stage {
+                within_beat = within_beat + raw * stage.multiplier;
+            } else {
+                ret = ret + raw * stage.multiplier;
+            }
+        }
+        if swing_stage != usize::MAX {
+            ret = ret + PosFloat::new_clamped(self.swing_time(*within_beat));
         }
         Ok(ret)
     }
@@ -356,6 +461,9 @@ impl<'a> TimebaseCollection<'a> {
             },
             _ => return Err("either specify a time in the default timebase, or the name of a timebase followed by a time in that timebase".to_string())
         };
+        if time.contains('/') {
+            return timebase.eval_note_value(time);
+        }
         match timebase.eval(
             time,
             !(items[0].ends_with("length")
@@ -402,7 +510,77 @@ fn timebase_parse() {
                     one_based: false,
                     multiplier: PosFloat::new_clamped(1.0 / 64.0)
                 }
-            ]
+            ],
+            beat: PosFloat::new_clamped(0.5),
+            swing: None,
         })
     );
 }
+
+#[test]
+fn swing_parse() {
+    let timebase = |items: &[&str]| {
+        Timebase::parse_source(
+            &items.iter().map(|x| x.to_string()).collect::<Vec<_>>(),
+        )
+    };
+    // bars of four beats, 120 beats per minute, two eighth notes per beat,
+    // 12 ticks per eighth
+    let groove =
+        timebase(&["@4", "120/m", "2", "12", "swing", "0.6"]).unwrap();
+    let eval = |x: &str| *groove.eval(x, false).unwrap();
+    let close = |a: f32, b: f32| (a - b).abs() < 0.0001;
+    // on-beats are untouched
+    assert!(close(eval("0.0.0.0"), 0.0));
+    assert!(close(eval("1.0.0.0"), 2.0));
+    assert!(close(eval("0.1.0.0"), 0.5));
+    // off-beats are pushed from 50% to 60% of the beat
+    assert!(close(eval("0.0.1.0"), 0.3));
+    assert!(close(eval("0.1.1.0"), 0.8));
+    // ticks within an eighth stay evenly spaced
+    assert!(close(eval("0.0.0.6"), 0.15));
+    assert!(close(eval("0.0.1.6"), 0.4));
+    // swing is clamped
+    let extreme = timebase(&["120/m", "2", "swing", "5"]).unwrap();
+    assert_eq!(extreme.swing, Some((1, MAX_SWING)));
+    // swing needs something to swing, and must come last
+    assert!(timebase(&["120/m", "swing", "0.6"]).is_err());
+    assert!(timebase(&["120/m", "swing", "0.6", "2"]).is_err());
+    assert!(timebase(&["120/m", "2", "swing"]).is_err());
+    assert!(timebase(&["120/m", "2", "swing", "fast"]).is_err());
+}
+
+#[test]
+fn note_value_parse() {
+    let mut timebases = TimebaseCollection::new();
+    timebases
+        .parse_timebase_node(&DinNode {
+            lineno: 1,
+            items: vec![
+                "timebase".to_string(),
+                "@4".to_string(),
+                "120/m".to_string(),
+                "32".to_string(),
+            ],
+            children: vec![],
+            metadata: HashMap::new(),
+        })
+        .unwrap();
+    let length = |x: &str| {
+        *timebases
+            .parse_time(&["length".to_string(), x.to_string()])
+            .unwrap()
+    };
+    assert_eq!(length("1/4"), 0.5);
+    assert_eq!(length("1/8"), 0.25);
+    assert_eq!(length("3/8"), 0.75);
+    assert_eq!(length("1/1"), 2.0);
+    assert_eq!(length("1/4."), 0.75);
+    assert_eq!(length("1/4.."), 0.875);
+    assert!((length("1/8t") - 1.0 / 6.0).abs() < 0.0001);
+    for bad in ["1/", "/4", "0/4", "1/0", "1/x", "1/4t.", "a/4"] {
+        assert!(timebases
+            .parse_time(&["length".to_string(), bad.to_string()])
+            .is_err());
+    }
+}
@@ -206,3 +206,267 @@ fn complex_flatten_if() {
         panic!("commands did not come out right");
     }
 }
+
+#[test]
+fn string_or_number_typed_accessors() {
+    let string = |x: &str| StringOrNumber::String(x.to_compact_string());
+    assert_eq!(string("").try_as_number(), Some(0.0));
+    assert_eq!(string("2.5").try_as_number(), Some(2.5));
+    assert_eq!(string("two").try_as_number(), None);
+    assert_eq!(StringOrNumber::Number(2.5).try_as_number(), Some(2.5));
+    assert_eq!(string("").as_i64(), Some(0));
+    assert_eq!(string("-42").as_i64(), Some(-42));
+    assert_eq!(string("9007199254740993").as_i64(), Some(9007199254740993));
+    assert_eq!(string("1e3").as_i64(), Some(1000));
+    assert_eq!(string("2.5").as_i64(), None);
+    assert_eq!(string("two").as_i64(), None);
+    assert_eq!(StringOrNumber::Number(7.0).as_i64(), Some(7));
+    assert_eq!(StringOrNumber::Number(7.5).as_i64(), None);
+    assert_eq!(StringOrNumber::Number(f32::NAN).as_i64(), None);
+    assert_eq!(StringOrNumber::Number(f32::INFINITY).as_i64(), None);
+    assert_eq!(StringOrNumber::Number(1e30).as_i64(), None);
+    assert!(string("yes").as_bool());
+    assert!(!string("false").as_bool());
+    assert!(!StringOrNumber::Number(0.0).as_bool());
+}
+
+#[test]
+fn dump_flow_indices() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+flow test_flow
+  node test_node
+    if $completion == "finished" then
+      start node victory
+    elseif $completion == "failed" then
+      start node defeat
+    else
+      start node drumroll
+    restart starting node
+  node victory
+  node defeat
+  node drumroll
+"#,
+    )
+    .unwrap();
+    assert!(soundtrack.dump_flow("nonexistent").is_none());
+    let dump = soundtrack.dump_flow("test_flow").unwrap();
+    let node = &soundtrack.flows["test_flow"].nodes["test_node"];
+    let listing = dump
+        .split_once("node \"test_node\":\n")
+        .unwrap()
+        .1
+        .split("node ")
+        .next()
+        .unwrap();
+    // Where each label is, and which index each line claims to be.
+    let mut labels = HashMap::new();
+    let mut next_index = 0;
+    let mut gotos = 0;
+    for line in listing.lines() {
+        let line = line.trim();
+        if let Some(label) = line.strip_suffix(':') {
+            labels.insert(label.to_string(), next_index);
+            continue;
+        }
+        let (index, rest) = line.split_once("  ").unwrap();
+        assert_eq!(index.parse::<usize>().unwrap(), next_index);
+        if let Some(rest) = rest.strip_prefix("goto ") {
+            let label = rest.split(' ').next().unwrap();
+            let Command::Goto(_, _, target) = &node.commands[next_index]
+            else {
+                panic!("{line:?} is not a goto");
+            };
+            assert_eq!(label, format!("L{target}"));
+            gotos += 1;
+        }
+        next_index += 1;
+    }
+    assert_eq!(next_index, node.commands.len());
+    assert!(gotos >= 3);
+    for command in node.commands.iter() {
+        if let Command::Goto(_, _, target) = command {
+            assert_eq!(labels[&format!("L{target}")], *target);
+        }
+    }
+}
+
+#[test]
+fn soundtrack_diff_round_trip() {
+    let old = Soundtrack::from_source(
+        r#"
+sound kept
+  file kept.wav
+sound edited
+  file edited.wav
+sound dropped
+  file dropped.wav
+flow main
+  play sound kept
+"#,
+    )
+    .unwrap();
+    let mut new = old
+        .clone()
+        .parse_source(
+            r#"
+sound edited
+  file edited2.wav
+sound extra
+  file extra.wav
+"#,
+        )
+        .unwrap();
+    new.sounds.remove("dropped");
+    let diff = old.diff(&new);
+    let sorted = |names: &mut dyn Iterator<Item = &str>| {
+        let mut names: Vec<String> = names.map(str::to_string).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(sorted(&mut diff.added_sounds()), ["extra"]);
+    assert_eq!(sorted(&mut diff.changed_sounds()), ["edited"]);
+    assert_eq!(sorted(&mut diff.removed_sounds()), ["dropped"]);
+    assert_eq!(diff.changed_flows().count(), 0);
+    let mut patched = old.clone();
+    patched.apply_diff(&diff);
+    assert!(patched.diff(&new).is_empty());
+    assert!(Arc::ptr_eq(&patched.sounds["extra"], &new.sounds["extra"]));
+}
+
+#[test]
+fn flow_channels_follow_sequences() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound kick
+  file kick.wav
+sequence inner
+  length 1
+  play sound kick
+    channel lead
+    send reverb 0.5
+sequence outer
+  length 1
+  play sound kick
+    channel drums
+  play sequence inner
+flow direct
+  play sound kick
+flow sequenced
+  play sequence outer
+"#,
+    )
+    .unwrap();
+    let sorted = |flow_name: &str| {
+        let mut channels: Vec<CompactString> = soundtrack
+            .flow_channels(flow_name)
+            .unwrap()
+            .into_iter()
+            .collect();
+        channels.sort();
+        channels
+    };
+    assert_eq!(sorted("direct"), ["main"]);
+    assert_eq!(sorted("sequenced"), ["drums", "lead", "reverb"]);
+    assert!(soundtrack.flow_channels("missing").is_none());
+}
+
+#[test]
+fn flow_durations_are_estimated() {
+    let soundtrack = Soundtrack::from_source(
+        r#"
+sound sting
+  file sting.wav
+  length 2
+sound mystery
+  file mystery.wav
+sequence bar
+  length 4
+flow intro
+  play sound sting and wait
+  start node body
+  node body
+    if $long then
+      play sequence bar and wait
+    start node tail
+  node tail
+    wait 1
+    start node coda
+  node coda
+    wait 5
+flow endless
+  wait until $never
+flow unknowable
+  play sound mystery and wait
+flow looping with loop
+  wait 1
+flow restarting
+  wait 1
+  start node again
+  node again
+    start node again
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        soundtrack.sequence_duration("bar"),
+        Some(PosFloat::new_clamped(4.0))
+    );
+    // sting, then bar, then tail's wait, then all of coda
+    assert_eq!(
+        soundtrack.flow_max_duration("intro"),
+        Some(PosFloat::new_clamped(12.0))
+    );
+    for indeterminate in
+        ["endless", "unknowable", "looping", "restarting", "missing"]
+    {
+        assert_eq!(soundtrack.flow_max_duration(indeterminate), None);
+    }
+}
+
+#[test]
+fn default_channel_is_configurable() {
+    let mut soundtrack = Soundtrack::new();
+    soundtrack.set_default_channel("bgm".into());
+    let soundtrack = soundtrack
+        .parse_source(
+            r#"
+sound kick
+  file kick.wav
+sequence beat
+  length 1
+  play sound kick
+flow direct
+  play sound kick
+flow sequenced
+  play sequence beat
+"#,
+        )
+        .unwrap();
+    let channels = |flow_name: &str| -> Vec<CompactString> {
+        soundtrack
+            .flow_channels(flow_name)
+            .unwrap()
+            .into_iter()
+            .collect()
+    };
+    assert_eq!(channels("direct"), ["bgm"]);
+    assert_eq!(channels("sequenced"), ["bgm"]);
+    assert_eq!(Soundtrack::new().default_channel(), "main");
+}
+
+#[test]
+fn channel_maxgain() {
+    let soundtrack = Soundtrack::from_source(
+        "channel lead maxgain 2.0\nchannel quiet maxgain 0.5\n",
+    )
+    .unwrap();
+    assert_eq!(
+        soundtrack.channel_max_volume("lead"),
+        Some(PosFloat::new_clamped(2.0))
+    );
+    assert_eq!(soundtrack.channel_max_volume("quiet"), Some(PosFloat::HALF));
+    assert_eq!(soundtrack.channel_max_volume("main"), None);
+    assert!(Soundtrack::from_source("channel lead maxgain loud\n").is_err());
+    assert!(Soundtrack::from_source("channel lead\n").is_err());
+}
@@ -1,4 +1,88 @@
-use super::FormattedSoundStream;
+use super::{FormattedSoundStream, StringOrNumber};
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiagLevel {
+    /// Something worth knowing about while debugging, but not a problem in
+    /// itself. (For example, a voice being stolen.) Only issued in debug
+    /// builds.
+    Debug,
+    /// Something doesn't make sense. Usually a mistake in the soundtrack, or
+    /// in the commands the game is issuing. SMS will ignore it and carry on.
+    Warning,
+    /// Something failed at runtime, e.g. a sound file couldn't be opened. SMS
+    /// will play silence in its place.
+    Error,
+    /// SMS's internal bookkeeping has become inconsistent. THIS IS A BUG IN
+    /// SMS, and should be reported as such.
+    Bug,
+}
+
+/// What a diagnostic is about. More codes may be added in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DiagCode {
+    /// A flow was referred to that doesn't exist.
+    MissingFlow,
+    /// A node was referred to that doesn't exist in its flow.
+    MissingNode,
+    /// A sound was referred to that doesn't exist.
+    MissingSound,
+    /// A sequence was referred to that doesn't exist.
+    MissingSequence,
+    /// A preset was referred to that doesn't exist.
+    MissingPreset,
+    /// A channel group was referred to that doesn't exist.
+    MissingGroup,
+    /// A MixControl was referred to that doesn't exist yet.
+    MissingMixControl,
+    /// An attempt was made to start a node that was already playing.
+    NodeAlreadyPlaying,
+    /// A node was referred to that isn't playing.
+    NodeNotPlaying,
+    /// A playing node was replaced by an edited version too short to carry
+    /// on where it left off, so it was restarted from its beginning.
+    NodeRewound,
+    /// A chain of flow successors would have looped back on itself.
+    SuccessorLoop,
+    /// A flow was precached more than once.
+    RedundantPrecache,
+    /// A flow was unprecached that wasn't precached.
+    UnbalancedUnprecache,
+    /// A channel's voice limit was reached, and an older sound was stopped to
+    /// make room for a newer one.
+    VoiceStolen,
+    /// A channel's voice limit was reached, and a new sound was not played.
+    VoiceDropped,
+    /// The same sound file is used both streamed and buffered.
+    StreamedAndBuffered,
+    /// A sound's length was needed, but couldn't be determined.
+    UnknownLength,
+    /// Buffered sounds are using more memory than the budget allows.
+    OverBudget,
+    /// The delegate couldn't open a sound file.
+    OpenFailed,
+    /// Loading a sound in the background failed.
+    LoadFailed,
+    /// A sound file couldn't be decoded all the way through (e.g. because
+    /// it's corrupt). See `SoundReader::take_error`.
+    DecodeFailed,
+    /// The delegate opened a sound file, but the stream it returned didn't
+    /// make sense: its sample rate was zero, or it didn't come out in whole
+    /// sample frames for its `SpeakerLayout`.
+    BadStreamFormat,
+    /// A resampler couldn't be set up.
+    ResamplerFailed,
+    /// A sound was unloaded more times than it was loaded.
+    UnbalancedUnload,
+    /// Flows ran too many instructions without any time passing, and were
+    /// held back until the next block of audio. See
+    /// `Engine::set_instruction_limit`.
+    RunawayFlow,
+    /// A flow waited longer than the load timeout for its sounds to load.
+    /// See `Engine::set_load_timeout`.
+    LoadTimedOut,
+}
 
 /// This is an object that SMS will hang onto, and will call upon to open sound
 /// files and issue warnings. It must be thread safe.
@@ -12,4 +96,36 @@ pub trait SoundDelegate: Send + Sync {
     fn warning(&self, message: &str) {
         eprintln!("SMS warning: {}", message);
     }
+    /// Present and/or log a diagnostic in some application-specific way. All
+    /// of SMS's own warnings come through here. The default implementation
+    /// ignores `level` and `code` and passes `message` on to `warning`.
+    fn diagnostic(&self, level: DiagLevel, code: DiagCode, message: &str) {
+        let _ = (level, code);
+        self.warning(message)
+    }
+    /// Called when a FlowControl is given a new value, either by a `set`
+    /// command in a flow or by `EngineCommands::set_flow_control`. Not called
+    /// if the value didn't actually change. Lets the music feed back to the
+    /// rest of the game (e.g. the music decided to enter "boss" mode) without
+    /// polling.
+    ///
+    /// This is called from whichever thread is turning the `Engine`'s handle
+    /// (i.e. probably your sound thread), so don't block! The default
+    /// implementation does nothing.
+    fn flow_control_changed(&self, name: &str, value: &StringOrNumber) {
+        let _ = (name, value);
+    }
+    /// Called each time a repeated sound (a `play sound` in a sequence with a
+    /// `repeat`) starts over, at the exact sample frame it does so. `node` is
+    /// the node that played the sequence, or `None` if it was the start node.
+    /// Lets visuals sync up with a looping phrase, e.g. to flash a beat
+    /// indicator on the loop point. Only called if turned on with
+    /// `Engine::set_loop_wrap_callbacks`.
+    ///
+    /// Like `flow_control_changed`, this is called from whichever thread is
+    /// turning the `Engine`'s handle, so keep it cheap (e.g. set a flag or
+    /// send a message and return). The default implementation does nothing.
+    fn loop_wrapped(&self, flow: &str, node: Option<&str>, sound: &str) {
+        let _ = (flow, node, sound);
+    }
 }
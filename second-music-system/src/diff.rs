@@ -0,0 +1,214 @@
+//! Working out what changed between two versions of a `Soundtrack`, and
+//! applying those changes to another copy of it.
+
+use super::*;
+
+/// The changes to one of a `Soundtrack`'s maps.
+#[derive(Debug)]
+struct MapDiff<T> {
+    added: HashMap<CompactString, Arc<T>>,
+    changed: HashMap<CompactString, Arc<T>>,
+    removed: Vec<CompactString>,
+}
+
+// (not derived, since that would require `T: Clone`)
+impl<T> Clone for MapDiff<T> {
+    fn clone(&self) -> MapDiff<T> {
+        MapDiff {
+            added: self.added.clone(),
+            changed: self.changed.clone(),
+            removed: self.removed.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> MapDiff<T> {
+    fn new(
+        old: &HashMap<CompactString, Arc<T>>,
+        new: &HashMap<CompactString, Arc<T>>,
+    ) -> MapDiff<T> {
+        let mut added = HashMap::new();
+        let mut changed = HashMap::new();
+        for (name, new_item) in new.iter() {
+            match old.get(name) {
+                None => {
+                    added.insert(name.clone(), new_item.clone());
+                }
+                // Entries that weren't touched between the two versions are
+                // still shared, so we can usually skip the comparison.
+                Some(old_item)
+                    if Arc::ptr_eq(old_item, new_item)
+                        || old_item == new_item => {}
+                Some(_) => {
+                    changed.insert(name.clone(), new_item.clone());
+                }
+            }
+        }
+        let removed = old
+            .keys()
+            .filter(|name| !new.contains_key(*name))
+            .cloned()
+            .collect();
+        MapDiff {
+            added,
+            changed,
+            removed,
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.changed.is_empty()
+            && self.removed.is_empty()
+    }
+    fn apply(&self, map: &mut HashMap<CompactString, Arc<T>>) {
+        for name in self.removed.iter() {
+            map.remove(name);
+        }
+        for (name, item) in self.added.iter().chain(self.changed.iter()) {
+            map.insert(name.clone(), item.clone());
+        }
+    }
+}
+
+/// What changed between two versions of a `Soundtrack`: which flows,
+/// sequences, sounds, and presets were added, removed, or changed, and
+/// whether the default channel, any `maxgain`s, or any channel groups were
+/// changed. Made by
+/// `Soundtrack::diff`, and applied by `Soundtrack::apply_diff`.
+///
+/// Added and changed entries are shared with the soundtrack the diff was
+/// made from, so a diff is cheap to make and to keep around.
+#[derive(Clone, Debug)]
+pub struct SoundtrackDiff {
+    flows: MapDiff<Flow>,
+    sequences: MapDiff<Sequence>,
+    sounds: MapDiff<Sound>,
+    presets: MapDiff<Preset>,
+    /// The new default channel, if it changed.
+    default_channel: Option<CompactString>,
+    /// The new `maxgain`s, if any of them changed.
+    channel_max_volumes: Option<Arcow<HashMap<CompactString, PosFloat>>>,
+    /// The new channel groups, if any of them changed.
+    channel_groups: Option<Arcow<HashMap<CompactString, Vec<CompactString>>>>,
+}
+
+impl SoundtrackDiff {
+    /// Returns true if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+            && self.sequences.is_empty()
+            && self.sounds.is_empty()
+            && self.presets.is_empty()
+            && self.default_channel.is_none()
+            && self.channel_max_volumes.is_none()
+            && self.channel_groups.is_none()
+    }
+    /// Returns the names of all the flows that were added, in no particular
+    /// order.
+    pub fn added_flows(&self) -> impl Iterator<Item = &str> {
+        self.flows.added.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the flows that were changed, in no
+    /// particular order.
+    pub fn changed_flows(&self) -> impl Iterator<Item = &str> {
+        self.flows.changed.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the flows that were removed, in no
+    /// particular order.
+    pub fn removed_flows(&self) -> impl Iterator<Item = &str> {
+        self.flows.removed.iter().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sequences that were added, in no
+    /// particular order.
+    pub fn added_sequences(&self) -> impl Iterator<Item = &str> {
+        self.sequences.added.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sequences that were changed, in no
+    /// particular order.
+    pub fn changed_sequences(&self) -> impl Iterator<Item = &str> {
+        self.sequences.changed.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sequences that were removed, in no
+    /// particular order.
+    pub fn removed_sequences(&self) -> impl Iterator<Item = &str> {
+        self.sequences.removed.iter().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sounds that were added, in no particular
+    /// order.
+    pub fn added_sounds(&self) -> impl Iterator<Item = &str> {
+        self.sounds.added.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sounds that were changed, in no
+    /// particular order.
+    pub fn changed_sounds(&self) -> impl Iterator<Item = &str> {
+        self.sounds.changed.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sounds that were removed, in no
+    /// particular order.
+    pub fn removed_sounds(&self) -> impl Iterator<Item = &str> {
+        self.sounds.removed.iter().map(CompactString::as_str)
+    }
+    /// Returns the names of all the presets that were added, in no
+    /// particular order.
+    pub fn added_presets(&self) -> impl Iterator<Item = &str> {
+        self.presets.added.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the presets that were changed, in no
+    /// particular order.
+    pub fn changed_presets(&self) -> impl Iterator<Item = &str> {
+        self.presets.changed.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the presets that were removed, in no
+    /// particular order.
+    pub fn removed_presets(&self) -> impl Iterator<Item = &str> {
+        self.presets.removed.iter().map(CompactString::as_str)
+    }
+    /// Returns the new default channel, if it was changed.
+    pub fn changed_default_channel(&self) -> Option<&str> {
+        self.default_channel.as_deref()
+    }
+}
+
+impl Soundtrack {
+    /// Works out what changed between this soundtrack and `other`, a later
+    /// version of it. Applying the result to this soundtrack (with
+    /// `apply_diff`) gives a soundtrack equivalent to `other`.
+    ///
+    /// This is fastest when `other` was made from a clone of this soundtrack
+    /// (e.g. with `parse_source`), since whatever wasn't touched is still
+    /// shared between the two, and doesn't need comparing.
+    pub fn diff(&self, other: &Soundtrack) -> SoundtrackDiff {
+        SoundtrackDiff {
+            flows: MapDiff::new(&self.flows, &other.flows),
+            sequences: MapDiff::new(&self.sequences, &other.sequences),
+            sounds: MapDiff::new(&self.sounds, &other.sounds),
+            presets: MapDiff::new(&self.presets, &other.presets),
+            default_channel: (self.default_channel != other.default_channel)
+                .then(|| other.default_channel.clone()),
+            channel_max_volumes: (*self.channel_max_volumes
+                != *other.channel_max_volumes)
+                .then(|| other.channel_max_volumes.clone()),
+            channel_groups: (*self.channel_groups != *other.channel_groups)
+                .then(|| other.channel_groups.clone()),
+        }
+    }
+    /// Applies the changes from a `SoundtrackDiff` to this soundtrack: adds
+    /// and replaces the flows, sequences, sounds, and presets that were added
+    /// or changed, removes the ones that were removed, and changes the default
+    /// channel, `maxgain`s, and channel groups if they were changed.
+    pub fn apply_diff(&mut self, diff: &SoundtrackDiff) {
+        diff.flows.apply(&mut self.flows);
+        diff.sequences.apply(&mut self.sequences);
+        diff.sounds.apply(&mut self.sounds);
+        diff.presets.apply(&mut self.presets);
+        if let Some(default_channel) = diff.default_channel.as_ref() {
+            self.default_channel = default_channel.clone();
+        }
+        if let Some(channel_max_volumes) = diff.channel_max_volumes.as_ref() {
+            self.channel_max_volumes = channel_max_volumes.clone();
+        }
+        if let Some(channel_groups) = diff.channel_groups.as_ref() {
+            self.channel_groups = channel_groups.clone();
+        }
+    }
+}
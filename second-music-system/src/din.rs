@@ -1,6 +1,17 @@
 //! Descriptively Indented Nodes
+//!
+//! Each line is a node, whose items are split up the way a shell would split
+//! them (so an item with spaces in it can be quoted, and a backslash escapes
+//! the character after it). A node indented further than the node above it is
+//! that node's child. Indentation is spaces only; tabs are an error.
+//!
+//! An unquoted, unescaped `#` begins a comment, which runs to the end of the
+//! line. A `#` in quotes (or after a backslash) is an ordinary character.
+//! Lines that are blank or only hold a comment are skipped entirely, no matter
+//! how (or with what) they're indented, so comments can go anywhere, including
+//! between the commands of a flow or after an expression.
 
-use std::{cmp::Ordering, mem::take};
+use std::{cmp::Ordering, collections::HashMap, mem::take};
 
 #[cfg(test)]
 macro_rules! node {
@@ -12,6 +23,7 @@ macro_rules! node {
             lineno: $lineno,
             items: $items.into_iter().map(|x| x.to_string()).collect(),
             children: $children.into_iter().map(Some).collect(),
+            metadata: HashMap::new(),
         }
     };
 }
@@ -60,6 +72,10 @@ impl Iterator for DinParser<'_> {
                         self.rem = &self.rem[1..];
                         space_count += 1;
                     }
+                    // (tabs are fine before a comment, or before nothing)
+                    b'\t' if is_blank_or_comment(self.rem) => {
+                        self.rem = &self.rem[1..];
+                    }
                     b'\t' => return Some(Err(format!(
                         "line {}: indentation contains tabs, which are not allowed",
                         self.lineno,
@@ -143,14 +159,32 @@ impl Iterator for DinParser<'_> {
     }
 }
 
+/// Returns true if the rest of the current line is only whitespace, with or
+/// without a comment after it.
+fn is_blank_or_comment(line: &[u8]) -> bool {
+    match line.iter().find(|x| **x != b' ' && **x != b'\t') {
+        None | Some(b'\n' | b'\r' | b'#') => true,
+        Some(_) => false,
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct DinNode {
     pub items: Vec<String>,
     pub children: Vec<Option<DinNode>>,
     pub lineno: usize,
+    /// The payloads of this node's `editor:` children, if they were kept. An
+    /// `editor:KEY VALUE...` child becomes an entry from `KEY` to the rest of
+    /// its items, separated by spaces.
+    pub metadata: HashMap<String, String>,
 }
 
-pub fn parse_din(src: &str) -> Result<Vec<DinNode>, String> {
+/// Parse a document. If `keep_metadata` is true, `editor:` nodes are turned
+/// into their parents' `metadata`; otherwise, they're thrown away.
+pub fn parse_din(
+    src: &str,
+    keep_metadata: bool,
+) -> Result<Vec<DinNode>, String> {
     let mut ret: Vec<DinNode> = vec![];
     let mut stack: Vec<DinNode> = vec![];
     for item in DinParser::new(src.as_bytes()) {
@@ -160,11 +194,21 @@ pub fn parse_din(src: &str) -> Result<Vec<DinNode>, String> {
                 items,
                 children: vec![],
                 lineno,
+                metadata: HashMap::new(),
             }),
             ParseItem::EndNode => {
                 let endut = stack.pop().unwrap();
-                // Ignore nodes that start with "editor:"
-                if endut.items[0].starts_with("editor:") {
+                // Ignore nodes that start with "editor:", unless we're keeping
+                // them
+                if let Some(key) = endut.items[0].strip_prefix("editor:") {
+                    if keep_metadata {
+                        if let Some(parent) = stack.last_mut() {
+                            parent.metadata.insert(
+                                key.to_string(),
+                                endut.items[1..].join(" "),
+                            );
+                        }
+                    }
                     continue;
                 }
                 match stack.last_mut() {
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use compact_str::CompactString;
 
 use crate::din::*;
@@ -11,6 +13,7 @@ fn basics() {
             .collect(),
         children: vec![],
         lineno: 1,
+        metadata: HashMap::new(),
     };
     let mut element_type = None;
     let mut name = None;
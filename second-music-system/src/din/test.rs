@@ -125,7 +125,7 @@ fn iterate_din_parser() {
 #[test]
 fn parse_din_nodes() {
     assert_eq!(
-        parse_din(std::str::from_utf8(TEST_DOCUMENT).unwrap()).unwrap(),
+        parse_din(std::str::from_utf8(TEST_DOCUMENT).unwrap(), false).unwrap(),
         &[
             node!(
                 1,
@@ -205,3 +205,23 @@ fn parse_din_nodes() {
         ]
     );
 }
+
+#[test]
+fn comments_are_ignored() {
+    const DOCUMENT: &str = "# a comment before anything\n\
+        flow \"# not a comment\" # a comment after something\n\
+        \t# a comment indented with a tab\n\
+        \x20     # a comment indented too far\n\
+        \x20 play sound \\#3\n\
+        # a comment at the top level, in the middle of a node\n\
+        \x20 wait 1\n";
+    assert_eq!(
+        parse_din(DOCUMENT, false).unwrap(),
+        &[node!(
+            2,
+            ["flow", "# not a comment"],
+            [node!(5, ["play", "sound", "#3"]), node!(7, ["wait", "1"]),]
+        )]
+    );
+    assert!(parse_din("flow\n\tplay sound foo\n", false).is_err());
+}
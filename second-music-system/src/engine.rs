@@ -3,9 +3,10 @@
 use super::*;
 
 use std::{
-    collections::{BinaryHeap, HashMap, HashSet},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet, VecDeque},
     fmt::{Debug, Formatter, Result as FmtResult},
     mem::{swap, MaybeUninit},
+    num::NonZeroUsize,
 };
 
 mod mixer;
@@ -16,12 +17,127 @@ mod adapter;
 use adapter::*;
 mod interpreter;
 use interpreter::*;
+mod outputresampler;
+use outputresampler::*;
+mod effect;
+use effect::*;
+mod positional;
+use positional::*;
+mod tap;
+pub use tap::*;
+mod routing;
+use routing::*;
+mod builder;
+pub use builder::*;
+#[cfg(feature = "async")]
+mod asynccommander;
+#[cfg(feature = "async")]
+pub use asynccommander::*;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+#[cfg(any(test, feature = "testing"))]
+pub use testing::*;
 
-/// The name of the default channel. The default channel is at volume 1.0 by
-/// default, while all other channels are at 0.0. Additionally, the default
-/// channel is exempted from the "all except main" channel commands.
+/// When advancing silently, how many sample frames' worth of scratch space to
+/// use while skipping sounds.
+const SKIP_BUF_FRAMES: usize = 4096;
+
+/// See `Engine::set_instruction_limit`.
+const DEFAULT_INSTRUCTION_LIMIT: usize = 10000;
+
+/// See `EngineCommands::set_time_scale`.
+const MIN_TIME_SCALE: PosFloat =
+    unsafe { PosFloat::new_unchecked(1.0 / 64.0) };
+
+/// The name of the default channel, unless changed with
+/// `Engine::set_default_channel` and `Soundtrack::set_default_channel`. The
+/// default channel is at volume 1.0 by default, while all other channels are
+/// at 0.0. Additionally, the default channel is exempted from the "all except
+/// main" channel commands.
 pub const DEFAULT_CHANNEL: &str = "main";
 
+/// What to do when a sound is about to start on a MixControl that already has
+/// as many sounds playing as its voice limit allows. See
+/// [`EngineCommands::set_mix_control_voice_limit`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// Stop the sound that has been playing on that MixControl the longest,
+    /// and start the new sound in its place.
+    #[default]
+    StealOldest,
+    /// Leave the sounds that are already playing alone, and don't start the
+    /// new sound at all.
+    DropNewest,
+}
+
+/// A built-in effect that can be put on a MixControl. See
+/// [`EngineCommands::set_mix_control_effect`].
+///
+/// `feedback` is how much of the effect's output is fed back into it. Values
+/// of 1.0 and up would ring forever, so they're treated as 0.99. `mix` is how
+/// much of the MixControl's output is the effect, from 0.0 (none; the effect
+/// is inaudible) to 1.0 (all; the original sound is inaudible). Values above
+/// 1.0 are treated as 1.0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelEffect {
+    /// An echo, `time` seconds after the original sound. Each echo is
+    /// `feedback` times as loud as the last.
+    Delay {
+        time: PosFloat,
+        feedback: PosFloat,
+        mix: PosFloat,
+    },
+    /// A simple reverb. `time` is roughly the size of the room, in seconds;
+    /// 0.05 is a small room, 0.1 a large hall. Higher `feedback` gives a
+    /// longer tail.
+    Reverb {
+        time: PosFloat,
+        feedback: PosFloat,
+        mix: PosFloat,
+    },
+}
+
+/// Where the listener is, and which way they're facing, for sounds that have
+/// a `position`. See [`EngineCommands::set_listener`].
+///
+/// The units are up to you, as long as they're the same ones the soundtrack
+/// uses for sound positions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Listener {
+    pub position: [f32; 3],
+    /// Which way the listener is facing. Doesn't need to be normalized.
+    pub forward: [f32; 3],
+    /// Which way is up, from the listener's point of view. Doesn't need to
+    /// be normalized, or exactly perpendicular to `forward`, but must not be
+    /// parallel to it.
+    pub up: [f32; 3],
+    /// Sounds at least this close to the listener are at full volume. Each
+    /// doubling of distance beyond this halves the volume.
+    pub reference_distance: PosFloat,
+}
+
+impl Default for Listener {
+    /// At the origin, facing -Z, with +Y up (and therefore +X to the right).
+    fn default() -> Listener {
+        Listener {
+            position: [0.0, 0.0, 0.0],
+            forward: [0.0, 0.0, -1.0],
+            up: [0.0, 1.0, 0.0],
+            reference_distance: PosFloat::ONE,
+        }
+    }
+}
+
+/// Which sounds a pitch bend applies to. See
+/// [`EngineCommands::fade_pitch_to`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ChannelOrFlow {
+    /// Every sound playing on the MixControl with the given name.
+    Channel(CompactString),
+    /// Every sound played by the Flow with the given name.
+    Flow(CompactString),
+}
+
 mod privacy_hack {
     use super::*;
     #[derive(Debug)]
@@ -32,6 +148,14 @@ mod privacy_hack {
         ReplaceSoundtrack {
             new_soundtrack: Soundtrack,
         },
+        CrossfadeSoundtrack {
+            new_soundtrack: Soundtrack,
+            fade_type: FadeType,
+            fade_length: PosFloat,
+        },
+        ReconcileSoundtrack {
+            new_soundtrack: Soundtrack,
+        },
         Precache {
             flow_name: CompactString,
         },
@@ -39,6 +163,12 @@ mod privacy_hack {
             flow_name: CompactString,
         },
         UnprecacheAll {},
+        CacheSound {
+            sound_name: CompactString,
+        },
+        UncacheSound {
+            sound_name: CompactString,
+        },
         IsFlowReady {
             flow_name: CompactString,
             tx: query::Responder<bool>,
@@ -63,6 +193,13 @@ mod privacy_hack {
         GetMixFlows {
             tx: query::Responder<Vec<MixFlowReport>>,
         },
+        GetStats {
+            tx: query::Responder<EngineStats>,
+        },
+        GetFlowLoadProgress {
+            flow_name: CompactString,
+            tx: query::Responder<Option<FlowLoadProgress>>,
+        },
         SetFlowControl {
             control_name: CompactString,
             new_value: StringOrNumber,
@@ -81,6 +218,15 @@ mod privacy_hack {
             control_prefix: CompactString,
         },
         ClearAllFlowControls {},
+        ApplyPreset {
+            preset_name: CompactString,
+        },
+        SeedRandom {
+            seed: u64,
+        },
+        SetTimeScale {
+            factor: PosFloat,
+        },
         FadeMixControlTo {
             control_name: CompactString,
             fade_type: FadeType,
@@ -98,6 +244,12 @@ mod privacy_hack {
             target_volume: PosFloat,
             fade_length: PosFloat,
         },
+        FadeGroupTo {
+            group_name: CompactString,
+            fade_type: FadeType,
+            target_volume: PosFloat,
+            fade_length: PosFloat,
+        },
         FadeAllMixControlsExceptMainTo {
             fade_type: FadeType,
             target_volume: PosFloat,
@@ -129,12 +281,97 @@ mod privacy_hack {
         },
         KillAllMixControls {},
         KillAllMixControlsExceptMain {},
+        SetMixControlVoiceLimit {
+            control_name: CompactString,
+            max_voices: NonZeroUsize,
+            stealing: VoiceStealing,
+        },
+        ClearMixControlVoiceLimit {
+            control_name: CompactString,
+        },
+        SoloMixControl {
+            control_name: CompactString,
+        },
+        ClearSolo {},
+        SetMixControlMuted {
+            control_name: CompactString,
+            muted: bool,
+        },
+        SetChannelEffect {
+            control_name: CompactString,
+            effect: Option<ChannelEffect>,
+        },
+        SetListener {
+            listener: Listener,
+        },
+        FadeChannelCutoffTo {
+            control_name: CompactString,
+            cutoff_hz: PosFloat,
+            fade_length: PosFloat,
+            fade_type: FadeType,
+        },
+        FadeChannelWidthTo {
+            control_name: CompactString,
+            width: PosFloat,
+            fade_length: PosFloat,
+            fade_type: FadeType,
+        },
+        FadePitchTo {
+            target: ChannelOrFlow,
+            fade_type: FadeType,
+            rate: PosFloat,
+            fade_length: PosFloat,
+        },
+        PlayOneShot {
+            sound_name: CompactString,
+            channel: CompactString,
+            volume: PosFloat,
+            pitch: PosFloat,
+            tx: query::Responder<bool>,
+        },
+        StopOneShots {
+            channel: Option<CompactString>,
+        },
         StartFlow {
             flow_name: CompactString,
+            node_name: Option<CompactString>,
+            offset: PosFloat,
+            fade_type: FadeType,
+            target_volume: PosFloat,
+            fade_length: PosFloat,
+        },
+        FadeNodeTo {
+            flow_name: CompactString,
+            node_name: Option<CompactString>,
+            fade_type: FadeType,
+            target_volume: PosFloat,
+            fade_length: PosFloat,
+        },
+        FadeSequenceElementTo {
+            flow_name: CompactString,
+            sequence_name: CompactString,
+            element: usize,
             fade_type: FadeType,
             target_volume: PosFloat,
             fade_length: PosFloat,
         },
+        StopSequenceElement {
+            flow_name: CompactString,
+            sequence_name: CompactString,
+            element: usize,
+        },
+        SetLayerIntensity {
+            flow_name: CompactString,
+            intensity: f32,
+            fade_type: FadeType,
+            fade_length: PosFloat,
+        },
+        SetFlowSuccessor {
+            flow_name: CompactString,
+            successor: Option<CompactString>,
+            fade_type: FadeType,
+            fade_length: PosFloat,
+        },
         FadeFlowTo {
             flow_name: CompactString,
             fade_type: FadeType,
@@ -157,6 +394,14 @@ mod privacy_hack {
             fade_type: FadeType,
             fade_length: PosFloat,
         },
+        FadeFlowToThenOut {
+            flow_name: CompactString,
+            fade_type: FadeType,
+            target_volume: PosFloat,
+            fade_length: PosFloat,
+            hold: PosFloat,
+            fade_out: PosFloat,
+        },
         FadePrefixedFlowsOut {
             flow_prefix: CompactString,
             fade_type: FadeType,
@@ -207,6 +452,13 @@ pub trait EngineCommands: EngineCommandIssuer {
             },
         }
     }
+    /// Sends all the commands in a `TransactionBuilder` at once, exactly as
+    /// if they had been put into a `Transaction` that was then `commit`ted.
+    fn send_transaction(&mut self, transaction: TransactionBuilder) {
+        self.issue(EngineCommand::Transaction {
+            commands: transaction.commands,
+        })
+    }
     /// Replace the active soundtrack with the given one. Currently-active
     /// nodes, sequences, and sounds will do their best to play to their
     /// conclusion.
@@ -218,11 +470,66 @@ pub trait EngineCommands: EngineCommandIssuer {
     fn replace_soundtrack(&mut self, new_soundtrack: Soundtrack) {
         self.issue(EngineCommand::ReplaceSoundtrack { new_soundtrack });
     }
+    /// Replace the active soundtrack with the given one, crossfading every
+    /// playing flow into its counterpart in the new soundtrack. This is meant
+    /// for hot-reloading a soundtrack while it's playing, e.g. while iterating
+    /// on it.
+    ///
+    /// Each flow that is currently playing will keep playing, from the old
+    /// soundtrack, while it fades out over `fade_length` seconds. If the new
+    /// soundtrack has a flow with the same name, it will be started and faded
+    /// up to the volume the old one was playing at (or fading to), over the
+    /// same period.
+    /// Flows that only exist in the old soundtrack are simply faded out.
+    /// Flows that were already fading out keep fading out, and don't get
+    /// started in the new soundtrack. Flows that were still waiting to start
+    /// will start in the new soundtrack, if they exist there.
+    ///
+    /// As with `replace_soundtrack`, the fading-out flows will play any
+    /// sounds and sequences they refer to from the *new* soundtrack.
+    /// Commands that name a flow, such as `kill_flow`, only affect its
+    /// replacement; the fading-out flows are only affected by commands that
+    /// affect every flow, or every flow with a given prefix.
+    fn crossfade_soundtrack(
+        &mut self,
+        new_soundtrack: Soundtrack,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::CrossfadeSoundtrack {
+            new_soundtrack,
+            fade_type,
+            fade_length,
+        });
+    }
+    /// Replace the active soundtrack with the given one, and switch every
+    /// playing node over to its edited counterpart in the new soundtrack,
+    /// without restarting anything. This is meant for live-editing a
+    /// soundtrack while listening to it.
+    ///
+    /// Each node that is currently playing, and whose flow still has a node
+    /// by the same name in the new soundtrack, will carry on from the same
+    /// instruction in the new version of that node. If the new version is too
+    /// short for that, it will start again from its beginning instead (and a
+    /// warning will be issued). Nodes that don't exist in the new soundtrack
+    /// keep playing their old version, as with `replace_soundtrack`. Flows
+    /// that are fading out after a `crossfade_soundtrack` are left alone.
+    ///
+    /// Sounds that are already playing are not affected.
+    fn reconcile_soundtrack(&mut self, new_soundtrack: Soundtrack) {
+        self.issue(EngineCommand::ReconcileSoundtrack { new_soundtrack });
+    }
     /// Requests that the given flow be precached for playback. The engine
     /// will attempt to load/preroll all requested sounds and streams in the
     /// background. Use `is_flow_ready` to determine when the loading is
     /// complete.
     ///
+    /// Loads for precaching get a lower priority than loads for flows that
+    /// have been started (see `TaskType`), so that precaching doesn't hold up
+    /// music that's needed right now. (A flow that's started while it's
+    /// still being precached keeps the lower priority for the loads that
+    /// were already under way.)
+    ///
     /// This is *not* recursive. If you call `precache` twice, then call
     /// `unprecache` once, the flow will no longer be precached.
     fn precache(&mut self, flow_name: CompactString) {
@@ -239,6 +546,10 @@ pub trait EngineCommands: EngineCommandIssuer {
     ///
     /// This is *not* recursive. If you call `precache` twice, then call
     /// `unprecache` once, the flow will no longer be precached.
+    ///
+    /// If any of the flow's sounds are still being loaded in the background,
+    /// those loads will be cancelled. (A sound that is already being decoded
+    /// may still finish decoding, but it will be thrown away.)
     fn unprecache(&mut self, flow_name: CompactString) {
         self.issue(EngineCommand::Unprecache { flow_name });
     }
@@ -251,6 +562,33 @@ pub trait EngineCommands: EngineCommandIssuer {
     fn unprecache_all(&mut self) {
         self.issue(EngineCommand::UnprecacheAll {});
     }
+    /// Requests that the given sound be loaded into memory in its entirety
+    /// and kept there, even if the soundtrack says it should be streamed.
+    /// This is useful for a streamed sound that turns out to be played very
+    /// often, such as a stinger. The sound will stay in memory until
+    /// `uncache_sound` is called.
+    ///
+    /// A buffered sound takes up a lot more memory than a streamed one;
+    /// about 4 bytes per sample per channel, for the whole length of the
+    /// sound. Don't do this to long sounds unless you have the memory to
+    /// spare.
+    ///
+    /// Instances of the sound that are already streaming will keep streaming.
+    /// Only instances that are loaded after all of those have finished will
+    /// use the cached copy.
+    ///
+    /// This is *not* recursive. If you call `cache_sound` twice, then call
+    /// `uncache_sound` once, the sound will no longer be forcibly cached.
+    fn cache_sound(&mut self, sound_name: CompactString) {
+        self.issue(EngineCommand::CacheSound { sound_name });
+    }
+    /// Undoes a previous request that the given sound be forcibly cached.
+    /// The sound will be let go once nothing is using it, and will go back to
+    /// being streamed the next time it's loaded (if the soundtrack says it
+    /// should be).
+    fn uncache_sound(&mut self, sound_name: CompactString) {
+        self.issue(EngineCommand::UncacheSound { sound_name });
+    }
     /// Returns a [`query::Response`](query/struct.Response.html) that will
     /// answer the question "Is this flow I precached now ready for instant
     /// playback?"
@@ -352,6 +690,29 @@ pub trait EngineCommands: EngineCommandIssuer {
         self.issue(EngineCommand::GetMixFlows { tx });
         rx
     }
+    /// Returns a [`query::Response`](query/struct.Response.html) that will
+    /// give you an [`EngineStats`](struct.EngineStats.html) for the `Engine`,
+    /// as of when the command is processed. (If you have the `Engine` itself
+    /// at hand, `Engine::stats` will answer immediately.)
+    fn get_stats(&mut self) -> query::Response<EngineStats> {
+        let (tx, rx) = query::make();
+        self.issue(EngineCommand::GetStats { tx });
+        rx
+    }
+    /// Returns a [`query::Response`](query/struct.Response.html) that will
+    /// give you a [`FlowLoadProgress`](struct.FlowLoadProgress.html) for the
+    /// given flow, telling you roughly how much longer it will be until the
+    /// flow is ready, or `None` if the flow isn't being precached or played.
+    /// (If you have the `Engine` itself at hand, `Engine::flow_load_progress`
+    /// will answer immediately.)
+    fn get_flow_load_progress(
+        &mut self,
+        flow_name: CompactString,
+    ) -> query::Response<Option<FlowLoadProgress>> {
+        let (tx, rx) = query::make();
+        self.issue(EngineCommand::GetFlowLoadProgress { flow_name, tx });
+        rx
+    }
     /// Clears a given FlowControl, removing any previous value.
     fn clear_flow_control(&mut self, control_name: CompactString) {
         self.issue(EngineCommand::ClearFlowControl { control_name });
@@ -367,12 +728,42 @@ pub trait EngineCommands: EngineCommandIssuer {
     fn clear_all_flow_controls(&mut self) {
         self.issue(EngineCommand::ClearAllFlowControls {});
     }
+    /// Sets all the FlowControls listed in the given preset from the current
+    /// soundtrack, all at once. The values are computed before any of the
+    /// FlowControls are changed, so a preset's expressions see the values the
+    /// FlowControls had *before* the preset was applied. FlowControls not
+    /// mentioned in the preset are left alone.
+    fn apply_preset(&mut self, preset_name: CompactString) {
+        self.issue(EngineCommand::ApplyPreset { preset_name });
+    }
+    /// Restarts the sequence of numbers returned by `random()` in the
+    /// soundtrack's expressions, based on the given seed. The same seed will
+    /// always result in the same sequence, so, as long as the same commands
+    /// are issued at the same times, a soundtrack will make the same "random"
+    /// decisions every time. Every `Engine` starts out as if it was seeded
+    /// with `0`.
+    fn seed_random(&mut self, seed: u64) {
+        self.issue(EngineCommand::SeedRandom { seed });
+    }
+    /// Stretches all musical timing (waits, and the timing of sequences) by
+    /// the given factor, without changing the pitch or speed of the sounds
+    /// themselves. At 0.5, the music plays at half speed, rhythmically; at
+    /// 2.0, at double speed. Every `Engine` starts out at 1.0. Factors below
+    /// 1/64 are treated as 1/64.
+    ///
+    /// This only affects timing worked out after the change: sounds that are
+    /// already queued keep their original start times and durations, and a
+    /// node that's already waiting finishes its wait as planned.
+    fn set_time_scale(&mut self, factor: PosFloat) {
+        self.issue(EngineCommand::SetTimeScale { factor });
+    }
     /// Fades a given MixControl to the given volume (0.0 to 1.0), using the
     /// given fading curve, over the given time period (in seconds).
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
-    /// it won't cause clipping. Don't give negative volumes.
+    /// it won't cause clipping (see `Soundtrack::channel_max_volume` for a
+    /// safety net). Don't give negative volumes.
     fn fade_mix_control_to(
         &mut self,
         control_name: CompactString,
@@ -393,7 +784,8 @@ pub trait EngineCommands: EngineCommandIssuer {
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
-    /// it won't cause clipping. Don't give negative volumes.
+    /// it won't cause clipping (see `Soundtrack::channel_max_volume` for a
+    /// safety net). Don't give negative volumes.
     fn fade_prefixed_mix_controls_to(
         &mut self,
         control_prefix: CompactString,
@@ -408,13 +800,39 @@ pub trait EngineCommands: EngineCommandIssuer {
             fade_length,
         });
     }
+    /// Fades every mix control in the given channel group (declared in the
+    /// soundtrack with `group NAME includes CHANNEL...`) to the given volume
+    /// (0.0 to 1.0), using the given fading curve, over the given time period
+    /// (in seconds). The group's members are looked up when the command is
+    /// carried out, in whichever soundtrack is current then. Members that
+    /// don't exist yet are created, with a warning.
+    ///
+    /// Use `FadeType::Exponential` unless you are doing intermixing of
+    /// correlated signals. Don't give a volume above 1.0 unless you are sure
+    /// it won't cause clipping (see `Soundtrack::channel_max_volume` for a
+    /// safety net). Don't give negative volumes.
+    fn fade_group_to(
+        &mut self,
+        group_name: CompactString,
+        target_volume: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadeGroupTo {
+            group_name,
+            fade_type,
+            target_volume,
+            fade_length,
+        });
+    }
     /// Fades all *currently existing* mix controls, *including* `main`, to
     /// the given volume (0.0 to 1.0), using the given fading curve, over the
     /// given time period (in seconds).
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
-    /// it won't cause clipping. Don't give negative volumes.
+    /// it won't cause clipping (see `Soundtrack::channel_max_volume` for a
+    /// safety net). Don't give negative volumes.
     fn fade_all_mix_controls_to(
         &mut self,
         target_volume: PosFloat,
@@ -427,13 +845,15 @@ pub trait EngineCommands: EngineCommandIssuer {
             fade_length,
         });
     }
-    /// Fades all *currently existing* mix controls, *except* `main`, to the
+    /// Fades all *currently existing* mix controls, *except* the default
+    /// channel (usually `main`; see `Engine::set_default_channel`), to the
     /// given volume (0.0 to 1.0), using the given fading curve, over the given
     /// time period (in seconds).
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
-    /// it won't cause clipping. Don't give negative volumes.
+    /// it won't cause clipping (see `Soundtrack::channel_max_volume` for a
+    /// safety net). Don't give negative volumes.
     fn fade_all_mix_controls_except_main_to(
         &mut self,
         target_volume: PosFloat,
@@ -507,9 +927,10 @@ pub trait EngineCommands: EngineCommandIssuer {
             fade_length,
         });
     }
-    /// Fades all *currently existing* mix controls, *except* `main`,
-    /// to zero volume, using the given fading curve, over the given time
-    /// period (in seconds). When the fade is complete, the MixControl will be
+    /// Fades all *currently existing* mix controls, *except* the default
+    /// channel (usually `main`; see `Engine::set_default_channel`), to zero
+    /// volume, using the given fading curve, over the given time period (in
+    /// seconds). When the fade is complete, the MixControl will be
     /// removed from existence rather than simply zeroed; future commands to
     /// "prefixed" and "all" will not resuscitate it (unless it is the target
     /// of a future, specific command).
@@ -555,8 +976,8 @@ pub trait EngineCommands: EngineCommandIssuer {
     fn kill_all_mix_controls(&mut self) {
         self.issue(EngineCommand::KillAllMixControls {});
     }
-    /// Kills all MixControls, *except* `main`, as if you yanked an audio
-    /// cable.
+    /// Kills all MixControls, *except* the default channel (usually `main`;
+    /// see `Engine::set_default_channel`), as if you yanked an audio cable.
     ///
     /// This is similar to fading that MixControl out over zero seconds, except
     /// that the MixControl in question is immediately removed (and therefore
@@ -565,6 +986,253 @@ pub trait EngineCommands: EngineCommandIssuer {
     fn kill_all_mix_controls_except_main(&mut self) {
         self.issue(EngineCommand::KillAllMixControlsExceptMain {});
     }
+    /// Limits the number of sounds that can play on a given MixControl at
+    /// once. When a sound is about to start on a MixControl that already has
+    /// `max_voices` sounds playing, `stealing` decides whether the oldest one
+    /// is cut off to make room, or the new one is never started. Either way,
+    /// this prevents a fast-looping node from stacking up dozens of copies of
+    /// the same sound, which wastes CPU and tends to clip.
+    ///
+    /// In debug builds, every stolen or dropped voice is reported as a
+    /// warning.
+    ///
+    /// The limit applies to the MixControl's *name*, so it stays in effect
+    /// even if the MixControl is killed and later faded back in.
+    fn set_mix_control_voice_limit(
+        &mut self,
+        control_name: CompactString,
+        max_voices: NonZeroUsize,
+        stealing: VoiceStealing,
+    ) {
+        self.issue(EngineCommand::SetMixControlVoiceLimit {
+            control_name,
+            max_voices,
+            stealing,
+        });
+    }
+    /// Removes the voice limit from a given MixControl, allowing any number
+    /// of sounds to play on it at once. (This is the default.)
+    fn clear_mix_control_voice_limit(&mut self, control_name: CompactString) {
+        self.issue(EngineCommand::ClearMixControlVoiceLimit { control_name });
+    }
+    /// Solos a given MixControl: every MixControl that isn't soloed is
+    /// silenced, until `clear_solo`. Soloing more than one MixControl solos
+    /// all of them together. Sounds `send`ing to a soloed MixControl are
+    /// still heard through it.
+    ///
+    /// Soloing doesn't touch any MixControl's volume or fades; silenced
+    /// MixControls keep fading as usual, and come back at whatever volume
+    /// they've reached when the solo is cleared. Soloing and clearing take
+    /// effect immediately, without fading.
+    fn solo_mix_control(&mut self, control_name: CompactString) {
+        self.issue(EngineCommand::SoloMixControl { control_name });
+    }
+    /// Un-solos every MixControl, so that they can all be heard again. See
+    /// `solo_mix_control`.
+    fn clear_solo(&mut self) {
+        self.issue(EngineCommand::ClearSolo {});
+    }
+    /// Mutes or unmutes a given MixControl, e.g. for a mute button in a
+    /// settings menu. A muted MixControl is silent, whatever its volume, until
+    /// it's unmuted. (Sounds `send`ing to it are still heard through their
+    /// other MixControls.)
+    ///
+    /// Like soloing, muting doesn't touch the MixControl's volume or fades,
+    /// so unmuting brings it back exactly where it would have been. The mute
+    /// is kept even if the MixControl is faded out (see
+    /// `fade_mix_control_out`) and comes back later. Muting and unmuting take
+    /// effect immediately, without fading.
+    fn set_mix_control_muted(
+        &mut self,
+        control_name: CompactString,
+        muted: bool,
+    ) {
+        self.issue(EngineCommand::SetMixControlMuted {
+            control_name,
+            muted,
+        });
+    }
+    /// Puts a built-in effect on a given MixControl, replacing any effect
+    /// that was already there. Only one effect per MixControl is supported.
+    /// Every sound playing on that MixControl (including sounds that are
+    /// `send`ing to it) is mixed together, the MixControl's volume is
+    /// applied, and *then* the effect processes the result. This means that
+    /// fading a MixControl out lets the echoes or reverb tail ring out
+    /// naturally.
+    ///
+    /// Effects cost CPU time whenever they're attached, even if nothing is
+    /// playing on their MixControl. A delay costs a few operations per
+    /// sample; a reverb costs about six times that. The effect's memory is
+    /// allocated when this command is processed, not during mixing.
+    /// (`advance_silently` skips effects entirely.)
+    ///
+    /// The effect applies to the MixControl's *name*, so it stays in effect
+    /// even if the MixControl is killed and later faded back in.
+    fn set_mix_control_effect(
+        &mut self,
+        control_name: CompactString,
+        effect: ChannelEffect,
+    ) {
+        self.issue(EngineCommand::SetChannelEffect {
+            control_name,
+            effect: Some(effect),
+        });
+    }
+    /// Removes the effect from a given MixControl. Any echoes or reverb tail
+    /// still ringing will be cut off.
+    fn clear_mix_control_effect(&mut self, control_name: CompactString) {
+        self.issue(EngineCommand::SetChannelEffect {
+            control_name,
+            effect: None,
+        });
+    }
+    /// Fades the cutoff frequency of a low-pass filter on a given MixControl
+    /// to `cutoff_hz`, using the given fading curve, over the given time
+    /// period (in seconds). Good for making music sound muffled, as if it's
+    /// coming from underwater or behind a wall.
+    ///
+    /// Every MixControl starts with its cutoff at the Nyquist frequency (half
+    /// the `Engine`'s sample rate), where the filter is bypassed entirely.
+    /// Fading the cutoff back up to (or above) Nyquist bypasses it again.
+    /// While the filter is not bypassed, it costs a few operations per
+    /// sample, whether or not anything is playing on the MixControl.
+    /// (`advance_silently` skips filtering entirely.)
+    ///
+    /// As with effects, the MixControl's volume is applied before filtering.
+    /// If the MixControl also has an effect, the filter comes after it, so
+    /// echoes and reverb tails are muffled too. The cutoff applies to the
+    /// MixControl's *name*, so it stays in effect even if the MixControl is
+    /// killed and later faded back in.
+    ///
+    /// Use `FadeType::Logarithmic` for glides that sound even.
+    fn fade_mix_control_cutoff_to(
+        &mut self,
+        control_name: CompactString,
+        cutoff_hz: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadeChannelCutoffTo {
+            control_name,
+            cutoff_hz,
+            fade_length,
+            fade_type,
+        });
+    }
+    /// Fades the stereo width of a given MixControl to `width`, using the
+    /// given fading curve, over the given time period (in seconds). The
+    /// MixControl's output is split into mid (what the left and right
+    /// speakers have in common) and side (how they differ), the side is
+    /// scaled by the width, and the two are put back together. 0.0 is mono,
+    /// 1.0 (where every MixControl starts) leaves the sound alone, and more
+    /// than 1.0 is wider. Good for making the mix "open up".
+    ///
+    /// On stereo and headphone output, this affects the whole MixControl. On
+    /// surround output, only the front left and right speakers are affected.
+    /// On mono output, this does nothing. Fading the width back to 1.0
+    /// bypasses it entirely.
+    ///
+    /// Width is applied after any effect or filter on the MixControl. As
+    /// with those, it applies to the MixControl's *name*, so it stays in
+    /// effect even if the MixControl is killed and later faded back in.
+    fn fade_mix_control_width_to(
+        &mut self,
+        control_name: CompactString,
+        width: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadeChannelWidthTo {
+            control_name,
+            width,
+            fade_length,
+            fade_type,
+        });
+    }
+    /// Moves the listener, for sounds that have a `position`. Positional
+    /// sounds get quieter with distance from the listener, and are panned
+    /// between the speakers according to their horizontal direction from
+    /// the listener. Elevation is ignored. On stereo (and headphone) output,
+    /// sounds behind the listener are panned as if they were in front. On
+    /// mono output, only distance matters.
+    ///
+    /// A positional sound's speaker gains are worked out once per mix, so
+    /// moving the listener a long way at once can cause audible clicks.
+    /// Call this often, with small movements, or from the sound thread.
+    fn set_listener(&mut self, listener: Listener) {
+        self.issue(EngineCommand::SetListener { listener });
+    }
+    /// Bends the pitch of every sound playing (or that will play) on a given
+    /// MixControl or Flow, by fading its playback rate to `rate` over the
+    /// given time period (in seconds). 1.0 is normal, 2.0 is twice as fast
+    /// (an octave up), 0.5 is half as fast (an octave down), and 0.0 stops
+    /// the sound in its tracks. A sound on a bent MixControl *and* a bent
+    /// Flow plays at the product of the two rates. This stacks with any
+    /// `pitch` given in the soundtrack.
+    ///
+    /// Bending only changes how fast sounds play back, not when they start.
+    ///
+    /// Bent sounds are resampled on the sound thread using linear
+    /// interpolation, which costs a few operations per sample, per sound.
+    /// This is cheap, but not free; if you bend a MixControl with dozens of
+    /// sounds on it at once, you will pay for all of them. Sounds that have
+    /// never been bent cost (almost) nothing extra. Once bent, a sound stays
+    /// on the slower path until it ends, even if the rate goes back to 1.0.
+    fn fade_pitch_to(
+        &mut self,
+        target: ChannelOrFlow,
+        rate: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadePitchTo {
+            target,
+            fade_type,
+            rate,
+            fade_length,
+        });
+    }
+    /// Plays a sound once, right away, on the given MixControl, without
+    /// needing a flow to play it. Useful for sound effects, e.g. UI clicks.
+    /// The sound is played at the given volume (on top of its MixControl's)
+    /// and at the given pitch (1.0 is normal, 2.0 is an octave up, 0.5 is an
+    /// octave down, on top of any `pitch` given in the soundtrack and any
+    /// bend of its MixControl).
+    ///
+    /// The sound must already be cached (see `cache_sound`) for it to start
+    /// without delay. It plays until it ends, is stopped with
+    /// `stop_one_shots`, or its MixControl is killed; fading or killing flows
+    /// doesn't affect it. It counts against its MixControl's voice limit, if
+    /// any.
+    ///
+    /// Returns a [`query::Response`](query/struct.Response.html) that will
+    /// arrive once the sound is done: `true` if it played (whether it ran to
+    /// its end or was cut off early), `false` if it never got to play at all
+    /// (e.g. the sound is missing or couldn't be loaded, or it was stopped
+    /// before it started). You can ignore it if you don't care.
+    fn play_one_shot(
+        &mut self,
+        sound_name: CompactString,
+        channel: CompactString,
+        volume: PosFloat,
+        pitch: PosFloat,
+    ) -> query::Response<bool> {
+        let (tx, rx) = query::make();
+        self.issue(EngineCommand::PlayOneShot {
+            sound_name,
+            channel,
+            volume,
+            pitch,
+            tx,
+        });
+        rx
+    }
+    /// Stops every sound started with `play_one_shot` on the given
+    /// MixControl, immediately. If `channel` is `None`, stops every such
+    /// sound on every MixControl.
+    fn stop_one_shots(&mut self, channel: Option<CompactString>) {
+        self.issue(EngineCommand::StopOneShots { channel });
+    }
     /// Starts a given flow if it's not already playing. If the flow
     /// is being newly started, it will be faded up from zero volume to the
     /// target volume, with the given fade curve. If the flow was
@@ -582,6 +1250,35 @@ pub trait EngineCommands: EngineCommandIssuer {
     ) {
         self.issue(EngineCommand::StartFlow {
             flow_name,
+            node_name: None,
+            offset: PosFloat::ZERO,
+            fade_type,
+            target_volume,
+            fade_length,
+        });
+    }
+    /// As `start_flow`, but if the flow is being newly started, it starts at
+    /// the given node (`None` for the starting node) instead of the starting
+    /// node, as if that node had already been playing for `offset` seconds.
+    /// Sounds that would already have started by then are skipped, rather
+    /// than starting partway through. If the flow was already playing, acts
+    /// just like `fade_flow_to`.
+    ///
+    /// Useful for resuming a flow roughly where it left off, e.g. after
+    /// loading a saved game.
+    fn start_flow_at(
+        &mut self,
+        flow_name: CompactString,
+        node_name: Option<CompactString>,
+        offset: PosFloat,
+        target_volume: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::StartFlow {
+            flow_name,
+            node_name,
+            offset,
             fade_type,
             target_volume,
             fade_length,
@@ -612,45 +1309,185 @@ pub trait EngineCommands: EngineCommandIssuer {
             fade_length,
         });
     }
-    /// Fades all *currently playing* flows whose names strictly start with
-    /// the given prefix to the given volume (0.0 to 1.0), using the given
-    /// fading curve, over the given time period (in seconds). Does nothing to
-    /// flows that haven't been started, or that have finished fading out.
+    /// Fades a single node of a given flow to the given volume (0.0 to 1.0),
+    /// using the given fading curve, over the given time period (in
+    /// seconds). `node_name` is `None` for the flow's starting node. This is
+    /// on top of the flow's own volume, and applies to every sound the node
+    /// plays, including those already playing. Nodes start out at full
+    /// volume.
     ///
-    /// Flows with zero volume will continue silently "playing", waiting to
-    /// be faded back up to non-zero volume. If this isn't what you want, use
-    /// `fade_prefixed_flows_out` instead.
+    /// This is meant for layered arrangements, where each node of a flow is
+    /// one instrument (or stem) that can be mixed in and out while the flow
+    /// plays.
+    ///
+    /// A warning is issued if the node isn't currently playing, but the
+    /// volume will still apply if the node starts later. Node volumes are
+    /// forgotten when their flow stops.
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
     /// it won't cause clipping. Don't give negative volumes.
-    fn fade_prefixed_flows_to(
+    fn fade_node_to(
         &mut self,
-        flow_prefix: CompactString,
+        flow_name: CompactString,
+        node_name: Option<CompactString>,
         target_volume: PosFloat,
         fade_length: PosFloat,
         fade_type: FadeType,
     ) {
-        self.issue(EngineCommand::FadePrefixedFlowsTo {
-            flow_prefix,
+        self.issue(EngineCommand::FadeNodeTo {
+            flow_name,
+            node_name,
             fade_type,
             target_volume,
             fade_length,
         });
     }
-    /// Fades all *currently playing* flows to the given volume (0.0 to
-    /// 1.0), using the given fading curve, over the given time period (in
-    /// seconds). Does nothing to flows that haven't been started, or that
-    /// have finished fading out.
+    /// Fades a single element of a sequence, as played by a given flow, to
+    /// the given volume (0.0 to 1.0), using the given fading curve, over the
+    /// given time period (in seconds). `element` counts from 0, in the order
+    /// the sequence's elements appear in the soundtrack. This is on top of
+    /// the flow's (and node's) own volume, and applies to every sound that
+    /// element plays, including those already playing and those it plays
+    /// later. Elements start out at full volume.
     ///
-    /// Flows with zero volume will continue silently "playing", waiting to
-    /// be faded back up to non-zero volume. If this isn't what you want, use
-    /// `fade_prefixed_flows_out` instead.
+    /// This is meant for sequences that play several things at once, e.g. an
+    /// ambient sequence where one element is birdsong, so that the game can
+    /// silence just the birds. If the same sequence is played by more than
+    /// one node of the flow, the element is faded in all of them.
+    ///
+    /// Element volumes are forgotten when their flow stops.
     ///
     /// Use `FadeType::Exponential` unless you are doing intermixing of
     /// correlated signals. Don't give a volume above 1.0 unless you are sure
     /// it won't cause clipping. Don't give negative volumes.
-    fn fade_all_flows_to(
+    fn fade_sequence_element_to(
+        &mut self,
+        flow_name: CompactString,
+        sequence_name: CompactString,
+        element: usize,
+        target_volume: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadeSequenceElementTo {
+            flow_name,
+            sequence_name,
+            element,
+            fade_type,
+            target_volume,
+            fade_length,
+        });
+    }
+    /// Immediately stops every sound that a single element of a sequence, as
+    /// played by a given flow, is playing or has queued up to play. (See
+    /// `fade_sequence_element_to` for how elements are counted.) This doesn't
+    /// stop the element from playing again, the next time the sequence is
+    /// played; to keep it quiet, fade it to zero instead.
+    fn stop_sequence_element(
+        &mut self,
+        flow_name: CompactString,
+        sequence_name: CompactString,
+        element: usize,
+    ) {
+        self.issue(EngineCommand::StopSequenceElement {
+            flow_name,
+            sequence_name,
+            element,
+        });
+    }
+    /// Sets the layer intensity of a given flow, fading each of its layer
+    /// nodes to the volume that intensity calls for, using the given fading
+    /// curve, over the given time period (in seconds).
+    ///
+    /// A layer node is one declared with an intensity curve in the
+    /// soundtrack, e.g. `node bass active above 0.3` (full volume at
+    /// intensity 0.3 or more, silent below), `node pad active below 0.5`, or
+    /// `node lead active from 0.4 to 0.8` (silent at 0.4, full volume at 0.8,
+    /// in between in between). Other nodes aren't affected. Intensity is
+    /// usually from 0.0 to 1.0, but any number will do.
+    ///
+    /// The intensity is remembered, and applied (instantly) whenever the flow
+    /// is started. Until a flow's intensity is first set, its layer nodes
+    /// play at full volume. This works by way of the same node volumes as
+    /// `fade_node_to`, so using both on the same node isn't a good idea.
+    fn set_layer_intensity(
+        &mut self,
+        flow_name: CompactString,
+        intensity: f32,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::SetLayerIntensity {
+            flow_name,
+            intensity,
+            fade_type,
+            fade_length,
+        });
+    }
+    /// Chains one flow to another: when the given flow finishes on its own
+    /// (all of its nodes have run out of things to do, and it doesn't
+    /// `autoloop`), the successor flow is started, and the finished flow is
+    /// faded out, both over the given time period (in seconds) with the given
+    /// fading curve. Passing `None` as the successor removes the chain.
+    ///
+    /// Flows that were faded out, killed, or replaced by a soundtrack
+    /// crossfade don't start their successors. A chain that would lead back
+    /// to the flow it starts from is refused, with a warning; use `autoloop`
+    /// in the soundtrack for a flow that should repeat.
+    fn set_flow_successor(
+        &mut self,
+        flow_name: CompactString,
+        successor: Option<CompactString>,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::SetFlowSuccessor {
+            flow_name,
+            successor,
+            fade_type,
+            fade_length,
+        });
+    }
+    /// Fades all *currently playing* flows whose names strictly start with
+    /// the given prefix to the given volume (0.0 to 1.0), using the given
+    /// fading curve, over the given time period (in seconds). Does nothing to
+    /// flows that haven't been started, or that have finished fading out.
+    ///
+    /// Flows with zero volume will continue silently "playing", waiting to
+    /// be faded back up to non-zero volume. If this isn't what you want, use
+    /// `fade_prefixed_flows_out` instead.
+    ///
+    /// Use `FadeType::Exponential` unless you are doing intermixing of
+    /// correlated signals. Don't give a volume above 1.0 unless you are sure
+    /// it won't cause clipping. Don't give negative volumes.
+    fn fade_prefixed_flows_to(
+        &mut self,
+        flow_prefix: CompactString,
+        target_volume: PosFloat,
+        fade_length: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadePrefixedFlowsTo {
+            flow_prefix,
+            fade_type,
+            target_volume,
+            fade_length,
+        });
+    }
+    /// Fades all *currently playing* flows to the given volume (0.0 to
+    /// 1.0), using the given fading curve, over the given time period (in
+    /// seconds). Does nothing to flows that haven't been started, or that
+    /// have finished fading out.
+    ///
+    /// Flows with zero volume will continue silently "playing", waiting to
+    /// be faded back up to non-zero volume. If this isn't what you want, use
+    /// `fade_prefixed_flows_out` instead.
+    ///
+    /// Use `FadeType::Exponential` unless you are doing intermixing of
+    /// correlated signals. Don't give a volume above 1.0 unless you are sure
+    /// it won't cause clipping. Don't give negative volumes.
+    fn fade_all_flows_to(
         &mut self,
         target_volume: PosFloat,
         fade_length: PosFloat,
@@ -681,6 +1518,33 @@ pub trait EngineCommands: EngineCommandIssuer {
             fade_length,
         });
     }
+    /// Fades a given flow to the given volume over `fade_length` seconds,
+    /// holds it there for `hold` more seconds, and then fades it out over
+    /// `fade_out` seconds, stopping it, as `fade_flow_out` would. The same
+    /// fading curve is used for both fades. Does nothing if the flow is not
+    /// currently playing.
+    ///
+    /// Any other command that fades, starts, or kills the flow before its
+    /// fade out begins (including another `fade_flow_to_then_out`) replaces
+    /// this one, and the scheduled fade out is forgotten.
+    fn fade_flow_to_then_out(
+        &mut self,
+        flow_name: CompactString,
+        target_volume: PosFloat,
+        fade_length: PosFloat,
+        hold: PosFloat,
+        fade_out: PosFloat,
+        fade_type: FadeType,
+    ) {
+        self.issue(EngineCommand::FadeFlowToThenOut {
+            flow_name,
+            fade_type,
+            target_volume,
+            fade_length,
+            hold,
+            fade_out,
+        });
+    }
     /// Fades all *currently playing* flows whose names strictly start with
     /// the given prefix to zero volume, using the given fading curve, over the
     /// given time period (in seconds). Does nothing to flows that haven't
@@ -778,6 +1642,17 @@ impl<'a, T: EngineCommandIssuer + ?Sized> Transaction<'a, T> {
             commands: self.commands,
         })
     }
+    /// As `commit`, but first throws away any fade that would be
+    /// immediately overridden by a later fade of the same thing, e.g. when a
+    /// slider has been dragged quickly and a dozen `fade_mix_control_to`s of
+    /// the same MixControl have piled up. Only fades of a single MixControl,
+    /// flow, node, cutoff, or pitch are thrown away, and only in favor of a
+    /// later fade of the same kind with no other kind of command in between,
+    /// so the end result is the same as with `commit`.
+    pub fn commit_coalesced(mut self) {
+        coalesce_fades(&mut self.commands);
+        self.commit()
+    }
     /// Aborts an in-progress transaction. None of the commands put into it
     /// will be issued.
     ///
@@ -785,6 +1660,63 @@ impl<'a, T: EngineCommandIssuer + ?Sized> Transaction<'a, T> {
     pub fn abort(self) {}
 }
 
+/// What a fade command fades, for `coalesce_fades`.
+#[derive(PartialEq, Eq, Hash)]
+enum FadeTarget<'a> {
+    MixControl(&'a str),
+    Flow(&'a str),
+    Node(&'a str, Option<&'a str>),
+    Element(&'a str, &'a str, usize),
+    Cutoff(&'a str),
+    Pitch(&'a ChannelOrFlow),
+}
+
+impl EngineCommand {
+    /// If this is a fade that a later fade of the same target would
+    /// completely override, returns what it fades.
+    fn fade_target(&self) -> Option<FadeTarget<'_>> {
+        use EngineCommand::*;
+        Some(match self {
+            FadeMixControlTo { control_name, .. } => {
+                FadeTarget::MixControl(control_name)
+            }
+            FadeFlowTo { flow_name, .. } => FadeTarget::Flow(flow_name),
+            FadeNodeTo {
+                flow_name,
+                node_name,
+                ..
+            } => FadeTarget::Node(flow_name, node_name.as_deref()),
+            FadeSequenceElementTo {
+                flow_name,
+                sequence_name,
+                element,
+                ..
+            } => FadeTarget::Element(flow_name, sequence_name, *element),
+            FadeChannelCutoffTo { control_name, .. } => {
+                FadeTarget::Cutoff(control_name)
+            }
+            FadePitchTo { target, .. } => FadeTarget::Pitch(target),
+            _ => return None,
+        })
+    }
+}
+
+/// Removes every fade that is followed by another fade of the same target,
+/// without any other kind of command in between. See
+/// `Transaction::commit_coalesced`.
+fn coalesce_fades(commands: &mut Vec<EngineCommand>) {
+    let mut keep = vec![true; commands.len()];
+    let mut later_targets = HashSet::new();
+    for (n, command) in commands.iter().enumerate().rev() {
+        match command.fade_target() {
+            Some(target) => keep[n] = later_targets.insert(target),
+            None => later_targets.clear(),
+        }
+    }
+    let mut keep = keep.into_iter();
+    commands.retain(|_| keep.next().unwrap());
+}
+
 impl<'a, T: EngineCommandIssuer + ?Sized> EngineCommandIssuer
     for Transaction<'a, T>
 {
@@ -798,6 +1730,45 @@ impl<'a, T: EngineCommandIssuer + ?Sized> EngineCommands
 {
 }
 
+/// A transaction that isn't tied to anything yet. You can send commands to it,
+/// exactly like you can to an `Engine`, and then send them all at once with
+/// `send_transaction`.
+///
+/// Unlike a `Transaction`, this doesn't borrow anything, so it can be passed
+/// around (e.g. between several of a game's systems, each adding its own music
+/// commands for the frame) before being sent. If it's dropped without being
+/// sent, none of the commands will be issued.
+#[derive(Default)]
+pub struct TransactionBuilder {
+    commands: Vec<EngineCommand>,
+}
+
+impl TransactionBuilder {
+    /// Creates a new, empty `TransactionBuilder`.
+    pub fn new() -> TransactionBuilder {
+        TransactionBuilder::default()
+    }
+    /// Creates a new, empty `TransactionBuilder`, with room for the given
+    /// number of commands. (This is an optimization hint only.)
+    pub fn with_capacity(length: usize) -> TransactionBuilder {
+        TransactionBuilder {
+            commands: Vec::with_capacity(length),
+        }
+    }
+    /// Returns true if no commands have been put into this transaction yet.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl EngineCommandIssuer for TransactionBuilder {
+    fn issue(&mut self, command: EngineCommand) {
+        self.commands.push(command);
+    }
+}
+
+impl EngineCommands for TransactionBuilder {}
+
 /// This exists to send commands to an `Engine` that belongs to some other
 /// thread. If you're operating entirely in a single thread, you can also just
 /// call any of these methods on an `Engine` directly.
@@ -831,58 +1802,295 @@ pub struct Engine {
     live_soundtrack: Soundtrack,
     mixer: Mixer<PlayingSoundID>,
     command_rx: Receiver<EngineCommand>,
+    /// See `async_commander`.
+    #[cfg(feature = "async")]
+    async_channels: Vec<AsyncChannel>,
     // for cloning senders
     command_tx: Sender<EngineCommand>,
     flow_controls: HashMap<CompactString, StringOrNumber>,
-    mix_controls: HashMap<CompactString, Fader>,
-    flow_volumes: HashMap<CompactString, Fader>,
+    /// Set whenever a FlowControl changes, so that nodes that are waiting
+    /// for a condition can check it again. See `wake_condition_waiters`.
+    flow_controls_changed: bool,
+    /// Used by `random()` in expressions.
+    rng: Rng,
+    /// How fast musical time passes, relative to real time. See
+    /// `set_time_scale`.
+    time_scale: PosFloat,
+    /// (This, `flow_volumes`, and `flow_loads` are `BTreeMap`s so that
+    /// commands that affect many MixControls or flows at once go through them
+    /// in name order, the same way every run.)
+    mix_controls: BTreeMap<CompactString, Fader>,
+    /// MixControls that have been soloed. If this isn't empty, every other
+    /// MixControl is silent, whatever its fader says.
+    soloed_mix_controls: HashSet<CompactString>,
+    /// MixControls that have been muted. They're silent, whatever their
+    /// faders say.
+    muted_mix_controls: HashSet<CompactString>,
+    /// The MixControl that starts out at full volume, and is spared by the
+    /// "all except main" commands.
+    default_channel: CompactString,
+    flow_volumes: BTreeMap<FlowKey, Fader>,
+    /// Volumes of individual nodes, on top of their flows' volumes. Nodes
+    /// that aren't in here are at full volume.
+    node_volumes: HashMap<NodeKey, Fader>,
+    /// Volumes of individual sequence elements, on top of their flows' and
+    /// nodes' volumes. Elements that aren't in here are at full volume.
+    element_volumes: HashMap<ElementKey, Fader>,
+    /// The last layer intensity set for each flow.
+    layer_intensities: HashMap<CompactString, f32>,
+    /// What to start when each flow finishes on its own.
+    flow_successors: HashMap<CompactString, FlowSuccessor>,
     /// Set of flows that are waiting to start.
-    starting_flows: HashSet<CompactString>,
+    starting_flows: HashSet<FlowKey>,
+    /// Where flows that are waiting to start should start, if not at the
+    /// beginning of their starting node. See `start_flow_at`.
+    flow_start_points:
+        HashMap<CompactString, (Option<CompactString>, PosFloat)>,
+    /// Every sound that has started since the last `take_started_sounds`.
+    #[cfg(any(test, feature = "testing"))]
+    started_sounds: Vec<QueuedSoundReport>,
     /// Set of flows that are fading out. Flows are added to this list
     /// when they are requested to fade *out*.
-    flows_fading_out: HashSet<CompactString>,
+    flows_fading_out: HashSet<FlowKey>,
+    /// Flows that will start fading out at a certain time, thanks to
+    /// `fade_flow_to_then_out`.
+    scheduled_fade_outs: HashMap<FlowKey, ScheduledFadeOut>,
     /// Set of MixControls that are fading out. Controls are added to this list
     /// when they are requested to fade *out*.
     mix_controls_fading_out: HashSet<CompactString>,
+    /// Maximum number of sounds that may play at once on each MixControl,
+    /// and what to do about it when a new one would go over.
+    voice_limits: HashMap<CompactString, (NonZeroUsize, VoiceStealing)>,
+    /// Most node instructions that may run in one `run_for`. See
+    /// `set_instruction_limit`.
+    instruction_limit: Option<NonZeroUsize>,
+    /// How long, in seconds, a flow may wait for its sounds to load before
+    /// we complain about it, and whether to start it anyway when it has. See
+    /// `set_load_timeout`.
+    load_timeout: Option<(PosFloat, bool)>,
+    /// Whether to tell the delegate whenever a repeated sound loops. See
+    /// `set_loop_wrap_callbacks`.
+    loop_wrap_callbacks: bool,
+    /// The most sample frames to mix at once before checking for new
+    /// commands again. See `set_max_chunk_length`.
+    max_chunk_frames: Option<u64>,
+    /// Where positional sounds are heard from.
+    listener: Listener,
+    /// MixControls that have effects, filters, or stereo widths on them. Each
+    /// one gets its own bus in the mixer, numbered from 1 in the order they
+    /// appear here.
+    buses: Vec<Bus>,
+    /// Playback rates of MixControls and Flows. An entry exists for every
+    /// MixControl and Flow with a sound playing on it, and for any that are
+    /// bent, whether or not they have sounds.
+    pitch_bends: HashMap<ChannelOrFlow, PitchBend>,
     deferred_kill: bool,
+    /// Flows from old soundtracks that are fading out after a crossfade.
+    outgoing_flows: HashMap<FlowKey, Arc<Flow>>,
+    /// Used to give each outgoing flow a unique `FlowKey`.
+    next_outgoing_flow_id: u64,
     sound_delegate: Arc<dyn SoundDelegate>,
     soundman: Box<dyn GenericSoundMan>,
-    flow_loads: HashMap<CompactString, FlowLoadStatus>,
+    /// Sounds that have been forced into the cache with `cache_sound`, as
+    /// they were when they were cached. The soundtrack may have changed (or
+    /// lost) them since, but this is what has to be let go of.
+    cached_sounds: HashMap<CompactString, Arc<Sound>>,
+    /// Shared with `soundman`. See `replace_runtime`.
+    loading_rt: Arc<SwappableRuntime>,
+    flow_loads: BTreeMap<FlowKey, FlowLoadStatus>,
     speaker_layout: SpeakerLayout,
     sample_rate: PosFloat,
     /// Temporary buffer for mixing
     mix_buf: Vec<MaybeUninit<f32>>,
+    /// Interleaved output, waiting to be split up by `turn_handle_planar`.
+    planar_buf: Vec<f32>,
+    /// The sample rate `turn_handle` is producing, if it isn't `sample_rate`.
+    output_sample_rate: Option<PosFloat>,
+    output_resampler: Option<OutputResampler>,
+    /// MixControls that are being mixed to their own outputs, during a
+    /// `turn_handle_routed`. Each one gets its own bus in the mixer,
+    /// numbered after the ones in `buses`, in the order they appear here.
+    routed_outputs: Vec<RoutedOutput>,
+    /// Extra outputs. Each one gets its own bus in the mixer, numbered after
+    /// the ones in `buses` and `routed_outputs`, in the order they appear
+    /// here.
+    taps: Vec<Tap>,
+    next_tap_id: u32,
+    /// Main output that was mixed early, for a tap, and hasn't been output
+    /// yet.
+    main_backlog: VecDeque<f32>,
+    /// Temporary buffers for `turn_handle_tap`
+    tap_buf: Vec<MaybeUninit<f32>>,
+    mix_ahead_buf: Vec<f32>,
     active_flow_nodes: Vec<ActiveNode>,
-    queued_sounds: BinaryHeap<QueuedSound>,
+    queued_sounds: SoundQueue,
+    /// See `audition_sound`.
+    audition: Option<Audition>,
 }
 
 impl EngineCommands for Engine {}
 
+/// A fade out that `fade_flow_to_then_out` will start later.
+struct ScheduledFadeOut {
+    /// The sample frame the fade out starts on.
+    when: u64,
+    fade_type: FadeType,
+    fade_length: PosFloat,
+}
+
+/// The playback rate of a MixControl or Flow.
+struct PitchBend {
+    fader: Fader,
+    /// Shared with every sound that's playing on this MixControl or Flow.
+    /// Updated from `fader` before every mix.
+    rate: SharedRate,
+    /// True if the live soundtrack has this Flow, or has flows that play
+    /// sounds on this MixControl. These are made when the soundtrack is
+    /// replaced, and kept around even when nothing is using them, so that
+    /// starting a sound doesn't have to make them.
+    pinned: bool,
+}
+
+impl Default for PitchBend {
+    fn default() -> PitchBend {
+        PitchBend {
+            fader: Fader::new(PosFloat::ONE),
+            rate: SharedRate::new(PosFloat::ONE),
+            pinned: false,
+        }
+    }
+}
+
+/// A MixControl with an effect, filter, and/or stereo width on it.
+struct Bus {
+    channel: CompactString,
+    effect: Option<EffectState>,
+    filter: Option<LowPassFilter>,
+    width: Option<StereoWidth>,
+}
+
 struct VolumeGetWrapper<'a, 'b> {
-    mix_controls: &'a mut HashMap<CompactString, Fader>,
-    flow_volumes: &'a mut HashMap<CompactString, Fader>,
-    flows_fading_out: &'a HashSet<CompactString>,
-    starting_flows: &'a HashSet<CompactString>,
-    seen_flows: &'b mut HashSet<CompactString>,
+    mix_controls: &'a mut BTreeMap<CompactString, Fader>,
+    soloed_mix_controls: &'a HashSet<CompactString>,
+    muted_mix_controls: &'a HashSet<CompactString>,
+    buses: &'a mut Vec<Bus>,
+    speaker_layout: SpeakerLayout,
+    listener: &'a Listener,
+    flow_volumes: &'a mut BTreeMap<FlowKey, Fader>,
+    node_volumes: &'a mut HashMap<NodeKey, Fader>,
+    element_volumes: &'a mut HashMap<ElementKey, Fader>,
+    flows_fading_out: &'a HashSet<FlowKey>,
+    starting_flows: &'a HashSet<FlowKey>,
+    routed_outputs: &'a [RoutedOutput],
+    taps: &'a [Tap],
+    seen_flows: &'b mut HashSet<FlowKey>,
+}
+
+/// A flow to start when another one finishes. See `set_flow_successor`.
+#[derive(Debug)]
+struct FlowSuccessor {
+    flow_name: CompactString,
+    fade_type: FadeType,
+    /// In seconds.
+    fade_length: PosFloat,
 }
 
 /// A Node from a Flow, queued to execute.
 #[derive(Debug)]
 struct ActiveNode {
-    /// The *name* of the flow this node is part of.
-    flow_name: CompactString,
+    /// The flow this node is part of.
+    flow: FlowKey,
     /// The *actual node* this node is.
     node: Arc<Node>,
     /// The time at which execution will resume.
     next_instruction_time: u64,
     /// The index of the next instruction we will execute
     next_instruction_index: usize,
+    /// If present, we're waiting for a sound whose length wasn't known when
+    /// we played it to finish, and `next_instruction_time` is meaningless.
+    awaiting_end: Option<AwaitedEnd>,
+    /// How many sample frames behind this node is, if it was started partway
+    /// through (see `start_flow_at`). Waits are cut short until it's caught
+    /// up, and sounds it plays that should already have started are skipped.
+    catch_up: u64,
+    /// The fraction of a sample frame left over from the last wait. See
+    /// `whole_frames`.
+    frame_fraction: f64,
+}
+
+impl ActiveNode {
+    /// Turn a wait of the given number of sample frames, which may not be a
+    /// whole number, into a whole number of them, carrying the leftover
+    /// fraction over to the next wait. That way, a sequence played back to
+    /// back with itself doesn't drift, however many times it's repeated.
+    fn whole_frames(&mut self, frames: f64) -> u64 {
+        let frames = frames + self.frame_fraction;
+        let whole = frames.floor();
+        self.frame_fraction = frames - whole;
+        whole as u64
+    }
+    /// Wait the given number of sample frames from `now`, less however much
+    /// we're still catching up. Returns true if any time actually has to
+    /// pass.
+    fn wait(&mut self, now: u64, frames: u64) -> bool {
+        if self.catch_up != 0 && self.catch_up >= frames {
+            self.catch_up -= frames;
+            false
+        } else {
+            self.next_instruction_time = now + (frames - self.catch_up);
+            self.catch_up = 0;
+            true
+        }
+    }
+}
+
+/// A sound played by `PlaySoundAndWait` whose length wasn't known yet. (Its
+/// length will be found out when it's loaded, if it's buffered, or when it
+/// runs out, if it's streamed.)
+#[derive(Debug)]
+struct AwaitedEnd {
+    sound: Arc<Sound>,
+    /// When it was played.
+    when: u64,
+    /// Answered when the sound is done with, one way or another.
+    finished: query::Response<bool>,
+}
+
+/// A sound repeated by a sequence whose length wasn't known yet. Its first
+/// time was queued as usual; the rest wait here until its length is found
+/// out, the same way as an `AwaitedEnd`.
+struct AwaitedRepeat {
+    /// When the first time was queued for.
+    first: u64,
+    /// Answered when the first time is done with, one way or another.
+    finished: query::Response<bool>,
+    /// The rest of the times, in order. Their `when`s, and every `length`
+    /// but the last one's, are filled in once the length is known.
+    passes: Vec<QueuedSound>,
+}
+
+/// The MixControl that auditioned sounds are reported as playing on. (The
+/// null character keeps it from being confused with a real one.)
+const AUDITION_CHANNEL: &str = "\0audition";
+
+/// A sound or sequence being auditioned. See `Engine::audition_sound`.
+struct Audition {
+    /// Every sound the audition plays, each loaded once, for as long as the
+    /// audition lasts.
+    sounds: Vec<Arc<Sound>>,
+    /// If present, the audition hasn't started yet, because some of its
+    /// sounds are still loading. These are its sounds, queued for sample
+    /// frames counting from when it starts.
+    pending: Option<SoundQueue>,
 }
 
 /// A Sound that is going to play
 struct QueuedSound {
     /// When?
     when: u64,
+    /// Breaks ties between sounds queued for the same `when`, so that they
+    /// start in the order they were queued. Assigned by `SoundQueue::push`.
+    seq: u64,
     /// Who?
     who: PlayingSoundID,
     /// What?
@@ -890,11 +2098,19 @@ struct QueuedSound {
     fade_in: PosFloat,
     length: Option<PosFloat>,
     fade_out: PosFloat,
+    /// The curve for `fade_in` and `fade_out`.
+    fade_type: FadeType,
+    /// Extra playback rate, on top of the sound's own `pitch` and any bends.
+    pitch: PosFloat,
+    /// True if this is a repetition of a repeated sound (other than the
+    /// first), whose start is where the sound loops. See
+    /// `Engine::set_loop_wrap_callbacks`.
+    loop_wrap: bool,
 }
 
 impl PartialEq for QueuedSound {
     fn eq(&self, other: &QueuedSound) -> bool {
-        self.when == other.when
+        self.when == other.when && self.seq == other.seq
     }
 }
 
@@ -903,7 +2119,7 @@ impl Eq for QueuedSound {}
 impl Ord for QueuedSound {
     fn cmp(&self, other: &QueuedSound) -> Ordering {
         // return a reversed comparison
-        other.when.cmp(&self.when)
+        (other.when, other.seq).cmp(&(self.when, self.seq))
     }
 }
 
@@ -913,20 +2129,216 @@ impl PartialOrd for QueuedSound {
     }
 }
 
-// oh boy
+/// Sounds that are going to play, soonest first. Sounds queued for the same
+/// sample frame come out in the order they went in.
+#[derive(Default)]
+struct SoundQueue {
+    heap: BinaryHeap<QueuedSound>,
+    /// The `seq` to give the next sound that's queued.
+    next_seq: u64,
+    /// Repeated sounds that are waiting to find out how long they are. See
+    /// `Engine::resolve_awaited_ends`.
+    awaited_repeats: Vec<AwaitedRepeat>,
+}
+
+impl SoundQueue {
+    fn push(&mut self, mut sound: QueuedSound) {
+        sound.seq = self.next_seq;
+        self.next_seq += 1;
+        self.heap.push(sound);
+    }
+    fn peek(&self) -> Option<&QueuedSound> {
+        self.heap.peek()
+    }
+    fn pop(&mut self) -> Option<QueuedSound> {
+        self.heap.pop()
+    }
+    #[cfg(any(test, feature = "testing"))]
+    fn iter(&self) -> impl Iterator<Item = &QueuedSound> {
+        self.heap.iter()
+    }
+    /// Move every sound from `other`, which were queued by a node that's
+    /// `catch_up` sample frames behind, into this queue. The ones that should
+    /// have started before `now` are skipped, rather than starting late.
+    fn append_caught_up(
+        &mut self,
+        mut other: SoundQueue,
+        now: u64,
+        catch_up: u64,
+    ) {
+        while let Some(mut sound) = other.pop() {
+            if sound.when >= now + catch_up {
+                sound.when -= catch_up;
+                self.push(sound);
+            }
+        }
+        for mut awaited in other.awaited_repeats.drain(..) {
+            awaited.first = awaited.first.saturating_sub(catch_up);
+            self.awaited_repeats.push(awaited);
+        }
+    }
+    /// Move every sound from `other`, whose `when`s count from zero, into
+    /// this queue, counting from `start` instead.
+    fn append_from(&mut self, mut other: SoundQueue, start: u64) {
+        while let Some(mut sound) = other.pop() {
+            sound.when += start;
+            self.push(sound);
+        }
+        for mut awaited in other.awaited_repeats.drain(..) {
+            awaited.first += start;
+            self.awaited_repeats.push(awaited);
+        }
+    }
+    /// Returns true if any queued sound (including ones waiting in
+    /// `awaited_repeats`) matches the given predicate.
+    fn any(&self, mut f: impl FnMut(&QueuedSound) -> bool) -> bool {
+        self.heap.iter().any(&mut f)
+            || self
+                .awaited_repeats
+                .iter()
+                .flat_map(|x| x.passes.iter())
+                .any(f)
+    }
+    /// Remove every queued sound (including ones waiting in
+    /// `awaited_repeats`) that doesn't match the given predicate.
+    fn retain(&mut self, mut f: impl FnMut(&QueuedSound) -> bool) {
+        self.heap.retain(&mut f);
+        for awaited in self.awaited_repeats.iter_mut() {
+            awaited.passes.retain(&mut f);
+        }
+        self.awaited_repeats.retain(|x| !x.passes.is_empty());
+    }
+    /// Call the given function on every queued sound (including ones waiting
+    /// in `awaited_repeats`). It must not change `when` or `seq`.
+    fn for_each_mut(&mut self, mut f: impl FnMut(&mut QueuedSound)) {
+        let mut sounds = std::mem::take(&mut self.heap).into_vec();
+        sounds.iter_mut().for_each(&mut f);
+        self.heap = sounds.into();
+        for awaited in self.awaited_repeats.iter_mut() {
+            awaited.passes.iter_mut().for_each(&mut f);
+        }
+    }
+}
+
+/// What a playing flow's state is kept under. Usually that's just its name,
+/// but an outgoing flow (see `Engine::crossfade_soundtrack`) also gets a
+/// number, to tell it apart from its replacement and from any other outgoing
+/// flows of the same name.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Debug)]
+struct FlowKey {
+    name: CompactString,
+    outgoing: Option<u64>,
+}
+
+impl FlowKey {
+    /// The key of the flow in the live soundtrack with the given name.
+    fn live(name: impl Into<CompactString>) -> FlowKey {
+        FlowKey {
+            name: name.into(),
+            outgoing: None,
+        }
+    }
+    fn is_outgoing(&self) -> bool {
+        self.outgoing.is_some()
+    }
+    /// Returns this flow's definition: from the live soundtrack, or, if it's
+    /// an outgoing flow, from the soundtrack it was playing from.
+    fn find<'a>(
+        &self,
+        live_soundtrack: &'a Soundtrack,
+        outgoing_flows: &'a HashMap<FlowKey, Arc<Flow>>,
+    ) -> Option<&'a Arc<Flow>> {
+        if self.is_outgoing() {
+            outgoing_flows.get(self)
+        } else {
+            live_soundtrack.flows.get(&self.name)
+        }
+    }
+}
+
+/// Identifies one node of one flow: the flow, and the name of the node (none
+/// for the starting node).
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Debug)]
-struct StringAndAHalf(CompactString, Option<CompactString>);
+struct NodeKey(FlowKey, Option<CompactString>);
+
+/// Identifies one element of one sequence, as played by one flow: the flow,
+/// the name of the sequence, and the index of the element.
+type ElementKey = (FlowKey, CompactString, usize);
 
 struct PlayingSoundID {
-    flow_and_node_name: StringAndAHalf,
+    flow_and_node_name: NodeKey,
     channel: CompactString,
+    /// Other channels the sound is also sent to, and at what levels.
+    sends: Vec<(CompactString, PosFloat)>,
+    /// From -1.0 (full left) to 1.0 (full right).
+    pan: f32,
+    /// If present, the sound is positional, and `pan` is ignored.
+    position: Option<[f32; 3]>,
+    /// The sound's own volume, on top of its flow's, node's, and channels'.
+    /// Usually 1.0, less if it's been humanized, and scaled by the sound's
+    /// `gain`.
+    volume: PosFloat,
     sound: CompactString,
+    /// If the sound was played by a sequence, the name of the sequence, and
+    /// which of its elements played it (counting from 0, in the order they
+    /// appear in the soundtrack). See `fade_sequence_element_to`.
+    element: Option<(CompactString, usize)>,
+    /// True if this sound is part of an audition (see
+    /// `Engine::audition_sound`), in which case it doesn't belong to any flow
+    /// or MixControl, and plays at its own volume.
+    audition: bool,
+    /// True if this sound was started by `play_one_shot`, in which case it
+    /// doesn't belong to any flow (and `flow_and_node_name` is empty).
+    one_shot: bool,
+    /// If present, someone is waiting to find out when this sound is done.
+    finish_signal: Option<FinishSignal>,
+}
+
+/// Answers a `play_one_shot` caller's query when it's dropped, i.e. when the
+/// sound it belongs to is done with, one way or another.
+struct FinishSignal {
+    tx: Option<query::Responder<bool>>,
+    /// Whether the sound made it into the mixer.
+    started: bool,
+}
+
+impl FinishSignal {
+    fn new(tx: query::Responder<bool>) -> FinishSignal {
+        FinishSignal {
+            tx: Some(tx),
+            started: false,
+        }
+    }
+}
+
+impl Drop for FinishSignal {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            tx.respond(self.started);
+        }
+    }
+}
+
+/// Returns the left and right gains for a sound panned to `pan` (from -1.0,
+/// full left, to 1.0, full right). This is a constant-power pan law, scaled
+/// so that a centered sound is at 1.0 on both sides, same as an unpanned
+/// one. A sound panned all the way to one side is 3dB louder on that side.
+fn pan_gains(pan: f32) -> [f32; 2] {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    let (sin, cos) = angle.sin_cos();
+    [
+        cos * std::f32::consts::SQRT_2,
+        sin * std::f32::consts::SQRT_2,
+    ]
 }
 
 impl PlayingSoundID {
-    fn flow_name(&self) -> &str {
+    fn flow(&self) -> &FlowKey {
         &self.flow_and_node_name.0
     }
+    fn flow_name(&self) -> &str {
+        &self.flow_and_node_name.0.name
+    }
     fn node_name(&self) -> Option<&str> {
         self.flow_and_node_name
             .1
@@ -953,108 +2365,161 @@ impl Debug for PlayingSoundID {
 }
 
 impl Engine {
+    /// Starts setting up a new Engine, one named setting at a time. This is
+    /// the recommended way to make an Engine. See
+    /// [`EngineBuilder`](struct.EngineBuilder.html).
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
     /// Creates a new Engine with the given properties, which will perform
-    /// loading in the background using a purely internal `Switchyard`. Only
-    /// available if you haven't disabled the `"switchyard"` feature.
+    /// loading in the background using a purely internal `Switchyard`. (If
+    /// you've disabled the `"switchyard"` feature, a `ThreadPoolTaskRuntime`
+    /// is used instead, and `affinity` is ignored.) Same as using
+    /// `Engine::builder()` with each of these settings.
     ///
     /// Once these properties are set, they cannot be changed without creating
-    /// a new Engine.
+    /// a new Engine. (Except for the loading runtime; see `replace_runtime`.)
     ///
     /// - `speaker_layout`: What kind of speaker layout your listener has. When
     ///   in doubt, use `Stereo`.
     /// - `sample_rate`: Number of samples per second you will be outputting.
     /// - `num_threads`: Number of threads to use for decoding and streaming.
-    ///   If `None` or 0, will use a reasonable default based on the number of
-    ///   available hardware threads and whether background loading is
-    ///   requested.
-    /// - `num_threads`: If `None`, will use a reasonable default based on the
-    ///   number of available hardware threads. If `Some(x)`, will use that
-    ///   exact number of hardware threads.
+    ///   If `None`, will use a reasonable default based on the number of
+    ///   available hardware threads. If `Some(x)`, will use exactly `x`
+    ///   threads.
     /// - `affinity`: Offset added to core affinity of threads. When in doubt,
     ///   use `0`.
-    #[cfg(feature = "switchyard")]
+    /// - `buffer_budget`: If `None`, buffered sounds will be freed as soon as
+    ///   no flow needs them. If `Some(x)`, buffered sounds will be kept in
+    ///   memory in case they're needed again, until their total size exceeds
+    ///   `x` bytes, at which point the least recently used ones are freed.
     pub fn new(
         sound_delegate: Arc<dyn SoundDelegate>,
         speaker_layout: SpeakerLayout,
         sample_rate: PosFloat,
-        num_threads: Option<std::num::NonZeroUsize>,
+        num_threads: Option<NonZeroUsize>,
         affinity: usize,
+        buffer_budget: Option<usize>,
     ) -> Engine {
-        let num_logical_cores = num_cpus::get();
-        let num_threads = num_threads
-            .map(std::num::NonZeroUsize::get)
-            .unwrap_or_else(|| num_cpus::get() / 3)
-            .max(1);
-        use ::switchyard::{threads::ThreadAllocationOutput, Switchyard};
-        let runtime = Switchyard::new(
-            (0..num_threads).map(|i| ThreadAllocationOutput {
-                name: Some(format!("SMSworker{i}")),
-                ident: i,
-                stack_size: Some(1024 * 1024),
-                affinity: Some((i + affinity) % num_logical_cores),
-            }),
-            || (),
-        )
-        .expect("Unable to create Switchyard runtime");
-        Self::new_with_runtime(
-            sound_delegate,
-            speaker_layout,
-            sample_rate,
-            Arc::new(runtime),
-        )
+        Engine::builder()
+            .delegate(sound_delegate)
+            .speaker_layout(speaker_layout)
+            .sample_rate(sample_rate)
+            .num_threads(num_threads)
+            .affinity(affinity)
+            .buffer_budget(buffer_budget)
+            .build()
     }
     /// Creates a new Engine with the given properties using a particular custom
     /// [`TaskRuntime`](trait.TaskRuntime.html) for loading tasks. If you want
     /// to perform offline rendering, or another task where you just want all
     /// loading to happen synchronously, pass `Arc::new(ForegroundTaskRuntime)`
-    /// as the loading runtime. If you don't care, and haven't disabled the
-    /// `switchyard` feature, just use `new` instead.
+    /// as the loading runtime. On a platform with no threads, such as
+    /// WebAssembly, pass a `CooperativeTaskRuntime`. Same as using
+    /// `Engine::builder()` with each of these settings.
     ///
     /// Once these properties are set, they cannot be changed without creating
-    /// a new Engine.
+    /// a new Engine. (Except for the loading runtime; see `replace_runtime`.)
     ///
     /// - `speaker_layout`: What kind of speaker layout your listener has. When
     ///   in doubt, use `Stereo`.
     /// - `sample_rate`: Number of samples per second you will be outputting.
-    /// - `num_threads`: Number of threads to use for decoding and streaming.
-    ///   If `None` or 0, will use a reasonable default based on the number of
-    ///   available hardware threads and whether background loading is
-    ///   requested.
+    /// - `loading_rt`: The runtime that loading tasks will be given to.
+    /// - `buffer_budget`: If `None`, buffered sounds will be freed as soon as
+    ///   no flow needs them. If `Some(x)`, buffered sounds will be kept in
+    ///   memory in case they're needed again, until their total size exceeds
+    ///   `x` bytes, at which point the least recently used ones are freed.
     pub fn new_with_runtime<Runtime: TaskRuntime>(
         sound_delegate: Arc<dyn SoundDelegate>,
         speaker_layout: SpeakerLayout,
         sample_rate: PosFloat,
         loading_rt: Arc<Runtime>,
+        buffer_budget: Option<usize>,
+    ) -> Engine {
+        Engine::new_with_swappable_runtime(
+            sound_delegate,
+            speaker_layout,
+            sample_rate,
+            Arc::new(SwappableRuntime::new(loading_rt)),
+            buffer_budget,
+        )
+    }
+    /// Does the work of `new_with_runtime` and `EngineBuilder::build`.
+    fn new_with_swappable_runtime(
+        sound_delegate: Arc<dyn SoundDelegate>,
+        speaker_layout: SpeakerLayout,
+        sample_rate: PosFloat,
+        loading_rt: Arc<SwappableRuntime>,
+        buffer_budget: Option<usize>,
     ) -> Engine {
         let (command_tx, command_rx) = unbounded();
         Engine {
             mixer: Mixer::new(speaker_layout.get_num_channels()),
             soundman: Box::new(SoundMan::new(
                 sound_delegate.clone(),
-                loading_rt,
+                loading_rt.clone(),
+                buffer_budget,
             )),
+            cached_sounds: HashMap::new(),
+            loading_rt,
             sound_delegate,
             speaker_layout,
             sample_rate,
             command_tx,
             command_rx,
+            #[cfg(feature = "async")]
+            async_channels: vec![],
             live_soundtrack: Soundtrack::new(),
             flow_controls: HashMap::new(),
+            flow_controls_changed: false,
+            rng: Rng::new(0),
+            time_scale: PosFloat::ONE,
             mix_controls: [(
                 DEFAULT_CHANNEL.to_compact_string(),
                 Fader::new(PosFloat::ONE),
             )]
             .into_iter()
             .collect(),
-            flow_volumes: HashMap::new(),
+            soloed_mix_controls: HashSet::new(),
+            muted_mix_controls: HashSet::new(),
+            default_channel: DEFAULT_CHANNEL.to_compact_string(),
+            flow_volumes: BTreeMap::new(),
+            node_volumes: HashMap::new(),
+            element_volumes: HashMap::new(),
+            layer_intensities: HashMap::new(),
+            flow_successors: HashMap::new(),
             active_flow_nodes: vec![],
-            queued_sounds: BinaryHeap::new(),
+            queued_sounds: SoundQueue::default(),
+            audition: None,
             mix_buf: vec![],
+            planar_buf: vec![],
+            output_sample_rate: None,
+            output_resampler: None,
+            instruction_limit: NonZeroUsize::new(DEFAULT_INSTRUCTION_LIMIT),
+            load_timeout: None,
+            loop_wrap_callbacks: false,
+            max_chunk_frames: None,
+            routed_outputs: vec![],
+            taps: vec![],
+            next_tap_id: 0,
+            main_backlog: VecDeque::new(),
+            tap_buf: vec![],
+            mix_ahead_buf: vec![],
             flows_fading_out: HashSet::new(),
+            scheduled_fade_outs: HashMap::new(),
             mix_controls_fading_out: HashSet::new(),
-            flow_loads: HashMap::new(),
+            voice_limits: HashMap::new(),
+            buses: vec![],
+            listener: Listener::default(),
+            pitch_bends: HashMap::new(),
+            flow_loads: BTreeMap::new(),
             deferred_kill: false,
+            outgoing_flows: HashMap::new(),
+            next_outgoing_flow_id: 0,
             starting_flows: HashSet::new(),
+            flow_start_points: HashMap::new(),
+            #[cfg(any(test, feature = "testing"))]
+            started_sounds: vec![],
         }
     }
     /// Makes an independent `Commander` that can send commands to this
@@ -1074,44 +2539,561 @@ impl Engine {
     ) -> HashMap<CompactString, StringOrNumber> {
         self.flow_controls.clone()
     }
-    /// Returns the `SpeakerLayout` this `Engine` was initialized for.
-    pub fn get_speaker_layout(&self) -> SpeakerLayout {
-        self.speaker_layout
+    /// Gets a copy of the current value of a single FlowControl, or `None` if
+    /// it's unset. Unlike `get_flow_control`, this answers immediately, but
+    /// it won't reflect any commands that haven't been processed yet (by a
+    /// call to `turn_handle`).
+    pub fn copy_flow_control(
+        &self,
+        control_name: &str,
+    ) -> Option<StringOrNumber> {
+        self.flow_controls.get(control_name).cloned()
     }
-    /// Returns the sample rate this `Engine` was initialized for.
-    pub fn get_sample_rate(&self) -> PosFloat {
-        self.sample_rate
+    /// Returns some statistics about what this `Engine` is doing right now,
+    /// for profiling. This is cheap. It won't reflect any commands that
+    /// haven't been processed yet (by a call to `turn_handle` or
+    /// `drain_commands`).
+    pub fn stats(&self) -> EngineStats {
+        // (a flow can have more than one active node while it crossfades;
+        // there are only ever a handful, so a quadratic scan beats hashing)
+        let nodes = &self.active_flow_nodes;
+        let active_flows = nodes
+            .iter()
+            .enumerate()
+            .filter(|(i, x)| !nodes[..*i].iter().any(|y| y.flow == x.flow))
+            .count();
+        EngineStats {
+            active_voices: self.mixer.num_playing(),
+            cached_sound_bytes: self.soundman.cached_sound_bytes(),
+            streaming_sounds: self.soundman.num_streaming_sounds(),
+            active_flows,
+        }
     }
-    /// Mix some audio, advance time! `out` must have a number of elements
-    /// divisible by the number of speaker channels. Any existing data in `out`
-    /// is mixed with the active music data. You may or may not want to zero
-    /// `out` before this call.
-    pub fn turn_handle(&mut self, mut out: &mut [f32]) {
-        assert_eq!(out.len() % self.speaker_layout.get_num_channels(), 0);
-        let mut mix_buf = Vec::new();
-        swap(&mut mix_buf, &mut self.mix_buf);
-        // TODO: slim this, Bloom filter?
-        let mut seen_flows =
-            HashSet::with_capacity(self.active_flow_nodes.len() * 2);
-        while !out.is_empty() {
-            let now = self.mixer.get_next_output_sample_frame_number();
-            // Here, at this command boundary, evaluate any commands we might
-            // have received.
-            while let Ok(cmd) = self.command_rx.try_recv() {
-                self.issue(cmd);
+    /// Returns how far along the loading of the given flow's sounds is, or
+    /// `None` if the flow isn't being precached or played. It won't reflect
+    /// any commands that haven't been processed yet.
+    ///
+    /// The time remaining is only an estimate, based on how long the
+    /// decoders *think* each sound is, and on how much of each has been
+    /// decoded so far. Some decoders can't tell how long a sound is until
+    /// they've decoded all of it; those sounds are counted in
+    /// `sounds_of_unknown_length` instead.
+    pub fn flow_load_progress(
+        &mut self,
+        flow_name: &str,
+    ) -> Option<FlowLoadProgress> {
+        let load_status = self.flow_loads.get(&FlowKey::live(flow_name))?;
+        if !load_status.load_requested {
+            return None;
+        }
+        let mut progress = FlowLoadProgress {
+            total_sounds: load_status.known_sounds.len(),
+            ..Default::default()
+        };
+        // (several sounds may be regions of the same file, which only gets
+        // loaded once)
+        let mut counted_paths = HashSet::new();
+        for sound in load_status.known_sounds.iter() {
+            if self.soundman.is_ready(sound) {
+                progress.sounds_ready += 1;
+            } else if counted_paths.insert(&sound.path) {
+                match self.soundman.seconds_remaining(sound) {
+                    Some(seconds) => progress.seconds_remaining += seconds,
+                    None => progress.sounds_of_unknown_length += 1,
+                }
             }
-            // See if any newly-started flows are ready to start
-            self.starting_flows.retain(|flow_name| {
-                let load_status = self.flow_loads.get_mut(flow_name).unwrap();
-                if load_status.is_ready(self.soundman.as_mut()) {
-                    // oh boy! start the start node!
-                    let flow =
-                        self.live_soundtrack.flows.get(flow_name).unwrap();
+        }
+        Some(progress)
+    }
+    /// Plays a single sound from the given soundtrack, which doesn't have to
+    /// be the live one, right away, without needing a flow to play it. This
+    /// is meant for soundtrack editors, e.g. for an "audition" button.
+    ///
+    /// The sound plays at unity gain, ignoring the soundtrack's `channel`
+    /// and every MixControl, flow, and pitch bend, so it doesn't interfere
+    /// with (and isn't interfered with by) whatever else is playing. It's
+    /// loaded when this is called, and starts as soon as it's ready. Only one
+    /// audition plays at a time; starting another one stops this one. It
+    /// plays until it ends, or until `stop_audition` is called.
+    pub fn audition_sound(
+        &mut self,
+        soundtrack: &Soundtrack,
+        sound_name: &str,
+    ) {
+        self.stop_audition();
+        let mut sounds = SoundQueue::default();
+        Engine::execute_sound(
+            soundtrack,
+            self.sample_rate,
+            0,
+            &FlowKey::live(""),
+            None,
+            sound_name,
+            &mut self.sound_delegate,
+            &mut sounds,
+            AUDITION_CHANNEL,
+            &[],
+            0.0,
+            None,
+            PosFloat::ZERO,
+            None,
+            PosFloat::ZERO,
+            FadeType::Linear,
+            PosFloat::ONE,
+            None,
+            false,
+            None,
+        );
+        self.begin_audition(sounds);
+    }
+    /// Plays a single sequence from the given soundtrack, which doesn't have
+    /// to be the live one, right away, without needing a flow to play it. As
+    /// `audition_sound`, except that the sequence's own pans, positions, and
+    /// fades are kept. (Its `channel`s and `send`s are ignored, like the
+    /// MixControls they name.) Conditional sounds are decided by the current
+    /// FlowControls. Humanization doesn't disturb the flows' `random()`.
+    pub fn audition_sequence(
+        &mut self,
+        soundtrack: &Soundtrack,
+        sequence_name: &str,
+    ) {
+        self.stop_audition();
+        let mut sounds = SoundQueue::default();
+        // (not our own RNG, so that live playback isn't affected)
+        let mut rng =
+            Rng::new(self.mixer.get_next_output_sample_frame_number());
+        Engine::execute_sequence(
+            soundtrack,
+            self.sample_rate,
+            PosFloat::ONE,
+            0,
+            0.0,
+            &FlowKey::live(""),
+            None,
+            sequence_name,
+            &mut self.sound_delegate,
+            &mut sounds,
+            &self.flow_controls,
+            &mut rng,
+        );
+        self.begin_audition(sounds);
+    }
+    /// Stops the sound or sequence being auditioned (see `audition_sound`),
+    /// if any, immediately.
+    pub fn stop_audition(&mut self) {
+        let Some(audition) = self.audition.take() else {
+            return;
+        };
+        self.mixer.stop_all(|id| id.audition);
+        self.queued_sounds.retain(|x| !x.who.audition);
+        for sound in audition.sounds.iter() {
+            self.soundman.unload(sound);
+        }
+    }
+    /// Returns true if a sound or sequence is being auditioned (see
+    /// `audition_sound`), including if it's still loading.
+    pub fn is_auditioning(&self) -> bool {
+        self.audition.is_some()
+    }
+    /// Common code for `audition_sound` and `audition_sequence`. Marks the
+    /// given sounds as an audition, and starts loading them.
+    fn begin_audition(&mut self, mut pending: SoundQueue) {
+        let mut sounds: Vec<Arc<Sound>> = vec![];
+        pending.for_each_mut(|queued_sound| {
+            queued_sound.who.audition = true;
+            queued_sound.who.channel = AUDITION_CHANNEL.to_compact_string();
+            queued_sound.who.sends.clear();
+            if !sounds.iter().any(|x| Arc::ptr_eq(x, &queued_sound.sound)) {
+                sounds.push(queued_sound.sound.clone());
+            }
+        });
+        if sounds.is_empty() {
+            // (nothing to play; any problems have already been reported)
+            return;
+        }
+        for sound in sounds.iter() {
+            self.soundman.load(sound, true);
+        }
+        self.audition = Some(Audition {
+            sounds,
+            pending: Some(pending),
+        });
+    }
+    /// Starts the audition, if it's waiting and its sounds are all loaded,
+    /// or ends it, if it's played all of its sounds.
+    fn update_audition(&mut self, now: u64) {
+        let Some(audition) = self.audition.as_mut() else {
+            return;
+        };
+        match audition.pending.take() {
+            Some(pending) => {
+                if audition.sounds.iter().all(|x| self.soundman.is_ready(x)) {
+                    self.queued_sounds.append_from(pending, now);
+                } else {
+                    audition.pending = Some(pending);
+                }
+            }
+            None => {
+                if self.mixer.count_playing(|id| id.audition) == 0
+                    && !self.queued_sounds.any(|x| x.who.audition)
+                {
+                    self.stop_audition();
+                }
+            }
+        }
+    }
+    /// Returns the `SpeakerLayout` this `Engine` was initialized for.
+    pub fn get_speaker_layout(&self) -> SpeakerLayout {
+        self.speaker_layout
+    }
+    /// Returns the sample rate this `Engine` was initialized for.
+    pub fn get_sample_rate(&self) -> PosFloat {
+        self.sample_rate
+    }
+    /// Returns the name of the default channel. See `set_default_channel`.
+    pub fn get_default_channel(&self) -> &str {
+        &self.default_channel
+    }
+    /// Changes the name of the default channel from `main` (or whatever it
+    /// was changed to before) to `channel`. Best done right after creating
+    /// the `Engine`, before anything has played. The default channel's
+    /// MixControl is renamed, keeping its volume, unless a MixControl by the
+    /// new name already exists, in which case both are left alone. From then
+    /// on, the "all except main" commands spare the new default channel
+    /// instead of the old one.
+    ///
+    /// This only decides which *MixControl* is the default. Which channel
+    /// sounds play on when the soundtrack doesn't say is up to the
+    /// soundtrack; see `Soundtrack::set_default_channel`. You will usually
+    /// want to give both the same name.
+    pub fn set_default_channel(&mut self, channel: CompactString) {
+        if channel == self.default_channel {
+            return;
+        }
+        if !self.mix_controls.contains_key(&channel) {
+            if let Some(fader) =
+                self.mix_controls.remove(&self.default_channel)
+            {
+                self.mix_controls_fading_out.remove(&self.default_channel);
+                self.mix_controls.insert(channel.clone(), fader);
+            }
+        }
+        self.default_channel = channel;
+    }
+    /// Sets the most flow node instructions that may run in a single call to
+    /// `turn_handle` (or `advance_silently`, etc.), or `None` for no limit.
+    /// The default is 10,000, which no sensible soundtrack should come close
+    /// to.
+    ///
+    /// This guards against soundtracks that loop without ever waiting (for
+    /// example, a looping flow with no `wait`s in it), which would otherwise
+    /// hang the audio thread. When the limit is reached, every playing node
+    /// is held back until the next call, and a `RunawayFlow` diagnostic is
+    /// issued naming the flow that was running at the time.
+    pub fn set_instruction_limit(&mut self, limit: Option<NonZeroUsize>) {
+        self.instruction_limit = limit;
+    }
+    /// Sets how long (in seconds) a starting flow may wait for its sounds to
+    /// load before something is done about it, or `None` (the default) to
+    /// wait forever.
+    ///
+    /// If many flows are started at once, or the loading runtime is busy with
+    /// other work, flows can spend a long time waiting to start, which looks
+    /// a lot like the music system has hung. When a flow has waited longer
+    /// than `timeout`, a `LoadTimedOut` diagnostic is issued. If
+    /// `start_anyway` is true, the flow is then started without waiting any
+    /// longer. Any sound it plays that still isn't loaded is skipped, and
+    /// later plays of that sound will be heard once it finishes loading.
+    pub fn set_load_timeout(
+        &mut self,
+        timeout: Option<PosFloat>,
+        start_anyway: bool,
+    ) {
+        self.load_timeout = timeout.map(|timeout| (timeout, start_anyway));
+    }
+    /// Sets whether `SoundDelegate::loop_wrapped` is called each time a
+    /// repeated sound (a `play sound` in a sequence with a `repeat`) starts
+    /// over. Off by default.
+    pub fn set_loop_wrap_callbacks(&mut self, enabled: bool) {
+        self.loop_wrap_callbacks = enabled;
+    }
+    /// Sets the most audio (in seconds) that will be mixed at once before
+    /// checking for new commands again, or `None` (the default) for no limit.
+    ///
+    /// Normally, commands are only received at the start of a call to
+    /// `turn_handle` (and whenever something in a flow is due to happen), so
+    /// a command that arrives while a very large buffer is being mixed may
+    /// take effect up to that whole buffer late. With a limit (e.g. 0.005
+    /// for five milliseconds), large buffers are mixed in small pieces, with
+    /// a check for commands between each one, so that commands land close to
+    /// when they were issued no matter how big the buffer is. This costs a
+    /// little performance.
+    pub fn set_max_chunk_length(&mut self, length: Option<PosFloat>) {
+        self.max_chunk_frames =
+            length.map(|x| x.seconds_to_frames(self.sample_rate).max(1));
+    }
+    /// Starts using a different [`TaskRuntime`](trait.TaskRuntime.html) for
+    /// loading tasks, e.g. to use fewer threads while the game is busy. Loads
+    /// that were already started on the old runtime are left to finish there,
+    /// and the `Engine` lets go of the old runtime once they have. Nothing
+    /// else about the `Engine` is affected.
+    ///
+    /// If the `Engine` was holding the last reference to the old runtime, it
+    /// will be dropped on whatever thread the `Engine` is being used from
+    /// (either here, or during a later load), which may block while its
+    /// threads shut down.
+    pub fn replace_runtime<Runtime: TaskRuntime>(
+        &mut self,
+        loading_rt: Arc<Runtime>,
+    ) {
+        self.loading_rt.replace(loading_rt);
+    }
+    /// Starts using a different `SoundDelegate`, e.g. to load sounds from a
+    /// downloaded bundle instead of from disk. Every load from now on opens
+    /// its files with the new delegate, and every diagnostic goes to it.
+    ///
+    /// Sounds that are already loaded aren't loaded again, and loads that
+    /// were already underway finish with the old delegate; the `Engine` lets
+    /// go of the old delegate once nothing is using it anymore. To make sure
+    /// a sound comes from the new delegate, make sure it isn't loaded (e.g.
+    /// by replacing the soundtrack with one that doesn't use it, or
+    /// `uncache_sound`ing it) before it's next needed.
+    pub fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>) {
+        self.soundman.replace_delegate(delegate.clone());
+        self.sound_delegate = delegate;
+    }
+    /// Returns the sample rate `turn_handle` is currently producing. This is
+    /// the sample rate this `Engine` was initialized for, unless it has been
+    /// changed with `set_output_sample_rate`.
+    pub fn get_output_sample_rate(&self) -> PosFloat {
+        self.output_sample_rate.unwrap_or(self.sample_rate)
+    }
+    /// Changes the sample rate `turn_handle` produces, e.g. because the
+    /// output device changed. Everything else, including all timing, still
+    /// runs at the sample rate this `Engine` was initialized for; the mixed
+    /// output is resampled (cheaply, with linear interpolation) on its way
+    /// out. Setting it back to the original rate turns the resampling off
+    /// again, which may drop up to a few milliseconds of audio.
+    ///
+    /// Panics if `sample_rate` is zero.
+    pub fn set_output_sample_rate(&mut self, sample_rate: PosFloat) {
+        assert!(sample_rate > PosFloat::ZERO);
+        if sample_rate == self.sample_rate {
+            self.output_sample_rate = None;
+            self.output_resampler = None;
+            return;
+        }
+        self.output_sample_rate = Some(sample_rate);
+        match self.output_resampler.as_mut() {
+            Some(resampler) => {
+                resampler.set_rates(self.sample_rate, sample_rate)
+            }
+            None => {
+                self.output_resampler = Some(OutputResampler::new(
+                    self.speaker_layout.get_num_channels(),
+                    self.sample_rate,
+                    sample_rate,
+                ))
+            }
+        }
+    }
+    /// Mix some audio, advance time! `out` must have a number of elements
+    /// divisible by the number of speaker channels. Any existing data in `out`
+    /// is overwritten; there's no need to zero it first.
+    ///
+    /// `out` is at the output sample rate; see `set_output_sample_rate`.
+    ///
+    /// Panics if `out` doesn't hold a whole number of sample frames. See
+    /// `try_turn_handle` for a version that doesn't.
+    pub fn turn_handle(&mut self, out: &mut [f32]) {
+        assert_eq!(out.len() % self.speaker_layout.get_num_channels(), 0);
+        self.turn_handle_frames(out)
+    }
+    /// As `turn_handle`, but if `out` doesn't hold a whole number of sample
+    /// frames, instead of panicking, mixes as many whole frames as fit and
+    /// returns an error. The leftover samples at the end of `out` are left
+    /// alone.
+    pub fn try_turn_handle(&mut self, out: &mut [f32]) -> Result<(), String> {
+        let num_channels = self.speaker_layout.get_num_channels();
+        let leftover = out.len() % num_channels;
+        let whole_len = out.len() - leftover;
+        self.turn_handle_frames(&mut out[..whole_len]);
+        if leftover == 0 {
+            Ok(())
+        } else {
+            Err(format!(
+                "output buffer of {} samples isn't a whole number of {}-channel \
+                 sample frames; the last {} samples were not mixed",
+                whole_len + leftover,
+                num_channels,
+                leftover
+            ))
+        }
+    }
+    /// Does the work of `turn_handle`, once `out` is known to hold a whole
+    /// number of sample frames.
+    fn turn_handle_frames(&mut self, out: &mut [f32]) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        match self.output_resampler.take() {
+            None => {
+                let num_frames = out.len() / num_channels;
+                self.run_for(num_frames as u64, Some(out));
+            }
+            Some(mut resampler) => {
+                resampler.resample(out, |buf| {
+                    let num_frames = buf.len() / num_channels;
+                    self.run_for(num_frames as u64, Some(buf));
+                });
+                self.output_resampler = Some(resampler);
+            }
+        }
+    }
+    /// As `turn_handle`, but with a separate output buffer for each speaker
+    /// channel, instead of a single interleaved buffer. There must be exactly
+    /// as many buffers as there are speaker channels, and they must all be the
+    /// same length. Any existing data in the buffers is overwritten.
+    pub fn turn_handle_planar(&mut self, outs: &mut [&mut [f32]]) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        assert_eq!(outs.len(), num_channels);
+        let num_frames = outs[0].len();
+        assert!(outs.iter().all(|out| out.len() == num_frames));
+        let mut planar_buf = Vec::new();
+        swap(&mut planar_buf, &mut self.planar_buf);
+        planar_buf.resize(num_frames * num_channels, 0.0);
+        self.turn_handle(&mut planar_buf);
+        for (channel, out) in outs.iter_mut().enumerate() {
+            for (dst, src) in out
+                .iter_mut()
+                .zip(planar_buf[channel..].iter().step_by(num_channels))
+            {
+                *dst = *src;
+            }
+        }
+        self.planar_buf = planar_buf;
+    }
+    /// Advance time by the given number of seconds, without producing any
+    /// audio. Everything else happens just as it would in `turn_handle`:
+    /// commands are processed, nodes run, sounds start (and end), faders
+    /// fade, and finished flows and MixControls are cleaned up. Playing
+    /// sounds are skipped ahead rather than mixed.
+    ///
+    /// Useful for fast-forwarding the music in tests, or for keeping the
+    /// music's state moving while the game is minimized. The time is rounded
+    /// to the nearest sample frame.
+    pub fn advance_silently(&mut self, duration: PosFloat) {
+        let num_frames = (duration * self.sample_rate).round() as u64;
+        self.run_for(num_frames, None);
+    }
+    /// Applies every command that has been issued so far, right now, without
+    /// mixing any audio or advancing time. (Normally, commands wait for the
+    /// next `turn_handle`.) Flows that were killed outright are cleaned up
+    /// immediately.
+    ///
+    /// Useful in tests, to issue some commands and then inspect the result,
+    /// and at shutdown, to make sure a final `kill_all_flows` takes effect.
+    pub fn drain_commands(&mut self) {
+        self.receive_commands();
+        self.perform_deferred_kill();
+    }
+    /// Applies every command that has been sent to us from elsewhere.
+    fn receive_commands(&mut self) {
+        while let Ok(cmd) = self.command_rx.try_recv() {
+            self.issue(cmd);
+        }
+        #[cfg(feature = "async")]
+        self.receive_async_commands();
+    }
+    /// Does the work of `turn_handle` (if `out` is `Some`) or
+    /// `advance_silently` (if it's `None`).
+    fn run_for(&mut self, num_frames: u64, mut out: Option<&mut [f32]>) {
+        if self.main_backlog.is_empty() {
+            self.mix_for(num_frames, out);
+        } else {
+            let used = self.use_main_backlog(num_frames, out.as_deref_mut());
+            let out = out.map(|out| {
+                &mut out
+                    [used as usize * self.speaker_layout.get_num_channels()..]
+            });
+            self.mix_for(num_frames - used, out);
+        }
+    }
+    /// As `run_for`, but ignoring the main output's backlog.
+    fn mix_for(&mut self, mut num_frames: u64, mut out: Option<&mut [f32]>) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        // (a runtime with no threads of its own does its loading now)
+        self.loading_rt.turn();
+        let mut mix_buf = Vec::new();
+        swap(&mut mix_buf, &mut self.mix_buf);
+        // TODO: slim this, Bloom filter?
+        let mut seen_flows =
+            HashSet::with_capacity(self.active_flow_nodes.len() * 2);
+        let instruction_limit =
+            self.instruction_limit.map_or(usize::MAX, NonZeroUsize::get);
+        let mut instructions_run = 0;
+        let mut held_back = false;
+        while num_frames > 0 {
+            let now = self.mixer.get_next_output_sample_frame_number();
+            // Here, at this command boundary, evaluate any commands we might
+            // have received.
+            self.receive_commands();
+            self.start_scheduled_fade_outs(now);
+            // See if any newly-started flows are ready to start
+            self.starting_flows.retain(|flow| {
+                let load_status = self.flow_loads.get_mut(flow).unwrap();
+                let waiting_since =
+                    *load_status.waiting_since.get_or_insert(now);
+                let mut ready = load_status.is_ready(self.soundman.as_mut());
+                if let Some((timeout, start_anyway)) = self.load_timeout {
+                    if !ready
+                        && now.saturating_sub(waiting_since)
+                            > timeout.seconds_to_frames(self.sample_rate)
+                    {
+                        if !load_status.timed_out {
+                            load_status.timed_out = true;
+                            self.sound_delegate.diagnostic(
+                                DiagLevel::Warning,
+                                DiagCode::LoadTimedOut,
+                                &format!(
+                                    "flow {:?} has been waiting more than {} \
+                                     seconds for its sounds to load{}",
+                                    flow.name,
+                                    timeout,
+                                    if start_anyway {
+                                        ", starting it anyway"
+                                    } else {
+                                        ""
+                                    }
+                                ),
+                            );
+                        }
+                        ready = start_anyway;
+                    }
+                }
+                if ready {
+                    load_status.waiting_since = None;
+                    load_status.timed_out = false;
+                    // oh boy! start the start node!
+                    let flow_key = flow.clone();
+                    let flow =
+                        self.live_soundtrack.flows.get(&flow.name).unwrap();
+                    let (node_name, offset) = self
+                        .flow_start_points
+                        .remove(&flow_key.name)
+                        .unwrap_or((None, PosFloat::ZERO));
+                    // (if the node went away in the meantime, fall back to
+                    // the starting node)
+                    let node = node_name
+                        .and_then(|x| flow.nodes.get(&x).cloned())
+                        .unwrap_or_else(|| flow.start_node.clone());
                     self.active_flow_nodes.push(ActiveNode {
-                        flow_name: flow_name.to_compact_string(),
-                        node: flow.start_node.clone(),
+                        flow: flow_key,
+                        node,
                         next_instruction_time: now,
                         next_instruction_index: 0,
+                        awaiting_end: None,
+                        catch_up: (offset / self.time_scale)
+                            .seconds_to_frames(self.sample_rate),
+                        frame_fraction: 0.0,
                     });
                     false
                 } else {
@@ -1119,73 +3101,119 @@ impl Engine {
                 } // still waiting
             });
             // Process every active node
-            let mut nodes_to_start: HashSet<StringAndAHalf> =
+            let mut nodes_to_start: HashSet<NodeKey> =
                 HashSet::with_capacity(16);
-            let mut nodes_to_restart: HashSet<StringAndAHalf> =
+            let mut nodes_to_restart: HashSet<NodeKey> =
                 HashSet::with_capacity(16);
-            let mut possible_autoloop_flows: Vec<Arc<Flow>> =
+            // (by key, since an outgoing flow may have the same
+            // `Flow` as its replacement; along with the `frame_fraction` of
+            // the node that finished, so that looping doesn't drift)
+            let mut possible_autoloop_flows: Vec<(FlowKey, f64)> =
+                Vec::with_capacity(16);
+            // (flows that had a node finish, and so might have finished
+            // entirely)
+            let mut possibly_finished_flows: Vec<FlowKey> =
                 Vec::with_capacity(16);
             let flow_controls = &mut self.flow_controls;
+            let rng = &mut self.rng;
             self.active_flow_nodes.retain_mut(|active_node| {
                 if active_node.next_instruction_time > now { return true }
                 let mut n = active_node.next_instruction_index;
                 while n < active_node.node.commands.len() {
+                    if instructions_run >= instruction_limit {
+                        // Hold this node back until the next block. (Only
+                        // the first flow to go over gets the blame.)
+                        if !held_back {
+                            self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::RunawayFlow, &format!("flow {:?} ran more than {} instructions without any time passing, holding all flows back", active_node.flow.name, instruction_limit));
+                            held_back = true;
+                        }
+                        active_node.next_instruction_time = now + num_frames;
+                        break;
+                    }
+                    instructions_run += 1;
+                    // (sounds played by a node that's still catching up go
+                    // through `append_caught_up`)
+                    let mut caught_up = SoundQueue::default();
+                    let queued_sounds = if active_node.catch_up == 0 { &mut self.queued_sounds } else { &mut caught_up };
                     let next_command = &active_node.node.commands[n];
                     n += 1;
                     match next_command {
                         Command::Done => {
-                            match self.live_soundtrack.flows.get(&active_node.flow_name) {
-                                Some(flow) if flow.autoloop && !possible_autoloop_flows.contains(flow) => {
-                                    possible_autoloop_flows.push(flow.clone());
+                            match active_node.flow.find(&self.live_soundtrack, &self.outgoing_flows) {
+                                Some(flow) if flow.autoloop && !possible_autoloop_flows.iter().any(|(x, _)| *x == active_node.flow) => {
+                                    possible_autoloop_flows.push((active_node.flow.clone(), active_node.frame_fraction));
                                 }
                                 _ => (),
                             }
+                            if !possibly_finished_flows.contains(&active_node.flow) {
+                                possibly_finished_flows.push(active_node.flow.clone());
+                            }
                             return false;
                         },
                         Command::Wait(sleep_time) => {
-                            active_node.next_instruction_time = now + sleep_time.seconds_to_frames(self.sample_rate);
-                            break;
+                            let frames = active_node.whole_frames(f64::from(*(*sleep_time * self.sample_rate / self.time_scale)));
+                            if active_node.wait(now, frames) { break }
                         },
-                        Command::PlaySound(sound_name) => {
-                            Self::execute_sound(&self.live_soundtrack, self.sample_rate, now, &active_node.flow_name, active_node.node.name.as_ref().map(CompactString::as_str), sound_name, &mut self.sound_delegate, &mut self.queued_sounds, DEFAULT_CHANNEL, PosFloat::ZERO, None, PosFloat::ZERO);
+                        Command::WaitForCondition(ops) => {
+                            if !evaluate(flow_controls, rng, ops).is_truthy() {
+                                // Check again when a FlowControl changes.
+                                // (See `wake_condition_waiters`.)
+                                n -= 1;
+                                active_node.next_instruction_time = u64::MAX;
+                                break;
+                            }
+                        },
+                        Command::PlaySound(sound_name, gain) => {
+                            Self::execute_sound(&self.live_soundtrack, self.sample_rate, now, &active_node.flow, active_node.node.name.as_ref().map(CompactString::as_str), sound_name, &mut self.sound_delegate, queued_sounds, &self.live_soundtrack.default_channel, &[], 0.0, None, PosFloat::ZERO, None, PosFloat::ZERO, FadeType::Linear, *gain, None, false, None);
+                            self.queued_sounds.append_caught_up(caught_up, now, active_node.catch_up);
                         },
-                        Command::PlaySoundAndWait(sound_name) => {
-                            let sleep_time = Self::execute_sound(&self.live_soundtrack, self.sample_rate, now, &active_node.flow_name, active_node.node.name.as_ref().map(CompactString::as_str), sound_name, &mut self.sound_delegate, &mut self.queued_sounds, DEFAULT_CHANNEL, PosFloat::ZERO, None, PosFloat::ZERO);
+                        Command::PlaySoundAndWait(sound_name, gain) => {
+                            let (tx, finished) = query::make();
+                            let sleep_time = Self::execute_sound(&self.live_soundtrack, self.sample_rate, now, &active_node.flow, active_node.node.name.as_ref().map(CompactString::as_str), sound_name, &mut self.sound_delegate, queued_sounds, &self.live_soundtrack.default_channel, &[], 0.0, None, PosFloat::ZERO, None, PosFloat::ZERO, FadeType::Linear, *gain, None, false, Some(FinishSignal::new(tx)));
+                            self.queued_sounds.append_caught_up(caught_up, now, active_node.catch_up);
                             if sleep_time == u64::MAX {
-                                if let Some(sound) = self.live_soundtrack.sounds.get(sound_name.as_str()) {
-                                    // this will warn the author what happened,
-                                    // and ensure that there will be a place-
-                                    // holder value for next time
-                                    let _ = sound.get_end(&*self.sound_delegate);
-                                }
-                                active_node.next_instruction_time = now + *self.sample_rate as u64;
-                            } else {
-                                active_node.next_instruction_time = now + sleep_time;
-                            }
-                            break;
+                                // We don't know how long the sound is yet.
+                                // Wait until we do. (See `resolve_awaited_ends`.)
+                                let sound = self.live_soundtrack.sounds.get(sound_name.as_str()).unwrap().clone();
+                                active_node.awaiting_end = Some(AwaitedEnd { sound, when: now, finished });
+                                active_node.next_instruction_time = u64::MAX;
+                                break;
+                            } else if active_node.wait(now, sleep_time) { break }
                         },
                         Command::PlaySequence(seqname) => {
-                            Self::execute_sequence(&self.live_soundtrack, self.sample_rate, now, &active_node.flow_name, active_node.node.name.as_ref().map(CompactString::as_str), seqname, &mut self.sound_delegate, &mut self.queued_sounds);
+                            Self::execute_sequence(&self.live_soundtrack, self.sample_rate, self.time_scale, now, active_node.frame_fraction, &active_node.flow, active_node.node.name.as_ref().map(CompactString::as_str), seqname, &mut self.sound_delegate, queued_sounds, flow_controls, rng);
+                            self.queued_sounds.append_caught_up(caught_up, now, active_node.catch_up);
                         },
                         Command::PlaySequenceAndWait(seqname) => {
-                            let sleep_time = Self::execute_sequence(&self.live_soundtrack, self.sample_rate, now, &active_node.flow_name, active_node.node.name.as_ref().map(CompactString::as_str), seqname, &mut self.sound_delegate, &mut self.queued_sounds);
-                            active_node.next_instruction_time = now + sleep_time;
-                            break;
+                            let sleep_time = Self::execute_sequence(&self.live_soundtrack, self.sample_rate, self.time_scale, now, active_node.frame_fraction, &active_node.flow, active_node.node.name.as_ref().map(CompactString::as_str), seqname, &mut self.sound_delegate, queued_sounds, flow_controls, rng);
+                            self.queued_sounds.append_caught_up(caught_up, now, active_node.catch_up);
+                            let frames = active_node.whole_frames(sleep_time);
+                            if active_node.wait(now, frames) { break }
                         },
                         Command::StartNode(node_name) => {
-                            nodes_to_start.insert(StringAndAHalf(active_node.flow_name.clone(), Some(node_name.clone())));
+                            nodes_to_start.insert(NodeKey(active_node.flow.clone(), Some(node_name.clone())));
                         },
                         Command::RestartNode(node_name) => {
-                            nodes_to_restart.insert(StringAndAHalf(active_node.flow_name.clone(), Some(node_name.clone())));
+                            nodes_to_restart.insert(NodeKey(active_node.flow.clone(), Some(node_name.clone())));
                         },
                         Command::RestartFlow => {
-                            nodes_to_restart.insert(StringAndAHalf(active_node.flow_name.clone(), None));
+                            nodes_to_restart.insert(NodeKey(active_node.flow.clone(), None));
+                        },
+                        Command::FadeNode { node, volume, length, fade_type } => {
+                            let key = NodeKey(active_node.flow.clone(), node.clone());
+                            let old_volume = self.node_volumes.get(&key).map(Fader::evaluate).unwrap_or(PosFloat::ONE);
+                            self.node_volumes.insert(key, Fader::start(*fade_type, old_volume, *volume, *length * self.sample_rate));
                         },
                         Command::Set(control_name, ops) => {
-                            flow_controls.insert(control_name.clone(), evaluate(flow_controls, ops));
+                            let new_value = evaluate(flow_controls, rng, ops);
+                            if flow_controls.get(control_name) != Some(&new_value) {
+                                self.sound_delegate.flow_control_changed(control_name, &new_value);
+                                flow_controls.insert(control_name.clone(), new_value);
+                                self.flow_controls_changed = true;
+                            }
                         },
                         Command::Goto(ops, cond, index) => {
-                            if evaluate(flow_controls, ops).is_truthy() == *cond {
+                            if evaluate(flow_controls, rng, ops).is_truthy() == *cond {
                                 n = *index;
                             }
                         },
@@ -1195,99 +3223,135 @@ impl Engine {
                     }
                 }
                 active_node.next_instruction_index = n;
-                active_node.next_instruction_index < active_node.node.commands.len()
+                if active_node.next_instruction_index < active_node.node.commands.len() {
+                    true
+                } else {
+                    if !possibly_finished_flows.contains(&active_node.flow) {
+                        possibly_finished_flows.push(active_node.flow.clone());
+                    }
+                    false
+                }
             });
-            for flow in possible_autoloop_flows.into_iter() {
-                debug_assert!(flow.autoloop);
+            let mut looped_fractions: HashMap<FlowKey, f64> = HashMap::new();
+            for (flow_key, frame_fraction) in
+                possible_autoloop_flows.into_iter()
+            {
                 if !self
                     .active_flow_nodes
                     .iter()
-                    .any(|active_node| flow.name == active_node.flow_name)
+                    .any(|active_node| flow_key == active_node.flow)
                 {
-                    nodes_to_start
-                        .insert(StringAndAHalf(flow.name.clone(), None));
+                    looped_fractions.insert(flow_key.clone(), frame_fraction);
+                    nodes_to_start.insert(NodeKey(flow_key, None));
                 }
             }
-            for StringAndAHalf(flow_name, node_name) in
-                nodes_to_start.into_iter()
-            {
-                match self.active_flow_nodes.iter_mut().find(|x| {
-                    x.flow_name == flow_name && x.node.name == node_name
-                }) {
+            for NodeKey(flow_key, node_name) in nodes_to_start.into_iter() {
+                match self
+                    .active_flow_nodes
+                    .iter_mut()
+                    .find(|x| x.flow == flow_key && x.node.name == node_name)
+                {
                     Some(_active_flow_node) => {
                         // Node is already playing. Do nothing.
-                        self.sound_delegate.warning(&format!("attempt to start node {:?}, which was already playing", node_name));
+                        self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::NodeAlreadyPlaying, &format!("attempt to start node {:?}, which was already playing", node_name));
                     }
                     None => {
                         // Node is not already playing. Start it.
-                        let flow =
-                            match self.live_soundtrack.flows.get(&flow_name) {
-                                None => {
-                                    // No such flow. (This should only happen
-                                    // when soundtrack shenanigans are happening.)
-                                    self.sound_delegate.warning(&format!(
+                        let flow = match flow_key
+                            .find(&self.live_soundtrack, &self.outgoing_flows)
+                        {
+                            None => {
+                                // No such flow. (This should only happen
+                                // when soundtrack shenanigans are happening.)
+                                self.sound_delegate.diagnostic(
+                                    DiagLevel::Warning,
+                                    DiagCode::MissingFlow,
+                                    &format!(
                                         "missing flow {:?} for node \"{:?}\"",
-                                        flow_name, node_name
-                                    ));
-                                    continue;
-                                }
-                                Some(flow) => flow,
-                            };
+                                        flow_key.name, node_name
+                                    ),
+                                );
+                                continue;
+                            }
+                            Some(flow) => flow,
+                        };
                         let node = match node_name.as_ref() {
                             Some(node_name) => match flow.nodes.get(node_name)
                             {
                                 None => {
-                                    self.sound_delegate.warning(&format!(
+                                    self.sound_delegate.diagnostic(
+                                        DiagLevel::Warning,
+                                        DiagCode::MissingNode,
+                                        &format!(
                                         "can't start missing node: {:?}::{:?}",
-                                        flow_name, node_name
-                                    ));
+                                        flow_key.name, node_name
+                                    ),
+                                    );
                                     continue;
                                 }
                                 Some(node) => node.clone(),
                             },
                             None => flow.start_node.clone(),
                         };
+                        // (a flow looping back to its starting node picks up
+                        // where the last go-around left off)
+                        let frame_fraction = match node_name {
+                            None => looped_fractions
+                                .remove(&flow_key)
+                                .unwrap_or(0.0),
+                            Some(_) => 0.0,
+                        };
                         self.active_flow_nodes.push(ActiveNode {
-                            flow_name,
+                            flow: flow_key,
                             node,
                             next_instruction_time: now,
                             next_instruction_index: 0,
+                            awaiting_end: None,
+                            catch_up: 0,
+                            frame_fraction,
                         });
                     }
                 }
             }
-            for StringAndAHalf(flow_name, node_name) in
-                nodes_to_restart.into_iter()
-            {
-                match self.active_flow_nodes.iter_mut().find(|x| {
-                    x.flow_name == flow_name && x.node.name == node_name
-                }) {
+            for NodeKey(flow_key, node_name) in nodes_to_restart.into_iter() {
+                match self
+                    .active_flow_nodes
+                    .iter_mut()
+                    .find(|x| x.flow == flow_key && x.node.name == node_name)
+                {
                     Some(afn) => {
                         // Node is already playing. Restart it.
                         afn.next_instruction_index = 0;
                         afn.next_instruction_time = now;
+                        afn.awaiting_end = None;
+                        afn.catch_up = 0;
                     }
                     None => {
                         // Node is not already playing. Start it.
-                        let flow =
-                            match self.live_soundtrack.flows.get(&flow_name) {
-                                None => {
-                                    // No such flow. (This should only happen
-                                    // when soundtrack shenanigans are happening.)
-                                    self.sound_delegate.warning(&format!(
+                        let flow = match flow_key
+                            .find(&self.live_soundtrack, &self.outgoing_flows)
+                        {
+                            None => {
+                                // No such flow. (This should only happen
+                                // when soundtrack shenanigans are happening.)
+                                self.sound_delegate.diagnostic(
+                                    DiagLevel::Warning,
+                                    DiagCode::MissingFlow,
+                                    &format!(
                                         "can't restart missing flow: {:?}",
-                                        flow_name
-                                    ));
-                                    continue;
-                                }
-                                Some(flow) => flow,
-                            };
+                                        flow_key.name
+                                    ),
+                                );
+                                continue;
+                            }
+                            Some(flow) => flow,
+                        };
                         let node = match node_name {
                             None => flow.start_node.clone(),
                             Some(node_name) => {
                                 match flow.nodes.get(&node_name) {
                                     None => {
-                                        self.sound_delegate.warning(&format!("can't restart missing flow: {:?}::{:?}", flow_name, node_name));
+                                        self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::MissingNode, &format!("can't restart missing flow: {:?}::{:?}", flow_key.name, node_name));
                                         continue;
                                     }
                                     Some(node) => node.clone(),
@@ -1295,14 +3359,23 @@ impl Engine {
                             }
                         };
                         self.active_flow_nodes.push(ActiveNode {
-                            flow_name,
+                            flow: flow_key,
                             node,
                             next_instruction_time: now,
                             next_instruction_index: 0,
+                            awaiting_end: None,
+                            catch_up: 0,
+                            frame_fraction: 0.0,
                         });
                     }
                 }
             }
+            self.wake_condition_waiters(now);
+            let mut started_successor = false;
+            for flow in possibly_finished_flows.into_iter() {
+                started_successor |= self.start_successor(&flow);
+            }
+            self.update_audition(now);
             // Consume queued sounds whose times have come
             while self
                 .queued_sounds
@@ -1311,6 +3384,23 @@ impl Engine {
                 .unwrap_or(false)
             {
                 let queued_sound = self.queued_sounds.pop().unwrap();
+                if queued_sound.loop_wrap
+                    && self.loop_wrap_callbacks
+                    && !queued_sound.who.audition
+                {
+                    let who = &queued_sound.who;
+                    self.sound_delegate.loop_wrapped(
+                        who.flow_name(),
+                        who.flow_and_node_name.1.as_deref(),
+                        &who.sound,
+                    );
+                }
+                #[cfg(any(test, feature = "testing"))]
+                self.started_sounds
+                    .push(QueuedSoundReport::new(&queued_sound));
+                // Positional sounds are mixed down to mono, and then spread
+                // to every speaker, so that the mixer can pan them anywhere.
+                let positional = queued_sound.who.position.is_some();
                 if let Some(adapter) = adaptify(
                     &self.sound_delegate,
                     self.soundman.as_mut(),
@@ -1318,44 +3408,190 @@ impl Engine {
                     queued_sound.fade_in,
                     queued_sound.length,
                     queued_sound.fade_out,
+                    queued_sound.fade_type,
                     self.sample_rate,
-                    self.speaker_layout,
+                    if positional {
+                        SpeakerLayout::Mono
+                    } else {
+                        self.speaker_layout
+                    },
                 ) {
-                    self.mixer.play(adapter, queued_sound.who);
+                    // (only now that the sound is sure to start; a sound that
+                    // can't be played shouldn't cut off one that can)
+                    if !self.make_room_for_voice(&queued_sound.who) {
+                        continue;
+                    }
+                    let adapter = if positional {
+                        new_spread_adapter(
+                            adapter,
+                            self.sample_rate,
+                            self.speaker_layout,
+                        )
+                    } else {
+                        adapter
+                    };
+                    let channel_and_flow = [
+                        ChannelOrFlow::Channel(
+                            queued_sound.who.channel.clone(),
+                        ),
+                        ChannelOrFlow::Flow(
+                            queued_sound.who.flow_name().to_compact_string(),
+                        ),
+                    ];
+                    // (one-shots don't have a flow to be bent along with, and
+                    // auditions don't have either)
+                    let num_bends = if queued_sound.who.audition {
+                        0
+                    } else if queued_sound.who.one_shot {
+                        1
+                    } else {
+                        2
+                    };
+                    // (these were usually made by `pin_pitch_bends` already;
+                    // one-shots on other MixControls are the exception)
+                    let mut rates: Vec<SharedRate> = channel_and_flow
+                        .into_iter()
+                        .take(num_bends)
+                        .map(|x| {
+                            self.pitch_bends.entry(x).or_default().rate.clone()
+                        })
+                        .collect();
+                    if queued_sound.pitch != PosFloat::ONE {
+                        rates.push(SharedRate::new(queued_sound.pitch));
+                    }
+                    let adapter = new_bend_adapter(
+                        adapter,
+                        self.speaker_layout.get_num_channels(),
+                        rates,
+                    );
+                    let mut who = queued_sound.who;
+                    if let Some(finish_signal) = who.finish_signal.as_mut() {
+                        finish_signal.started = true;
+                    }
+                    self.mixer.play(adapter, who);
                 }
             }
+            self.resolve_awaited_ends(now);
             // Note: This might be zero, in which case, we will loop around
             // again and maybe process more nodes. This will happen almost
-            // every time we start a node.
-            let max_wait = self.get_num_sample_frames_until_next_exec();
-            let buf_len = max_wait
-                .map(|x| {
-                    (x * self.speaker_layout.get_num_channels() as u64)
-                        .min(out.len() as u64) as usize
-                })
-                .unwrap_or(out.len());
-            if buf_len > 0 {
-                let buf = &mut out[..buf_len];
-                buf.fill(0.0);
-                if mix_buf.len() < buf.len() {
-                    mix_buf.resize(buf.len(), MaybeUninit::uninit());
-                }
-                self.mixer.mix(
-                    buf,
-                    &mut mix_buf[..buf.len()],
-                    VolumeGetWrapper {
-                        mix_controls: &mut self.mix_controls,
-                        flow_volumes: &mut self.flow_volumes,
-                        flows_fading_out: &self.flows_fading_out,
-                        starting_flows: &self.starting_flows,
-                        seen_flows: &mut seen_flows,
-                    },
-                );
-                out = &mut out[buf_len..];
+            // every time we start a node, and whenever we start a successor
+            // flow (so that it gets its chance to start right away).
+            let max_wait = if started_successor {
+                Some(0)
+            } else {
+                self.get_num_sample_frames_until_next_exec()
+            };
+            let buf_frames =
+                max_wait.map(|x| x.min(num_frames)).unwrap_or(num_frames);
+            let buf_frames = self
+                .max_chunk_frames
+                .map_or(buf_frames, |x| x.min(buf_frames));
+            if buf_frames > 0 {
+                for bend in self.pitch_bends.values() {
+                    bend.rate.set(bend.fader.evaluate());
+                }
+                let volume_getter = VolumeGetWrapper {
+                    mix_controls: &mut self.mix_controls,
+                    soloed_mix_controls: &self.soloed_mix_controls,
+                    muted_mix_controls: &self.muted_mix_controls,
+                    buses: &mut self.buses,
+                    speaker_layout: self.speaker_layout,
+                    listener: &self.listener,
+                    flow_volumes: &mut self.flow_volumes,
+                    node_volumes: &mut self.node_volumes,
+                    element_volumes: &mut self.element_volumes,
+                    flows_fading_out: &self.flows_fading_out,
+                    starting_flows: &self.starting_flows,
+                    routed_outputs: &self.routed_outputs,
+                    taps: &self.taps,
+                    seen_flows: &mut seen_flows,
+                };
+                let buf_len = buf_frames as usize * num_channels;
+                if let Some(rest) = out.take() {
+                    let (buf, rest) = rest.split_at_mut(buf_len);
+                    buf.fill(0.0);
+                    if mix_buf.len() < buf.len() {
+                        mix_buf.resize(buf.len(), MaybeUninit::uninit());
+                    }
+                    self.mixer.mix(
+                        buf,
+                        &mut mix_buf[..buf.len()],
+                        volume_getter,
+                    );
+                    self.collect_tap_outputs();
+                    self.collect_routed_outputs();
+                    out = Some(rest);
+                } else {
+                    // (any size will do, as long as it's whole sample frames)
+                    let scratch_len =
+                        buf_len.min(SKIP_BUF_FRAMES * num_channels);
+                    if mix_buf.len() < scratch_len {
+                        mix_buf.resize(scratch_len, MaybeUninit::uninit());
+                    }
+                    self.mixer.skip(
+                        buf_len,
+                        &mut mix_buf[..scratch_len],
+                        volume_getter,
+                    );
+                }
+                let frames = PosFloat::from(buf_frames);
+                for bend in self.pitch_bends.values_mut() {
+                    // (not `Iterator::step_by`!)
+                    Fader::step_by(&mut bend.fader, frames);
+                }
+                for filter in
+                    self.buses.iter_mut().filter_map(|x| x.filter.as_mut())
+                {
+                    filter.step_by(frames);
+                }
+                for width in
+                    self.buses.iter_mut().filter_map(|x| x.width.as_mut())
+                {
+                    width.step_by(frames);
+                }
+                self.remove_unused_buses();
+                num_frames -= buf_frames;
             }
         }
         self.mix_buf = mix_buf;
         self.kill_the_unseen(seen_flows);
+        // (so that an audition that just ended doesn't linger until the next
+        // mix)
+        self.update_audition(self.mixer.get_next_output_sample_frame_number());
+    }
+    /// Returns the bus for the given MixControl, making a new (empty) one if
+    /// there isn't one already.
+    fn get_bus(&mut self, channel: CompactString) -> &mut Bus {
+        let index = match self.buses.iter().position(|x| x.channel == channel)
+        {
+            Some(index) => index,
+            None => {
+                self.buses.push(Bus {
+                    channel,
+                    effect: None,
+                    filter: None,
+                    width: None,
+                });
+                self.buses.len() - 1
+            }
+        };
+        &mut self.buses[index]
+    }
+    /// Remove filters that have been faded back up to Nyquist, and widths
+    /// that have been faded back to 1.0, and then buses that have nothing
+    /// left on them.
+    fn remove_unused_buses(&mut self) {
+        for bus in self.buses.iter_mut() {
+            if bus.filter.as_ref().is_some_and(LowPassFilter::is_bypassed) {
+                bus.filter = None;
+            }
+            if bus.width.as_ref().is_some_and(StereoWidth::is_bypassed) {
+                bus.width = None;
+            }
+        }
+        self.buses.retain(|bus| {
+            bus.effect.is_some() || bus.filter.is_some() || bus.width.is_some()
+        });
     }
     /// Returns the number of sample frames left to output before the next
     /// scheduled `Node` command or `Region` start, or none if the schedule is
@@ -1383,154 +3619,883 @@ impl Engine {
                 ret = Some(x.when);
             }
         }
+        for scheduled in self.scheduled_fade_outs.values() {
+            let sooner = match ret {
+                None => true,
+                Some(time) => scheduled.when < time,
+            };
+            if sooner {
+                ret = Some(scheduled.when.max(now));
+            }
+        }
         ret.map(|x| x - now)
     }
-    fn perform_deferred_kill(&mut self) {
-        if !self.deferred_kill {
-            return;
+    /// Starts fading out every flow whose `fade_flow_to_then_out` hold is
+    /// over.
+    fn start_scheduled_fade_outs(&mut self, now: u64) {
+        let mut due: Vec<FlowKey> = self
+            .scheduled_fade_outs
+            .iter()
+            .filter(|(_, scheduled)| scheduled.when <= now)
+            .map(|(flow, _)| flow.clone())
+            .collect();
+        due.sort_unstable();
+        for flow in due {
+            let scheduled = self.scheduled_fade_outs.remove(&flow).unwrap();
+            self.issue(EngineCommand::FadeFlowOut {
+                flow_name: flow.name,
+                fade_type: scheduled.fade_type,
+                fade_length: scheduled.fade_length,
+            });
         }
-        self.deferred_kill = false;
-        let mut seen_flows =
-            HashSet::with_capacity(self.active_flow_nodes.len() * 2);
-        self.mixer.bump(VolumeGetWrapper {
-            mix_controls: &mut self.mix_controls,
-            flow_volumes: &mut self.flow_volumes,
-            flows_fading_out: &self.flows_fading_out,
-            starting_flows: &self.starting_flows,
-            seen_flows: &mut seen_flows,
-        });
-        self.kill_the_unseen(seen_flows);
     }
-    /// Make nodes, flows, and mix controls that were not processed and (if
-    /// relevant) have zero current volume stop existing.
-    fn kill_the_unseen(&mut self, seen_flows: HashSet<CompactString>) {
-        self.flow_volumes.retain(|k, _| {
-            // We will stay alive if any of the below are true:
-            // - any samples were mixed from this flow
-            // - this flow is not fading out
-            // - this flow is still being started
-            if seen_flows.contains(k)
-                || !self.flows_fading_out.contains(k)
-                || self.starting_flows.contains(k)
+    /// If any FlowControls have changed, wake up any nodes that are waiting
+    /// for a condition (`wait until ...`), so they can check it again. (If
+    /// it's still false, they'll go right back to sleep.)
+    fn wake_condition_waiters(&mut self, now: u64) {
+        if !std::mem::take(&mut self.flow_controls_changed) {
+            return;
+        }
+        for node in self.active_flow_nodes.iter_mut() {
+            if node.next_instruction_time == u64::MAX
+                && node.awaiting_end.is_none()
+                && matches!(
+                    node.node.commands.get(node.next_instruction_index),
+                    Some(Command::WaitForCondition(_))
+                )
             {
-                true
-            } else {
-                let load_status = self.flow_loads.get_mut(k).unwrap();
-                load_status.active_loading = false;
-                load_status.maybe_unload(
-                    &self.live_soundtrack,
-                    self.soundman.as_mut(),
-                );
-                self.active_flow_nodes.retain(|afn| afn.flow_name != k);
-                self.flows_fading_out.retain(|flow_name| flow_name != k);
-                false
+                node.next_instruction_time = now;
             }
-        });
-        self.mix_controls.retain(|k, fader| {
-            fader.evaluate() != PosFloat::ONE
-                || !self.mix_controls_fading_out.contains(k)
-        });
+        }
     }
-    fn replace_soundtrack(&mut self, new_soundtrack: Soundtrack) {
-        self.live_soundtrack = new_soundtrack;
-        let mut new_flow_loads =
-            HashMap::with_capacity(self.live_soundtrack.flows.len());
-        for (flow_name, flow) in self.live_soundtrack.flows.iter() {
-            let (active_loading, precaching) =
-                match self.flow_loads.get(flow_name) {
-                    Some(x) => (x.active_loading, x.precaching),
-                    None => (false, false),
-                };
-            let mut new_load_status = FlowLoadStatus {
-                active_loading,
-                precaching,
+    /// Wake up any nodes that were waiting for sounds of unknown length, whose
+    /// lengths we now know. (A streamed sound's length is found out when it
+    /// runs out, so the node will usually wake up a little late, at the start
+    /// of the next mix after it ends. The same goes for the second time a
+    /// repeated sound plays.)
+    fn resolve_awaited_ends(&mut self, now: u64) {
+        for active_node in self.active_flow_nodes.iter_mut() {
+            let Some(awaited) = active_node.awaiting_end.as_ref() else {
+                continue;
+            };
+            let sound = &awaited.sound;
+            let end = match sound.end.get() {
+                Some(end) => *end,
+                // The sound is done with, but we never found out how long it
+                // was (maybe it couldn't be opened). Fall back to a guess.
+                None if awaited.finished.poll() => {
+                    sound.get_end(&*self.sound_delegate)
+                }
+                None => continue,
+            };
+            let length = end.saturating_sub(sound.start) / sound.pitch;
+            active_node.next_instruction_time = (awaited.when
+                + length.seconds_to_frames(self.sample_rate))
+            .max(now);
+            active_node.awaiting_end = None;
+        }
+        // Likewise, queue the rest of any repeated sounds whose lengths we
+        // now know.
+        let awaited_repeats =
+            std::mem::take(&mut self.queued_sounds.awaited_repeats);
+        for awaited in awaited_repeats {
+            let sound = awaited.passes[0].sound.clone();
+            let end = match sound.end.get() {
+                Some(end) => *end,
+                None if awaited.finished.poll() => {
+                    sound.get_end(&*self.sound_delegate)
+                }
+                None => {
+                    self.queued_sounds.awaited_repeats.push(awaited);
+                    continue;
+                }
+            };
+            let length = end.saturating_sub(sound.start) / sound.pitch;
+            // (in fractional frames, so that the repetitions don't drift)
+            let step = f64::from(*length) * f64::from(*self.sample_rate);
+            let start = (awaited.first + step as u64).max(now);
+            let num_passes = awaited.passes.len();
+            for (pass, mut queued_sound) in
+                awaited.passes.into_iter().enumerate()
+            {
+                queued_sound.when = start + (step * pass as f64) as u64;
+                if pass + 1 < num_passes {
+                    queued_sound.length = Some(length);
+                }
+                self.queued_sounds.push(queued_sound);
+            }
+        }
+    }
+    /// Called when one of a flow's nodes finishes. If that was the flow's
+    /// last node, and the flow finished on its own, fades it out and starts
+    /// its successor (if any). Returns true if it did.
+    fn start_successor(&mut self, flow: &FlowKey) -> bool {
+        if flow.is_outgoing()
+            || self
+                .active_flow_nodes
+                .iter()
+                .any(|active_node| active_node.flow == *flow)
+            || self.flows_fading_out.contains(flow)
+        {
+            return false;
+        }
+        let Some(successor) = self.flow_successors.get(&flow.name) else {
+            return false;
+        };
+        let Some(volume) = self.flow_volumes.get(flow).map(Fader::evaluate)
+        else {
+            return false;
+        };
+        let successor_name = successor.flow_name.clone();
+        let fade_type = successor.fade_type;
+        let fade_length = successor.fade_length;
+        self.issue(EngineCommand::FadeFlowOut {
+            flow_name: flow.name.clone(),
+            fade_type,
+            fade_length,
+        });
+        self.issue(EngineCommand::StartFlow {
+            flow_name: successor_name,
+            node_name: None,
+            offset: PosFloat::ZERO,
+            fade_type,
+            target_volume: volume,
+            fade_length,
+        });
+        true
+    }
+    /// Enforces the voice limit (if any) of the MixControl the given sound is
+    /// about to start on, stealing the oldest voice if needed. Returns false
+    /// if the new sound should be dropped instead.
+    fn make_room_for_voice(&mut self, who: &PlayingSoundID) -> bool {
+        let Some((max_voices, stealing)) = self.voice_limits.get(&who.channel)
+        else {
+            return true;
+        };
+        let num_voices =
+            self.mixer.count_playing(|id| id.channel == who.channel);
+        if num_voices < max_voices.get() {
+            return true;
+        }
+        match stealing {
+            VoiceStealing::StealOldest => {
+                let stolen =
+                    self.mixer.stop_oldest(|id| id.channel == who.channel);
+                if cfg!(debug_assertions) {
+                    if let Some(stolen) = stolen {
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Debug,
+                            DiagCode::VoiceStolen,
+                            &format!(
+                            "voice limit reached, sound {:?} ({:?}) stolen by \
+                             sound {:?} ({:?})",
+                            stolen.sound, stolen, who.sound, who
+                        ),
+                        );
+                    }
+                }
+                true
+            }
+            VoiceStealing::DropNewest => {
+                if cfg!(debug_assertions) {
+                    self.sound_delegate.diagnostic(
+                        DiagLevel::Debug,
+                        DiagCode::VoiceDropped,
+                        &format!(
+                            "voice limit reached, sound {:?} ({:?}) dropped",
+                            who.sound, who
+                        ),
+                    );
+                }
+                false
+            }
+        }
+    }
+    fn perform_deferred_kill(&mut self) {
+        if !self.deferred_kill {
+            return;
+        }
+        self.deferred_kill = false;
+        let mut seen_flows =
+            HashSet::with_capacity(self.active_flow_nodes.len() * 2);
+        self.mixer.bump(VolumeGetWrapper {
+            mix_controls: &mut self.mix_controls,
+            soloed_mix_controls: &self.soloed_mix_controls,
+            muted_mix_controls: &self.muted_mix_controls,
+            buses: &mut self.buses,
+            speaker_layout: self.speaker_layout,
+            listener: &self.listener,
+            flow_volumes: &mut self.flow_volumes,
+            node_volumes: &mut self.node_volumes,
+            element_volumes: &mut self.element_volumes,
+            flows_fading_out: &self.flows_fading_out,
+            starting_flows: &self.starting_flows,
+            routed_outputs: &self.routed_outputs,
+            taps: &self.taps,
+            seen_flows: &mut seen_flows,
+        });
+        self.kill_the_unseen(seen_flows);
+    }
+    /// Make nodes, flows, and mix controls that were not processed and (if
+    /// relevant) have zero current volume stop existing.
+    fn kill_the_unseen(&mut self, seen_flows: HashSet<FlowKey>) {
+        // Flows that were killed outright have already lost their volume, but
+        // their nodes and loads still need cleaning up.
+        let mut dead_flows: Vec<FlowKey> = self
+            .flows_fading_out
+            .iter()
+            .filter(|k| !self.flow_volumes.contains_key(*k))
+            .cloned()
+            .collect();
+        self.flow_volumes.retain(|k, _| {
+            // We will stay alive if any of the below are true:
+            // - any samples were mixed from this flow
+            // - this flow is not fading out
+            // - this flow is still being started
+            if seen_flows.contains(k)
+                || !self.flows_fading_out.contains(k)
+                || self.starting_flows.contains(k)
+            {
+                true
+            } else {
+                dead_flows.push(k.clone());
+                false
+            }
+        });
+        for k in dead_flows.iter() {
+            self.forget_flow(k);
+        }
+        self.node_volumes
+            .retain(|k, _| self.flow_volumes.contains_key(&k.0));
+        self.element_volumes
+            .retain(|k, _| self.flow_volumes.contains_key(&k.0));
+        self.mix_controls.retain(|k, fader| {
+            fader.evaluate() != PosFloat::ONE
+                || !self.mix_controls_fading_out.contains(k)
+        });
+        // Pitch bends are only worth remembering if they're bent, if some
+        // sound is still following them, or if the soundtrack might play a
+        // sound that will.
+        self.pitch_bends.retain(|_, bend| {
+            bend.pinned
+                || !bend.rate.is_unused()
+                || bend.fader.evaluate() != PosFloat::ONE
+        });
+    }
+    /// Make a pitch bend for every flow in the live soundtrack, and every
+    /// MixControl its flows play sounds on, so that they're there when its
+    /// sounds start. Unpin the ones that it doesn't need anymore.
+    fn pin_pitch_bends(&mut self) {
+        for bend in self.pitch_bends.values_mut() {
+            bend.pinned = false;
+        }
+        let soundtrack = &self.live_soundtrack;
+        for (flow_name, flow) in soundtrack.flows.iter() {
+            let channels = flow.referenced_channels(soundtrack);
+            let targets =
+                channels.into_iter().map(ChannelOrFlow::Channel).chain(
+                    std::iter::once(ChannelOrFlow::Flow(flow_name.clone())),
+                );
+            for target in targets {
+                self.pitch_bends.entry(target).or_default().pinned = true;
+            }
+        }
+    }
+    /// Returns true if the live soundtrack has the given sequence, and it has
+    /// the given element. Otherwise, issues a warning and returns false.
+    fn check_sequence_element(
+        &mut self,
+        sequence_name: &str,
+        element: usize,
+    ) -> bool {
+        let message = match self.live_soundtrack.sequences.get(sequence_name) {
+            None => format!("no such sequence: {:?}", sequence_name),
+            Some(sequence) if element >= sequence.elements.len() => format!(
+                "sequence {:?} has no element {} (it has {})",
+                sequence_name,
+                element,
+                sequence.elements.len()
+            ),
+            Some(_) => return true,
+        };
+        self.sound_delegate.diagnostic(
+            DiagLevel::Warning,
+            DiagCode::MissingSequence,
+            &message,
+        );
+        false
+    }
+    /// Stop loading, and stop running the nodes of, a flow that no longer
+    /// has a volume.
+    fn forget_flow(&mut self, k: &FlowKey) {
+        // (the flow may have been removed by a soundtrack change)
+        if let Some(load_status) = self.flow_loads.get_mut(k) {
+            load_status.active_loading = false;
+            load_status
+                .maybe_unload(&self.live_soundtrack, self.soundman.as_mut());
+        }
+        if self.outgoing_flows.remove(k).is_some() {
+            self.flow_loads.remove(k);
+        }
+        self.active_flow_nodes.retain(|afn| afn.flow != *k);
+        self.flows_fading_out.remove(k);
+        self.scheduled_fade_outs.remove(k);
+    }
+    fn replace_soundtrack(&mut self, new_soundtrack: Soundtrack) {
+        self.live_soundtrack = new_soundtrack;
+        self.pin_pitch_bends();
+        // Flows that were waiting to start, but don't exist anymore, never
+        // will start.
+        self.starting_flows.retain(|flow| {
+            if self.live_soundtrack.flows.contains_key(&flow.name) {
+                true
+            } else {
+                self.flow_volumes.remove(flow);
+                self.flows_fading_out.remove(flow);
+                false
+            }
+        });
+        // Outgoing flows that have been killed since the crossfade began are
+        // done with.
+        self.outgoing_flows
+            .retain(|flow, _| self.flow_volumes.contains_key(flow));
+        let mut new_flow_loads = BTreeMap::new();
+        for (flow_name, flow) in self.live_soundtrack.flows.iter() {
+            let key = FlowKey::live(flow_name.clone());
+            let (active_loading, precaching) = match self.flow_loads.get(&key)
+            {
+                Some(x) => (x.active_loading, x.precaching),
+                None => (false, false),
+            };
+            let mut new_load_status = FlowLoadStatus {
+                active_loading,
+                precaching,
                 load_requested: false,
                 known_all_ready: false,
+                waiting_since: None,
+                timed_out: false,
                 known_sounds: flow.find_all_sounds(
                     &self.live_soundtrack,
                     |name| {
-                        self.sound_delegate
-                            .warning(&format!("missing sound: {:?}", name))
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::MissingSound,
+                            &format!("missing sound: {:?}", name),
+                        )
                     },
                     |name| {
-                        self.sound_delegate
-                            .warning(&format!("missing sequence: {:?}", name))
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::MissingSequence,
+                            &format!("missing sequence: {:?}", name),
+                        )
                     },
                 ),
             };
             new_load_status
                 .maybe_load(&self.live_soundtrack, self.soundman.as_mut());
-            new_flow_loads.insert(flow_name.clone(), new_load_status);
+            new_flow_loads.insert(key, new_load_status);
         }
         // unload the old ones AFTER loading the new ones, that way anything
         // that's still in common will remain loaded
-        for load_status in self.flow_loads.values_mut() {
-            load_status
-                .force_unload(&self.live_soundtrack, self.soundman.as_mut());
+        for (flow, mut load_status) in std::mem::take(&mut self.flow_loads) {
+            if self.outgoing_flows.contains_key(&flow) {
+                // still fading out, still needs its sounds
+                new_flow_loads.insert(flow, load_status);
+            } else {
+                load_status.force_unload(
+                    &self.live_soundtrack,
+                    self.soundman.as_mut(),
+                );
+            }
         }
         self.flow_loads = new_flow_loads;
     }
+    /// Replace the soundtrack, turning every playing flow into an outgoing
+    /// flow that fades out, and starting its replacement (if any) in the new
+    /// soundtrack.
+    fn crossfade_soundtrack(
+        &mut self,
+        new_soundtrack: Soundtrack,
+        fade_type: FadeType,
+        fade_length: PosFloat,
+    ) {
+        self.perform_deferred_kill();
+        let playing_flows: Vec<FlowKey> = self
+            .flow_volumes
+            .keys()
+            .filter(|flow| {
+                !flow.is_outgoing() && !self.starting_flows.contains(*flow)
+            })
+            .cloned()
+            .collect();
+        let mut renames = HashMap::with_capacity(playing_flows.len());
+        let mut flows_to_start = Vec::with_capacity(playing_flows.len());
+        for flow in playing_flows.into_iter() {
+            let outgoing = FlowKey {
+                name: flow.name.clone(),
+                outgoing: Some(self.next_outgoing_flow_id),
+            };
+            self.next_outgoing_flow_id += 1;
+            let mut fader = self.flow_volumes.remove(&flow).unwrap();
+            if !self.flows_fading_out.remove(&flow) {
+                // (if it was still fading, its replacement picks up where it
+                // was headed, not where it happened to be)
+                let target_volume = fader.target();
+                fader = Fader::start(
+                    fade_type,
+                    fader.evaluate(),
+                    PosFloat::ZERO,
+                    fade_length * self.sample_rate,
+                );
+                if new_soundtrack.flows.contains_key(&flow.name) {
+                    flows_to_start.push((flow.name.clone(), target_volume));
+                }
+            }
+            self.flow_volumes.insert(outgoing.clone(), fader);
+            self.flows_fading_out.insert(outgoing.clone());
+            // The outgoing flow keeps its node and sequence element volumes,
+            // and the replacement flow starts out with the same ones.
+            let node_volumes: Vec<(NodeKey, Fader)> = self
+                .node_volumes
+                .iter()
+                .filter(|(k, _)| k.0 == flow)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            for (NodeKey(_, node_name), fader) in node_volumes {
+                self.node_volumes
+                    .insert(NodeKey(outgoing.clone(), node_name), fader);
+            }
+            let element_volumes: Vec<(ElementKey, Fader)> = self
+                .element_volumes
+                .iter()
+                .filter(|(k, _)| k.0 == flow)
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            for ((_, sequence_name, index), fader) in element_volumes {
+                self.element_volumes
+                    .insert((outgoing.clone(), sequence_name, index), fader);
+            }
+            if let Some(mut load_status) = self.flow_loads.remove(&flow) {
+                // The replacement flow will inherit the precaching status.
+                // The outgoing flow only needs to stay loaded while it plays.
+                self.flow_loads.insert(
+                    flow.clone(),
+                    FlowLoadStatus {
+                        precaching: load_status.precaching,
+                        active_loading: false,
+                        load_requested: false,
+                        known_all_ready: false,
+                        known_sounds: vec![],
+                        waiting_since: None,
+                        timed_out: false,
+                    },
+                );
+                load_status.precaching = false;
+                self.flow_loads.insert(outgoing.clone(), load_status);
+            }
+            if let Some(definition) =
+                self.live_soundtrack.flows.get(&flow.name)
+            {
+                self.outgoing_flows
+                    .insert(outgoing.clone(), definition.clone());
+            }
+            renames.insert(flow, outgoing);
+        }
+        for active_node in self.active_flow_nodes.iter_mut() {
+            if let Some(x) = renames.get(&active_node.flow) {
+                active_node.flow = x.clone();
+            }
+        }
+        self.queued_sounds.for_each_mut(|queued_sound| {
+            if let Some(x) =
+                renames.get(&queued_sound.who.flow_and_node_name.0)
+            {
+                queued_sound.who.flow_and_node_name.0 = x.clone();
+            }
+        });
+        for id in self.mixer.identities_mut() {
+            if let Some(x) = renames.get(&id.flow_and_node_name.0) {
+                id.flow_and_node_name.0 = x.clone();
+            }
+        }
+        self.replace_soundtrack(new_soundtrack);
+        for (flow_name, target_volume) in flows_to_start.into_iter() {
+            self.issue(EngineCommand::StartFlow {
+                flow_name,
+                node_name: None,
+                offset: PosFloat::ZERO,
+                fade_type,
+                target_volume,
+                fade_length,
+            });
+        }
+    }
+    /// If the given flow is playing (or starting), and has a layer intensity,
+    /// fade each of its layer nodes to the volume that intensity calls for.
+    /// `fade_length` is in sample frames.
+    fn apply_layer_intensity(
+        &mut self,
+        flow_name: &CompactString,
+        fade_type: FadeType,
+        fade_length: PosFloat,
+    ) {
+        let Some(&intensity) = self.layer_intensities.get(flow_name) else {
+            return;
+        };
+        let flow_key = FlowKey::live(flow_name.clone());
+        if !self.flow_volumes.contains_key(&flow_key) {
+            // (it'll be applied when the flow starts)
+            return;
+        }
+        let Some(flow) = self.live_soundtrack.flows.get(flow_name) else {
+            return;
+        };
+        for (node_name, node) in flow.nodes.iter() {
+            let Some(layer) = node.layer else { continue };
+            let key = NodeKey(flow_key.clone(), Some(node_name.clone()));
+            let old_volume = self
+                .node_volumes
+                .get(&key)
+                .map(Fader::evaluate)
+                .unwrap_or(PosFloat::ONE);
+            self.node_volumes.insert(
+                key,
+                Fader::start(
+                    fade_type,
+                    old_volume,
+                    layer.volume_at(intensity),
+                    fade_length,
+                ),
+            );
+        }
+    }
+    /// Replace the soundtrack, and point every active node (other than those
+    /// of outgoing flows) at its new definition, if it has one.
+    fn reconcile_soundtrack(&mut self, new_soundtrack: Soundtrack) {
+        self.replace_soundtrack(new_soundtrack);
+        for active_node in self.active_flow_nodes.iter_mut() {
+            if active_node.flow.is_outgoing() {
+                continue;
+            }
+            let Some(flow) =
+                self.live_soundtrack.flows.get(&active_node.flow.name)
+            else {
+                continue;
+            };
+            let new_node = match active_node.node.name.as_ref() {
+                None => &flow.start_node,
+                Some(node_name) => match flow.nodes.get(node_name) {
+                    Some(x) => x,
+                    None => continue,
+                },
+            };
+            if Arc::ptr_eq(new_node, &active_node.node) {
+                continue;
+            }
+            if active_node.next_instruction_index >= new_node.commands.len() {
+                self.sound_delegate.diagnostic(
+                    DiagLevel::Warning,
+                    DiagCode::NodeRewound,
+                    &format!(
+                        "node {:?} of flow {:?} was about to run instruction \
+                         {}, but now only has {} instructions; restarting it \
+                         from the beginning",
+                        new_node.name.as_deref().unwrap_or("(start)"),
+                        active_node.flow,
+                        active_node.next_instruction_index,
+                        new_node.commands.len(),
+                    ),
+                );
+                active_node.next_instruction_index = 0;
+            }
+            active_node.node = new_node.clone();
+        }
+    }
     /// Start a sequence being played. Returns the number of *sample frames*
-    /// this sequence will last.
+    /// this sequence will last. (This isn't necessarily a whole number; see
+    /// `ActiveNode::whole_frames`.) `now_fraction` is how far past `now`, in
+    /// fractions of a sample frame, the sequence really starts; element
+    /// times are worked out from there, so that sequences played back to back
+    /// stay in phase with each other. The sequence's timing (but not its
+    /// sounds) is stretched by `time_scale`; see `set_time_scale`.
     #[allow(clippy::too_many_arguments)] // (internal function, doesn't care)
     fn execute_sequence(
         soundtrack: &Soundtrack,
         sample_rate: PosFloat,
+        time_scale: PosFloat,
         now: u64,
-        flow_name: &str,
+        now_fraction: f64,
+        flow: &FlowKey,
         node_name: Option<&str>,
         seqname: &str,
         sound_delegate: &mut Arc<dyn SoundDelegate>,
-        queued_sounds: &mut BinaryHeap<QueuedSound>,
-    ) -> u64 {
+        queued_sounds: &mut SoundQueue,
+        flow_controls: &HashMap<CompactString, StringOrNumber>,
+        rng: &mut Rng,
+    ) -> f64 {
         match soundtrack.sequences.get(seqname) {
             None => {
-                sound_delegate.warning(&format!(
-                    "can't play missing sequence: {:?}",
-                    seqname
-                ));
-                0
+                sound_delegate.diagnostic(
+                    DiagLevel::Warning,
+                    DiagCode::MissingSequence,
+                    &format!("can't play missing sequence: {:?}", seqname),
+                );
+                0.0
             }
             Some(sequence) => {
                 let sequence = sequence.clone();
-                let len = sequence.length.seconds_to_frames(sample_rate);
-                for (when, what) in sequence.elements.iter() {
-                    let when = now + when.seconds_to_frames(sample_rate);
+                // (sample frames per second of musical time)
+                let frame_rate = sample_rate / time_scale;
+                let len = f64::from(*(sequence.length * frame_rate));
+                for (index, (when, what)) in
+                    sequence.elements.iter().enumerate()
+                {
+                    // (in fractional sample frames, from where the sequence
+                    // really started)
+                    let at = now_fraction + f64::from(*(*when * frame_rate));
                     match what {
                         SequenceElement::PlaySequence { sequence } => {
                             assert_ne!(sequence, &seqname);
                             Engine::execute_sequence(
                                 soundtrack,
                                 sample_rate,
-                                when,
-                                flow_name,
+                                time_scale,
+                                now + at as u64,
+                                at.fract(),
+                                flow,
                                 node_name,
                                 seqname,
                                 sound_delegate,
                                 queued_sounds,
+                                flow_controls,
+                                rng,
                             );
                         }
                         SequenceElement::PlaySound {
                             sound,
                             channel,
+                            sends,
+                            pan,
+                            position,
                             fade_in,
                             length,
                             fade_out,
+                            lead,
+                            humanize_time,
+                            humanize_volume,
+                            repeat,
+                            condition,
                         } => {
-                            Engine::execute_sound(
-                                soundtrack,
-                                sample_rate,
-                                when,
-                                flow_name,
-                                node_name,
-                                sound,
-                                sound_delegate,
-                                queued_sounds,
-                                channel,
-                                *fade_in,
-                                *length,
-                                *fade_out,
-                            );
+                            let sound = match condition {
+                                None => sound,
+                                Some(SoundCondition {
+                                    condition,
+                                    else_sound,
+                                }) => {
+                                    if evaluate(flow_controls, rng, condition)
+                                        .is_truthy()
+                                    {
+                                        sound
+                                    } else if let Some(else_sound) = else_sound
+                                    {
+                                        else_sound
+                                    } else {
+                                        continue;
+                                    }
+                                }
+                            };
+                            let at = (at - f64::from(*(*lead * sample_rate)))
+                                .max(0.0);
+                            let mut when = now + at as u64;
+                            // (only consulting the RNG if we need it, so
+                            // that sequences without humanization don't
+                            // disturb `random()`)
+                            if *humanize_time != PosFloat::ZERO {
+                                let jitter = (rng.next_f32() * 2.0 - 1.0)
+                                    * **humanize_time
+                                    * *sample_rate;
+                                when = when.saturating_add_signed(
+                                    jitter.round() as i64,
+                                );
+                            }
+                            // (keeping the sound from starting any earlier
+                            // than the sequence did)
+                            let when = when.max(now);
+                            let volume = if *humanize_volume != PosFloat::ZERO
+                            {
+                                PosFloat::new_clamped(
+                                    1.0 - rng.next_f32() * **humanize_volume,
+                                )
+                            } else {
+                                PosFloat::ONE
+                            };
+                            if *repeat == 1 {
+                                Engine::execute_sound(
+                                    soundtrack,
+                                    sample_rate,
+                                    when,
+                                    flow,
+                                    node_name,
+                                    sound,
+                                    sound_delegate,
+                                    queued_sounds,
+                                    channel,
+                                    sends,
+                                    *pan,
+                                    *position,
+                                    *fade_in,
+                                    *length,
+                                    *fade_out,
+                                    FadeType::Linear,
+                                    volume,
+                                    Some((seqname, index)),
+                                    false,
+                                    None,
+                                );
+                                continue;
+                            }
+                            let repeated = soundtrack.sounds.get(sound);
+                            if length.is_none()
+                                && repeated
+                                    .is_some_and(|x| x.end.get().is_none())
+                            {
+                                // We don't know how long the sound is yet.
+                                // Play it once, and queue the rest once we
+                                // do. (See `resolve_awaited_ends`.) Without
+                                // knowing when the first time ends, there's
+                                // no crossfading them together.
+                                let (tx, finished) = query::make();
+                                Engine::execute_sound(
+                                    soundtrack,
+                                    sample_rate,
+                                    when,
+                                    flow,
+                                    node_name,
+                                    sound,
+                                    sound_delegate,
+                                    queued_sounds,
+                                    channel,
+                                    sends,
+                                    *pan,
+                                    *position,
+                                    *fade_in,
+                                    None,
+                                    PosFloat::ZERO,
+                                    FadeType::Linear,
+                                    volume,
+                                    Some((seqname, index)),
+                                    false,
+                                    Some(FinishSignal::new(tx)),
+                                );
+                                let mut passes = SoundQueue::default();
+                                for pass in 1..*repeat {
+                                    let last = pass + 1 == *repeat;
+                                    Engine::execute_sound(
+                                        soundtrack,
+                                        sample_rate,
+                                        0,
+                                        flow,
+                                        node_name,
+                                        sound,
+                                        sound_delegate,
+                                        &mut passes,
+                                        channel,
+                                        sends,
+                                        *pan,
+                                        *position,
+                                        PosFloat::ZERO,
+                                        None,
+                                        if last {
+                                            *fade_out
+                                        } else {
+                                            PosFloat::ZERO
+                                        },
+                                        FadeType::Linear,
+                                        volume,
+                                        Some((seqname, index)),
+                                        true,
+                                        None,
+                                    );
+                                }
+                                queued_sounds.awaited_repeats.push(
+                                    AwaitedRepeat {
+                                        first: when,
+                                        finished,
+                                        passes: std::iter::from_fn(|| {
+                                            passes.pop()
+                                        })
+                                        .collect(),
+                                    },
+                                );
+                                continue;
+                            }
+                            // Each time is as long as `length` and `fade_out`
+                            // put together, or as the whole sound.
+                            let pass_length = length
+                                .map(|x| x + *fade_out)
+                                .or_else(|| {
+                                    let sound = repeated?;
+                                    Some(
+                                        sound
+                                            .get_end(&**sound_delegate)
+                                            .saturating_sub(sound.start)
+                                            / sound.pitch,
+                                    )
+                                })
+                                .unwrap_or(PosFloat::ZERO);
+                            // Each time after the first starts this early,
+                            // and crossfades with the end of the one before.
+                            let crossfade = repeated
+                                .map(|x| x.loop_crossfade)
+                                .unwrap_or(PosFloat::ZERO)
+                                .min(pass_length);
+                            let (step, fade_type) =
+                                if crossfade > PosFloat::ZERO {
+                                    (
+                                        pass_length.saturating_sub(crossfade),
+                                        FadeType::EqualPower,
+                                    )
+                                } else {
+                                    (pass_length, FadeType::Linear)
+                                };
+                            for pass in 0..*repeat {
+                                let last = pass + 1 == *repeat;
+                                // (in fractional frames, so that the
+                                // repetitions don't drift)
+                                let offset = (at.fract()
+                                    + f64::from(*step)
+                                        * f64::from(pass)
+                                        * f64::from(*sample_rate))
+                                .floor()
+                                    as u64;
+                                Engine::execute_sound(
+                                    soundtrack,
+                                    sample_rate,
+                                    when + offset,
+                                    flow,
+                                    node_name,
+                                    sound,
+                                    sound_delegate,
+                                    queued_sounds,
+                                    channel,
+                                    sends,
+                                    *pan,
+                                    *position,
+                                    if pass == 0 {
+                                        *fade_in
+                                    } else {
+                                        crossfade
+                                    },
+                                    if last { *length } else { Some(step) },
+                                    if last { *fade_out } else { crossfade },
+                                    fade_type,
+                                    volume,
+                                    Some((seqname, index)),
+                                    pass > 0,
+                                    None,
+                                );
+                            }
                         }
                     }
                 }
@@ -1545,44 +4510,72 @@ impl Engine {
         soundtrack: &Soundtrack,
         sample_rate: PosFloat,
         when: u64,
-        flow_name: &str,
+        flow: &FlowKey,
         node_name: Option<&str>,
         sound_name: &str,
         sound_delegate: &mut Arc<dyn SoundDelegate>,
-        queued_sounds: &mut BinaryHeap<QueuedSound>,
+        queued_sounds: &mut SoundQueue,
         channel: &str,
+        sends: &[(CompactString, PosFloat)],
+        pan: f32,
+        position: Option<[f32; 3]>,
         fade_in: PosFloat,
         length: Option<PosFloat>,
         fade_out: PosFloat,
+        fade_type: FadeType,
+        volume: PosFloat,
+        element: Option<(&str, usize)>,
+        loop_wrap: bool,
+        finish_signal: Option<FinishSignal>,
     ) -> u64 {
         let sound = match soundtrack.sounds.get(sound_name) {
             Some(x) => x.clone(),
             None => {
-                sound_delegate.warning(&format!(
-                    "can't play missing sound: {:?}",
-                    sound_name
-                ));
+                sound_delegate.diagnostic(
+                    DiagLevel::Warning,
+                    DiagCode::MissingSound,
+                    &format!("can't play missing sound: {:?}", sound_name),
+                );
                 return 0;
             }
         };
         let ret = length
-            .or_else(|| sound.end.get().map(|x| x.saturating_sub(sound.start)))
+            .or_else(|| {
+                sound
+                    .end
+                    .get()
+                    .map(|x| x.saturating_sub(sound.start) / sound.pitch)
+            })
             .map(|x| x.seconds_to_frames(sample_rate))
             .unwrap_or(u64::MAX);
         queued_sounds.push(QueuedSound {
             when,
+            seq: 0, // (assigned by `push`)
             who: PlayingSoundID {
-                flow_and_node_name: StringAndAHalf(
-                    flow_name.to_compact_string(),
+                flow_and_node_name: NodeKey(
+                    flow.clone(),
                     node_name.map(|x| x.to_compact_string()),
                 ),
                 channel: channel.to_compact_string(),
+                sends: sends.to_vec(),
+                pan,
+                position,
+                volume: volume * sound.gain,
                 sound: sound.name.clone(),
+                element: element.map(|(sequence_name, index)| {
+                    (sequence_name.to_compact_string(), index)
+                }),
+                audition: false,
+                one_shot: false,
+                finish_signal,
             },
             sound,
             fade_in,
             length,
             fade_out,
+            fade_type,
+            pitch: PosFloat::ONE,
+            loop_wrap,
         });
         ret
     }
@@ -1590,8 +4583,18 @@ impl Engine {
 
 impl VolumeGetter<PlayingSoundID> for VolumeGetWrapper<'_, '_> {
     fn step_faders_by(&mut self, n: PosFloat) {
-        for (flow_name, fader) in self.flow_volumes.iter_mut() {
-            if !self.starting_flows.contains(flow_name) {
+        for (flow, fader) in self.flow_volumes.iter_mut() {
+            if !self.starting_flows.contains(flow) {
+                fader.step_by(n);
+            }
+        }
+        for (flow_and_node_name, fader) in self.node_volumes.iter_mut() {
+            if !self.starting_flows.contains(&flow_and_node_name.0) {
+                fader.step_by(n);
+            }
+        }
+        for ((flow, _, _), fader) in self.element_volumes.iter_mut() {
+            if !self.starting_flows.contains(flow) {
                 fader.step_by(n);
             }
         }
@@ -1604,50 +4607,240 @@ impl VolumeGetter<PlayingSoundID> for VolumeGetWrapper<'_, '_> {
         id: &PlayingSoundID,
         t: PosFloat,
     ) -> Option<PosFloat> {
+        if id.audition {
+            return Some(id.volume);
+        }
         // the seen_* fields will be updated by `is_silent`
-        let flow_fader = match self.flow_volumes.get_mut(id.flow_name()) {
-            None => return None,
-            Some(x) => x,
-        };
-        let flow_volume = flow_fader.evaluate_t(t);
-        if flow_volume == PosFloat::ZERO
-            && self.flows_fading_out.contains(id.flow_name())
+        let flow_volume = self.get_flow_volume(id, t)?;
+        // The sound's contribution to each channel it goes to, all summed.
+        let channel_volume = |channel: &str| self.mix_control_volume(channel);
+        let mut volume = channel_volume(&id.channel);
+        for (send_channel, level) in id.sends.iter() {
+            volume = volume + *level * channel_volume(send_channel);
+        }
+        Some(flow_volume * volume)
+    }
+    fn get_routes(
+        &mut self,
+        id: &PlayingSoundID,
+        t: PosFloat,
+        routes: &mut Vec<Route>,
+    ) -> bool {
+        if id.audition {
+            // (straight to the main output, with no effects, and not to any
+            // taps)
+            routes.push((0, id.volume));
+            return true;
+        }
+        if self.buses.is_empty()
+            && self.routed_outputs.is_empty()
+            && self.taps.is_empty()
         {
-            return None;
+            // (the common case)
+            match self.get_volume(id, t) {
+                None => return false,
+                Some(volume) => {
+                    routes.push((0, volume));
+                    return true;
+                }
+            }
+        }
+        let Some(flow_volume) = self.get_flow_volume(id, t) else {
+            return false;
+        };
+        let sends = std::iter::once((&id.channel, PosFloat::ONE))
+            .chain(id.sends.iter().map(|(channel, level)| (channel, *level)));
+        let channel_volume = |channel: &str| self.mix_control_volume(channel);
+        for (channel, level) in sends.clone() {
+            let bus = match self
+                .routed_outputs
+                .iter()
+                .position(|x| x.channel == channel)
+            {
+                Some(n) => self.buses.len() + 1 + n,
+                None => self
+                    .buses
+                    .iter()
+                    .position(|x| x.channel == channel)
+                    .map(|x| x + 1)
+                    .unwrap_or(0),
+            };
+            routes.push((bus, flow_volume * level * channel_volume(channel)));
+        }
+        // Taps don't get effects, so each one only needs one route.
+        for (n, tap) in self.taps.iter().enumerate() {
+            let mut volume = PosFloat::ZERO;
+            for (channel, level) in sends.clone() {
+                volume = volume
+                    + level
+                        * channel_volume(channel)
+                        * tap.channel_gain(channel);
+            }
+            routes.push((
+                self.buses.len() + self.routed_outputs.len() + 1 + n,
+                flow_volume * volume,
+            ));
+        }
+        true
+    }
+    fn num_buses(&mut self) -> usize {
+        self.buses.len() + self.routed_outputs.len() + self.taps.len() + 1
+    }
+    fn num_outputs(&mut self) -> usize {
+        self.routed_outputs.len()
+    }
+    fn num_taps(&mut self) -> usize {
+        self.taps.len()
+    }
+    fn get_channel_gains(
+        &mut self,
+        id: &PlayingSoundID,
+        gains: &mut [f32],
+    ) -> bool {
+        if let Some(position) = id.position {
+            positional_gains(
+                self.speaker_layout,
+                self.listener,
+                position,
+                gains,
+            );
+            return true;
+        }
+        if id.pan == 0.0 {
+            return false;
+        }
+        match self.speaker_layout {
+            SpeakerLayout::Stereo | SpeakerLayout::Headphones => {
+                gains.copy_from_slice(&pan_gains(id.pan));
+                true
+            }
+            // Panning doesn't apply to other speaker layouts (yet).
+            _ => false,
+        }
+    }
+    fn process_bus(&mut self, bus: usize, buf: &mut [f32]) {
+        let bus = match bus.checked_sub(self.buses.len() + 1) {
+            None => &mut self.buses[bus - 1],
+            // (a routed output, which gets its MixControl's effect, etc., if
+            // it has any)
+            Some(n) => {
+                let channel = &self.routed_outputs[n].channel;
+                match self.buses.iter_mut().find(|x| x.channel == *channel) {
+                    Some(bus) => bus,
+                    None => return,
+                }
+            }
+        };
+        if let Some(effect) = bus.effect.as_mut() {
+            effect.process(buf);
+        }
+        if let Some(filter) = bus.filter.as_mut() {
+            filter.process(buf);
+        }
+        if let Some(width) = bus.width.as_mut() {
+            width.process(buf);
         }
-        let channel_fader = self.mix_controls.get_mut(&id.channel);
-        let channel_volume = channel_fader
-            .as_ref()
-            .map(|x| x.evaluate())
-            .unwrap_or(PosFloat::ZERO);
-        Some(flow_volume * channel_volume)
     }
     fn is_varying(&mut self, id: &PlayingSoundID) -> Option<bool> {
+        if id.one_shot || id.audition {
+            // (its volume only changes with its MixControl's, if it has one,
+            // which isn't our concern here)
+            return Some(false);
+        }
+        let element_varying = self
+            .element_fader(id)
+            .map(|x| !x.complete())
+            .unwrap_or(false);
         // stop if the flow has stopped
-        let flow_fader = self.flow_volumes.get_mut(id.flow_name())?;
+        let flow_fader = self.flow_volumes.get_mut(id.flow())?;
         // DO NOT stop if the channel is silenced, UNLESS it's also fading
         if flow_fader.complete()
             && flow_fader.evaluate() == PosFloat::ONE
-            && self.flows_fading_out.contains(id.flow_name())
+            && self.flows_fading_out.contains(id.flow())
         {
             return None;
         }
-        if !self.seen_flows.contains(id.flow_name()) {
-            self.seen_flows.insert(id.flow_name().to_compact_string());
+        if !self.seen_flows.contains(id.flow()) {
+            self.seen_flows.insert(id.flow().clone());
         }
+        let node_varying = self
+            .node_volumes
+            .get(&id.flow_and_node_name)
+            .map(|x| !x.complete())
+            .unwrap_or(false);
         // TODO: "fader quality" setting
-        Some(!flow_fader.complete())
+        Some(!flow_fader.complete() || node_varying || element_varying)
     }
 }
 
-impl EngineCommandIssuer for Engine {
-    // Since this is the Engine, we can handle the commands immediately instead
-    // of shoving them in a channel or whatnot.
-    //
-    // Commands sent to us via our channel will end up being processed here.
-    fn issue(&mut self, command: EngineCommand) {
-        use EngineCommand::*;
-        match command {
+impl VolumeGetWrapper<'_, '_> {
+    /// Returns the current volume of the given MixControl, or zero if it's
+    /// been muted, or some other MixControl has been soloed.
+    fn mix_control_volume(&self, channel: &str) -> PosFloat {
+        if self.muted_mix_controls.contains(channel)
+            || (!self.soloed_mix_controls.is_empty()
+                && !self.soloed_mix_controls.contains(channel))
+        {
+            return PosFloat::ZERO;
+        }
+        self.mix_controls
+            .get(channel)
+            .map(|x| x.evaluate())
+            .unwrap_or(PosFloat::ZERO)
+    }
+    /// Returns the volume of the given sound's flow, and node, at the given
+    /// time, times the sound's own volume. Returns `None` if the flow has
+    /// stopped or faded out. One-shots have no flow or node, so they only
+    /// have their own volume.
+    fn get_flow_volume(
+        &mut self,
+        id: &PlayingSoundID,
+        t: PosFloat,
+    ) -> Option<PosFloat> {
+        if id.one_shot {
+            return Some(id.volume);
+        }
+        let flow_fader = self.flow_volumes.get_mut(id.flow())?;
+        let flow_volume = flow_fader.evaluate_t(t);
+        if flow_volume == PosFloat::ZERO
+            && self.flows_fading_out.contains(id.flow())
+        {
+            return None;
+        }
+        let mut flow_volume = flow_volume * id.volume;
+        if let Some(node_fader) = self.node_volumes.get(&id.flow_and_node_name)
+        {
+            flow_volume = flow_volume * node_fader.evaluate_t(t);
+        }
+        if let Some(element_fader) = self.element_fader(id) {
+            flow_volume = flow_volume * element_fader.evaluate_t(t);
+        }
+        Some(flow_volume)
+    }
+    /// Returns the volume of the sequence element that played the given
+    /// sound, if it has one.
+    fn element_fader(&self, id: &PlayingSoundID) -> Option<&Fader> {
+        // (the common case, saving us from building a key)
+        if self.element_volumes.is_empty() {
+            return None;
+        }
+        let (sequence_name, index) = id.element.as_ref()?;
+        self.element_volumes.get(&(
+            id.flow().clone(),
+            sequence_name.clone(),
+            *index,
+        ))
+    }
+}
+
+impl EngineCommandIssuer for Engine {
+    // Since this is the Engine, we can handle the commands immediately instead
+    // of shoving them in a channel or whatnot.
+    //
+    // Commands sent to us via our channel will end up being processed here.
+    fn issue(&mut self, command: EngineCommand) {
+        use EngineCommand::*;
+        match command {
             Transaction { commands } => {
                 for command in commands.into_iter() {
                     self.issue(command)
@@ -1656,14 +4849,35 @@ impl EngineCommandIssuer for Engine {
             ReplaceSoundtrack { new_soundtrack } => {
                 self.replace_soundtrack(new_soundtrack);
             }
+            CrossfadeSoundtrack {
+                new_soundtrack,
+                fade_type,
+                fade_length,
+            } => {
+                self.crossfade_soundtrack(
+                    new_soundtrack,
+                    fade_type,
+                    fade_length,
+                );
+            }
+            ReconcileSoundtrack { new_soundtrack } => {
+                self.reconcile_soundtrack(new_soundtrack);
+            }
             Precache { flow_name } => {
-                match self.flow_loads.get_mut(&flow_name) {
+                match self
+                    .flow_loads
+                    .get_mut(&FlowKey::live(flow_name.clone()))
+                {
                     Some(load_status) => {
                         if load_status.precaching {
-                            self.sound_delegate.warning(&format!(
+                            self.sound_delegate.diagnostic(
+                                DiagLevel::Warning,
+                                DiagCode::RedundantPrecache,
+                                &format!(
                                 "attempt to precache flow {:?} more than once",
                                 flow_name
-                            ));
+                            ),
+                            );
                         } else {
                             load_status.precaching = true;
                             load_status.maybe_load(
@@ -1673,26 +4887,27 @@ impl EngineCommandIssuer for Engine {
                         }
                     }
                     None => {
-                        self.sound_delegate.warning(&format!("attempt to precache flow {:?}, which does not exist", flow_name));
+                        self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::MissingFlow, &format!("attempt to precache flow {:?}, which does not exist", flow_name));
                     }
                 }
             }
             Unprecache { flow_name } => {
-                match self.flow_loads.get_mut(&flow_name) {
-                    None => self.sound_delegate.warning(&format!("attempt to unprecache flow {:?}, which does not exist", flow_name)),
+                match self.flow_loads.get_mut(&FlowKey::live(flow_name.clone()))
+                {
+                    None => self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::MissingFlow, &format!("attempt to unprecache flow {:?}, which does not exist", flow_name)),
                     Some(load_status) => {
                         if load_status.precaching {
                             load_status.precaching = false;
                             load_status.maybe_unload(&self.live_soundtrack, self.soundman.as_mut());
                         }
                         else {
-                            self.sound_delegate.warning(&format!("attempt to unprecache flow {:?} that wasn't currently precached", flow_name));
+                            self.sound_delegate.diagnostic(DiagLevel::Warning, DiagCode::UnbalancedUnprecache, &format!("attempt to unprecache flow {:?} that wasn't currently precached", flow_name));
                         }
                     },
                 }
             }
             UnprecacheAll {} => {
-                for load_status in self.flow_loads.values_mut() {
+                for (_, load_status) in self.flow_loads.iter_mut() {
                     if load_status.precaching {
                         load_status.precaching = false;
                         load_status.maybe_unload(
@@ -1702,21 +4917,112 @@ impl EngineCommandIssuer for Engine {
                     }
                 }
             }
+            CacheSound { sound_name } => {
+                match self.live_soundtrack.sounds.get(&sound_name) {
+                    Some(sound) => {
+                        let sound = sound.clone();
+                        match self
+                            .cached_sounds
+                            .insert(sound_name, sound.clone())
+                        {
+                            // (already cached, just as it is now)
+                            Some(old)
+                                if old.path == sound.path
+                                    && old.start == sound.start => {}
+                            old => {
+                                if let Some(old) = old {
+                                    self.soundman.uncache_sound(&old);
+                                }
+                                self.soundman.cache_sound(&sound);
+                            }
+                        }
+                    }
+                    None => self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingSound,
+                        &format!(
+                        "attempt to cache sound {:?}, which does not exist",
+                        sound_name
+                    ),
+                    ),
+                }
+            }
+            UncacheSound { sound_name } => {
+                match self.cached_sounds.remove(&sound_name) {
+                    Some(sound) => self.soundman.uncache_sound(&sound),
+                    None if self
+                        .live_soundtrack
+                        .sounds
+                        .contains_key(&sound_name) => {}
+                    None => self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingSound,
+                        &format!(
+                        "attempt to uncache sound {:?}, which does not exist",
+                        sound_name
+                    ),
+                    ),
+                }
+            }
             SetFlowControl {
                 control_name,
                 new_value,
             } => {
-                self.flow_controls.insert(control_name, new_value);
+                if self.flow_controls.get(&control_name) != Some(&new_value) {
+                    self.sound_delegate
+                        .flow_control_changed(&control_name, &new_value);
+                    self.flow_controls.insert(control_name, new_value);
+                    self.flow_controls_changed = true;
+                }
             }
             ClearFlowControl { control_name } => {
                 self.flow_controls.remove(&control_name);
+                self.flow_controls_changed = true;
             }
             ClearPrefixedFlowControls { control_prefix } => {
                 self.flow_controls
                     .retain(|k, _| !k.starts_with(&control_prefix[..]));
+                self.flow_controls_changed = true;
             }
             ClearAllFlowControls {} => {
                 self.flow_controls.clear();
+                self.flow_controls_changed = true;
+            }
+            SeedRandom { seed } => {
+                self.rng = Rng::new(seed);
+            }
+            SetTimeScale { factor } => {
+                self.time_scale = factor.max(MIN_TIME_SCALE);
+            }
+            ApplyPreset { preset_name } => {
+                match self.live_soundtrack.presets.get(&preset_name) {
+                    Some(preset) => {
+                        let values: Vec<_> = preset
+                            .sets
+                            .iter()
+                            .map(|(control_name, ops)| {
+                                (
+                                    control_name.clone(),
+                                    evaluate(
+                                        &self.flow_controls,
+                                        &mut self.rng,
+                                        ops,
+                                    ),
+                                )
+                            })
+                            .collect();
+                        self.flow_controls.extend(values);
+                        self.flow_controls_changed = true;
+                    }
+                    None => self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingPreset,
+                        &format!(
+                        "attempt to apply preset {:?}, which does not exist",
+                        preset_name
+                    ),
+                    ),
+                }
             }
             FadeMixControlTo {
                 control_name,
@@ -1731,6 +5037,9 @@ impl EngineCommandIssuer for Engine {
                     .get(&control_name)
                     .map(Fader::evaluate)
                     .unwrap_or(PosFloat::ZERO);
+                let target_volume = self
+                    .live_soundtrack
+                    .clamp_channel_volume(&control_name, target_volume);
                 self.mix_controls.insert(
                     control_name,
                     Fader::start(
@@ -1754,13 +5063,52 @@ impl EngineCommandIssuer for Engine {
                         *fader = Fader::start(
                             fade_type,
                             fader.evaluate(),
-                            target_volume,
+                            self.live_soundtrack.clamp_channel_volume(
+                                control_name,
+                                target_volume,
+                            ),
                             fade_length
                                 .seconds_to_frac_frames(self.sample_rate),
                         );
                     }
                 }
             }
+            FadeGroupTo {
+                group_name,
+                fade_type,
+                target_volume,
+                fade_length,
+            } => {
+                let Some(members) =
+                    self.live_soundtrack.channel_groups.get(&group_name)
+                else {
+                    self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingGroup,
+                        &format!("can't fade missing group: {:?}", group_name),
+                    );
+                    return;
+                };
+                for control_name in members.clone() {
+                    if !self.mix_controls.contains_key(&control_name) {
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::MissingMixControl,
+                            &format!(
+                                "group {:?} includes {:?}, which doesn't \
+                                 exist yet",
+                                group_name, control_name
+                            ),
+                        );
+                    }
+                    self.issue(FadeMixControlTo {
+                        control_name,
+                        fade_type,
+                        target_volume,
+                        fade_length,
+                    });
+                }
+            }
             FadeAllMixControlsTo {
                 fade_type,
                 target_volume,
@@ -1772,7 +5120,8 @@ impl EngineCommandIssuer for Engine {
                     *fader = Fader::start(
                         fade_type,
                         fader.evaluate(),
-                        target_volume,
+                        self.live_soundtrack
+                            .clamp_channel_volume(control_name, target_volume),
                         fade_length * self.sample_rate,
                     );
                 }
@@ -1784,12 +5133,15 @@ impl EngineCommandIssuer for Engine {
             } => {
                 self.perform_deferred_kill();
                 for (control_name, fader) in self.mix_controls.iter_mut() {
-                    if control_name != &DEFAULT_CHANNEL {
+                    if control_name != &self.default_channel {
                         self.mix_controls_fading_out.remove(control_name);
                         *fader = Fader::start(
                             fade_type,
                             fader.evaluate(),
-                            target_volume,
+                            self.live_soundtrack.clamp_channel_volume(
+                                control_name,
+                                target_volume,
+                            ),
                             fade_length * self.sample_rate,
                         )
                     }
@@ -1853,7 +5205,7 @@ impl EngineCommandIssuer for Engine {
             } => {
                 self.perform_deferred_kill();
                 for (control_name, fader) in self.mix_controls.iter_mut() {
-                    if control_name != &DEFAULT_CHANNEL {
+                    if control_name != &self.default_channel {
                         *fader = Fader::start(
                             fade_type,
                             fader.evaluate(),
@@ -1894,7 +5246,7 @@ impl EngineCommandIssuer for Engine {
             }
             KillAllMixControlsExceptMain {} => {
                 self.mix_controls.retain(|control_name, _| {
-                    if control_name == &DEFAULT_CHANNEL {
+                    if control_name == &self.default_channel {
                         true
                     } else {
                         self.mix_controls_fading_out
@@ -1904,28 +5256,202 @@ impl EngineCommandIssuer for Engine {
                     }
                 });
             }
+            SetMixControlVoiceLimit {
+                control_name,
+                max_voices,
+                stealing,
+            } => {
+                self.voice_limits
+                    .insert(control_name, (max_voices, stealing));
+            }
+            ClearMixControlVoiceLimit { control_name } => {
+                self.voice_limits.remove(&control_name);
+            }
+            SoloMixControl { control_name } => {
+                self.soloed_mix_controls.insert(control_name);
+            }
+            ClearSolo {} => {
+                self.soloed_mix_controls.clear();
+            }
+            SetMixControlMuted {
+                control_name,
+                muted,
+            } => {
+                if muted {
+                    self.muted_mix_controls.insert(control_name);
+                } else {
+                    self.muted_mix_controls.remove(&control_name);
+                }
+            }
+            SetChannelEffect {
+                control_name,
+                effect,
+            } => {
+                let effect = effect.map(|effect| {
+                    EffectState::new(
+                        &effect,
+                        self.speaker_layout.get_num_channels(),
+                        self.sample_rate,
+                    )
+                });
+                self.get_bus(control_name).effect = effect;
+                self.remove_unused_buses();
+            }
+            SetListener { listener } => {
+                self.listener = listener;
+            }
+            FadeChannelCutoffTo {
+                control_name,
+                cutoff_hz,
+                fade_length,
+                fade_type,
+            } => {
+                let num_channels = self.speaker_layout.get_num_channels();
+                let sample_rate = self.sample_rate;
+                let filter =
+                    self.get_bus(control_name).filter.get_or_insert_with(
+                        || LowPassFilter::new(num_channels, sample_rate),
+                    );
+                filter.fade_cutoff_to(
+                    fade_type,
+                    cutoff_hz,
+                    fade_length * sample_rate,
+                );
+                self.remove_unused_buses();
+            }
+            FadeChannelWidthTo {
+                control_name,
+                width,
+                fade_length,
+                fade_type,
+            } => {
+                let num_channels = self.speaker_layout.get_num_channels();
+                if num_channels < 2 {
+                    // (there's no width to speak of)
+                    return;
+                }
+                let sample_rate = self.sample_rate;
+                self.get_bus(control_name)
+                    .width
+                    .get_or_insert_with(|| StereoWidth::new(num_channels))
+                    .fade_width_to(
+                        fade_type,
+                        width,
+                        fade_length * sample_rate,
+                    );
+                self.remove_unused_buses();
+            }
+            FadePitchTo {
+                target,
+                fade_type,
+                rate,
+                fade_length,
+            } => {
+                let bend = self.pitch_bends.entry(target).or_default();
+                bend.fader = Fader::start(
+                    fade_type,
+                    bend.fader.evaluate(),
+                    rate,
+                    fade_length * self.sample_rate,
+                );
+            }
+            PlayOneShot {
+                sound_name,
+                channel,
+                volume,
+                pitch,
+                tx,
+            } => {
+                let Some(sound) =
+                    self.live_soundtrack.sounds.get(&sound_name).cloned()
+                else {
+                    self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingSound,
+                        &format!("can't play missing sound: {:?}", sound_name),
+                    );
+                    tx.respond(false);
+                    return;
+                };
+                self.queued_sounds.push(QueuedSound {
+                    when: self.mixer.get_next_output_sample_frame_number(),
+                    seq: 0, // (assigned by `push`)
+                    who: PlayingSoundID {
+                        flow_and_node_name: NodeKey(FlowKey::live(""), None),
+                        channel,
+                        sends: vec![],
+                        pan: 0.0,
+                        position: None,
+                        volume: volume * sound.gain,
+                        sound: sound_name,
+                        element: None,
+                        audition: false,
+                        one_shot: true,
+                        finish_signal: Some(FinishSignal::new(tx)),
+                    },
+                    sound,
+                    fade_in: PosFloat::ZERO,
+                    length: None,
+                    fade_out: PosFloat::ZERO,
+                    fade_type: FadeType::Linear,
+                    pitch,
+                    loop_wrap: false,
+                });
+            }
+            StopOneShots { channel } => {
+                let matches = |id: &PlayingSoundID| {
+                    id.one_shot
+                        && (channel.is_none()
+                            || channel.as_ref() == Some(&id.channel))
+                };
+                self.mixer.stop_all(matches);
+                self.queued_sounds.retain(|x| !matches(&x.who));
+            }
             StartFlow {
                 flow_name,
+                node_name,
+                offset,
                 fade_type,
                 target_volume,
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                let load_status = match self.flow_loads.get_mut(&flow_name) {
+                let flow = FlowKey::live(flow_name.clone());
+                let load_status = match self.flow_loads.get_mut(&flow) {
                     Some(x) => x,
                     None => {
-                        self.sound_delegate.warning(&format!(
-                            "attempt to start non-existent flow {:?}",
-                            flow_name
-                        ));
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::MissingFlow,
+                            &format!(
+                                "attempt to start non-existent flow {:?}",
+                                flow_name
+                            ),
+                        );
                         return;
                     }
                 };
-                if let Some(x) = self.flow_volumes.get(&flow_name) {
+                if let Some(node_name) = node_name.as_ref() {
+                    let definition =
+                        self.live_soundtrack.flows.get(&flow_name).unwrap();
+                    if !definition.nodes.contains_key(node_name) {
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::MissingNode,
+                            &format!(
+                                "attempt to start flow {:?} at non-existent node {:?}",
+                                flow_name, node_name
+                            ),
+                        );
+                        return;
+                    }
+                }
+                self.scheduled_fade_outs.remove(&flow);
+                if let Some(x) = self.flow_volumes.get(&flow) {
                     let old_volume = x.evaluate();
-                    self.flows_fading_out.remove(&flow_name);
+                    self.flows_fading_out.remove(&flow);
                     self.flow_volumes.insert(
-                        flow_name,
+                        flow,
                         Fader::start(
                             fade_type,
                             old_volume,
@@ -1940,9 +5466,17 @@ impl EngineCommandIssuer for Engine {
                         self.soundman.as_mut(),
                     );
                     // we will check if it's loaded the next time the handle turns
-                    self.starting_flows.insert(flow_name.clone());
+                    load_status.waiting_since = None;
+                    load_status.timed_out = false;
+                    self.starting_flows.insert(flow.clone());
+                    if node_name.is_some() || offset > PosFloat::ZERO {
+                        self.flow_start_points
+                            .insert(flow_name.clone(), (node_name, offset));
+                    } else {
+                        self.flow_start_points.remove(&flow_name);
+                    }
                     self.flow_volumes.insert(
-                        flow_name,
+                        flow,
                         Fader::start(
                             fade_type,
                             PosFloat::ZERO,
@@ -1950,7 +5484,166 @@ impl EngineCommandIssuer for Engine {
                             fade_length * self.sample_rate,
                         ),
                     );
+                    self.apply_layer_intensity(
+                        &flow_name,
+                        FadeType::Linear,
+                        PosFloat::ZERO,
+                    );
+                }
+            }
+            SetLayerIntensity {
+                flow_name,
+                intensity,
+                fade_type,
+                fade_length,
+            } => {
+                self.perform_deferred_kill();
+                if !self.live_soundtrack.flows.contains_key(&flow_name) {
+                    self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::MissingFlow,
+                        &format!(
+                            "attempt to set layer intensity of non-existent \
+                             flow {:?}",
+                            flow_name
+                        ),
+                    );
+                    return;
+                }
+                self.layer_intensities.insert(flow_name.clone(), intensity);
+                self.apply_layer_intensity(
+                    &flow_name,
+                    fade_type,
+                    fade_length * self.sample_rate,
+                );
+            }
+            SetFlowSuccessor {
+                flow_name,
+                successor,
+                fade_type,
+                fade_length,
+            } => {
+                let Some(successor) = successor else {
+                    self.flow_successors.remove(&flow_name);
+                    return;
+                };
+                // Follow the chain from the new successor. If it leads back
+                // here, the flows would start each other forever.
+                let mut next = Some(&successor);
+                while let Some(name) = next {
+                    if *name == flow_name {
+                        self.sound_delegate.diagnostic(
+                            DiagLevel::Warning,
+                            DiagCode::SuccessorLoop,
+                            &format!(
+                                "refusing to make flow {:?} the successor of \
+                                 flow {:?}, since that would make a loop (use \
+                                 `autoloop` instead)",
+                                successor, flow_name
+                            ),
+                        );
+                        return;
+                    }
+                    next =
+                        self.flow_successors.get(name).map(|x| &x.flow_name);
+                }
+                self.flow_successors.insert(
+                    flow_name,
+                    FlowSuccessor {
+                        flow_name: successor,
+                        fade_type,
+                        fade_length,
+                    },
+                );
+            }
+            FadeNodeTo {
+                flow_name,
+                node_name,
+                fade_type,
+                target_volume,
+                fade_length,
+            } => {
+                self.perform_deferred_kill();
+                let flow = FlowKey::live(flow_name.clone());
+                if !self
+                    .active_flow_nodes
+                    .iter()
+                    .any(|x| x.flow == flow && x.node.name == node_name)
+                {
+                    self.sound_delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::NodeNotPlaying,
+                        &format!(
+                            "attempt to fade node {:?} of flow {:?}, which \
+                             isn't playing",
+                            node_name.as_deref().unwrap_or("(start)"),
+                            flow_name
+                        ),
+                    );
+                }
+                let key = NodeKey(flow, node_name);
+                let old_volume = self
+                    .node_volumes
+                    .get(&key)
+                    .map(Fader::evaluate)
+                    .unwrap_or(PosFloat::ONE);
+                self.node_volumes.insert(
+                    key,
+                    Fader::start(
+                        fade_type,
+                        old_volume,
+                        target_volume,
+                        fade_length * self.sample_rate,
+                    ),
+                );
+            }
+            FadeSequenceElementTo {
+                flow_name,
+                sequence_name,
+                element,
+                fade_type,
+                target_volume,
+                fade_length,
+            } => {
+                self.perform_deferred_kill();
+                if !self.check_sequence_element(&sequence_name, element) {
+                    return;
+                }
+                let key = (FlowKey::live(flow_name), sequence_name, element);
+                let old_volume = self
+                    .element_volumes
+                    .get(&key)
+                    .map(Fader::evaluate)
+                    .unwrap_or(PosFloat::ONE);
+                self.element_volumes.insert(
+                    key,
+                    Fader::start(
+                        fade_type,
+                        old_volume,
+                        target_volume,
+                        fade_length * self.sample_rate,
+                    ),
+                );
+            }
+            StopSequenceElement {
+                flow_name,
+                sequence_name,
+                element,
+            } => {
+                self.perform_deferred_kill();
+                if !self.check_sequence_element(&sequence_name, element) {
+                    return;
                 }
+                let flow = FlowKey::live(flow_name);
+                let matches = |id: &PlayingSoundID| {
+                    !id.one_shot
+                        && *id.flow() == flow
+                        && id.element.as_ref().is_some_and(|(name, index)| {
+                            *name == sequence_name && *index == element
+                        })
+                };
+                self.mixer.stop_all(matches);
+                self.queued_sounds.retain(|x| !matches(&x.who));
             }
             FadeFlowTo {
                 flow_name,
@@ -1959,14 +5652,16 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                self.flows_fading_out.remove(&flow_name);
+                let flow = FlowKey::live(flow_name);
+                self.flows_fading_out.remove(&flow);
+                self.scheduled_fade_outs.remove(&flow);
                 let old_volume = self
                     .flow_volumes
-                    .get(&flow_name)
+                    .get(&flow)
                     .map(Fader::evaluate)
                     .unwrap_or(PosFloat::ZERO);
                 self.flow_volumes.insert(
-                    flow_name,
+                    flow,
                     Fader::start(
                         fade_type,
                         old_volume,
@@ -1982,9 +5677,13 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                for (flow_name, fader) in self.flow_volumes.iter_mut() {
-                    if flow_name.starts_with(&flow_prefix[..]) {
-                        self.flows_fading_out.remove(flow_name);
+                for (flow, fader) in self.flow_volumes.iter_mut() {
+                    // (outgoing flows must not be brought back)
+                    if flow.name.starts_with(&flow_prefix[..])
+                        && !flow.is_outgoing()
+                    {
+                        self.flows_fading_out.remove(flow);
+                        self.scheduled_fade_outs.remove(flow);
                         *fader = Fader::start(
                             fade_type,
                             fader.evaluate(),
@@ -2000,8 +5699,12 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                for (flow_name, fader) in self.flow_volumes.iter_mut() {
-                    self.flows_fading_out.remove(flow_name);
+                for (flow, fader) in self.flow_volumes.iter_mut() {
+                    if flow.is_outgoing() {
+                        continue;
+                    }
+                    self.flows_fading_out.remove(flow);
+                    self.scheduled_fade_outs.remove(flow);
                     *fader = Fader::start(
                         fade_type,
                         fader.evaluate(),
@@ -2016,7 +5719,8 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                if let Some(fader) = self.flow_volumes.get_mut(&flow_name) {
+                let flow = FlowKey::live(flow_name);
+                if let Some(fader) = self.flow_volumes.get_mut(&flow) {
                     let old_volume = fader.evaluate();
                     *fader = Fader::start(
                         fade_type,
@@ -2024,8 +5728,39 @@ impl EngineCommandIssuer for Engine {
                         PosFloat::ZERO,
                         fade_length * self.sample_rate,
                     );
-                    self.flows_fading_out.insert(flow_name);
+                    self.scheduled_fade_outs.remove(&flow);
+                    self.flows_fading_out.insert(flow);
+                }
+            }
+            FadeFlowToThenOut {
+                flow_name,
+                fade_type,
+                target_volume,
+                fade_length,
+                hold,
+                fade_out,
+            } => {
+                self.perform_deferred_kill();
+                let flow = FlowKey::live(flow_name.clone());
+                if !self.flow_volumes.contains_key(&flow) {
+                    return;
                 }
+                let when = self.mixer.get_next_output_sample_frame_number()
+                    + (fade_length + hold).seconds_to_frames(self.sample_rate);
+                self.issue(FadeFlowTo {
+                    flow_name: flow_name.clone(),
+                    fade_type,
+                    target_volume,
+                    fade_length,
+                });
+                self.scheduled_fade_outs.insert(
+                    flow,
+                    ScheduledFadeOut {
+                        when,
+                        fade_type,
+                        fade_length: fade_out,
+                    },
+                );
             }
             FadePrefixedFlowsOut {
                 flow_prefix,
@@ -2033,16 +5768,16 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                for (flow_name, fader) in self.flow_volumes.iter_mut() {
-                    if flow_name.starts_with(&flow_prefix[..]) {
+                for (flow, fader) in self.flow_volumes.iter_mut() {
+                    if flow.name.starts_with(&flow_prefix[..]) {
                         *fader = Fader::start(
                             fade_type,
                             fader.evaluate(),
                             PosFloat::ZERO,
                             fade_length * self.sample_rate,
                         );
-                        self.flows_fading_out
-                            .insert(flow_name.to_compact_string());
+                        self.scheduled_fade_outs.remove(flow);
+                        self.flows_fading_out.insert(flow.clone());
                     }
                 }
             }
@@ -2051,37 +5786,35 @@ impl EngineCommandIssuer for Engine {
                 fade_length,
             } => {
                 self.perform_deferred_kill();
-                for (flow_name, fader) in self.flow_volumes.iter_mut() {
+                for (flow, fader) in self.flow_volumes.iter_mut() {
                     *fader = Fader::start(
                         fade_type,
                         fader.evaluate(),
                         PosFloat::ZERO,
                         fade_length * self.sample_rate,
                     );
-                    self.flows_fading_out
-                        .insert(flow_name.to_compact_string());
+                    self.flows_fading_out.insert(flow.clone());
                 }
+                self.scheduled_fade_outs.clear();
             }
             KillFlow { flow_name } => {
-                if self.starting_flows.remove(&flow_name) {
-                    debug_assert!(self.flow_volumes.contains_key(&flow_name));
+                let flow = FlowKey::live(flow_name);
+                if self.starting_flows.remove(&flow) {
+                    debug_assert!(self.flow_volumes.contains_key(&flow));
                 }
-                if self.flow_volumes.contains_key(&flow_name) {
-                    self.flow_volumes.remove(&flow_name);
-                    self.flows_fading_out.insert(flow_name);
+                if self.flow_volumes.remove(&flow).is_some() {
+                    self.flows_fading_out.insert(flow);
                     self.deferred_kill = true;
                 }
             }
             KillPrefixedFlows { flow_prefix } => {
-                self.starting_flows.retain(|flow_name| {
-                    !flow_name.starts_with(&flow_prefix[..])
-                });
-                self.flow_volumes.retain(|flow_name, _| {
-                    if !flow_name.starts_with(&flow_prefix[..]) {
+                self.starting_flows
+                    .retain(|flow| !flow.name.starts_with(&flow_prefix[..]));
+                self.flow_volumes.retain(|flow, _| {
+                    if !flow.name.starts_with(&flow_prefix[..]) {
                         true
                     } else {
-                        self.flows_fading_out
-                            .insert(flow_name.to_compact_string());
+                        self.flows_fading_out.insert(flow.clone());
                         self.deferred_kill = true;
                         false
                     }
@@ -2089,18 +5822,17 @@ impl EngineCommandIssuer for Engine {
             }
             KillAllFlows {} => {
                 self.starting_flows.clear();
-                self.flow_volumes.retain(|flow_name, _| {
-                    self.flows_fading_out
-                        .insert(flow_name.to_compact_string());
+                self.flow_volumes.retain(|flow, _| {
+                    self.flows_fading_out.insert(flow.clone());
                     self.deferred_kill = true;
                     false
                 });
             }
             IsFlowActive { flow_name, tx } => {
-                tx.respond(matches!(self.flow_loads.get(&flow_name), Some(x) if x.active_loading));
+                tx.respond(matches!(self.flow_loads.get(&FlowKey::live(flow_name)), Some(x) if x.active_loading));
             }
             IsFlowReady { flow_name, tx } => {
-                tx.respond(matches!(self.flow_loads.get(&flow_name), Some(x) if x.known_all_ready));
+                tx.respond(matches!(self.flow_loads.get(&FlowKey::live(flow_name)), Some(x) if x.known_all_ready));
             }
             GetFlowControl { control_name, tx } => {
                 tx.respond(self.flow_controls.get(&control_name).cloned());
@@ -2109,7 +5841,7 @@ impl EngineCommandIssuer for Engine {
                 let active_set: HashSet<CompactString> = self
                     .active_flow_nodes
                     .iter()
-                    .map(|x| x.flow_name.clone())
+                    .map(|x| x.flow.name.clone())
                     .collect();
                 tx.respond(active_set.into_iter().collect());
             }
@@ -2118,7 +5850,7 @@ impl EngineCommandIssuer for Engine {
                     .active_flow_nodes
                     .iter()
                     .map(|x| ActiveNodeReport {
-                        flow: x.flow_name.clone(),
+                        flow: x.flow.name.clone(),
                         node: x.node.name.clone(),
                     })
                     .collect();
@@ -2129,9 +5861,18 @@ impl EngineCommandIssuer for Engine {
                     .mixer
                     .report_volumes(VolumeGetWrapper {
                         mix_controls: &mut self.mix_controls,
+                        soloed_mix_controls: &self.soloed_mix_controls,
+                        muted_mix_controls: &self.muted_mix_controls,
+                        buses: &mut self.buses,
+                        speaker_layout: self.speaker_layout,
+                        listener: &self.listener,
                         flow_volumes: &mut self.flow_volumes,
+                        node_volumes: &mut self.node_volumes,
+                        element_volumes: &mut self.element_volumes,
                         flows_fading_out: &self.flows_fading_out,
                         starting_flows: &self.starting_flows,
+                        routed_outputs: &self.routed_outputs,
+                        taps: &self.taps,
                         seen_flows: &mut HashSet::new(),
                     })
                     .map(|(x, y)| MixFlowReport {
@@ -2144,6 +5885,12 @@ impl EngineCommandIssuer for Engine {
                     .collect();
                 tx.respond(report);
             }
+            GetStats { tx } => {
+                tx.respond(self.stats());
+            }
+            GetFlowLoadProgress { flow_name, tx } => {
+                tx.respond(self.flow_load_progress(&flow_name));
+            }
             GetMixControl { control_name, tx } => {
                 tx.respond(
                     self.mix_controls
@@ -2187,6 +5934,12 @@ struct FlowLoadStatus {
     load_requested: bool,
     /// List of all Sounds that we knew this Flow requires.
     known_sounds: Vec<Arc<Sound>>,
+    /// The sample frame at which this Flow started waiting to be ready to
+    /// start, if it is waiting.
+    waiting_since: Option<u64>,
+    /// True if this Flow has waited longer than the load timeout, and we've
+    /// already said so.
+    timed_out: bool,
 }
 
 impl FlowLoadStatus {
@@ -2227,8 +5980,10 @@ impl FlowLoadStatus {
         if self.load_requested || !self.should_be_loaded() {
             return;
         }
+        // (a flow that's being played, and not just precached, jumps the
+        // queue)
         for sound in self.known_sounds.iter() {
-            soundman.load(sound);
+            soundman.load(sound, self.active_loading);
         }
         self.load_requested = true;
     }
@@ -2251,6 +6006,41 @@ pub struct ActiveNodeReport {
     pub node: Option<CompactString>,
 }
 
+/// A snapshot of what an `Engine` is doing, for profiling. See
+/// `Engine::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EngineStats {
+    /// How many sounds the mixer is currently playing.
+    pub active_voices: usize,
+    /// Roughly how many bytes of sample data are being kept in memory for
+    /// buffered (non-streamed) sounds. Sounds that have been let go of, but
+    /// are still being played, aren't counted.
+    pub cached_sound_bytes: usize,
+    /// How many different sound files are currently loaded for streaming.
+    pub streaming_sounds: usize,
+    /// How many flows are playing, including any old ones that are fading out
+    /// after a crossfade.
+    pub active_flows: usize,
+}
+
+/// How far along the loading of a flow's sounds is. See
+/// `Engine::flow_load_progress`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlowLoadProgress {
+    /// How many of the flow's sounds are ready to play.
+    pub sounds_ready: usize,
+    /// How many sounds the flow uses, in total.
+    pub total_sounds: usize,
+    /// Roughly how many seconds of audio still have to be decoded before the
+    /// flow is ready. This is an *estimate*, and doesn't include sounds of
+    /// unknown length.
+    pub seconds_remaining: f32,
+    /// How many of the sounds that aren't ready yet are of unknown length,
+    /// either because their loading hasn't started yet or because their
+    /// decoder can't tell.
+    pub sounds_of_unknown_length: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct MixFlowReport {
     /// The flow in question
@@ -2263,3 +6053,2958 @@ pub struct MixFlowReport {
     pub sound: CompactString,
     pub volume: PosFloat,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A mono Engine at 1000Hz, that loads everything in the foreground.
+    fn test_engine(delegate: Arc<dyn SoundDelegate>) -> Engine {
+        Engine::new_with_runtime(
+            delegate,
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        )
+    }
+
+    /// Never gets around to running any of its tasks.
+    #[derive(Default)]
+    struct SaturatedRuntime(
+        std::sync::Mutex<
+            Vec<
+                std::pin::Pin<
+                    Box<dyn std::future::Future<Output = ()> + Send>,
+                >,
+            >,
+        >,
+    );
+    impl TaskRuntime for SaturatedRuntime {
+        fn spawn_task(
+            &self,
+            _kind: TaskType,
+            task: impl std::future::Future<Output = ()> + Send + 'static,
+        ) {
+            self.0.lock().unwrap().push(Box::pin(task));
+        }
+    }
+
+    /// Full-scale DC, for as many samples as it's made with.
+    struct Dc(usize);
+    impl SoundReader<f32> for Dc {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            let len = buf.len().min(self.0);
+            buf[..len].fill(MaybeUninit::new(1.0));
+            self.0 -= len;
+            len
+        }
+    }
+
+    /// Every file is full-scale mono DC at 1000Hz. It lasts as many sample
+    /// frames as the number its name starts with (`300.wav` is 300 sample
+    /// frames long), or one second if its name doesn't start with a number.
+    struct DcDelegate;
+    impl SoundDelegate for DcDelegate {
+        fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+            let digits = name
+                .find(|x: char| !x.is_ascii_digit())
+                .unwrap_or(name.len());
+            Some(FormattedSoundStream {
+                sample_rate: PosFloat::new_clamped(1000.0),
+                speaker_layout: SpeakerLayout::Mono,
+                reader: FormattedSoundReader::F32(Box::new(Dc(name
+                    [..digits]
+                    .parse()
+                    .unwrap_or(1000)))),
+            })
+        }
+    }
+
+    #[test]
+    fn unopenable_buffered_sounds_are_silent() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound missing\n  file missing.wav\nflow test\n  \
+                 play sound missing\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        // (the placeholder that stands in for the sound is played, so it had
+        // better be silence)
+        let mut out = [1.0; 200];
+        engine.turn_handle(&mut out);
+        assert!(out.iter().all(|x| *x == 0.0), "{out:?}");
+    }
+
+    #[test]
+    fn simultaneous_sounds_keep_their_order() {
+        let soundtrack =
+            Soundtrack::from_source("sound test\n  file test.wav\n").unwrap();
+        let mut delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        let mut queued_sounds = SoundQueue::default();
+        // (interleaved with sounds at other times, to give the heap some
+        // shuffling to do)
+        let mut expected = vec![];
+        for n in 0..8 {
+            for (when, channel) in
+                [(100, format!("late{n}")), (50, format!("{n}"))]
+            {
+                Engine::execute_sound(
+                    &soundtrack,
+                    PosFloat::new_clamped(1000.0),
+                    when,
+                    &FlowKey::live("flow"),
+                    None,
+                    "test",
+                    &mut delegate,
+                    &mut queued_sounds,
+                    &channel,
+                    &[],
+                    0.0,
+                    None,
+                    PosFloat::ZERO,
+                    None,
+                    PosFloat::ZERO,
+                    FadeType::Linear,
+                    PosFloat::ONE,
+                    None,
+                    false,
+                    None,
+                );
+            }
+            expected.push(format!("{n}"));
+        }
+        expected.extend((0..8).map(|n| format!("late{n}")));
+        let mut order = vec![];
+        while let Some(queued_sound) = queued_sounds.pop() {
+            order.push(queued_sound.who.channel);
+        }
+        assert_eq!(order, expected);
+    }
+
+    #[test]
+    fn lead_starts_sounds_early() {
+        let soundtrack = Soundtrack::from_source(
+            r#"sequence test
+  length 2
+  play sound foo
+    at 1
+    lead 0.25
+  play sound bar
+    at 0
+    lead 0.5
+sound foo
+  file foo.wav
+sound bar
+  file bar.wav
+"#,
+        )
+        .unwrap();
+        let mut delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        let mut queued_sounds = SoundQueue::default();
+        Engine::execute_sequence(
+            &soundtrack,
+            PosFloat::new_clamped(1000.0),
+            PosFloat::ONE,
+            100,
+            0.0,
+            &FlowKey::live("flow"),
+            None,
+            "test",
+            &mut delegate,
+            &mut queued_sounds,
+            &HashMap::new(),
+            &mut Rng::new(0),
+        );
+        let mut whens = vec![];
+        while let Some(queued_sound) = queued_sounds.pop() {
+            whens.push((queued_sound.when, queued_sound.who.sound));
+        }
+        // (`bar` would have started before the sequence did)
+        assert_eq!(whens, [(100, "bar".into()), (850, "foo".into())]);
+    }
+    #[test]
+    fn repeated_sounds_crossfade() {
+        let soundtrack = Soundtrack::from_source(
+            r#"sequence test
+  length 2
+  play sound foo
+    repeat 3
+sound foo
+  file foo.wav
+  length 0.5
+  loop_crossfade 0.1
+"#,
+        )
+        .unwrap();
+        let mut delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        let mut queued_sounds = SoundQueue::default();
+        Engine::execute_sequence(
+            &soundtrack,
+            PosFloat::new_clamped(1000.0),
+            PosFloat::ONE,
+            0,
+            0.0,
+            &FlowKey::live("flow"),
+            None,
+            "test",
+            &mut delegate,
+            &mut queued_sounds,
+            &HashMap::new(),
+            &mut Rng::new(0),
+        );
+        let mut plays = vec![];
+        while let Some(queued_sound) = queued_sounds.pop() {
+            assert_eq!(queued_sound.fade_type, FadeType::EqualPower);
+            plays.push((
+                queued_sound.when,
+                *queued_sound.fade_in,
+                queued_sound.length.map(|x| *x),
+                *queued_sound.fade_out,
+            ));
+        }
+        assert_eq!(
+            plays,
+            [
+                (0, 0.0, Some(0.4), 0.1),
+                (400, 0.1, Some(0.4), 0.1),
+                (800, 0.1, None, 0.0)
+            ]
+        );
+    }
+    #[test]
+    fn repeated_sounds_play_back_to_back() {
+        let soundtrack = Soundtrack::from_source(
+            r#"sequence test
+  length 2
+  play sound foo
+    repeat 3
+    for 0.5
+    fade_out 0.25
+sound foo
+  file foo.wav
+"#,
+        )
+        .unwrap();
+        let mut delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        let mut queued_sounds = SoundQueue::default();
+        Engine::execute_sequence(
+            &soundtrack,
+            PosFloat::new_clamped(1000.0),
+            PosFloat::ONE,
+            100,
+            0.0,
+            &FlowKey::live("flow"),
+            None,
+            "test",
+            &mut delegate,
+            &mut queued_sounds,
+            &HashMap::new(),
+            &mut Rng::new(0),
+        );
+        let mut plays = vec![];
+        while let Some(queued_sound) = queued_sounds.pop() {
+            plays.push((
+                queued_sound.when,
+                queued_sound.length.map(|x| *x),
+                *queued_sound.fade_out,
+            ));
+        }
+        assert_eq!(
+            plays,
+            [
+                (100, Some(0.5), 0.0),
+                (600, Some(0.5), 0.0),
+                (1100, Some(0.25), 0.25)
+            ]
+        );
+    }
+
+    #[test]
+    fn repeated_sounds_find_out_unknown_lengths() {
+        let delegate =
+            ReportingDelegate::new(|name| DcDelegate.open_file(name));
+        let mut engine = test_engine(delegate.clone());
+        // (`s` has no `end`, and is streamed, so its length isn't known until
+        // it has played once)
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"sound s
+  file 300.wav
+  stream
+sequence riff
+  length 2
+  play sound s
+    repeat 3
+flow test
+  play sequence riff
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        for _ in 0..30 {
+            engine.step_frames(50);
+        }
+        let whens: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        let [first, second, third] = whens[..] else {
+            panic!("expected three sounds, got {whens:?}");
+        };
+        // The second time may start up to one mix late...
+        assert!((first + 300..=first + 350).contains(&second));
+        // ...but after that, the length is known.
+        assert_eq!(third, second + 300);
+        assert!(!delegate.codes().contains(&DiagCode::UnknownLength));
+    }
+
+    #[test]
+    fn conditional_sounds_follow_flow_controls() {
+        let soundtrack = Soundtrack::from_source(
+            r#"sequence test
+  length 1
+  play sound if $mood == "happy" then foo else bar
+  play sound if $loud then baz
+sound foo
+  file foo.wav
+sound bar
+  file bar.wav
+sound baz
+  file baz.wav
+"#,
+        )
+        .unwrap();
+        let mut delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        let mut played = |flow_controls| {
+            let mut queued_sounds = SoundQueue::default();
+            Engine::execute_sequence(
+                &soundtrack,
+                PosFloat::new_clamped(1000.0),
+                PosFloat::ONE,
+                0,
+                0.0,
+                &FlowKey::live("flow"),
+                None,
+                "test",
+                &mut delegate,
+                &mut queued_sounds,
+                &flow_controls,
+                &mut Rng::new(0),
+            );
+            let mut sounds = vec![];
+            while let Some(queued_sound) = queued_sounds.pop() {
+                sounds.push(queued_sound.who.sound);
+            }
+            sounds.sort();
+            sounds
+        };
+        assert_eq!(played(HashMap::new()), ["bar"]);
+        assert_eq!(
+            played(HashMap::from([
+                ("mood".into(), StringOrNumber::String("happy".into())),
+                ("loud".into(), StringOrNumber::Number(1.0)),
+            ])),
+            ["baz", "foo"]
+        );
+    }
+
+    #[test]
+    fn drain_commands_applies_commands_immediately() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source("flow test\n  wait 10\n").unwrap(),
+        );
+        let mut commander = engine.clone_commander();
+        commander.set_flow_control("x".into(), StringOrNumber::Number(3.0));
+        commander.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        assert_eq!(engine.copy_flow_control("x"), None);
+        engine.drain_commands();
+        assert_eq!(
+            engine.copy_flow_control("x"),
+            Some(StringOrNumber::Number(3.0))
+        );
+        assert_eq!(engine.current_frame(), 0);
+        engine.step_frames(1);
+        assert_eq!(engine.stats().active_flows, 1);
+        commander.kill_all_flows();
+        engine.drain_commands();
+        assert_eq!(engine.stats().active_flows, 0);
+        assert_eq!(engine.current_frame(), 1);
+    }
+    #[test]
+    fn stats_count_flows_and_voices() {
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file 10000.wav
+flow one
+  play sound a
+  start node other
+  wait 10
+  node other
+    play sound a
+    wait 10
+flow two
+  play sound a
+  wait 10
+"#,
+            )
+            .unwrap(),
+        );
+        for flow in ["one", "two"] {
+            engine.start_flow(
+                flow.into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        engine.step_frames(1);
+        // (flow one has two nodes playing, but it's still one flow)
+        let stats = engine.stats();
+        assert_eq!((stats.active_flows, stats.active_voices), (2, 3));
+        assert_eq!(stats.cached_sound_bytes, 40000);
+        engine.kill_flow("one".into());
+        engine.step_frames(1);
+        let stats = engine.stats();
+        assert_eq!((stats.active_flows, stats.active_voices), (1, 1));
+        engine.kill_all_flows();
+        engine.step_frames(1);
+        assert_eq!(engine.stats(), EngineStats::default());
+    }
+    #[test]
+    fn voice_limits_steal_or_drop() {
+        for (stealing, survivors, code) in [
+            (
+                VoiceStealing::StealOldest,
+                ["b", "d"],
+                DiagCode::VoiceStolen,
+            ),
+            (
+                VoiceStealing::DropNewest,
+                ["a", "b"],
+                DiagCode::VoiceDropped,
+            ),
+        ] {
+            let delegate =
+                ReportingDelegate::new(|name| DcDelegate.open_file(name));
+            let mut engine = test_engine(delegate.clone());
+            engine.replace_soundtrack(
+                Soundtrack::from_source(
+                    r#"
+sound a
+  file 10000a.wav
+sound b
+  file 10000b.wav
+sound c
+  file 10000c.wav
+sound d
+  file 10000d.wav
+flow test
+  play sound a
+  wait 0.1
+  play sound b
+  wait 0.4
+  play sound d
+  wait 10
+flow late
+  play sound c
+  wait 10
+"#,
+                )
+                .unwrap(),
+            );
+            engine.set_mix_control_voice_limit(
+                "main".into(),
+                NonZeroUsize::new(2).unwrap(),
+                stealing,
+            );
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            let playing = |engine: &mut Engine| {
+                let mut sounds: Vec<String> = engine
+                    .mixer
+                    .identities_mut()
+                    .map(|x| x.sound.to_string())
+                    .collect();
+                sounds.sort();
+                sounds
+            };
+            engine.step_frames(150);
+            assert_eq!(playing(&mut engine), ["a", "b"]);
+            // (a sound that isn't ready to play doesn't take anyone's place)
+            engine.replace_runtime(Arc::new(SaturatedRuntime::default()));
+            engine.set_load_timeout(Some(PosFloat::new_clamped(0.05)), true);
+            engine.start_flow(
+                "late".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            // (flows are only checked on when the handle turns)
+            for _ in 0..3 {
+                engine.step_frames(100);
+            }
+            assert_eq!(delegate.codes(), [DiagCode::LoadTimedOut]);
+            assert_eq!(engine.take_started_sounds().len(), 3);
+            assert_eq!(playing(&mut engine), ["a", "b"]);
+            engine.step_frames(100);
+            assert_eq!(playing(&mut engine), survivors);
+            assert_eq!(delegate.messages(code).len(), 1);
+        }
+    }
+
+    #[test]
+    fn testing_engine_reports_started_sounds() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Stereo,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file a.wav
+sound b
+  file b.wav
+flow test
+  play sound a
+  wait 0.25
+  start node other
+  node other
+    play sound b
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(1);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 1);
+        assert_eq!((started[0].when, &started[0].sound[..]), (0, "a"));
+        assert_eq!(engine.inspect_active_nodes().len(), 1);
+        engine.step_frames(249);
+        assert!(engine.take_started_sounds().is_empty());
+        engine.step_frames(1);
+        assert_eq!(engine.current_frame(), 251);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].when, 250);
+        assert_eq!(started[0].node.as_deref(), Some("other"));
+        assert!(engine.inspect_queued_sounds().is_empty());
+    }
+    #[test]
+    fn flows_can_start_partway_through() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file a.wav
+sound b
+  file b.wav
+sound c
+  file c.wav
+flow test
+  play sound c
+  node other
+    play sound a
+    wait 0.5
+    play sound b
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow_at(
+            "test".into(),
+            Some("other".into()),
+            PosFloat::new_clamped(0.2),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(1000);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 1);
+        assert_eq!((started[0].when, &started[0].sound[..]), (300, "b"));
+    }
+
+    #[test]
+    fn looped_sequences_dont_drift() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        // (1001.5 sample frames long)
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file a.wav
+sequence s
+  length 1.0015
+  play sound a
+flow test with loop
+  play sequence s and wait
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(1001500);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 1000);
+        for (n, sound) in started.iter().enumerate() {
+            assert_eq!(sound.when, n as u64 * 10015 / 10);
+        }
+    }
+    #[test]
+    fn sequence_elements_stay_in_phase() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1024.0),
+        );
+        // (1.5 sample frames long, with b half a sample frame in)
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file a.wav
+sound b
+  file b.wav
+sequence s
+  length 0.00146484375
+  play sound a
+  play sound b
+    at 0.00048828125
+flow test with loop
+  play sequence s and wait
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(300);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 400);
+        for (n, pair) in started.chunks(2).enumerate() {
+            assert_eq!(pair[0].sound, "a");
+            assert_eq!(pair[0].when, n as u64 * 3 / 2);
+            assert_eq!(pair[1].sound, "b");
+            assert_eq!(pair[1].when, (n as u64 * 3).div_ceil(2));
+        }
+    }
+
+    #[test]
+    fn time_scale_stretches_timing() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file a.wav
+sequence s
+  length 0.01
+  play sound a
+flow test with loop
+  play sequence s and wait
+  wait 0.01
+"#,
+            )
+            .unwrap(),
+        );
+        engine.set_time_scale(PosFloat::HALF);
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(200);
+        let when: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        assert_eq!(when, [0, 40, 80, 120, 160]);
+        // (the wait that's already underway isn't stretched any further)
+        engine.set_time_scale(PosFloat::ONE);
+        engine.step_frames(100);
+        let when: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        assert_eq!(when, [200, 220, 240, 260, 280]);
+    }
+
+    #[test]
+    fn node_fades_use_the_chosen_curve() {
+        for (fade, fade_type) in [
+            ("fade starting node over 1 scurve", FadeType::SCurve),
+            ("fade starting node over 1", FadeType::Exponential),
+        ] {
+            let mut engine = test_engine(Arc::new(DcDelegate));
+            engine.replace_soundtrack(
+                Soundtrack::from_source(&format!(
+                    "sound dc\n  file dc.wav\nflow test\n  play sound dc\n  \
+                     {fade}\n"
+                ))
+                .unwrap(),
+            );
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            let mut out = [0.0; 500];
+            engine.turn_handle(&mut out);
+            let fader = Fader::start(
+                fade_type,
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                PosFloat::new_clamped(1000.0),
+            );
+            for t in [100, 250, 400] {
+                let expected = *fader.evaluate_t(PosFloat::from(t));
+                assert!(
+                    (out[t] - expected).abs() < 0.01,
+                    "{fade_type:?} at {t}: {} != {expected}",
+                    out[t]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flows_fade_hold_and_fade_out() {
+        for interrupted in [false, true] {
+            let mut engine = test_engine(Arc::new(DcDelegate));
+            engine.replace_soundtrack(
+                Soundtrack::from_source(
+                    "sound dc\n  file 10000.wav\nflow test\n  \
+                     play sound dc\n",
+                )
+                .unwrap(),
+            );
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            engine.step_frames(10);
+            engine.fade_flow_to_then_out(
+                "test".into(),
+                PosFloat::HALF,
+                PosFloat::new_clamped(0.1),
+                PosFloat::new_clamped(0.2),
+                PosFloat::new_clamped(0.1),
+                FadeType::Linear,
+            );
+            let mut out = [0.0; 500];
+            engine.turn_handle(&mut out[..200]);
+            assert!((out[50] - 0.75).abs() < 0.01, "{}", out[50]);
+            assert!(out[100..200].iter().all(|x| *x == 0.5));
+            if interrupted {
+                // a later fade replaces the scheduled fade out
+                engine.fade_flow_to(
+                    "test".into(),
+                    PosFloat::HALF,
+                    PosFloat::ZERO,
+                    FadeType::Linear,
+                );
+            }
+            engine.turn_handle(&mut out[200..]);
+            if interrupted {
+                assert!(out[200..].iter().all(|x| *x == 0.5));
+                assert!(engine
+                    .flow_volumes
+                    .contains_key(&FlowKey::live("test")));
+            } else {
+                assert!(out[200..300].iter().all(|x| *x == 0.5));
+                assert!((out[350] - 0.25).abs() < 0.01, "{}", out[350]);
+                assert!(out[400..].iter().all(|x| *x == 0.0));
+                // (it's noticed as soon as the next mix)
+                engine.step_frames(1);
+                assert!(!engine
+                    .flow_volumes
+                    .contains_key(&FlowKey::live("test")));
+            }
+        }
+    }
+
+    /// Returns the ids of the given flow's outgoing flows.
+    fn outgoing_flows_of(engine: &Engine, flow_name: &str) -> Vec<u64> {
+        let mut ret: Vec<u64> = engine
+            .flow_volumes
+            .keys()
+            .filter(|x| x.name == flow_name)
+            .filter_map(|x| x.outgoing)
+            .collect();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn crossfades_keep_same_named_flows_apart() {
+        const OLD: &str =
+            "sound a\n  file 10000.wav\nflow music\n  play sound a\n";
+        const NEW: &str = "sound a\n  file 10000.wav\nsound b\n  \
+                           file 10000.wav\nflow music\n  play sound b\n";
+        for how in ["kill", "fade", "neither"] {
+            let mut engine = test_engine(Arc::new(DcDelegate));
+            engine.replace_soundtrack(Soundtrack::from_source(OLD).unwrap());
+            engine.start_flow(
+                "music".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            engine.step_frames(10);
+            engine.crossfade_soundtrack(
+                Soundtrack::from_source(NEW).unwrap(),
+                FadeType::Linear,
+                PosFloat::ONE,
+            );
+            let mut out = [0.0; 1500];
+            engine.turn_handle(&mut out[..500]);
+            // the outgoing flow and its replacement add up to full volume
+            assert!(out[..500].iter().all(|x| (x - 1.0).abs() < 0.01));
+            assert_eq!(outgoing_flows_of(&engine, "music").len(), 1);
+            assert!(engine.flow_volumes.contains_key(&FlowKey::live("music")));
+            // the replacement plays from the new soundtrack, and the outgoing
+            // flow from the old one
+            let mut sounds: Vec<String> = engine
+                .mixer
+                .identities_mut()
+                .map(|x| x.sound.to_string())
+                .collect();
+            sounds.sort();
+            assert_eq!(sounds, ["a", "b"]);
+            // commands that name the flow only affect the replacement
+            match how {
+                "kill" => engine.kill_flow("music".into()),
+                "fade" => engine.fade_flow_out(
+                    "music".into(),
+                    PosFloat::ZERO,
+                    FadeType::Linear,
+                ),
+                _ => (),
+            }
+            engine.turn_handle(&mut out[500..]);
+            if how == "neither" {
+                assert!(out[500..].iter().all(|x| (x - 1.0).abs() < 0.01));
+                assert!(engine
+                    .flow_volumes
+                    .contains_key(&FlowKey::live("music")));
+            } else {
+                assert!((out[500] - 0.5).abs() < 0.01, "{}", out[500]);
+                assert!((out[750] - 0.25).abs() < 0.01, "{}", out[750]);
+                assert!(out[1000..].iter().all(|x| *x == 0.0));
+            }
+            // (it takes one mix for the silent sounds to stop, and another for
+            // the flows they belonged to to be forgotten)
+            engine.step_frames(1);
+            engine.step_frames(1);
+            assert_eq!(
+                engine.flow_volumes.contains_key(&FlowKey::live("music")),
+                how == "neither"
+            );
+            assert!(outgoing_flows_of(&engine, "music").is_empty());
+        }
+    }
+
+    #[test]
+    fn outgoing_flows_cant_be_named() {
+        const SOURCE: &str =
+            "sound a\n  file 10000.wav\nflow music\n  play sound a\n";
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(Soundtrack::from_source(SOURCE).unwrap());
+        engine.start_flow(
+            "music".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(10);
+        engine.crossfade_soundtrack(
+            Soundtrack::from_source(SOURCE).unwrap(),
+            FadeType::Linear,
+            PosFloat::ONE,
+        );
+        assert_eq!(outgoing_flows_of(&engine, "music"), [0]);
+        // however the outgoing flow is told apart from its replacement, no
+        // name given from outside can reach it
+        for name in ["music\u{0}0", "music\u{0}", "music0"] {
+            engine.kill_flow(name.into());
+            engine.fade_flow_out(
+                name.into(),
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        let mut out = [0.0; 500];
+        engine.turn_handle(&mut out);
+        assert!(out.iter().all(|x| (x - 1.0).abs() < 0.01));
+        assert_eq!(outgoing_flows_of(&engine, "music"), [0]);
+    }
+
+    #[test]
+    fn fades_dont_speed_up_with_more_sounds() {
+        const SOURCE: &str = "sound a\n  file 10000.wav\nsound b\n  \
+                              file 10000.wav\nsound c\n  file 10000.wav\n\
+                              flow chord\n  play sound a\n  play sound b\n  \
+                              play sound c\n";
+        for skip in [false, true] {
+            let mut engine = test_engine(Arc::new(DcDelegate));
+            engine
+                .replace_soundtrack(Soundtrack::from_source(SOURCE).unwrap());
+            engine.start_flow(
+                "chord".into(),
+                PosFloat::ONE,
+                PosFloat::ONE,
+                FadeType::Linear,
+            );
+            let mut out = [0.0; 1000];
+            if skip {
+                engine.turn_handle(&mut out[..1]);
+                engine.advance_silently(PosFloat::new_clamped(0.499));
+                engine.turn_handle(&mut out[500..]);
+            } else {
+                engine.turn_handle(&mut out);
+            }
+            // the flow's fader steps once per mix, not once per sound, so
+            // three sounds fade in over the same second as one would
+            assert!((out[500] - 1.5).abs() < 0.01, "{}", out[500]);
+            assert!((out[999] - 3.0).abs() < 0.01, "{}", out[999]);
+        }
+    }
+
+    #[test]
+    fn crossfades_can_interrupt_crossfades() {
+        const SOURCE: &str =
+            "sound a\n  file 10000.wav\nflow music\n  play sound a\n";
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(Soundtrack::from_source(SOURCE).unwrap());
+        engine.start_flow(
+            "music".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(10);
+        engine.crossfade_soundtrack(
+            Soundtrack::from_source(SOURCE).unwrap(),
+            FadeType::Linear,
+            PosFloat::ONE,
+        );
+        let mut out = [0.0; 1500];
+        engine.turn_handle(&mut out[..500]);
+        let first = outgoing_flows_of(&engine, "music");
+        assert_eq!(first.len(), 1);
+        engine.crossfade_soundtrack(
+            Soundtrack::from_source(SOURCE).unwrap(),
+            FadeType::Linear,
+            PosFloat::ONE,
+        );
+        // the first outgoing flow keeps fading out as it was, the half-faded
+        // in replacement becomes a second outgoing flow, and a new
+        // replacement fades in to where the old one was headed
+        let both = outgoing_flows_of(&engine, "music");
+        assert_eq!(both.len(), 2);
+        assert!(both.contains(&first[0]));
+        engine.turn_handle(&mut out[500..]);
+        assert!((out[500] - 1.0).abs() < 0.01, "{}", out[500]);
+        // 0.25 + 0.375 + 0.25
+        assert!((out[750] - 0.875).abs() < 0.01, "{}", out[750]);
+        assert!(out[1499] > 0.99, "{}", out[1499]);
+        engine.step_frames(1);
+        engine.step_frames(1);
+        assert!(outgoing_flows_of(&engine, "music").is_empty());
+        assert_eq!(
+            engine.flow_volumes[&FlowKey::live("music")].evaluate(),
+            PosFloat::ONE
+        );
+    }
+
+    #[test]
+    fn pitch_bends_ramp() {
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound dc\n  file 1000.wav\nflow test\n  play sound dc\n",
+            )
+            .unwrap(),
+        );
+        // the bends its sounds will follow are already there, and stay there
+        engine.step_frames(10);
+        let main = ChannelOrFlow::Channel("main".into());
+        let test = ChannelOrFlow::Flow("test".into());
+        assert_eq!(engine.pitch_bends.len(), 2);
+        assert!(engine.pitch_bends.contains_key(&main));
+        assert!(engine.pitch_bends.contains_key(&test));
+        engine.fade_pitch_to(
+            main,
+            PosFloat::new_clamped(2.0),
+            PosFloat::ONE,
+            FadeType::Linear,
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 1000];
+        for chunk in out.chunks_mut(10) {
+            engine.turn_handle(chunk);
+        }
+        assert_eq!(engine.pitch_bends.len(), 2);
+        // Speeding up from 1.0 to 2.0 over a second, a second's worth of
+        // sound lasts about 0.73 seconds. (The rate only changes between
+        // chunks, so it lags a little.)
+        let end = out.iter().position(|x| *x == 0.0).unwrap();
+        assert!((730..760).contains(&end), "{end}");
+    }
+
+    #[test]
+    fn sequence_elements_can_be_faded_and_stopped() {
+        let mut engine = test_engine(Arc::new(SyntheticSoundDelegate::new(
+            PosFloat::new_clamped(1000.0),
+        )));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound wind
+  file sine:250
+sound birds
+  file sine:250
+sequence ambience
+  length 1
+  play sound wind
+  play sound birds
+flow test
+  play sequence ambience
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 100];
+        engine.turn_handle(&mut out);
+        assert!((out[1] - 1.0).abs() < 0.001, "{}", out[1]);
+        // (out of range, so only a warning)
+        engine.fade_sequence_element_to(
+            "test".into(),
+            "ambience".into(),
+            2,
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.fade_sequence_element_to(
+            "test".into(),
+            "ambience".into(),
+            1,
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert!((out[1] - 0.5).abs() < 0.001, "{}", out[1]);
+        engine.stop_sequence_element("test".into(), "ambience".into(), 0);
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [0.0; 100]);
+        assert_eq!(engine.mixer.num_playing(), 1);
+    }
+
+    #[test]
+    fn reconciled_nodes_carry_on_where_they_can() {
+        let soundtrack = |flow: &str| {
+            let mut source = String::new();
+            for sound in ["a", "b", "c", "d", "e"] {
+                source += &format!("sound {sound}\n  file {sound}.wav\n");
+            }
+            Soundtrack::from_source(&(source + flow)).unwrap()
+        };
+        const OLD: &str = "flow test\n  play sound a\n  wait 0.1\n  \
+                           play sound b\n  wait 0.1\n  play sound c\n  \
+                           wait 10\n";
+        const LONGER: &str = "flow test\n  play sound a\n  wait 0.1\n  \
+                              play sound b\n  wait 0.1\n  play sound d\n  \
+                              wait 10\n";
+        const SHORTER: &str = "flow test\n  play sound e\n  wait 10\n";
+        let delegate = ReportingDelegate::new(|_| None);
+        let mut engine = test_engine(delegate.clone());
+        engine.replace_soundtrack(soundtrack(OLD));
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(50);
+        engine.reconcile_soundtrack(soundtrack(LONGER));
+        engine.step_frames(200);
+        let started: Vec<(u64, CompactString)> = engine
+            .take_started_sounds()
+            .into_iter()
+            .map(|x| (x.when, x.sound))
+            .collect();
+        assert_eq!(
+            started,
+            [(0, "a".into()), (100, "b".into()), (200, "d".into())]
+        );
+        assert!(!delegate.codes().contains(&DiagCode::NodeRewound));
+        // (the node has run past the end of this one, so it starts over, once
+        // it's done with the wait it was in the middle of)
+        engine.reconcile_soundtrack(soundtrack(SHORTER));
+        engine.step_frames(10000);
+        let started = engine.take_started_sounds();
+        assert_eq!(started.len(), 1);
+        assert_eq!((started[0].when, &started[0].sound[..]), (10200, "e"));
+        assert_eq!(
+            delegate
+                .codes()
+                .iter()
+                .filter(|x| **x == DiagCode::NodeRewound)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn nodes_can_be_faded() {
+        let delegate =
+            ReportingDelegate::new(|name| DcDelegate.open_file(name));
+        let mut engine = test_engine(delegate.clone());
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound a
+  file 10000.wav
+flow test
+  play sound a
+  start node other
+  wait 10
+  node other
+    play sound a
+    wait 10
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 100];
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [2.0; 100]);
+        engine.fade_node_to(
+            "test".into(),
+            Some("other".into()),
+            PosFloat::ZERO,
+            PosFloat::new_clamped(0.1),
+            FadeType::Linear,
+        );
+        engine.fade_node_to(
+            "test".into(),
+            None,
+            PosFloat::HALF,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert!((out[0] - 1.5).abs() < 0.02, "{}", out[0]);
+        assert!((out[50] - 1.0).abs() < 0.02, "{}", out[50]);
+        engine.turn_handle(&mut out);
+        assert!(out.iter().all(|x| (x - 0.5).abs() < 0.001), "{out:?}");
+        assert_eq!(delegate.codes(), []);
+        // (a node that isn't playing gets a warning)
+        engine.fade_node_to(
+            "test".into(),
+            Some("nowhere".into()),
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert_eq!(delegate.codes(), [DiagCode::NodeNotPlaying]);
+        assert!(out.iter().all(|x| (x - 0.5).abs() < 0.001), "{out:?}");
+    }
+
+    #[test]
+    fn repeated_sounds_report_loop_wraps() {
+        struct LoopDelegate {
+            synthetic: SyntheticSoundDelegate,
+            wraps: std::sync::Mutex<Vec<(String, Option<String>, String)>>,
+        }
+        impl SoundDelegate for LoopDelegate {
+            fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+                self.synthetic.open_file(name)
+            }
+            fn loop_wrapped(
+                &self,
+                flow: &str,
+                node: Option<&str>,
+                sound: &str,
+            ) {
+                self.wraps.lock().unwrap().push((
+                    flow.to_string(),
+                    node.map(str::to_string),
+                    sound.to_string(),
+                ));
+            }
+        }
+        let delegate = Arc::new(LoopDelegate {
+            synthetic: SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            )),
+            wraps: Default::default(),
+        });
+        let mut engine = test_engine(delegate.clone());
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"
+sound beat
+  file sine:250
+  length 0.25
+sequence phrase
+  length 1
+  play sound beat
+    repeat 3
+flow test
+  play sequence phrase
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        // (off by default)
+        engine.step_frames(300);
+        assert!(delegate.wraps.lock().unwrap().is_empty());
+        engine.set_loop_wrap_callbacks(true);
+        engine.step_frames(700);
+        assert_eq!(
+            *delegate.wraps.lock().unwrap(),
+            [("test".to_string(), None, "beat".to_string())]
+        );
+    }
+
+    #[test]
+    fn nodes_wait_for_conditions() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\nflow test\n  play sound a\n  \
+                 wait until $door_open\n  play sound a\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(100);
+        assert_eq!(engine.take_started_sounds().len(), 1);
+        engine
+            .set_flow_control("door_open".into(), StringOrNumber::Number(1.0));
+        engine.step_frames(100);
+        let when: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        assert_eq!(when, [100]);
+        // another node can open the door, too
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\nflow test\n  play sound a\n  \
+                 node opener\n    wait 0.05\n    set door_open to 1\n  \
+                 start node opener\n  wait until $door_open\n  \
+                 play sound a\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(100);
+        let when: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        assert_eq!(when, [0, 50]);
+    }
+
+    /// A mono test engine, already playing the soundtrack's `test` flow.
+    fn playing_engine(
+        delegate: Arc<dyn SoundDelegate>,
+        source: &str,
+    ) -> Engine {
+        let mut engine = test_engine(delegate);
+        engine.replace_soundtrack(Soundtrack::from_source(source).unwrap());
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine
+    }
+
+    /// A 10-frame pulse, played on `main` and sent to `echo`.
+    const SENT_PULSE: &str = "sound pulse\n  file 10.wav\n\
+                              sequence s\n  length 1\n  \
+                              play sound pulse\n    send echo 0.5\n\
+                              flow test\n  play sequence s\n";
+
+    #[test]
+    fn sends_without_effects_add_to_the_output() {
+        let mut engine = playing_engine(Arc::new(DcDelegate), SENT_PULSE);
+        engine.fade_mix_control_to(
+            "echo".into(),
+            PosFloat::HALF,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 20];
+        engine.turn_handle(&mut out);
+        // 1.0 dry, plus 0.5 sent to a MixControl at 0.5
+        assert!(out[..10].iter().all(|x| *x == 1.25));
+        assert!(out[10..].iter().all(|x| *x == 0.0));
+        assert!(engine.buses.is_empty());
+        // a send to a MixControl that isn't there goes nowhere
+        let mut engine = playing_engine(Arc::new(DcDelegate), SENT_PULSE);
+        engine.turn_handle(&mut out);
+        assert!(out[..10].iter().all(|x| *x == 1.0));
+    }
+
+    /// A 10-frame pulse, played on `main`.
+    const PULSE: &str = "sound pulse\n  file 10.wav\n\
+                         flow test\n  play sound pulse\n";
+
+    #[test]
+    fn delays_echo_on_time() {
+        let mut engine = playing_engine(Arc::new(DcDelegate), PULSE);
+        engine.set_mix_control_effect(
+            "main".into(),
+            ChannelEffect::Delay {
+                time: PosFloat::new_clamped(0.1),
+                feedback: PosFloat::HALF,
+                mix: PosFloat::HALF,
+            },
+        );
+        let mut out = [0.0; 300];
+        engine.turn_handle(&mut out);
+        for (n, sample) in out.iter().enumerate() {
+            let expected = match n {
+                0..=9 => 0.5,
+                100..=109 => 0.5,
+                200..=209 => 0.25,
+                _ => 0.0,
+            };
+            assert_eq!(*sample, expected, "wrong at sample frame {n}");
+        }
+    }
+
+    #[test]
+    fn reverb_tails_die_away() {
+        let mut engine = playing_engine(Arc::new(DcDelegate), PULSE);
+        engine.set_mix_control_effect(
+            "main".into(),
+            ChannelEffect::Reverb {
+                time: PosFloat::new_clamped(0.05),
+                feedback: PosFloat::new_clamped(0.7),
+                mix: PosFloat::HALF,
+            },
+        );
+        let mut out = [0.0; 1010];
+        engine.turn_handle(&mut out);
+        // the tail, after the pulse is over, in 200-frame windows
+        let peaks: Vec<f32> = out[10..]
+            .chunks(200)
+            .map(|window| window.iter().fold(0.0, |a, x| x.abs().max(a)))
+            .collect();
+        assert!(peaks[0] > 0.01, "no tail: {peaks:?}");
+        assert!(
+            peaks.windows(2).all(|x| x[1] < x[0]),
+            "tail isn't dying away: {peaks:?}"
+        );
+        assert!(peaks[4] < peaks[0] * 0.01, "tail too long: {peaks:?}");
+    }
+
+    #[test]
+    fn sends_go_through_their_effects() {
+        let mut engine = playing_engine(Arc::new(DcDelegate), SENT_PULSE);
+        engine.fade_mix_control_to(
+            "echo".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.set_mix_control_effect(
+            "echo".into(),
+            ChannelEffect::Delay {
+                time: PosFloat::new_clamped(0.1),
+                feedback: PosFloat::ZERO,
+                mix: PosFloat::ONE,
+            },
+        );
+        let mut out = [0.0; 200];
+        engine.turn_handle(&mut out);
+        assert_eq!(engine.buses.len(), 1);
+        // the dry sound on `main` right away, and only the send echoed
+        for (n, sample) in out.iter().enumerate() {
+            let expected = match n {
+                0..=9 => 1.0,
+                100..=109 => 0.5,
+                _ => 0.0,
+            };
+            assert_eq!(*sample, expected, "wrong at sample frame {n}");
+        }
+    }
+
+    #[test]
+    fn cleared_effects_stop_echoing() {
+        let mut engine = playing_engine(Arc::new(DcDelegate), PULSE);
+        engine.set_mix_control_effect(
+            "main".into(),
+            ChannelEffect::Delay {
+                time: PosFloat::new_clamped(0.1),
+                feedback: PosFloat::HALF,
+                mix: PosFloat::HALF,
+            },
+        );
+        let mut out = [0.0; 50];
+        engine.turn_handle(&mut out);
+        assert!(out[..10].iter().all(|x| *x == 0.5));
+        assert_eq!(engine.buses.len(), 1);
+        engine.clear_mix_control_effect("main".into());
+        let mut out = [0.0; 250];
+        engine.turn_handle(&mut out);
+        assert!(engine.buses.is_empty());
+        assert!(out.iter().all(|x| *x == 0.0));
+    }
+
+    /// A mono test engine playing a sine wave at the given frequency, with
+    /// a 20Hz low-pass on `main`.
+    fn low_passed_engine(tone_hz: u32) -> Engine {
+        let mut engine = playing_engine(
+            Arc::new(SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            ))),
+            &format!(
+                "sound tone\n  file sine:{tone_hz}\n\
+                 flow test\n  play sound tone\n"
+            ),
+        );
+        engine.fade_mix_control_cutoff_to(
+            "main".into(),
+            PosFloat::new_clamped(20.0),
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine
+    }
+
+    fn peak(buf: &[f32]) -> f32 {
+        buf.iter().fold(0.0, |a, x| x.abs().max(a))
+    }
+
+    #[test]
+    fn low_passes_cut_the_highs() {
+        let mut out = [0.0; 500];
+        let mut low = low_passed_engine(5);
+        let mut high = low_passed_engine(200);
+        // (let the filters settle first)
+        low.turn_handle(&mut out);
+        high.turn_handle(&mut out);
+        low.turn_handle(&mut out);
+        // the synthetic tones are at about half scale
+        assert!(peak(&out) > 0.4, "low tone was cut: {}", peak(&out));
+        high.turn_handle(&mut out);
+        assert!(peak(&out) < 0.01, "high tone wasn't cut: {}", peak(&out));
+    }
+
+    #[test]
+    fn low_passes_at_nyquist_are_bypassed() {
+        let mut engine = low_passed_engine(200);
+        let mut out = [0.0; 100];
+        engine.turn_handle(&mut out);
+        assert_eq!(engine.buses.len(), 1);
+        engine.fade_mix_control_cutoff_to(
+            "main".into(),
+            PosFloat::new_clamped(500.0),
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert!(engine.buses.is_empty());
+        // the same as never having had a filter
+        let mut unfiltered = playing_engine(
+            Arc::new(SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            ))),
+            "sound tone\n  file sine:200\nflow test\n  play sound tone\n",
+        );
+        let mut expected = [0.0; 200];
+        unfiltered.turn_handle(&mut expected);
+        assert_eq!(out, expected[100..]);
+    }
+
+    #[test]
+    fn low_passes_carry_on_between_turns() {
+        let mut whole = low_passed_engine(50);
+        let mut split = low_passed_engine(50);
+        let mut expected = [0.0; 1000];
+        whole.turn_handle(&mut expected);
+        let mut out = [0.0; 1000];
+        let (a, b) = out.split_at_mut(333);
+        split.turn_handle(a);
+        split.turn_handle(b);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn stereo_width_scales_the_side() {
+        /// Every file is a second of full-scale DC, on the left only.
+        struct LeftDelegate;
+        struct Left(usize);
+        impl SoundReader<f32> for Left {
+            fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+                let len = buf.len().min(self.0 * 2);
+                for frame in buf[..len].chunks_exact_mut(2) {
+                    frame[0] = MaybeUninit::new(1.0);
+                    frame[1] = MaybeUninit::new(0.0);
+                }
+                self.0 -= len / 2;
+                len
+            }
+        }
+        impl SoundDelegate for LeftDelegate {
+            fn open_file(&self, _name: &str) -> Option<FormattedSoundStream> {
+                Some(FormattedSoundStream {
+                    sample_rate: PosFloat::new_clamped(1000.0),
+                    speaker_layout: SpeakerLayout::Stereo,
+                    reader: FormattedSoundReader::F32(Box::new(Left(1000))),
+                })
+            }
+        }
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(LeftDelegate),
+            SpeakerLayout::Stereo,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound left\n  file left.wav\nflow test\n  \
+                 play sound left\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 16];
+        engine.turn_handle(&mut out);
+        assert_eq!(out[..2], [1.0, 0.0]);
+        engine.fade_mix_control_width_to(
+            "main".into(),
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert_eq!(out[..2], [0.5, 0.5]);
+        engine.fade_mix_control_width_to(
+            "main".into(),
+            PosFloat::new_clamped(2.0),
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert_eq!(out[..2], [1.5, -0.5]);
+        // back to normal, and out of the way
+        engine.fade_mix_control_width_to(
+            "main".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.turn_handle(&mut out);
+        assert_eq!(out[..2], [1.0, 0.0]);
+        assert!(engine.buses.is_empty());
+    }
+
+    #[test]
+    fn renamed_default_channel_is_spared() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        engine.set_default_channel("bgm".into());
+        assert_eq!(engine.get_default_channel(), "bgm");
+        engine.fade_mix_control_to(
+            "sfx".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.kill_all_mix_controls_except_main();
+        let mut names: Vec<&str> = engine
+            .mix_controls
+            .keys()
+            .map(CompactString::as_str)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["bgm"]);
+        assert_eq!(engine.mix_controls["bgm"].evaluate(), PosFloat::ONE);
+    }
+
+    #[test]
+    fn mix_control_fades_respect_maxgain() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source("channel lead maxgain 1.5\n").unwrap(),
+        );
+        for control_name in ["lead", "other"] {
+            engine.fade_mix_control_to(
+                control_name.into(),
+                PosFloat::new_clamped(4.0),
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        assert_eq!(
+            engine.mix_controls["lead"].evaluate(),
+            PosFloat::new_clamped(1.5)
+        );
+        assert_eq!(
+            engine.mix_controls["other"].evaluate(),
+            PosFloat::new_clamped(4.0)
+        );
+        engine.fade_all_mix_controls_to(
+            PosFloat::new_clamped(3.0),
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        assert_eq!(
+            engine.mix_controls["lead"].evaluate(),
+            PosFloat::new_clamped(1.5)
+        );
+    }
+
+    #[test]
+    fn advancing_silently_keeps_time_like_mixing() {
+        const SOURCE: &str = r#"
+sound a
+  file 137.wav
+sound b
+  file 90.wav
+flow test
+  play sound a and wait
+  play sound b
+  wait 0.05
+  play sound a and wait
+  play sound b and wait
+  play sound a
+  wait 0.01
+  play sound b
+"#;
+        let mut engines = [(); 2].map(|_| {
+            let mut engine = test_engine(Arc::new(DcDelegate));
+            engine
+                .replace_soundtrack(Soundtrack::from_source(SOURCE).unwrap());
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            engine
+        });
+        // (0.333 seconds is partway through the second `a`)
+        engines[0].advance_silently(PosFloat::new_clamped(0.333));
+        engines[1].turn_handle(&mut [0.0; 333]);
+        let [silent, mixed] = &mut engines;
+        assert_eq!(silent.current_frame(), 333);
+        assert_eq!(mixed.current_frame(), 333);
+        let started = silent.take_started_sounds();
+        assert_eq!(started.len(), 4);
+        assert_eq!(started, mixed.take_started_sounds());
+        assert_eq!(
+            silent.inspect_queued_sounds(),
+            mixed.inspect_queued_sounds()
+        );
+        // the sounds that were already playing carry on from the same places,
+        // and the rest start at the same times
+        let mut silent_out = [0.0; 300];
+        let mut mixed_out = [0.0; 300];
+        silent.turn_handle(&mut silent_out);
+        mixed.turn_handle(&mut mixed_out);
+        assert_eq!(silent_out, mixed_out);
+        let started = silent.take_started_sounds();
+        assert_eq!(started.len(), 2);
+        assert_eq!(started, mixed.take_started_sounds());
+    }
+
+    #[test]
+    fn flow_control_changes_are_reported() {
+        let delegate = ReportingDelegate::new(|_| None);
+        let mut engine = test_engine(delegate.clone());
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "flow test\n  set mood to \"boss\"\n  set mood to \"boss\"\n",
+            )
+            .unwrap(),
+        );
+        engine.set_flow_control("level".into(), StringOrNumber::Number(1.0));
+        engine.set_flow_control("level".into(), StringOrNumber::Number(1.0));
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.advance_silently(PosFloat::ONE);
+        engine.set_flow_control(
+            "mood".into(),
+            StringOrNumber::String("calm".into()),
+        );
+        assert_eq!(
+            *delegate.flow_control_changes.lock().unwrap(),
+            [
+                "level=Number(1.0)",
+                "mood=String(\"boss\")",
+                "mood=String(\"calm\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_failures_are_reported_and_cut_off() {
+        /// 300 sample frames of DC, and then a decode error.
+        struct Corrupt {
+            position: usize,
+            failed: bool,
+        }
+        impl SoundReader<f32> for Corrupt {
+            fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+                let len = buf.len().min(300 - self.position);
+                buf[..len].fill(MaybeUninit::new(1.0));
+                self.position += len;
+                if len < buf.len() {
+                    self.failed = true;
+                }
+                len
+            }
+            fn take_error(&mut self) -> Option<String> {
+                std::mem::take(&mut self.failed).then(|| "bad CRC".to_string())
+            }
+        }
+        for (stream, expected_len) in [("", 0), ("  stream\n", 300)] {
+            let delegate = ReportingDelegate::new(|_| {
+                Some(FormattedSoundStream {
+                    sample_rate: PosFloat::new_clamped(1000.0),
+                    speaker_layout: SpeakerLayout::Mono,
+                    reader: FormattedSoundReader::F32(Box::new(Corrupt {
+                        position: 0,
+                        failed: false,
+                    })),
+                })
+            });
+            let mut engine = test_engine(delegate.clone());
+            engine.replace_soundtrack(
+                Soundtrack::from_source(&format!(
+                    "sound bad\n  file bad.wav\n{stream}flow test\n  \
+                     play sound bad\n"
+                ))
+                .unwrap(),
+            );
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            let mut out = vec![0.0; 600];
+            engine.turn_handle(&mut out);
+            // (a buffered sound is silenced, rather than cut short)
+            assert!(out[..expected_len].iter().all(|x| *x == 1.0), "{stream}");
+            assert!(out[expected_len..].iter().all(|x| *x == 0.0), "{stream}");
+            let failures = delegate.messages(DiagCode::DecodeFailed);
+            assert_eq!(failures.len(), 1, "{:?}", delegate.codes());
+            assert!(failures[0].contains("\"bad.wav\""));
+            assert!(failures[0].contains("bad CRC"));
+        }
+    }
+
+    #[test]
+    fn nonsensical_streams_are_reported_and_silenced() {
+        /// `zero.wav` has a sample rate of zero. `odd.wav` is "stereo", but
+        /// has an odd number of samples.
+        fn open_bad(name: &str) -> Option<FormattedSoundStream> {
+            let (sample_rate, speaker_layout) = match name {
+                "zero.wav" => (PosFloat::ZERO, SpeakerLayout::Mono),
+                _ => (PosFloat::new_clamped(1000.0), SpeakerLayout::Stereo),
+            };
+            Some(FormattedSoundStream {
+                sample_rate,
+                speaker_layout,
+                reader: FormattedSoundReader::F32(Box::new(Dc(301))),
+            })
+        }
+        for file in ["zero.wav", "odd.wav"] {
+            for stream in ["", "  stream\n"] {
+                let delegate = ReportingDelegate::new(open_bad);
+                let mut engine = test_engine(delegate.clone());
+                engine.replace_soundtrack(
+                    Soundtrack::from_source(&format!(
+                        "sound bad\n  file {file}\n{stream}flow test\n  \
+                         play sound bad\n"
+                    ))
+                    .unwrap(),
+                );
+                engine.start_flow(
+                    "test".into(),
+                    PosFloat::ONE,
+                    PosFloat::ZERO,
+                    FadeType::Linear,
+                );
+                let mut out = vec![0.0; 600];
+                engine.turn_handle(&mut out);
+                if file == "zero.wav" || stream.is_empty() {
+                    assert!(out.iter().all(|x| *x == 0.0), "{file} {stream}");
+                }
+                let complaints = delegate.messages(DiagCode::BadStreamFormat);
+                assert_eq!(complaints.len(), 1, "{:?}", delegate.codes());
+                assert!(complaints[0].contains(file));
+            }
+        }
+    }
+
+    #[test]
+    fn builders_build_what_they_were_told() {
+        let engine = Engine::builder()
+            .sample_rate(PosFloat::new_clamped(1000.0))
+            .runtime(Arc::new(ForegroundTaskRuntime))
+            .delegate(Arc::new(NullSoundDelegate))
+            .build();
+        assert_eq!(engine.get_speaker_layout(), SpeakerLayout::Stereo);
+        assert_eq!(*engine.get_sample_rate(), 1000.0);
+        let engine = EngineBuilder::new()
+            .delegate(Arc::new(NullSoundDelegate))
+            .speaker_layout(SpeakerLayout::Surround51)
+            .sample_rate(PosFloat::new_clamped(44100.0))
+            .num_threads(NonZeroUsize::new(1))
+            .buffer_budget(Some(1024))
+            .build();
+        assert_eq!(engine.get_speaker_layout(), SpeakerLayout::Surround51);
+        assert_eq!(*engine.get_sample_rate(), 44100.0);
+    }
+
+    #[test]
+    fn runaway_flows_are_held_back() {
+        let delegate = ReportingDelegate::new(|_| None);
+        let mut engine = test_engine(delegate.clone());
+        engine.set_instruction_limit(NonZeroUsize::new(100));
+        // (this loops forever without any time passing)
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "flow test with loop\n  set count to $count + 1\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.step_frames(10);
+        let count = engine.copy_flow_control("count");
+        engine.step_frames(10);
+        assert!(count.is_some());
+        assert_ne!(engine.copy_flow_control("count"), count);
+        assert_eq!(
+            delegate.codes(),
+            [DiagCode::RunawayFlow, DiagCode::RunawayFlow]
+        );
+    }
+
+    #[test]
+    fn flows_stuck_loading_time_out() {
+        let delegate = ReportingDelegate::new(|_| None);
+        let mut engine = Engine::new_with_runtime(
+            delegate.clone(),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(SaturatedRuntime::default()),
+            None,
+        );
+        engine.set_load_timeout(Some(PosFloat::HALF), true);
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\n\
+                 flow test\n  play sound a\n  set started to 1\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        // (flows are only checked on when the handle turns)
+        for _ in 0..4 {
+            engine.step_frames(100);
+        }
+        assert_eq!(engine.copy_flow_control("started"), None);
+        assert!(delegate.codes().is_empty());
+        for _ in 0..3 {
+            engine.step_frames(100);
+        }
+        assert!(engine.copy_flow_control("started").is_some());
+        assert_eq!(delegate.codes(), [DiagCode::LoadTimedOut]);
+    }
+
+    #[test]
+    fn commands_land_between_chunks() {
+        /// Starts the `hum` flow as soon as the `go` FlowControl is set.
+        struct StartingDelegate {
+            synthetic: SyntheticSoundDelegate,
+            commander: std::sync::Mutex<Option<Commander>>,
+        }
+        impl SoundDelegate for StartingDelegate {
+            fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+                self.synthetic.open_file(name)
+            }
+            fn flow_control_changed(&self, name: &str, _: &StringOrNumber) {
+                if name == "go" {
+                    self.commander
+                        .lock()
+                        .unwrap()
+                        .as_mut()
+                        .unwrap()
+                        .start_flow(
+                            "hum".into(),
+                            PosFloat::ONE,
+                            PosFloat::ZERO,
+                            FadeType::Linear,
+                        );
+                }
+            }
+        }
+        let delegate = Arc::new(StartingDelegate {
+            synthetic: SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            )),
+            commander: Default::default(),
+        });
+        let mut engine = test_engine(delegate.clone());
+        *delegate.commander.lock().unwrap() = Some(engine.clone_commander());
+        engine.set_max_chunk_length(Some(PosFloat::new_clamped(0.01)));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound hum\n  file sine:250\nflow hum\n  play sound hum\n\
+                 flow trigger\n  wait 0.1\n  set go to 1\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "trigger".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 1000];
+        engine.turn_handle(&mut out);
+        // (without the limit, `hum` wouldn't have started until the next
+        // turn)
+        assert!(out[..100].iter().all(|x| *x == 0.0));
+        assert!(out[..120].iter().any(|x| *x != 0.0));
+        assert!(out[500..].iter().any(|x| *x != 0.0));
+    }
+
+    #[test]
+    fn replaced_runtimes_get_new_loads() {
+        #[derive(Default)]
+        struct CountingRuntime(std::sync::atomic::AtomicUsize);
+        impl TaskRuntime for CountingRuntime {
+            fn spawn_task(
+                &self,
+                kind: TaskType,
+                task: impl std::future::Future<Output = ()> + Send + 'static,
+            ) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                ForegroundTaskRuntime.spawn_task(kind, task);
+            }
+        }
+        let old_rt = Arc::new(CountingRuntime::default());
+        let new_rt = Arc::new(CountingRuntime::default());
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(NullSoundDelegate),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            old_rt.clone(),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\nsound b\n  file b.wav\n",
+            )
+            .unwrap(),
+        );
+        engine.cache_sound("a".into());
+        engine.replace_runtime(new_rt.clone());
+        engine.cache_sound("b".into());
+        let count = |rt: &CountingRuntime| {
+            rt.0.load(std::sync::atomic::Ordering::Relaxed)
+        };
+        assert_eq!((count(&old_rt), count(&new_rt)), (1, 1));
+        // (its one load is long finished, so the engine has let go of it)
+        assert_eq!(Arc::strong_count(&old_rt), 1);
+    }
+
+    #[test]
+    fn uncaching_lets_go_of_sounds_the_soundtrack_lost() {
+        let delegate =
+            ReportingDelegate::new(|name| DcDelegate.open_file(name));
+        let mut engine = test_engine(delegate.clone());
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound s\n  file 100.wav\n  stream\nflow f\n  play sound s\n",
+            )
+            .unwrap(),
+        );
+        engine.cache_sound("s".into());
+        engine.cache_sound("s".into());
+        // (play it once, so it's fully loaded, and then only the cache is
+        // holding on to it)
+        engine.start_flow(
+            "f".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 10];
+        engine.turn_handle(&mut out);
+        engine.kill_flow("f".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(engine.stats().cached_sound_bytes, 400);
+        engine.replace_soundtrack(Soundtrack::new());
+        engine.uncache_sound("s".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(engine.stats().cached_sound_bytes, 0);
+        assert_eq!(delegate.codes(), []);
+    }
+
+    #[test]
+    fn replaced_delegates_get_new_loads() {
+        /// Opens synthetic sounds, and writes down which ones it opened.
+        struct RecordingDelegate {
+            synthetic: SyntheticSoundDelegate,
+            opened: std::sync::Mutex<Vec<String>>,
+        }
+        impl SoundDelegate for RecordingDelegate {
+            fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+                self.opened.lock().unwrap().push(name.to_string());
+                self.synthetic.open_file(name)
+            }
+        }
+        let new_delegate = || {
+            Arc::new(RecordingDelegate {
+                synthetic: SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                    1000.0,
+                )),
+                opened: Default::default(),
+            })
+        };
+        let old_delegate = new_delegate();
+        let new_delegate = new_delegate();
+        let mut engine = test_engine(old_delegate.clone());
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound low\n  file sine:250\nsound high\n  file sine:500\n\
+                 flow low\n  play sound low\nflow high\n  play sound high\n",
+            )
+            .unwrap(),
+        );
+        let start = |engine: &mut Engine, flow_name: &str| {
+            engine.start_flow(
+                flow_name.into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            engine.step_frames(10);
+        };
+        start(&mut engine, "low");
+        engine.replace_delegate(new_delegate.clone());
+        start(&mut engine, "high");
+        assert_eq!(*old_delegate.opened.lock().unwrap(), ["sine:250"]);
+        assert_eq!(*new_delegate.opened.lock().unwrap(), ["sine:500"]);
+        // (and the sound the old delegate opened keeps playing)
+        assert_eq!(engine.mixer.num_playing(), 2);
+    }
+
+    #[test]
+    fn started_flows_load_urgently() {
+        #[derive(Default)]
+        struct RecordingRuntime(std::sync::Mutex<Vec<TaskType>>);
+        impl TaskRuntime for RecordingRuntime {
+            fn spawn_task(
+                &self,
+                kind: TaskType,
+                task: impl std::future::Future<Output = ()> + Send + 'static,
+            ) {
+                self.0.lock().unwrap().push(kind);
+                ForegroundTaskRuntime.spawn_task(kind, task);
+            }
+        }
+        let rt = Arc::new(RecordingRuntime::default());
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(NullSoundDelegate),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            rt.clone(),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\nsound b\n  file b.wav\n  stream\n\
+                 sound c\n  file c.wav\nsound d\n  file d.wav\n  stream\n\
+                 flow later\n  play sound a\n  play sound b\n\
+                 flow now\n  play sound c\n  play sound d\n",
+            )
+            .unwrap(),
+        );
+        engine.precache("later".into());
+        engine.start_flow(
+            "now".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.drain_commands();
+        let mut kinds = rt.0.lock().unwrap().clone();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            [
+                TaskType::BufferLoad,
+                TaskType::StreamLoad,
+                TaskType::UrgentBufferLoad,
+                TaskType::UrgentStreamLoad,
+            ]
+        );
+    }
+
+    #[test]
+    fn flow_load_progress_is_estimated() {
+        /// Loads each sound on its own thread.
+        #[derive(Default)]
+        struct ThreadRuntime(
+            std::sync::Mutex<Vec<std::thread::JoinHandle<()>>>,
+        );
+        impl TaskRuntime for ThreadRuntime {
+            fn spawn_task(
+                &self,
+                kind: TaskType,
+                task: impl std::future::Future<Output = ()> + Send + 'static,
+            ) {
+                self.0.lock().unwrap().push(std::thread::spawn(move || {
+                    ForegroundTaskRuntime.spawn_task(kind, task)
+                }));
+            }
+        }
+        /// A second of silence at 1000Hz, whose reading stops a quarter of
+        /// the way through until `gate` lets it go on.
+        struct GatedDelegate {
+            reached: Sender<()>,
+            gate: Receiver<()>,
+        }
+        struct GatedSilence {
+            position: usize,
+            reached: Sender<()>,
+            gate: Receiver<()>,
+        }
+        impl SoundDelegate for GatedDelegate {
+            fn open_file(&self, _name: &str) -> Option<FormattedSoundStream> {
+                Some(FormattedSoundStream {
+                    sample_rate: PosFloat::new_clamped(1000.0),
+                    speaker_layout: SpeakerLayout::Mono,
+                    reader: FormattedSoundReader::F32(Box::new(
+                        GatedSilence {
+                            position: 0,
+                            reached: self.reached.clone(),
+                            gate: self.gate.clone(),
+                        },
+                    )),
+                })
+            }
+        }
+        impl SoundReader<f32> for GatedSilence {
+            fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+                if self.position == 250 {
+                    self.reached.send(()).unwrap();
+                    self.gate.recv().unwrap();
+                }
+                let end = if self.position < 250 { 250 } else { 1000 };
+                let len = buf.len().min(end - self.position);
+                buf[..len].fill(MaybeUninit::new(0.0));
+                self.position += len;
+                len
+            }
+            fn estimate_len(&mut self) -> Option<u64> {
+                Some(1000)
+            }
+        }
+        let (reached_tx, reached_rx) = crossbeam::channel::bounded(0);
+        let (gate_tx, gate_rx) = crossbeam::channel::bounded(0);
+        let rt = Arc::new(ThreadRuntime::default());
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(GatedDelegate {
+                reached: reached_tx,
+                gate: gate_rx,
+            }),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            rt.clone(),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file a.wav\nflow test\n  play sound a\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(engine.flow_load_progress("test"), None);
+        engine.precache("test".into());
+        engine.drain_commands();
+        reached_rx.recv().unwrap();
+        let progress = engine.flow_load_progress("test").unwrap();
+        assert_eq!(progress.sounds_ready, 0);
+        assert_eq!(progress.total_sounds, 1);
+        assert_eq!(progress.sounds_of_unknown_length, 0);
+        assert!((progress.seconds_remaining - 0.75).abs() < 0.001);
+        gate_tx.send(()).unwrap();
+        for thread in rt.0.lock().unwrap().drain(..) {
+            thread.join().unwrap();
+        }
+        assert_eq!(
+            engine.flow_load_progress("test"),
+            Some(FlowLoadProgress {
+                sounds_ready: 1,
+                total_sounds: 1,
+                seconds_remaining: 0.0,
+                sounds_of_unknown_length: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn cooperative_loading_takes_turns() {
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            ))),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(CooperativeTaskRuntime::new(NonZeroUsize::MIN)),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound a\n  file sine:100:10\nflow test\n  play sound a\n",
+            )
+            .unwrap(),
+        );
+        engine.precache("test".into());
+        engine.drain_commands();
+        let mut out = [0.0; 10];
+        let mut seconds_remaining = vec![];
+        while engine.flow_load_progress("test").unwrap().sounds_ready == 0 {
+            engine.turn_handle(&mut out);
+            seconds_remaining.push(
+                engine.flow_load_progress("test").unwrap().seconds_remaining,
+            );
+            assert!(seconds_remaining.len() < 100, "never finished loading");
+        }
+        // (one turn for each 4096-frame chunk, and one more to find the end)
+        let expected = [5.904, 1.808, 0.0, 0.0];
+        assert_eq!(seconds_remaining.len(), expected.len());
+        assert!(
+            seconds_remaining
+                .iter()
+                .zip(expected)
+                .all(|(x, y)| (x - y).abs() < 0.001),
+            "{seconds_remaining:?}"
+        );
+    }
+
+    #[test]
+    fn and_wait_finds_out_unknown_lengths() {
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        // (neither sound has an `end`, and `s` is streamed)
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"sound s
+  file 300.wav
+  stream
+sound t
+  file 200.wav
+flow test
+  play sound s and wait
+  play sound t and wait
+  play sound s
+"#,
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        for _ in 0..20 {
+            engine.step_frames(50);
+        }
+        let whens: Vec<u64> = engine
+            .take_started_sounds()
+            .iter()
+            .map(|x| x.when)
+            .collect();
+        let [first, second, third] = whens[..] else {
+            panic!("expected three sounds, got {whens:?}");
+        };
+        // A streamed sound's length is only found out when it runs out, so
+        // the node may resume up to one mix late...
+        assert!((first + 300..=first + 350).contains(&second));
+        // ...but a buffered sound's is known as soon as it starts.
+        assert_eq!(third, second + 200);
+    }
+
+    #[test]
+    fn bulk_commands_go_in_name_order() {
+        /// Opens files like `DcDelegate`, and remembers which ones it opened.
+        #[derive(Default)]
+        struct OpenCounter(std::sync::Mutex<Vec<String>>);
+        impl SoundDelegate for OpenCounter {
+            fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+                self.0.lock().unwrap().push(name.to_string());
+                DcDelegate.open_file(name)
+            }
+            // (going over budget while precaching is expected here)
+            fn diagnostic(&self, _level: DiagLevel, _code: DiagCode, _: &str) {
+            }
+        }
+        let delegate = Arc::new(OpenCounter::default());
+        // (room for one sound's 100 sample frames, and no more)
+        let mut engine = Engine::new_with_runtime(
+            delegate.clone(),
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(ForegroundTaskRuntime),
+            Some(100 * std::mem::size_of::<f32>()),
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                r#"sound a
+  file 100-a.wav
+sound b
+  file 100-b.wav
+sound c
+  file 100-c.wav
+flow alpha
+  play sound a
+flow bravo
+  play sound b
+flow charlie
+  play sound c
+"#,
+            )
+            .unwrap(),
+        );
+        for flow_name in ["charlie", "bravo", "alpha"] {
+            engine.precache(flow_name.into());
+            engine.drain_commands();
+            engine.flow_load_progress(flow_name);
+        }
+        // Each sound is let go of as soon as it's unprecached, until we're
+        // back under budget, so only the last one (in name order) stays.
+        engine.unprecache_all();
+        engine.drain_commands();
+        delegate.0.lock().unwrap().clear();
+        for flow_name in ["alpha", "bravo", "charlie"] {
+            engine.precache(flow_name.into());
+            engine.drain_commands();
+        }
+        assert_eq!(*delegate.0.lock().unwrap(), ["100-a.wav", "100-b.wav"]);
+    }
+
+    #[test]
+    fn fades_are_coalesced() {
+        let fade = |control_name: &str, volume: f32| {
+            EngineCommand::FadeMixControlTo {
+                control_name: control_name.into(),
+                fade_type: FadeType::Linear,
+                target_volume: PosFloat::new_clamped(volume),
+                fade_length: PosFloat::ONE,
+            }
+        };
+        let mut commands = vec![
+            fade("a", 0.1),
+            fade("a", 0.2),
+            fade("b", 0.5),
+            EngineCommand::SetFlowControl {
+                control_name: "x".into(),
+                new_value: StringOrNumber::Number(1.0),
+            },
+            fade("a", 0.3),
+            fade("a", 0.4),
+        ];
+        coalesce_fades(&mut commands);
+        let summary: Vec<String> = commands
+            .iter()
+            .map(|x| match x {
+                EngineCommand::FadeMixControlTo {
+                    control_name,
+                    target_volume,
+                    ..
+                } => format!("{control_name}={}", *target_volume),
+                _ => "other".to_string(),
+            })
+            .collect();
+        assert_eq!(summary, ["a=0.2", "b=0.5", "other", "a=0.4"]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_commanders_wait_for_room() {
+        use futures::{FutureExt, StreamExt};
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        let mut commander =
+            engine.async_commander(NonZeroUsize::new(1).unwrap());
+        let mut transaction = TransactionBuilder::new();
+        transaction.set_flow_control("a".into(), StringOrNumber::Number(1.0));
+        assert!(commander.send_async(transaction).now_or_never().is_some());
+        // the channel is full now
+        let mut transaction = TransactionBuilder::new();
+        transaction.set_flow_control("b".into(), StringOrNumber::Number(2.0));
+        let responses: query::ResponseStream<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|x| transaction.get_flow_control(x.into()))
+            .collect();
+        let mut send = Box::pin(commander.send_async(transaction));
+        assert!(send.as_mut().now_or_never().is_none());
+        engine.drain_commands();
+        assert!(send.now_or_never().is_some());
+        engine.drain_commands();
+        assert_eq!(
+            futures::executor::block_on(responses.collect::<Vec<_>>()),
+            [
+                Some(StringOrNumber::Number(1.0)),
+                Some(StringOrNumber::Number(2.0)),
+                None
+            ]
+        );
+        drop(commander);
+        engine.drain_commands();
+        assert!(engine.async_channels.is_empty());
+    }
+
+    #[test]
+    fn transaction_builders_are_sent_whole() {
+        let mut engine = Engine::new_for_testing(
+            SpeakerLayout::Mono,
+            PosFloat::new_clamped(1000.0),
+        );
+        let mut commander = engine.clone_commander();
+        let mut builder = TransactionBuilder::new();
+        assert!(builder.is_empty());
+        builder.set_flow_control("a".into(), StringOrNumber::Number(1.0));
+        builder.set_flow_control("b".into(), StringOrNumber::Number(2.0));
+        assert_eq!(engine.copy_flow_control("a"), None);
+        commander.send_transaction(builder);
+        engine.drain_commands();
+        assert_eq!(
+            engine.copy_flow_control("a"),
+            Some(StringOrNumber::Number(1.0))
+        );
+        assert_eq!(
+            engine.copy_flow_control("b"),
+            Some(StringOrNumber::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn taps_get_their_own_mix() {
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound dc\n  file dc.wav\nflow test\n  play sound dc\n",
+            )
+            .unwrap(),
+        );
+        let tap = engine.add_tap(SpeakerLayout::Stereo);
+        engine.set_tap_channel_gain(tap, "main".into(), PosFloat::HALF);
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        // The tap goes first, so the main output has to be kept for later.
+        let mut tap_out = [0.0; 64];
+        engine.turn_handle_tap(tap, &mut tap_out);
+        assert_eq!(tap_out, [0.5; 64]);
+        assert_eq!(engine.current_frame(), 32);
+        let mut main_out = [0.0; 48];
+        engine.turn_handle(&mut main_out);
+        assert_eq!(main_out, [1.0; 48]);
+        assert_eq!(engine.current_frame(), 48);
+        // Now the tap is 16 frames behind.
+        engine.set_tap_volume(tap, PosFloat::ZERO);
+        engine.turn_handle_tap(tap, &mut tap_out);
+        assert_eq!(tap_out[..32], [0.5; 32]);
+        assert_eq!(tap_out[32..], [0.0; 32]);
+        engine.remove_tap(tap);
+        engine.turn_handle(&mut main_out);
+        assert_eq!(main_out, [1.0; 48]);
+    }
+
+    fn stereo_engine() -> Engine {
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(SyntheticSoundDelegate::new(PosFloat::new_clamped(
+                1000.0,
+            ))),
+            SpeakerLayout::Stereo,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        );
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound hum\n  file sine:125\nsequence left\n  length 1\n  \
+                 play sound hum\n    pan -0.5\nflow test\n  \
+                 play sequence left\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine
+    }
+
+    #[test]
+    fn planar_output_matches_interleaved() {
+        let mut interleaved_engine = stereo_engine();
+        let mut planar_engine = stereo_engine();
+        let mut interleaved = [0.0; 200];
+        let mut left = [1.0; 100];
+        let mut right = [1.0; 100];
+        // (twice, so the second turn reuses the first one's buffer)
+        for _ in 0..2 {
+            interleaved_engine.turn_handle(&mut interleaved);
+            planar_engine.turn_handle_planar(&mut [&mut left, &mut right]);
+            for n in 0..100 {
+                assert_eq!(left[n], interleaved[n * 2], "{n}");
+                assert_eq!(right[n], interleaved[n * 2 + 1], "{n}");
+            }
+        }
+        assert!(left.iter().zip(right.iter()).any(|(l, r)| l != r));
+    }
+
+    #[test]
+    #[should_panic]
+    fn planar_output_needs_a_buffer_per_channel() {
+        let mut out = [0.0; 100];
+        stereo_engine().turn_handle_planar(&mut [&mut out]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn planar_output_needs_buffers_of_the_same_length() {
+        let mut left = [0.0; 100];
+        let mut right = [0.0; 99];
+        stereo_engine().turn_handle_planar(&mut [&mut left, &mut right]);
+    }
+
+    #[test]
+    fn soloing_silences_other_mix_controls() {
+        let mut engine = test_engine(Arc::new(DcDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound dc\n  file dc.wav\nsequence both\n  length 1\n  \
+                 play sound dc\n    at 0\n  play sound dc\n    at 0\n    \
+                 channel sfx\nflow test\n  play sequence both\n",
+            )
+            .unwrap(),
+        );
+        engine.fade_mix_control_to(
+            "sfx".into(),
+            PosFloat::HALF,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 16];
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.5; 16]);
+        engine.solo_mix_control("sfx".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [0.5; 16]);
+        // soloing another one joins it to the solo group
+        engine.solo_mix_control("main".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.5; 16]);
+        engine.clear_solo();
+        engine.solo_mix_control("main".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.0; 16]);
+        // the silenced MixControl's own volume was left alone
+        assert_eq!(engine.mix_controls["sfx"].evaluate(), PosFloat::HALF);
+        engine.clear_solo();
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.5; 16]);
+        // muting silences just the one MixControl, also leaving its volume
+        engine.set_mix_control_muted("sfx".into(), true);
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.0; 16]);
+        assert_eq!(engine.mix_controls["sfx"].evaluate(), PosFloat::HALF);
+        // a muted MixControl stays silent even when soloed
+        engine.solo_mix_control("sfx".into());
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [0.0; 16]);
+        engine.clear_solo();
+        engine.set_mix_control_muted("sfx".into(), false);
+        engine.turn_handle(&mut out);
+        assert_eq!(out, [1.5; 16]);
+    }
+
+    #[test]
+    fn routed_mix_controls_get_their_own_outputs() {
+        let mut engine = test_engine(Arc::new(SyntheticSoundDelegate::new(
+            PosFloat::new_clamped(1000.0),
+        )));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound tone\n  file sine:250\nsequence both\n  length 1\n  \
+                 play sound tone\n  play sound tone\n    channel voice\n\
+                 flow test\n  play sequence both\n",
+            )
+            .unwrap(),
+        );
+        engine.fade_mix_control_to(
+            "voice".into(),
+            PosFloat::HALF,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let mut out = [0.0; 100];
+        let mut voice = [7.0; 100];
+        let mut nothing = [7.0; 100];
+        engine.turn_handle_routed(
+            &mut out,
+            &mut [("voice", &mut voice), ("nothing", &mut nothing)],
+        );
+        assert!((out[1] - 0.5).abs() < 0.001, "{}", out[1]);
+        assert!((voice[1] - 0.25).abs() < 0.001, "{}", voice[1]);
+        assert_eq!(nothing, [0.0; 100]);
+        // (back to normal)
+        engine.turn_handle(&mut out);
+        assert!((out[1] - 0.75).abs() < 0.001, "{}", out[1]);
+    }
+
+    #[test]
+    fn groups_fade_all_their_mix_controls() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        let soundtrack = Soundtrack::from_source(
+            "group band includes guitar drums
+",
+        )
+        .unwrap();
+        assert_eq!(
+            soundtrack.channel_group("band").unwrap(),
+            ["guitar", "drums"]
+        );
+        engine.replace_soundtrack(soundtrack);
+        engine.fade_mix_control_to(
+            "vocals".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        engine.fade_group_to(
+            "band".into(),
+            PosFloat::HALF,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        assert_eq!(engine.mix_controls["guitar"].evaluate(), PosFloat::HALF);
+        assert_eq!(engine.mix_controls["drums"].evaluate(), PosFloat::HALF);
+        assert_eq!(engine.mix_controls["vocals"].evaluate(), PosFloat::ONE);
+        // missing groups do nothing
+        engine.fade_group_to(
+            "choir".into(),
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        assert_eq!(engine.mix_controls.len(), 4);
+    }
+
+    #[test]
+    fn one_shots_are_queued_and_stopped() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source("sound click\n  file click.wav\n")
+                .unwrap(),
+        );
+        let mut ui_click = engine.play_one_shot(
+            "click".into(),
+            "ui".into(),
+            PosFloat::HALF,
+            PosFloat::ONE,
+        );
+        let mut sfx_click = engine.play_one_shot(
+            "click".into(),
+            "sfx".into(),
+            PosFloat::ONE,
+            PosFloat::ONE,
+        );
+        // (a missing sound is only a warning)
+        let mut clack = engine.play_one_shot(
+            "clack".into(),
+            "ui".into(),
+            PosFloat::ONE,
+            PosFloat::ONE,
+        );
+        assert_eq!(clack.take(), Some(false));
+        engine.stop_one_shots(Some("sfx".into()));
+        assert_eq!(sfx_click.take(), Some(false));
+        assert!(!ui_click.poll());
+        let queued_sound = engine.queued_sounds.pop().unwrap();
+        assert!(queued_sound.who.one_shot);
+        assert_eq!(queued_sound.who.channel, "ui");
+        assert_eq!(queued_sound.who.volume, PosFloat::HALF);
+        assert!(engine.queued_sounds.pop().is_none());
+        drop(queued_sound);
+        assert_eq!(ui_click.take(), Some(false));
+    }
+
+    #[test]
+    fn auditions_play_apart_from_live_playback() {
+        let mut engine = test_engine(Arc::new(SyntheticSoundDelegate::new(
+            PosFloat::new_clamped(1000.0),
+        )));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "sound hum\n  file sine:250\nflow test\n  play sound hum\n",
+            )
+            .unwrap(),
+        );
+        engine.start_flow(
+            "test".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        // (the live flow is silenced, but keeps playing)
+        engine.fade_all_mix_controls_to(
+            PosFloat::ZERO,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let draft = Soundtrack::from_source(
+            r#"
+sound beep
+  file sine:250:0.05
+sequence beeps
+  length 1
+  play sound beep
+  play sound beep
+    at 0.5
+"#,
+        )
+        .unwrap();
+        engine.audition_sound(&draft, "nonexistent");
+        assert!(!engine.is_auditioning());
+        engine.audition_sound(&draft, "beep");
+        assert!(engine.is_auditioning());
+        let mut out = [0.0; 100];
+        engine.turn_handle(&mut out);
+        assert!((out[1] - 0.5).abs() < 0.001, "{}", out[1]);
+        assert_eq!(out[50..], [0.0; 50]);
+        engine.turn_handle(&mut out);
+        assert!(!engine.is_auditioning());
+        // (the beep is unloaded, leaving only the live flow's hum)
+        assert_eq!(engine.soundman.cached_sound_bytes(), 4000);
+        engine.audition_sequence(&draft, "beeps");
+        engine.turn_handle(&mut out);
+        assert!((out[1] - 0.5).abs() < 0.001, "{}", out[1]);
+        engine.stop_audition();
+        assert!(!engine.is_auditioning());
+        assert!(engine.queued_sounds.pop().is_none());
+        // (only the live flow's sound is left)
+        assert_eq!(engine.mixer.num_playing(), 1);
+    }
+
+    #[test]
+    fn finished_flow_starts_its_successor() {
+        let mut engine = test_engine(Arc::new(NullSoundDelegate));
+        engine.replace_soundtrack(
+            Soundtrack::from_source(
+                "flow a\n  wait 0.5\nflow b\n  wait 10\nflow c\n  wait 10\n",
+            )
+            .unwrap(),
+        );
+        for (flow_name, successor) in [("a", "b"), ("b", "c"), ("c", "a")] {
+            engine.set_flow_successor(
+                flow_name.into(),
+                Some(successor.into()),
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+        }
+        // (the last one would have made a loop, and should be refused)
+        assert!(!engine.flow_successors.contains_key("c"));
+        engine.start_flow(
+            "a".into(),
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            FadeType::Linear,
+        );
+        let playing_flows = |engine: &Engine| {
+            let mut flows: Vec<_> = engine
+                .active_flow_nodes
+                .iter()
+                .map(|x| x.flow.name.clone())
+                .collect();
+            flows.dedup();
+            flows
+        };
+        engine.advance_silently(PosFloat::new_clamped(0.25));
+        assert_eq!(playing_flows(&engine), ["a"]);
+        engine.advance_silently(PosFloat::new_clamped(0.5));
+        assert_eq!(playing_flows(&engine), ["b"]);
+    }
+
+    #[test]
+    fn ragged_buffer_mixes_whole_frames() {
+        let mut engine = Engine::new_with_runtime(
+            Arc::new(NullSoundDelegate),
+            SpeakerLayout::Stereo,
+            PosFloat::new_clamped(1000.0),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        );
+        let mut buf = [7.0; 5];
+        assert!(engine.try_turn_handle(&mut buf).is_err());
+        assert_eq!(buf[4], 7.0);
+        assert!(engine.try_turn_handle(&mut buf[..4]).is_ok());
+    }
+}
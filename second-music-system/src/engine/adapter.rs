@@ -3,7 +3,9 @@ use super::*;
 mod fadeadapter;
 use fadeadapter::*;
 mod chanadapter;
-use chanadapter::*;
+pub(crate) use chanadapter::{new_channel_adapter, new_spread_adapter};
+mod bendadapter;
+pub(crate) use bendadapter::*;
 #[cfg(feature = "resample-soxr")]
 #[path = "adapter/rateadapter_soxr.rs"]
 mod rateadapter;
@@ -16,15 +18,50 @@ use rateadapter::*;
 pub(crate) fn adaptify(
     delegate: &Arc<dyn SoundDelegate>,
     soundman: &mut dyn GenericSoundMan,
-    sound: &Sound,
+    sound: &Arc<Sound>,
     fade_in: PosFloat,
     length: Option<PosFloat>,
     fade_out: PosFloat,
+    fade_type: FadeType,
     out_sample_rate: PosFloat,
     out_speaker_layout: SpeakerLayout,
 ) -> Option<Box<dyn SoundReader<f32>>> {
     let stream = soundman.get_sound(sound)?;
-    let in_sample_rate = stream.sample_rate;
+    Some(adapt_stream(
+        delegate,
+        sound,
+        stream,
+        fade_in,
+        length,
+        fade_out,
+        fade_type,
+        out_sample_rate,
+        out_speaker_layout,
+    ))
+}
+
+/// The part of `adaptify` that happens after the stream has been obtained.
+#[allow(clippy::too_many_arguments)] // (internal function, doesn't care)
+fn adapt_stream(
+    delegate: &Arc<dyn SoundDelegate>,
+    sound: &Arc<Sound>,
+    stream: FormattedSoundStream,
+    fade_in: PosFloat,
+    length: Option<PosFloat>,
+    fade_out: PosFloat,
+    fade_type: FadeType,
+    out_sample_rate: PosFloat,
+    out_speaker_layout: SpeakerLayout,
+) -> Box<dyn SoundReader<f32>> {
+    // The fade adapter works in the sound's own time, but the fades and
+    // length we were given are in output time.
+    let pitch = sound.pitch;
+    let fade_in = fade_in * pitch;
+    let length = length.map(|x| x * pitch);
+    let fade_out = fade_out * pitch;
+    // Playing a sound faster is the same as pretending it has a higher sample
+    // rate, and then resampling it.
+    let in_sample_rate = stream.sample_rate * pitch;
     let in_speaker_layout = stream.speaker_layout;
     /*
     // TODO: if it's already an F32 stream and has no interesting fade, use it
@@ -44,6 +81,7 @@ pub(crate) fn adaptify(
             sound.end.get().map(|x| x.saturating_sub(sound.start))
         }),
         fade_out,
+        fade_type,
     );
     let need_chan_adapter = in_speaker_layout != out_speaker_layout;
     let num_channels = if need_chan_adapter && in_sample_rate < out_sample_rate
@@ -75,5 +113,92 @@ pub(crate) fn adaptify(
             out_speaker_layout,
         );
     }
-    Some(stream)
+    stream
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::OnceLock;
+
+    /// A sine wave of a given frequency, forever.
+    struct Tone {
+        frequency: f32,
+        sample_rate: f32,
+        position: u64,
+    }
+
+    impl SoundReader<f32> for Tone {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            for out in buf.iter_mut() {
+                let t = self.position as f32 / self.sample_rate;
+                out.write((t * self.frequency * std::f32::consts::TAU).sin());
+                self.position += 1;
+            }
+            buf.len()
+        }
+    }
+
+    #[test]
+    fn pitched_tone() {
+        const SAMPLE_RATE: f32 = 8000.0;
+        const FREQUENCY: f32 = 250.0;
+        let delegate: Arc<dyn SoundDelegate> = Arc::new(NullSoundDelegate);
+        for pitch in [0.5, 1.0, 2.0] {
+            let sound = Arc::new(Sound {
+                name: "tone".into(),
+                path: "tone".into(),
+                start: PosFloat::ZERO,
+                end: OnceLock::new(),
+                stream: false,
+                pitch: PosFloat::new_clamped(pitch),
+                gain: PosFloat::ONE,
+                loop_crossfade: PosFloat::ZERO,
+                regions: HashMap::new(),
+                metadata: HashMap::new(),
+            });
+            let stream = FormattedSoundStream {
+                sample_rate: PosFloat::new_clamped(SAMPLE_RATE),
+                speaker_layout: SpeakerLayout::Mono,
+                reader: FormattedSoundReader::F32(Box::new(Tone {
+                    frequency: FREQUENCY,
+                    sample_rate: SAMPLE_RATE,
+                    position: 0,
+                })),
+            };
+            let mut adapted = adapt_stream(
+                &delegate,
+                &sound,
+                stream,
+                PosFloat::ZERO,
+                None,
+                PosFloat::ZERO,
+                FadeType::Linear,
+                PosFloat::new_clamped(SAMPLE_RATE),
+                SpeakerLayout::Mono,
+            );
+            // one second of output, read in engine-sized chunks
+            let mut buf = vec![];
+            let mut chunk = [MaybeUninit::uninit(); 1024];
+            while buf.len() < SAMPLE_RATE as usize {
+                let amount = adapted.read(&mut chunk);
+                assert_ne!(amount, 0);
+                buf.extend(
+                    chunk[..amount].iter().map(|x| unsafe { x.assume_init() }),
+                );
+            }
+            buf.truncate(SAMPLE_RATE as usize);
+            // two zero crossings per cycle
+            let crossings = buf
+                .windows(2)
+                .filter(|x| (x[0] < 0.0) != (x[1] < 0.0))
+                .count();
+            let expected = FREQUENCY * pitch * 2.0;
+            assert!(
+                (crossings as f32 - expected).abs() <= expected * 0.02,
+                "pitch {pitch}: expected about {expected} zero crossings, got {crossings}",
+            );
+        }
+    }
 }
@@ -0,0 +1,247 @@
+//! The bend adapter plays a stream back at a rate that can change while it's
+//! playing, for pitch bends. It uses linear interpolation, which is cheap but
+//! not especially high quality. Until the rate first changes from 1.0, it
+//! stays out of the way entirely.
+
+use super::*;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// A playback rate, shared between the engine (which sets it) and the bend
+/// adapters of every sound it applies to (which read it).
+#[derive(Debug, Clone)]
+pub(crate) struct SharedRate(Arc<AtomicU32>);
+
+impl SharedRate {
+    pub fn new(rate: PosFloat) -> SharedRate {
+        SharedRate(Arc::new(AtomicU32::new(rate.to_bits())))
+    }
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, rate: PosFloat) {
+        self.0.store(rate.to_bits(), Ordering::Relaxed)
+    }
+    /// Returns true if nothing other than this `SharedRate` is referring to
+    /// this rate.
+    pub fn is_unused(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+/// How many sample frames to read from the inner stream at a time.
+const IN_BUF_FRAMES: usize = 256;
+
+struct BendAdapter {
+    inner: Box<dyn SoundReader<f32>>,
+    /// Our playback rate is all of these multiplied together.
+    rates: Vec<SharedRate>,
+    num_channels: usize,
+    /// False until the rate first differs from 1.0. Until then, we pass reads
+    /// straight through.
+    bending: bool,
+    /// The sample frames we're interpolating between, one after the other.
+    prev_and_next: Vec<f32>,
+    /// How far we are between `prev` and `next`, from 0 to 1.
+    frac: f32,
+    in_buf: Vec<MaybeUninit<f32>>,
+    in_buf_pos: usize,
+    in_buf_len: usize,
+    /// True if the inner stream has ended.
+    fini: bool,
+}
+
+pub(crate) fn new_bend_adapter(
+    inner: Box<dyn SoundReader<f32>>,
+    num_channels: usize,
+    rates: Vec<SharedRate>,
+) -> Box<dyn SoundReader<f32>> {
+    Box::new(BendAdapter {
+        inner,
+        rates,
+        num_channels,
+        bending: false,
+        prev_and_next: vec![0.0; num_channels * 2],
+        frac: 0.0,
+        in_buf: vec![MaybeUninit::uninit(); IN_BUF_FRAMES * num_channels],
+        in_buf_pos: 0,
+        in_buf_len: 0,
+        fini: false,
+    })
+}
+
+impl BendAdapter {
+    fn get_rate(&self) -> f32 {
+        self.rates.iter().map(SharedRate::get).product()
+    }
+    /// Shift `next` into `prev`, and read a new `next`. Returns false if the
+    /// inner stream has ended.
+    fn advance(&mut self) -> bool {
+        if self.in_buf_pos >= self.in_buf_len {
+            if self.fini {
+                return false;
+            }
+            self.in_buf_len = self.inner.read(&mut self.in_buf);
+            self.in_buf_pos = 0;
+            if self.in_buf_len < self.in_buf.len() {
+                self.fini = true;
+            }
+            if self.in_buf_len == 0 {
+                return false;
+            }
+        }
+        let num_channels = self.num_channels;
+        self.prev_and_next.copy_within(num_channels.., 0);
+        for n in 0..num_channels {
+            self.prev_and_next[num_channels + n] =
+                unsafe { self.in_buf[self.in_buf_pos + n].assume_init() };
+        }
+        self.in_buf_pos += num_channels;
+        true
+    }
+}
+
+impl SoundReader<f32> for BendAdapter {
+    fn read(&mut self, out: &mut [MaybeUninit<f32>]) -> usize {
+        let rate = self.get_rate();
+        if !self.bending {
+            if rate == 1.0 {
+                return self.inner.read(out);
+            }
+            // Prime the pump.
+            self.bending = true;
+            if !self.advance() || !self.advance() {
+                return 0;
+            }
+        }
+        let num_channels = self.num_channels;
+        let mut produced = 0;
+        while produced < out.len() {
+            while self.frac >= 1.0 {
+                if !self.advance() {
+                    return produced;
+                }
+                self.frac -= 1.0;
+            }
+            let (prev, next) = self.prev_and_next.split_at(num_channels);
+            for n in 0..num_channels {
+                out[produced + n]
+                    .write(prev[n] + (next[n] - prev[n]) * self.frac);
+            }
+            produced += num_channels;
+            self.frac += rate;
+        }
+        produced
+    }
+    fn seek(&mut self, _pos: u64) -> Option<u64> {
+        panic!("SMS logic error: attempt to seek a bend adapter");
+    }
+    fn estimate_len(&mut self) -> Option<u64> {
+        panic!(
+            "SMS logic error: attempt to estimate length of a bend adapter"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Each sample is the number of its sample frame, plus 1000 times its
+    /// channel number, for as many sample frames as it's made with.
+    struct Ramp {
+        position: usize,
+        len: usize,
+        num_channels: usize,
+    }
+
+    impl SoundReader<f32> for Ramp {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            let mut amount = 0;
+            for frame in buf.chunks_exact_mut(self.num_channels) {
+                if self.position >= self.len {
+                    break;
+                }
+                for (n, out) in frame.iter_mut().enumerate() {
+                    out.write(self.position as f32 + 1000.0 * n as f32);
+                }
+                self.position += 1;
+                amount += self.num_channels;
+            }
+            amount
+        }
+    }
+
+    fn bent_ramp(
+        len: usize,
+        num_channels: usize,
+    ) -> (Box<dyn SoundReader<f32>>, SharedRate) {
+        let rate = SharedRate::new(PosFloat::ONE);
+        let ramp = Ramp {
+            position: 0,
+            len,
+            num_channels,
+        };
+        let adapter =
+            new_bend_adapter(Box::new(ramp), num_channels, vec![rate.clone()]);
+        (adapter, rate)
+    }
+
+    /// Reads the given number of samples, and returns however many there
+    /// were.
+    fn read(adapter: &mut dyn SoundReader<f32>, amount: usize) -> Vec<f32> {
+        let mut buf = vec![MaybeUninit::uninit(); amount];
+        let len = adapter.read(&mut buf);
+        buf[..len]
+            .iter()
+            .map(|x| unsafe { x.assume_init() })
+            .collect()
+    }
+
+    #[test]
+    fn rate_changes_take_effect_between_reads() {
+        let (mut adapter, rate) = bent_ramp(1000, 2);
+        // unbent, it passes straight through
+        assert_eq!(read(&mut *adapter, 4), [0.0, 1000.0, 1.0, 1001.0]);
+        rate.set(PosFloat::new_clamped(2.0));
+        assert_eq!(
+            read(&mut *adapter, 8),
+            [2.0, 1002.0, 4.0, 1004.0, 6.0, 1006.0, 8.0, 1008.0]
+        );
+        rate.set(PosFloat::HALF);
+        assert_eq!(
+            read(&mut *adapter, 8),
+            [10.0, 1010.0, 10.5, 1010.5, 11.0, 1011.0, 11.5, 1011.5]
+        );
+        // going back to 1.0 keeps interpolating, from where it was
+        rate.set(PosFloat::ONE);
+        assert_eq!(read(&mut *adapter, 4), [12.0, 1012.0, 13.0, 1013.0]);
+    }
+
+    #[test]
+    fn ramped_bends_add_up() {
+        let (mut adapter, rate) = bent_ramp(1000, 1);
+        let mut expected = 0.0;
+        for n in 0..200 {
+            let r = 1.0 + n as f32 / 100.0;
+            rate.set(PosFloat::new_clamped(r));
+            let got = read(&mut *adapter, 1)[0];
+            assert!((got - expected).abs() < 0.001, "{n}: {got} {expected}");
+            expected += r;
+        }
+    }
+
+    #[test]
+    fn bent_streams_end() {
+        let (mut adapter, rate) = bent_ramp(10, 1);
+        rate.set(PosFloat::new_clamped(2.0));
+        // (it can't interpolate past the last sample frame)
+        assert_eq!(read(&mut *adapter, 16), [0.0, 2.0, 4.0, 6.0, 8.0]);
+        assert_eq!(read(&mut *adapter, 16), []);
+        // and it ends even if it only just started bending
+        let (mut adapter, rate) = bent_ramp(10, 1);
+        assert_eq!(read(&mut *adapter, 9).len(), 9);
+        rate.set(PosFloat::new_clamped(2.0));
+        assert_eq!(read(&mut *adapter, 16), []);
+    }
+}
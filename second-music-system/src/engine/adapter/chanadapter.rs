@@ -50,6 +50,40 @@ make_upmixer!(MonoToSurround71(c) -> {
     sr = 0.0;
 });
 
+///////////////////////////////////////////////////////////////////////////////
+// --- Mono source, spread to every speaker ---
+///////////////////////////////////////////////////////////////////////////////
+
+// These are for positional sounds, which the mixer pans by applying a
+// different gain to each speaker. (Mono to stereo already spreads.)
+
+make_upmixer!(MonoToAllQuadraphonic(c) -> {
+    fl = c;
+    fr = c;
+    rl = c;
+    rr = c;
+});
+
+make_upmixer!(MonoToAllSurround51(c) -> {
+    fl = c;
+    fr = c;
+    c = c;
+    lfe = 0.0;
+    rl = c;
+    rr = c;
+});
+
+make_upmixer!(MonoToAllSurround71(c) -> {
+    fl = c;
+    fr = c;
+    c = c;
+    lfe = 0.0;
+    rl = c;
+    rr = c;
+    sl = c;
+    sr = c;
+});
+
 ///////////////////////////////////////////////////////////////////////////////
 // --- Stereo source ---
 ///////////////////////////////////////////////////////////////////////////////
@@ -303,6 +337,33 @@ pub(crate) fn new_channel_adapter(
     }
 }
 
+/// Takes in a mono stream, and outputs a stream with the same sound on every
+/// speaker (except the subwoofer).
+pub(crate) fn new_spread_adapter(
+    in_stream: Box<dyn SoundReader<f32>>,
+    sample_rate: PosFloat,
+    out_layout: SpeakerLayout,
+) -> Box<dyn SoundReader<f32>> {
+    match out_layout {
+        SpeakerLayout::Mono => in_stream,
+        SpeakerLayout::Stereo => {
+            MonoToStereo::new_boxed(sample_rate, in_stream)
+        }
+        SpeakerLayout::Headphones => {
+            MonoToHeadphones::new_boxed(sample_rate, in_stream)
+        }
+        SpeakerLayout::Quadraphonic => {
+            MonoToAllQuadraphonic::new_boxed(sample_rate, in_stream)
+        }
+        SpeakerLayout::Surround51 => {
+            MonoToAllSurround51::new_boxed(sample_rate, in_stream)
+        }
+        SpeakerLayout::Surround71 => {
+            MonoToAllSurround71::new_boxed(sample_rate, in_stream)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -22,14 +22,22 @@ struct FadeAdapter<T: Sample> {
     /// `None`, fade out will not occur, and the stream will end when it ends.
     fade_out: Option<Fader>,
     buf: Vec<MaybeUninit<T>>,
+    /// If the sound's end isn't known yet, the sound, so that we can fill it
+    /// in when the source stream runs out.
+    unmeasured_sound: Option<Arc<Sound>>,
+    /// Number of samples taken from the source stream so far.
+    samples_consumed: u64,
+    sample_rate: PosFloat,
 }
 
 impl<T: Sample> FadeAdapter<T> {
+    #[allow(clippy::too_many_arguments)] // (internal function, doesn't care)
     fn new_boxed(
-        sound: &Sound,
+        sound: &Arc<Sound>,
         fade_in: PosFloat,
         how_long_to_play_before_fade: Option<PosFloat>,
         fade_out: PosFloat,
+        fade_type: FadeType,
         sample_rate: PosFloat,
         speaker_layout: SpeakerLayout,
         source_stream: Box<dyn SoundReader<T>>,
@@ -67,20 +75,33 @@ impl<T: Sample> FadeAdapter<T> {
             samples_till_fade_out,
             samples_left,
             fade_in: Fader::maybe_start(
-                FadeType::Linear,
+                fade_type,
                 PosFloat::ZERO,
                 PosFloat::ONE,
                 fade_in,
             ),
             fade_out: Fader::maybe_start(
-                FadeType::Linear,
+                fade_type,
                 PosFloat::ONE,
                 PosFloat::ZERO,
                 fade_out,
             ),
             buf: vec![MaybeUninit::uninit(); 64],
+            unmeasured_sound: sound.end.get().is_none().then(|| sound.clone()),
+            samples_consumed: 0,
+            sample_rate,
         })
     }
+    /// The source stream has run out. If we didn't know where the sound
+    /// ended, now we do.
+    fn source_ended(&mut self) {
+        if let Some(sound) = self.unmeasured_sound.take() {
+            let frames = self.samples_consumed
+                / self.speaker_layout.get_num_channels() as u64;
+            let length = PosFloat::from(frames) / self.sample_rate;
+            sound.end.get_or_init(|| sound.start + length);
+        }
+    }
 }
 
 impl<T: Sample> SoundReader<f32> for FadeAdapter<T> {
@@ -112,6 +133,10 @@ impl<T: Sample> SoundReader<f32> for FadeAdapter<T> {
             panic!("bug in program's sound delegate: didn't read a whole sample frame at a time");
         }
         debug_assert!(amount_read <= amount_to_read);
+        self.samples_consumed += amount_read as u64;
+        if amount_read < amount_to_read {
+            self.source_ended();
+        }
         if amount_read == 0 {
             // we hit the end. prematurely? don't care. nothing left for us here
             self.samples_left = 0;
@@ -181,6 +206,7 @@ impl<T: Sample> SoundReader<f32> for FadeAdapter<T> {
         // should be fine...
         let buf = unsafe { std::mem::transmute(buf) };
         let result = self.source_stream.skip_coarse(count, buf);
+        self.samples_consumed += result;
         if result > 0 {
             self.samples_till_fade_out =
                 self.samples_till_fade_out.saturating_sub(result);
@@ -199,10 +225,13 @@ impl<T: Sample> SoundReader<f32> for FadeAdapter<T> {
         let buf: &mut [MaybeUninit<T>] = unsafe { std::mem::transmute(buf) };
         let mut rem = count.checked_sub(self.source_stream.skip_coarse(count, buf))
             .expect("bug in program's sound delegate: skip_coarse skipped too many samples!");
+        self.samples_consumed += count - rem;
         while rem > 0 {
             let amt = (buf.len() as u64).min(rem) as usize;
             let red = self.source_stream.read(&mut buf[..amt]);
+            self.samples_consumed += red as u64;
             if red == 0 {
+                self.source_ended();
                 // premature end? uh oh
                 self.samples_left = 0;
                 return false;
@@ -225,11 +254,12 @@ impl<T: Sample> SoundReader<f32> for FadeAdapter<T> {
 }
 
 pub(crate) fn new_fade_adapter(
-    sound: &Sound,
+    sound: &Arc<Sound>,
     stream: FormattedSoundStream,
     fade_in: PosFloat,
     length: Option<PosFloat>,
     fade_out: PosFloat,
+    fade_type: FadeType,
 ) -> Box<dyn SoundReader<f32>> {
     let FormattedSoundStream {
         sample_rate,
@@ -242,6 +272,7 @@ pub(crate) fn new_fade_adapter(
             fade_in,
             length,
             fade_out,
+            fade_type,
             sample_rate,
             speaker_layout,
             x,
@@ -251,6 +282,7 @@ pub(crate) fn new_fade_adapter(
             fade_in,
             length,
             fade_out,
+            fade_type,
             sample_rate,
             speaker_layout,
             x,
@@ -260,6 +292,7 @@ pub(crate) fn new_fade_adapter(
             fade_in,
             length,
             fade_out,
+            fade_type,
             sample_rate,
             speaker_layout,
             x,
@@ -269,6 +302,7 @@ pub(crate) fn new_fade_adapter(
             fade_in,
             length,
             fade_out,
+            fade_type,
             sample_rate,
             speaker_layout,
             x,
@@ -278,6 +312,7 @@ pub(crate) fn new_fade_adapter(
             fade_in,
             length,
             fade_out,
+            fade_type,
             sample_rate,
             speaker_layout,
             x,
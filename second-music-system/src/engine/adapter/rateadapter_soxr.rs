@@ -51,10 +51,14 @@ pub(crate) fn new_rate_adapter(
     ) {
         Ok(x) => x,
         Err(x) => {
-            delegate.warning(&format!(
-                "Unable to initialize resampler for {} -> {} Hz: {}",
-                in_sample_rate, out_sample_rate, x
-            ));
+            delegate.diagnostic(
+                DiagLevel::Error,
+                DiagCode::ResamplerFailed,
+                &format!(
+                    "Unable to initialize resampler for {} -> {} Hz: {}",
+                    in_sample_rate, out_sample_rate, x
+                ),
+            );
             return in_stream;
         }
     };
@@ -48,8 +48,9 @@ pub(crate) fn new_rate_adapter(
         in_buf_pos: 0,
         buffer_numerator,
         buffer_denominator,
-        output_numerator: *out_sample_rate,
-        output_denominator: *in_sample_rate,
+        // each output frame moves us `in/out` input frames along
+        output_numerator: *in_sample_rate,
+        output_denominator: *out_sample_rate,
         output_accumulator: 0.0,
         fini: false,
         num_channels,
@@ -97,7 +98,8 @@ impl SoundReader<f32> for RateAdapter {
             self.in_buf_pos = 0;
         }
         if out_produced > 0 || self.fini {
-            out_produced * self.num_channels as usize
+            // (already counted in samples, not sample frames)
+            out_produced
         } else {
             self.read(out)
         }
@@ -114,3 +116,70 @@ impl SoundReader<f32> for RateAdapter {
 
 // Sound because of note above about thread counts
 unsafe impl Send for RateAdapter {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Stereo, with each sample frame `[n, -n]` for `n` counting up from 0.
+    struct Ramp {
+        pos: usize,
+        len: usize,
+    }
+    impl SoundReader<f32> for Ramp {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            let frames = (buf.len() / 2).min(self.len - self.pos);
+            for (n, frame) in buf[..frames * 2].chunks_mut(2).enumerate() {
+                let value = (self.pos + n) as f32;
+                frame[0].write(value);
+                frame[1].write(-value);
+            }
+            self.pos += frames;
+            frames * 2
+        }
+    }
+
+    /// Resamples 100 frames of `Ramp`, returning the left channel.
+    fn resample(in_rate: f32, out_rate: f32) -> Vec<f32> {
+        let delegate: Arc<dyn SoundDelegate> =
+            Arc::new(crate::engine::NullSoundDelegate);
+        let mut adapter = new_rate_adapter(
+            &delegate,
+            Box::new(Ramp { pos: 0, len: 100 }),
+            2,
+            PosFloat::new_clamped(in_rate),
+            PosFloat::new_clamped(out_rate),
+        );
+        let mut ret = vec![];
+        let mut buf = [MaybeUninit::uninit(); 64];
+        loop {
+            let len = adapter.read(&mut buf);
+            if len == 0 {
+                break;
+            }
+            assert_eq!(len % 2, 0);
+            for frame in buf[..len].chunks(2) {
+                let (left, right) = unsafe {
+                    (frame[0].assume_init(), frame[1].assume_init())
+                };
+                assert_eq!(left, -right);
+                ret.push(left);
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn upsampling_repeats_frames() {
+        let out = resample(1000.0, 2000.0);
+        let expected: Vec<f32> = (0..200).map(|n| (n / 2) as f32).collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn downsampling_drops_frames() {
+        let out = resample(2000.0, 1000.0);
+        let expected: Vec<f32> = (0..50).map(|n| (n * 2) as f32).collect();
+        assert_eq!(out, expected);
+    }
+}
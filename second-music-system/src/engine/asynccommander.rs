@@ -0,0 +1,132 @@
+//! Sending commands from async code, over a bounded channel, so that a game
+//! that gets ahead of its `Engine` waits for it instead of piling up
+//! commands. Only available with the `async` feature.
+
+use super::*;
+
+use std::task::Poll;
+
+use crossbeam::channel::{bounded, TryRecvError, TrySendError};
+use futures::task::AtomicWaker;
+
+/// One `AsyncCommander`'s channel, as seen by the `Engine`.
+pub(super) struct AsyncChannel {
+    rx: Receiver<EngineCommand>,
+    /// Woken whenever the `Engine` makes room in the channel.
+    space: Arc<AtomicWaker>,
+}
+
+/// Sends commands to an `Engine` from async code. Unlike a `Commander`, it
+/// has a limited number of commands that can be waiting for the `Engine` at
+/// once; `send_async` waits for there to be room, rather than letting them
+/// pile up. Make one with `Engine::async_commander`.
+///
+/// Commands are sent a `TransactionBuilder` at a time. Put whatever commands
+/// you like into one (including questions, such as `is_flow_active`, whose
+/// `Response`s you can `.await`), then send it. Each transaction takes up
+/// one space in the channel, however many commands it holds.
+pub struct AsyncCommander {
+    tx: Sender<EngineCommand>,
+    space: Arc<AtomicWaker>,
+}
+
+impl AsyncCommander {
+    /// Sends all the commands in a `TransactionBuilder` to the `Engine`, at
+    /// once, waiting until there's room in the channel if it's full. If the
+    /// `Engine` is gone, the commands are quietly dropped.
+    pub async fn send_async(&mut self, transaction: TransactionBuilder) {
+        let mut command = Some(EngineCommand::Transaction {
+            commands: transaction.commands,
+        });
+        futures::future::poll_fn(|cx| {
+            let Err(command_back) =
+                self.try_send_command(command.take().unwrap())
+            else {
+                return Poll::Ready(());
+            };
+            self.space.register(cx.waker());
+            // (the `Engine` may have made room before we registered)
+            match self.try_send_command(command_back) {
+                Ok(()) => Poll::Ready(()),
+                Err(command_back) => {
+                    command = Some(command_back);
+                    Poll::Pending
+                }
+            }
+        })
+        .await
+    }
+    /// As `send_async`, but never waits. If the channel is full, gives the
+    /// transaction back.
+    pub fn try_send(
+        &mut self,
+        transaction: TransactionBuilder,
+    ) -> Result<(), TransactionBuilder> {
+        self.try_send_command(EngineCommand::Transaction {
+            commands: transaction.commands,
+        })
+        .map_err(|command| match command {
+            EngineCommand::Transaction { commands } => {
+                TransactionBuilder { commands }
+            }
+            _ => unreachable!(),
+        })
+    }
+    /// Tries to send a command, giving it back if the channel is full.
+    fn try_send_command(
+        &mut self,
+        command: EngineCommand,
+    ) -> Result<(), EngineCommand> {
+        match self.tx.try_send(command) {
+            Ok(()) | Err(TrySendError::Disconnected(_)) => Ok(()),
+            Err(TrySendError::Full(command)) => Err(command),
+        }
+    }
+}
+
+impl Engine {
+    /// Makes an `AsyncCommander` that can send commands to this `Engine`,
+    /// from async code, with room for up to `capacity` transactions to be
+    /// waiting for the `Engine` at once. (Make it before the `Engine` goes
+    /// off to its own thread.)
+    ///
+    /// This doesn't change how `Commander`s work; they still never wait.
+    pub fn async_commander(
+        &mut self,
+        capacity: NonZeroUsize,
+    ) -> AsyncCommander {
+        let (tx, rx) = bounded(capacity.get());
+        let space = Arc::new(AtomicWaker::new());
+        self.async_channels.push(AsyncChannel {
+            rx,
+            space: space.clone(),
+        });
+        AsyncCommander { tx, space }
+    }
+    /// Applies every command waiting in every `AsyncCommander`'s channel, and
+    /// forgets the channels of `AsyncCommander`s that have been dropped.
+    pub(super) fn receive_async_commands(&mut self) {
+        let mut n = 0;
+        while n < self.async_channels.len() {
+            let mut received = false;
+            let connected = loop {
+                match self.async_channels[n].rx.try_recv() {
+                    Ok(cmd) => {
+                        received = true;
+                        self.issue(cmd);
+                    }
+                    Err(TryRecvError::Empty) => break true,
+                    Err(TryRecvError::Disconnected) => break false,
+                }
+            };
+            if !connected {
+                self.async_channels.swap_remove(n);
+                continue;
+            }
+            if received {
+                self.async_channels[n].space.wake();
+            }
+            n += 1;
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! `EngineBuilder`, for setting up an `Engine` one named setting at a time.
+
+use super::*;
+
+/// Marks a required setting of an `EngineBuilder` that hasn't been given
+/// yet. An `EngineBuilder` can't `build` until it has a `delegate` and a
+/// `sample_rate`; leaving either out is a compile error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unset;
+
+/// Sets up an `Engine`, one named setting at a time. Start with
+/// `Engine::builder()`, give it (at least) a `delegate` and a `sample_rate`,
+/// change any other settings you like, then `build` it.
+///
+/// Once an `Engine` is built, these settings cannot be changed without
+/// building a new one. (Except for the loading runtime; see
+/// `Engine::replace_runtime`.)
+pub struct EngineBuilder<Delegate = Unset, SampleRate = Unset> {
+    delegate: Delegate,
+    sample_rate: SampleRate,
+    speaker_layout: SpeakerLayout,
+    num_threads: Option<NonZeroUsize>,
+    affinity: usize,
+    runtime: Option<Arc<SwappableRuntime>>,
+    buffer_budget: Option<usize>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+}
+
+impl EngineBuilder {
+    /// Starts setting up an `Engine`. (Same as `Engine::builder()`.)
+    pub fn new() -> EngineBuilder {
+        EngineBuilder {
+            delegate: Unset,
+            sample_rate: Unset,
+            speaker_layout: SpeakerLayout::Stereo,
+            num_threads: None,
+            affinity: 0,
+            runtime: None,
+            buffer_budget: None,
+        }
+    }
+}
+
+impl<Delegate, SampleRate> EngineBuilder<Delegate, SampleRate> {
+    /// **Required.** The `SoundDelegate` that will open sound files and hear
+    /// about problems.
+    pub fn delegate(
+        self,
+        delegate: Arc<dyn SoundDelegate>,
+    ) -> EngineBuilder<Arc<dyn SoundDelegate>, SampleRate> {
+        EngineBuilder {
+            delegate,
+            sample_rate: self.sample_rate,
+            speaker_layout: self.speaker_layout,
+            num_threads: self.num_threads,
+            affinity: self.affinity,
+            runtime: self.runtime,
+            buffer_budget: self.buffer_budget,
+        }
+    }
+    /// **Required.** Number of samples per second you will be outputting.
+    /// (You can have `turn_handle` output at a different rate later; see
+    /// `Engine::set_output_sample_rate`.)
+    pub fn sample_rate(
+        self,
+        sample_rate: PosFloat,
+    ) -> EngineBuilder<Delegate, PosFloat> {
+        EngineBuilder {
+            delegate: self.delegate,
+            sample_rate,
+            speaker_layout: self.speaker_layout,
+            num_threads: self.num_threads,
+            affinity: self.affinity,
+            runtime: self.runtime,
+            buffer_budget: self.buffer_budget,
+        }
+    }
+    /// What kind of speaker layout your listener has. Defaults to `Stereo`.
+    pub fn speaker_layout(mut self, speaker_layout: SpeakerLayout) -> Self {
+        self.speaker_layout = speaker_layout;
+        self
+    }
+    /// Exactly how many threads to use for decoding and streaming in the
+    /// background. Defaults to `None`, which picks a reasonable number based
+    /// on the number of available hardware threads. Ignored if you give your
+    /// own `runtime`.
+    pub fn num_threads(mut self, num_threads: Option<NonZeroUsize>) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+    /// Offset added to the core affinity of the background threads. Defaults
+    /// to 0. Ignored if you give your own `runtime`, or if the `switchyard`
+    /// feature is disabled.
+    pub fn affinity(mut self, affinity: usize) -> Self {
+        self.affinity = affinity;
+        self
+    }
+    /// A particular [`TaskRuntime`](trait.TaskRuntime.html) to use for
+    /// loading tasks, instead of making one. If you want to perform offline
+    /// rendering, or another task where you just want all loading to happen
+    /// synchronously, pass `Arc::new(ForegroundTaskRuntime)`. On a platform
+    /// with no threads, such as WebAssembly, pass a `CooperativeTaskRuntime`.
+    ///
+    /// If you don't give one, loading will happen in the background using a
+    /// purely internal `Switchyard` (or, if you've disabled the `switchyard`
+    /// feature, a `ThreadPoolTaskRuntime`).
+    pub fn runtime<Runtime: TaskRuntime>(
+        mut self,
+        runtime: Arc<Runtime>,
+    ) -> Self {
+        self.runtime = Some(Arc::new(SwappableRuntime::new(runtime)));
+        self
+    }
+    /// If `None` (the default), buffered sounds will be freed as soon as no
+    /// flow needs them. If `Some(x)`, buffered sounds will be kept in memory
+    /// in case they're needed again, until their total size exceeds `x`
+    /// bytes, at which point the least recently used ones are freed.
+    pub fn buffer_budget(mut self, buffer_budget: Option<usize>) -> Self {
+        self.buffer_budget = buffer_budget;
+        self
+    }
+}
+
+impl EngineBuilder<Arc<dyn SoundDelegate>, PosFloat> {
+    /// Builds the `Engine`.
+    pub fn build(self) -> Engine {
+        let runtime = self.runtime.unwrap_or_else(|| {
+            Arc::new(default_runtime(self.num_threads, self.affinity))
+        });
+        Engine::new_with_swappable_runtime(
+            self.delegate,
+            self.speaker_layout,
+            self.sample_rate,
+            runtime,
+            self.buffer_budget,
+        )
+    }
+}
+
+/// Makes the runtime an `Engine` uses if it isn't given one: a `Switchyard`.
+#[cfg(feature = "switchyard")]
+fn default_runtime(
+    num_threads: Option<NonZeroUsize>,
+    affinity: usize,
+) -> SwappableRuntime {
+    let num_logical_cores = num_cpus::get();
+    let num_threads = num_threads
+        .map(NonZeroUsize::get)
+        .unwrap_or_else(|| num_cpus::get() / 3)
+        .max(1);
+    use ::switchyard::{threads::ThreadAllocationOutput, Switchyard};
+    let runtime = Switchyard::new(
+        (0..num_threads).map(|i| ThreadAllocationOutput {
+            name: Some(format!("SMSworker{i}")),
+            ident: i,
+            stack_size: Some(1024 * 1024),
+            affinity: Some((i + affinity) % num_logical_cores),
+        }),
+        || (),
+    )
+    .expect("Unable to create Switchyard runtime");
+    SwappableRuntime::new(Arc::new(runtime))
+}
+
+/// Makes the runtime an `Engine` uses if it isn't given one: a
+/// `ThreadPoolTaskRuntime`, since the `switchyard` feature is disabled.
+#[cfg(not(feature = "switchyard"))]
+fn default_runtime(
+    num_threads: Option<NonZeroUsize>,
+    _affinity: usize,
+) -> SwappableRuntime {
+    let num_threads = num_threads.unwrap_or_else(|| {
+        let num_logical_cores = std::thread::available_parallelism()
+            .map(NonZeroUsize::get)
+            .unwrap_or(1);
+        NonZeroUsize::new(num_logical_cores / 3).unwrap_or(NonZeroUsize::MIN)
+    });
+    SwappableRuntime::new(Arc::new(ThreadPoolTaskRuntime::new(num_threads)))
+}
@@ -0,0 +1,322 @@
+//! The built-in effects that can be put on a MixControl. These are simple,
+//! cheap, and not particularly high quality. They're meant to give a game
+//! *some* sense of space without needing a separate DSP library.
+
+use super::*;
+
+/// Schroeder's comb filter lengths, relative to the longest one.
+const COMB_RATIOS: [f32; 4] = [29.7 / 43.7, 37.1 / 43.7, 41.1 / 43.7, 1.0];
+/// Schroeder's allpass filter lengths, in seconds. These don't scale with the
+/// reverb's `time`.
+const ALLPASS_TIMES: [f32; 2] = [0.005, 0.0017];
+const ALLPASS_GAIN: f32 = 0.7;
+/// How many sample frames longer each speaker channel's delay lines are than
+/// the previous channel's, so that the channels don't all ring in lockstep.
+const CHANNEL_SPREAD: usize = 23;
+/// Feedback of 1.0 or more would ring forever (or blow up).
+const MAX_FEEDBACK: f32 = 0.99;
+
+/// A circular buffer of samples, for one speaker channel.
+struct DelayLine {
+    buf: Vec<f32>,
+    pos: usize,
+}
+
+impl DelayLine {
+    fn new(len: usize) -> DelayLine {
+        DelayLine {
+            buf: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+    /// Returns the sample that went in `len` samples ago, i.e. the one that
+    /// the next `push` will replace.
+    fn peek(&self) -> f32 {
+        self.buf[self.pos]
+    }
+    fn push(&mut self, input: f32) {
+        self.buf[self.pos] = input;
+        self.pos += 1;
+        if self.pos == self.buf.len() {
+            self.pos = 0;
+        }
+    }
+}
+
+enum EffectKind {
+    /// One delay line per speaker channel.
+    Delay(Vec<DelayLine>),
+    /// Four combs and two allpasses per speaker channel.
+    Reverb(Vec<([DelayLine; 4], [DelayLine; 2])>),
+}
+
+pub(crate) struct EffectState {
+    kind: EffectKind,
+    num_channels: usize,
+    feedback: f32,
+    mix: f32,
+}
+
+impl EffectState {
+    pub fn new(
+        effect: &ChannelEffect,
+        num_channels: usize,
+        sample_rate: PosFloat,
+    ) -> EffectState {
+        let frames =
+            |seconds: f32| (seconds * *sample_rate).round().max(1.0) as usize;
+        let (kind, feedback, mix) = match *effect {
+            ChannelEffect::Delay {
+                time,
+                feedback,
+                mix,
+            } => (
+                EffectKind::Delay(
+                    (0..num_channels)
+                        .map(|n| {
+                            DelayLine::new(frames(*time) + n * CHANNEL_SPREAD)
+                        })
+                        .collect(),
+                ),
+                feedback,
+                mix,
+            ),
+            ChannelEffect::Reverb {
+                time,
+                feedback,
+                mix,
+            } => (
+                EffectKind::Reverb(
+                    (0..num_channels)
+                        .map(|n| {
+                            let spread = n * CHANNEL_SPREAD;
+                            (
+                                COMB_RATIOS.map(|ratio| {
+                                    DelayLine::new(
+                                        frames(*time * ratio) + spread,
+                                    )
+                                }),
+                                ALLPASS_TIMES
+                                    .map(|time| DelayLine::new(frames(time))),
+                            )
+                        })
+                        .collect(),
+                ),
+                feedback,
+                mix,
+            ),
+        };
+        EffectState {
+            kind,
+            num_channels,
+            feedback: (*feedback).min(MAX_FEEDBACK),
+            mix: (*mix).min(1.0),
+        }
+    }
+    /// Process a buffer of interleaved sample frames in place.
+    pub fn process(&mut self, buf: &mut [f32]) {
+        let feedback = self.feedback;
+        let dry_level = 1.0 - self.mix;
+        let wet_level = self.mix;
+        for frame in buf.chunks_exact_mut(self.num_channels) {
+            for (n, sample) in frame.iter_mut().enumerate() {
+                let input = *sample;
+                let wet = match &mut self.kind {
+                    EffectKind::Delay(lines) => {
+                        let line = &mut lines[n];
+                        let wet = line.peek();
+                        line.push(input + wet * feedback);
+                        wet
+                    }
+                    EffectKind::Reverb(lines) => {
+                        let (combs, allpasses) = &mut lines[n];
+                        let mut wet = 0.0;
+                        for comb in combs.iter_mut() {
+                            let delayed = comb.peek();
+                            comb.push(input + delayed * feedback);
+                            wet += delayed;
+                        }
+                        wet *= 1.0 / combs.len() as f32;
+                        for allpass in allpasses.iter_mut() {
+                            let delayed = allpass.peek();
+                            let output = delayed - ALLPASS_GAIN * wet;
+                            allpass.push(wet + ALLPASS_GAIN * output);
+                            wet = output;
+                        }
+                        wet
+                    }
+                };
+                *sample = input * dry_level + wet * wet_level;
+            }
+        }
+    }
+}
+
+/// How many sample frames to filter between recalculating the coefficients
+/// of a filter whose cutoff is gliding.
+const COEFFICIENT_INTERVAL: usize = 32;
+/// The lowest cutoff frequency we'll actually use, in Hz. Lower cutoffs make
+/// for unstable coefficients.
+const MIN_CUTOFF: f32 = 1.0;
+/// The highest cutoff frequency we'll actually use, relative to the Nyquist
+/// frequency. At exactly Nyquist, the coefficients are degenerate.
+const MAX_CUTOFF_RATIO: f32 = 0.98;
+/// The filter's Q. This one gives a Butterworth response: as flat as possible
+/// below the cutoff, with no resonant peak.
+const FILTER_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// A two-pole low-pass filter that can be put on a MixControl.
+pub(crate) struct LowPassFilter {
+    num_channels: usize,
+    sample_rate: PosFloat,
+    /// The cutoff frequency, relative to the Nyquist frequency. (Not in Hz,
+    /// because an exponential fade between such large numbers would
+    /// overflow.)
+    cutoff: Fader,
+    /// The filter's memory: the last two inputs and the last two outputs, for
+    /// each speaker channel. (Direct form I, which copes better than the
+    /// other forms with coefficients changing underneath it.)
+    state: Vec<[f32; 4]>,
+}
+
+impl LowPassFilter {
+    /// Make a new filter, with its cutoff at the Nyquist frequency (i.e.
+    /// doing nothing).
+    pub fn new(num_channels: usize, sample_rate: PosFloat) -> LowPassFilter {
+        LowPassFilter {
+            num_channels,
+            sample_rate,
+            cutoff: Fader::new(PosFloat::ONE),
+            state: vec![[0.0; 4]; num_channels],
+        }
+    }
+    fn nyquist(&self) -> PosFloat {
+        self.sample_rate * PosFloat::HALF
+    }
+    /// Start fading the cutoff frequency from wherever it is now to
+    /// `cutoff_hz`, over `fade_length` sample frames.
+    pub fn fade_cutoff_to(
+        &mut self,
+        fade_type: FadeType,
+        cutoff_hz: PosFloat,
+        fade_length: PosFloat,
+    ) {
+        self.cutoff = Fader::start(
+            fade_type,
+            self.cutoff.evaluate(),
+            cutoff_hz / self.nyquist(),
+            fade_length,
+        );
+    }
+    /// Step the cutoff frequency's fade by `count` sample frames.
+    pub fn step_by(&mut self, count: PosFloat) {
+        // (not `Iterator::step_by`!)
+        Fader::step_by(&mut self.cutoff, count);
+    }
+    /// Returns true if the cutoff is at or above the Nyquist frequency and
+    /// isn't going anywhere, in which case the filter does nothing.
+    pub fn is_bypassed(&self) -> bool {
+        self.cutoff.complete() && self.cutoff.evaluate() >= PosFloat::ONE
+    }
+    /// Returns `[b0, b1, b2, a1, a2]` for the given cutoff, normalized so
+    /// that a0 is 1. (From Robert Bristow-Johnson's "Audio EQ Cookbook".)
+    fn coefficients(&self, cutoff: PosFloat) -> [f32; 5] {
+        let min_cutoff = MIN_CUTOFF / *self.nyquist();
+        let cutoff = (*cutoff).clamp(min_cutoff, MAX_CUTOFF_RATIO);
+        let w0 = std::f32::consts::PI * cutoff;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * FILTER_Q);
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - cos) / a0;
+        let b0 = b1 * 0.5;
+        [b0, b1, b0, -2.0 * cos / a0, (1.0 - alpha) / a0]
+    }
+    /// Filter a buffer of interleaved sample frames in place. The cutoff is
+    /// evaluated as of the start of the buffer; stepping it is up to the
+    /// caller.
+    pub fn process(&mut self, buf: &mut [f32]) {
+        if self.is_bypassed() {
+            // (so that we start clean if the cutoff comes back down)
+            self.state.fill([0.0; 4]);
+            return;
+        }
+        let block_len = COEFFICIENT_INTERVAL * self.num_channels;
+        for (n, block) in buf.chunks_mut(block_len).enumerate() {
+            let t = PosFloat::from(n * COEFFICIENT_INTERVAL);
+            let [b0, b1, b2, a1, a2] =
+                self.coefficients(self.cutoff.evaluate_t(t));
+            for frame in block.chunks_exact_mut(self.num_channels) {
+                for (sample, state) in frame.iter_mut().zip(&mut self.state) {
+                    let [x1, x2, y1, y2] = *state;
+                    let input = *sample;
+                    let output =
+                        b0 * input + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+                    *state = [input, x1, output, y1];
+                    *sample = output;
+                }
+            }
+        }
+    }
+}
+
+/// Stereo width, by way of mid/side processing, that can be put on a
+/// MixControl. Only the front left and right speakers are affected (which,
+/// on stereo and headphone output, are all of them).
+pub(crate) struct StereoWidth {
+    num_channels: usize,
+    /// How much of the side (difference) signal to keep. 0.0 is mono, 1.0
+    /// leaves the sound alone, and more is wider.
+    width: Fader,
+}
+
+impl StereoWidth {
+    /// Make a new stereo width, at 1.0 (i.e. doing nothing). There must be
+    /// at least two speaker channels.
+    pub fn new(num_channels: usize) -> StereoWidth {
+        debug_assert!(num_channels >= 2);
+        StereoWidth {
+            num_channels,
+            width: Fader::new(PosFloat::ONE),
+        }
+    }
+    /// Start fading the width from wherever it is now to `width`, over
+    /// `fade_length` sample frames.
+    pub fn fade_width_to(
+        &mut self,
+        fade_type: FadeType,
+        width: PosFloat,
+        fade_length: PosFloat,
+    ) {
+        self.width =
+            Fader::start(fade_type, self.width.evaluate(), width, fade_length);
+    }
+    /// Step the width's fade by `count` sample frames.
+    pub fn step_by(&mut self, count: PosFloat) {
+        // (not `Iterator::step_by`!)
+        Fader::step_by(&mut self.width, count);
+    }
+    /// Returns true if the width is 1.0 and isn't going anywhere, in which
+    /// case this does nothing.
+    pub fn is_bypassed(&self) -> bool {
+        self.width.complete() && self.width.evaluate() == PosFloat::ONE
+    }
+    /// Process a buffer of interleaved sample frames in place. The width is
+    /// evaluated as of the start of the buffer; stepping it is up to the
+    /// caller.
+    pub fn process(&mut self, buf: &mut [f32]) {
+        if self.is_bypassed() {
+            return;
+        }
+        let block_len = COEFFICIENT_INTERVAL * self.num_channels;
+        for (n, block) in buf.chunks_mut(block_len).enumerate() {
+            let t = PosFloat::from(n * COEFFICIENT_INTERVAL);
+            let width = *self.width.evaluate_t(t);
+            for frame in block.chunks_exact_mut(self.num_channels) {
+                let mid = (frame[0] + frame[1]) * 0.5;
+                let side = (frame[0] - frame[1]) * 0.5 * width;
+                frame[0] = mid + side;
+                frame[1] = mid - side;
+            }
+        }
+    }
+}
@@ -20,8 +20,46 @@ macro_rules! op {
     }};
 }
 
+macro_rules! ternary_op {
+    ($stack:ident, |$a:ident, $b:ident, $c:ident| $expr:expr) => {{
+        assert!($stack.len() >= 3, "stack underflow");
+        let $c = $stack.pop().unwrap();
+        let $b = $stack.pop().unwrap();
+        let $a = $stack.pop().unwrap();
+        let result = $expr.into();
+        $stack.push(result);
+    }};
+}
+
+/// A small, fast, deterministic random number generator (SplitMix64). Used
+/// by the `random()` function, so that a given seed always results in the
+/// same sequence of "random" decisions.
+#[derive(Debug, Clone)]
+pub(crate) struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+    /// Returns a number in the range [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        // 24 bits is all the precision an f32 has
+        (self.next_u64() >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+    }
+}
+
 pub(crate) fn evaluate(
     flow_controls: &HashMap<CompactString, StringOrNumber>,
+    rng: &mut Rng,
     ops: &[PredicateOp],
 ) -> StringOrNumber {
     let mut stack: Vec<StringOrNumber> = Vec::with_capacity(16);
@@ -68,6 +106,37 @@ pub(crate) fn evaluate(
             Abs => op!(stack, |a| a.as_number().abs()),
             Sign => op!(stack, |a| a.as_number().signum()),
             Negate => op!(stack, |a| -a.as_number()),
+            Clamp => ternary_op!(stack, |x, lo, hi| x
+                .as_number()
+                .max(lo.as_number())
+                .min(hi.as_number())),
+            Lerp => ternary_op!(stack, |a, b, t| {
+                let a = a.as_number();
+                a + (b.as_number() - a) * t.as_number()
+            }),
+            Random => stack.push(rng.next_f32().into()),
+            // Numbers used as strings get their default formatting, as in
+            // `StringOrNumber::as_string`. `1 concat 2` is `"12"`, and
+            // `0.50 startswith 0.5` is true.
+            Concat => op!(stack, |a, b| {
+                let mut a = CompactString::from(a.as_string());
+                a.push_str(&b.as_string());
+                a
+            }),
+            StartsWith => {
+                op!(stack, |a, b| a.as_string().starts_with(&*b.as_string()))
+            }
+            Contains => {
+                op!(stack, |a, b| a.as_string().contains(&*b.as_string()))
+            }
+            Length => op!(stack, |a| a.as_string().chars().count() as f32),
+            Select => ternary_op!(stack, |condition, if_true, if_false| {
+                if condition.is_truthy() {
+                    if_true
+                } else {
+                    if_false
+                }
+            }),
         }
     }
     assert_eq!(stack.len(), 1, "stack left with more than one value???");
@@ -5,8 +5,9 @@ use crate::{PosFloat, SoundReader};
 /// Something that has opinions on how loud a particular mixer channel should
 /// be.
 pub(crate) trait VolumeGetter<ID: Debug> {
-    /// Called after every output buffer. You should step all your faders by
-    /// the given number of sample frames.
+    /// Called once after every output buffer, however many sounds were
+    /// mixed into it. You should step all your faders by the given number of
+    /// sample frames.
     fn step_faders_by(&mut self, #[allow(unused)] n: PosFloat) {}
     /// If the sound should stop playing, returns `None`. If the sound should
     /// play at a specific volume, returns `Some(volume)` instead. `t` is the
@@ -29,6 +30,135 @@ pub(crate) trait VolumeGetter<ID: Debug> {
     /// example, mark each components of the given identity as individually
     /// being still relevant.
     fn is_varying(&mut self, identity: &ID) -> Option<bool>;
+    /// As `get_volume`, but breaking the volume down by bus. Push a `Route`
+    /// onto `routes` (which will be empty) for each bus the sound should be
+    /// mixed onto. More than one route to the same bus is fine; their volumes
+    /// will be added together. If the sound should stop playing, returns
+    /// false.
+    ///
+    /// The default routes everything to bus 0, at the volume returned by
+    /// `get_volume`.
+    fn get_routes(
+        &mut self,
+        identity: &ID,
+        t: PosFloat,
+        routes: &mut Vec<Route>,
+    ) -> bool {
+        match self.get_volume(identity, t) {
+            None => false,
+            Some(volume) => {
+                routes.push((0, volume));
+                true
+            }
+        }
+    }
+    /// Returns the number of buses, including bus 0 and any taps. Called
+    /// once per `mix`.
+    fn num_buses(&mut self) -> usize {
+        1
+    }
+    /// Returns how many of the buses (counting back from the last one) are
+    /// taps: extra outputs that are neither processed nor added to `out`, but
+    /// left for the caller to pick up with `Mixer::tap_output`. Called once
+    /// per `mix`.
+    fn num_taps(&mut self) -> usize {
+        0
+    }
+    /// Returns how many of the buses just before the taps are separate
+    /// outputs: processed like any other bus, but not added to `out`, and
+    /// left for the caller to pick up with `Mixer::output`. Called once per
+    /// `mix`.
+    fn num_outputs(&mut self) -> usize {
+        0
+    }
+    /// Called once per `mix`, for every bus other than bus 0 (and the taps),
+    /// after all the sounds have been mixed onto it and before it's added to
+    /// the output (if it is). Called even if no sounds were routed to that
+    /// bus.
+    fn process_bus(
+        &mut self,
+        #[allow(unused)] bus: usize,
+        #[allow(unused)] buf: &mut [f32],
+    ) {
+    }
+    /// Fill `gains` (which has one element per speaker channel) with extra
+    /// gains to apply to each channel of the given sound, on top of its
+    /// volume. Return false if there are none, i.e. every gain would be 1.0.
+    /// Called once per sound per `mix`.
+    ///
+    /// The default returns false.
+    fn get_channel_gains(
+        &mut self,
+        #[allow(unused)] identity: &ID,
+        #[allow(unused)] gains: &mut [f32],
+    ) -> bool {
+        false
+    }
+}
+
+/// Where (some of) a sound is mixed, and how loud: a bus number and a volume.
+/// Bus 0 is the output itself. Every other bus gets its own buffer, which is
+/// processed and then added to the output once all the sounds are mixed.
+pub(crate) type Route = (usize, PosFloat);
+
+/// Add together the volumes of routes to the same bus, and remove any routes
+/// that are silent.
+fn merge_routes(routes: &mut Vec<Route>) {
+    if routes.len() > 1 {
+        routes.sort_unstable_by_key(|(bus, _)| *bus);
+        routes.dedup_by(|later, earlier| {
+            if later.0 == earlier.0 {
+                earlier.1 = earlier.1 + later.1;
+                true
+            } else {
+                false
+            }
+        });
+    }
+    routes.retain(|(_, volume)| *volume != PosFloat::ZERO);
+}
+
+/// Returns the given part of the given bus. (Bus 0 is `out`.)
+fn bus_slice<'a>(
+    out: &'a mut [f32],
+    bus_bufs: &'a mut [Vec<f32>],
+    bus: usize,
+    pos: usize,
+    len: usize,
+) -> &'a mut [f32] {
+    match bus {
+        0 => &mut out[pos..pos + len],
+        bus => &mut bus_bufs[bus - 1][pos..pos + len],
+    }
+}
+
+/// Add `src` to `dst` at the given volume. If `gains` is given, it has one
+/// gain per speaker channel, and `dst` and `src` must start at the beginning
+/// of a sample frame.
+fn add_to_bus(
+    dst: &mut [f32],
+    src: &[MaybeUninit<f32>],
+    volume: PosFloat,
+    gains: Option<&[f32]>,
+) {
+    if let Some(gains) = gains {
+        // hardest mode
+        for ((dst, src), gain) in
+            dst.iter_mut().zip(src.iter()).zip(gains.iter().cycle())
+        {
+            *dst += unsafe { *src.assume_init_ref() } * *volume * *gain;
+        }
+    } else if volume == PosFloat::ONE {
+        // easy mode
+        for (dst, src) in dst.iter_mut().zip(src.iter()) {
+            *dst += unsafe { *src.assume_init_ref() };
+        }
+    } else {
+        // hard mode
+        for (dst, src) in dst.iter_mut().zip(src.iter()) {
+            *dst += unsafe { *src.assume_init_ref() } * *volume;
+        }
+    }
 }
 
 impl<'a, T: VolumeGetter<ID>, ID: Debug> VolumeGetter<ID> for &'a mut T {
@@ -41,6 +171,26 @@ impl<'a, T: VolumeGetter<ID>, ID: Debug> VolumeGetter<ID> for &'a mut T {
     fn is_varying(&mut self, identity: &ID) -> Option<bool> {
         (*self).is_varying(identity)
     }
+    fn get_routes(
+        &mut self,
+        identity: &ID,
+        t: PosFloat,
+        routes: &mut Vec<Route>,
+    ) -> bool {
+        (*self).get_routes(identity, t, routes)
+    }
+    fn num_buses(&mut self) -> usize {
+        (*self).num_buses()
+    }
+    fn num_taps(&mut self) -> usize {
+        (*self).num_taps()
+    }
+    fn process_bus(&mut self, bus: usize, buf: &mut [f32]) {
+        (*self).process_bus(bus, buf)
+    }
+    fn get_channel_gains(&mut self, identity: &ID, gains: &mut [f32]) -> bool {
+        (*self).get_channel_gains(identity, gains)
+    }
 }
 
 struct Channel<ID: Debug> {
@@ -54,6 +204,15 @@ pub(crate) struct Mixer<ID: Debug> {
     /// audio.
     samples_per_frame: usize,
     next_output_sample_frame_number: u64,
+    /// Buffers for buses other than bus 0. The last `num_taps` are taps, and
+    /// the `num_outputs` before those are separate outputs.
+    bus_bufs: Vec<Vec<f32>>,
+    num_taps: usize,
+    num_outputs: usize,
+    /// Scratch space for `VolumeGetter::get_routes`.
+    routes: Vec<Route>,
+    /// Scratch space for `VolumeGetter::get_channel_gains`.
+    gains: Vec<f32>,
 }
 
 impl<ID: Debug> Mixer<ID> {
@@ -62,23 +221,38 @@ impl<ID: Debug> Mixer<ID> {
             channels: vec![],
             samples_per_frame,
             next_output_sample_frame_number: 0,
+            bus_bufs: vec![],
+            num_taps: 0,
+            num_outputs: 0,
+            routes: vec![],
+            gains: vec![1.0; samples_per_frame],
         }
     }
     pub fn play(&mut self, stream: Box<dyn SoundReader<f32>>, identity: ID) {
         self.channels.push(Channel { stream, identity });
     }
     /// Returns true if the channel lived, false if the channel died.
+    #[allow(clippy::too_many_arguments)] // (internal function, doesn't care)
     fn mix_channel<T: VolumeGetter<ID>>(
         channel: &mut Channel<ID>,
-        mut out: &mut [f32],
+        out: &mut [f32],
+        bus_bufs: &mut [Vec<f32>],
         mix_buf: &mut [MaybeUninit<f32>],
+        routes: &mut Vec<Route>,
+        gains: &mut [f32],
         mut volume_getter: T,
         samples_per_frame: usize,
     ) -> bool {
-        let mut accum_len = 0;
-        while !out.is_empty() {
-            debug_assert!(out.len() % samples_per_frame == 0);
-            debug_assert!(out.len() <= mix_buf.len());
+        let gains = volume_getter
+            .get_channel_gains(&channel.identity, gains)
+            .then_some(&*gains);
+        let mut pos = 0;
+        while pos < out.len() {
+            let remaining = out.len() - pos;
+            // (the faders only step after the whole buffer is mixed)
+            let start = PosFloat::from(pos / samples_per_frame);
+            debug_assert!(remaining % samples_per_frame == 0);
+            debug_assert!(remaining <= mix_buf.len());
             let stream = &mut channel.stream;
             let identity = &channel.identity;
             let is_varying = volume_getter.is_varying(identity);
@@ -88,85 +262,75 @@ impl<ID: Debug> Mixer<ID> {
                 }
                 Some(false) => {
                     // Time to mix!
-                    let out_frames = out.len() / samples_per_frame;
+                    let out_frames = remaining / samples_per_frame;
                     // (use the volume at the halfway point)
-                    let t = PosFloat::from(out_frames) * PosFloat::HALF;
-                    // Cache the volume given by the VolumeGetter, and use it
+                    let t =
+                        start + PosFloat::from(out_frames) * PosFloat::HALF;
+                    // Cache the routes given by the VolumeGetter, and use them
                     // for the whole buffer. We can do this because the volume
                     // is not currently varying.
-                    let volume = volume_getter.get_volume(identity, t);
-                    match volume {
+                    routes.clear();
+                    if !volume_getter.get_routes(identity, t, routes) {
                         // we're done here
-                        None => return false,
-                        Some(volume) => {
-                            if volume == PosFloat::ZERO {
-                                if !stream
-                                    .skip_precise(out.len() as u64, mix_buf)
-                                {
-                                    return false;
-                                }
-                                out.len()
-                            } else if volume == PosFloat::ONE {
-                                // easy mode
-                                let len =
-                                    stream.read(&mut mix_buf[..out.len()]);
-                                assert!(len % samples_per_frame == 0);
-                                for x in 0..len {
-                                    out[x] += unsafe {
-                                        *mix_buf[x].assume_init_ref()
-                                    };
-                                }
-                                len
-                            } else {
-                                // hard mode
-                                let len =
-                                    stream.read(&mut mix_buf[..out.len()]);
-                                assert!(len % samples_per_frame == 0);
-                                for x in 0..len {
-                                    out[x] += unsafe {
-                                        *mix_buf[x].assume_init_ref()
-                                    } * *volume;
-                                }
-                                len
-                            }
+                        return false;
+                    }
+                    merge_routes(routes);
+                    if routes.is_empty() {
+                        if !stream.skip_precise(remaining as u64, mix_buf) {
+                            return false;
                         }
+                        remaining
+                    } else {
+                        let len = stream.read(&mut mix_buf[..remaining]);
+                        assert!(len % samples_per_frame == 0);
+                        for &(bus, volume) in routes.iter() {
+                            add_to_bus(
+                                bus_slice(out, bus_bufs, bus, pos, len),
+                                &mix_buf[..len],
+                                volume,
+                                gains,
+                            );
+                        }
+                        len
                     }
                 }
                 Some(true) => {
                     // Time to bix!
-                    // We will have to call GetVolume every sample frame,
+                    // We will have to call GetRoutes every sample frame,
                     // because the volume is currently varying.
-                    let mut time_accumulator = PosFloat::HALF;
-                    let len = stream.read(&mut mix_buf[..out.len()]);
+                    let mut time_accumulator = start + PosFloat::HALF;
+                    let len = stream.read(&mut mix_buf[..remaining]);
                     assert!(len % samples_per_frame == 0);
                     for x in (0..len).step_by(samples_per_frame) {
-                        let volume = volume_getter
-                            .get_volume(identity, time_accumulator);
-                        time_accumulator = time_accumulator + PosFloat::ONE;
-                        match volume {
+                        routes.clear();
+                        if !volume_getter.get_routes(
+                            identity,
+                            time_accumulator,
+                            routes,
+                        ) {
                             // we're done here
-                            None => return false,
-                            Some(volume) => {
-                                if volume == PosFloat::ZERO {
-                                    // we have nothing to mix, and we assume
-                                    // we won't for the rest of the buffer
-                                    break;
-                                } else if volume == PosFloat::ONE {
-                                    // easy mode
-                                    for x in x..x + samples_per_frame {
-                                        out[x] += unsafe {
-                                            *mix_buf[x].assume_init_ref()
-                                        };
-                                    }
-                                } else {
-                                    // hard mode
-                                    for x in x..x + samples_per_frame {
-                                        out[x] += unsafe {
-                                            *mix_buf[x].assume_init_ref()
-                                        } * *volume;
-                                    }
-                                }
-                            }
+                            return false;
+                        }
+                        time_accumulator = time_accumulator + PosFloat::ONE;
+                        merge_routes(routes);
+                        if routes.is_empty() {
+                            // we have nothing to mix, and we assume we won't
+                            // for the rest of the buffer
+                            break;
+                        }
+                        for &(bus, volume) in routes.iter() {
+                            add_to_bus(
+                                bus_slice(
+                                    out,
+                                    bus_bufs,
+                                    bus,
+                                    pos + x,
+                                    samples_per_frame,
+                                ),
+                                &mix_buf[x..x + samples_per_frame],
+                                volume,
+                                gains,
+                            );
                         }
                     }
                     len
@@ -174,24 +338,21 @@ impl<ID: Debug> Mixer<ID> {
             };
             if len == 0 {
                 // (Maybe) done outputting forever
-                return accum_len != 0;
-            } else if len < out.len() {
-                // Need to mix a little bit more
-                out = &mut out[len..];
-                accum_len += len;
-                volume_getter.step_faders_by((len / samples_per_frame).into());
-                continue;
-            } else {
-                debug_assert_eq!(len, out.len());
-                volume_getter.step_faders_by((len / samples_per_frame).into());
-                // All done for now
-                return true;
+                return pos != 0;
             }
+            // Need to mix a little bit more?
+            pos += len;
         }
         true
     }
     /// Adds the active sounds to `out`. Unless you're combining more than one
     /// `Mixer`, you definitely *definitely* want to zero `out`.
+    ///
+    /// Sounds routed to buses other than 0 are mixed separately, then each of
+    /// those buses is passed to `VolumeGetter::process_bus` and added to
+    /// `out`. Separate outputs are processed, but not added to `out`; see
+    /// `output`. Taps are mixed the same way, but are left alone afterward;
+    /// see `tap_output`.
     pub fn mix<T: VolumeGetter<ID>>(
         &mut self,
         out: &mut [f32],
@@ -200,20 +361,93 @@ impl<ID: Debug> Mixer<ID> {
     ) {
         debug_assert!(out.len() % self.samples_per_frame == 0);
         debug_assert_eq!(out.len(), mix_buf.len());
+        self.bus_bufs
+            .resize_with(volume_getter.num_buses().max(1) - 1, Vec::new);
+        self.num_taps = volume_getter.num_taps().min(self.bus_bufs.len());
+        self.num_outputs = volume_getter
+            .num_outputs()
+            .min(self.bus_bufs.len() - self.num_taps);
+        for buf in self.bus_bufs.iter_mut() {
+            buf.clear();
+            buf.resize(out.len(), 0.0);
+        }
         self.channels.retain_mut(|channel| {
             Self::mix_channel(
                 channel,
                 out,
+                &mut self.bus_bufs,
                 mix_buf,
+                &mut self.routes,
+                &mut self.gains,
                 &mut volume_getter,
                 self.samples_per_frame,
             )
         });
+        let num_processed_buses = self.bus_bufs.len() - self.num_taps;
+        let num_summed_buses = num_processed_buses - self.num_outputs;
+        for (n, buf) in
+            self.bus_bufs[..num_processed_buses].iter_mut().enumerate()
+        {
+            volume_getter.process_bus(n + 1, buf);
+            if n < num_summed_buses {
+                for (dst, src) in out.iter_mut().zip(buf.iter()) {
+                    *dst += *src;
+                }
+            }
+        }
         let out_frames = out.len() / self.samples_per_frame;
+        volume_getter.step_faders_by(out_frames.into());
         self.next_output_sample_frame_number = self
             .next_output_sample_frame_number
             .wrapping_add(out_frames as u64);
     }
+    /// Returns what the given separate output (numbered from 0) got during
+    /// the last `mix`.
+    pub fn output(&self, output: usize) -> &[f32] {
+        let first_output =
+            self.bus_bufs.len() - self.num_taps - self.num_outputs;
+        &self.bus_bufs[first_output + output]
+    }
+    /// Returns what the given tap (numbered from 0) got during the last `mix`.
+    pub fn tap_output(&self, tap: usize) -> &[f32] {
+        let first_tap = self.bus_bufs.len() - self.num_taps;
+        &self.bus_bufs[first_tap + tap]
+    }
+    /// As `mix`, but throws away `num_samples` samples of each active sound
+    /// instead of mixing them. Sounds end, and faders step, just as they
+    /// would have if the sound had been mixed. `scratch_buf` may be any
+    /// (nonzero) number of whole sample frames long.
+    pub fn skip<T: VolumeGetter<ID>>(
+        &mut self,
+        num_samples: usize,
+        scratch_buf: &mut [MaybeUninit<f32>],
+        mut volume_getter: T,
+    ) {
+        debug_assert_eq!(num_samples % self.samples_per_frame, 0);
+        debug_assert_eq!(scratch_buf.len() % self.samples_per_frame, 0);
+        let out_frames = num_samples / self.samples_per_frame;
+        self.channels.retain_mut(|channel| {
+            if volume_getter.is_varying(&channel.identity).is_none() {
+                return false;
+            }
+            let t = PosFloat::from(out_frames) * PosFloat::HALF;
+            if volume_getter.get_volume(&channel.identity, t).is_none() {
+                return false;
+            }
+            channel.stream.skip_precise(num_samples as u64, scratch_buf)
+        });
+        volume_getter.step_faders_by(out_frames.into());
+        self.next_output_sample_frame_number = self
+            .next_output_sample_frame_number
+            .wrapping_add(out_frames as u64);
+    }
+    /// Iterates over the identities of all the sounds currently playing,
+    /// allowing them to be changed.
+    pub fn identities_mut(&mut self) -> impl Iterator<Item = &mut ID> {
+        self.channels
+            .iter_mut()
+            .map(|channel| &mut channel.identity)
+    }
     /// Similar to `mix` with empty buffers. Use this if you desperately need
     /// the mixer to notice that some sounds have died.
     pub fn bump<T: VolumeGetter<ID>>(&mut self, mut volume_getter: T) {
@@ -221,6 +455,41 @@ impl<ID: Debug> Mixer<ID> {
             volume_getter.is_varying(&channel.identity).is_some()
         });
     }
+    /// Returns the number of sounds currently playing.
+    pub fn num_playing(&self) -> usize {
+        self.channels.len()
+    }
+    /// Returns the number of sounds currently playing whose identities match
+    /// the given predicate.
+    pub fn count_playing(
+        &self,
+        mut predicate: impl FnMut(&ID) -> bool,
+    ) -> usize {
+        self.channels
+            .iter()
+            .filter(|channel| predicate(&channel.identity))
+            .count()
+    }
+    /// Stops the sound that has been playing the longest out of those whose
+    /// identities match the given predicate. Returns its identity, or `None`
+    /// if no sound matched.
+    pub fn stop_oldest(
+        &mut self,
+        mut predicate: impl FnMut(&ID) -> bool,
+    ) -> Option<ID> {
+        // `play` pushes and `retain` preserves order, so the first match is
+        // the oldest
+        let index = self
+            .channels
+            .iter()
+            .position(|channel| predicate(&channel.identity))?;
+        Some(self.channels.remove(index).identity)
+    }
+    /// Stops every sound whose identity matches the given predicate.
+    pub fn stop_all(&mut self, mut predicate: impl FnMut(&ID) -> bool) {
+        self.channels
+            .retain(|channel| !predicate(&channel.identity));
+    }
     /// Returns the sample *frame* number of the next output sample frame.
     /// Every time you call `mix`, this will increase by the number of sample
     /// *frames* you mix.
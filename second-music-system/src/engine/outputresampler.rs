@@ -0,0 +1,137 @@
+//! Converts the `Engine`'s mixed output from its own sample rate to the
+//! output device's, for when the two don't match. Uses linear interpolation,
+//! which is cheap and good enough for the small rate differences we expect
+//! (e.g. 44.1kHz vs. 48kHz).
+
+use super::*;
+
+/// The most (internal) sample frames to mix at a time.
+const MIX_CHUNK_FRAMES: usize = 1024;
+
+pub(crate) struct OutputResampler {
+    num_channels: usize,
+    /// How many internal sample frames pass per output sample frame.
+    step: f64,
+    /// The sample frames we're interpolating between, one after the other.
+    prev_and_next: Vec<f32>,
+    /// How far we are between `prev` and `next`, from 0 to 1.
+    frac: f64,
+    /// Mixed sample frames that haven't been used yet.
+    in_buf: Vec<f32>,
+    in_buf_pos: usize,
+}
+
+impl OutputResampler {
+    pub fn new(
+        num_channels: usize,
+        in_sample_rate: PosFloat,
+        out_sample_rate: PosFloat,
+    ) -> OutputResampler {
+        OutputResampler {
+            num_channels,
+            step: *in_sample_rate as f64 / *out_sample_rate as f64,
+            prev_and_next: vec![0.0; num_channels * 2],
+            // (so that the first output frame pulls in two input frames)
+            frac: 2.0,
+            in_buf: vec![],
+            in_buf_pos: 0,
+        }
+    }
+    /// Change the output sample rate without disturbing playback.
+    pub fn set_rates(
+        &mut self,
+        in_sample_rate: PosFloat,
+        out_sample_rate: PosFloat,
+    ) {
+        self.step = *in_sample_rate as f64 / *out_sample_rate as f64;
+    }
+    /// Fill `out` with resampled audio. `mix` will be called to produce more
+    /// audio at the internal sample rate whenever needed, with a buffer to
+    /// overwrite.
+    pub fn resample(
+        &mut self,
+        out: &mut [f32],
+        mut mix: impl FnMut(&mut [f32]),
+    ) {
+        let num_channels = self.num_channels;
+        let num_frames = out.len() / num_channels;
+        for (n, out_frame) in out.chunks_exact_mut(num_channels).enumerate() {
+            while self.frac >= 1.0 {
+                if self.in_buf_pos >= self.in_buf.len() {
+                    // Mix only about as much as this call will need, so that
+                    // we don't run (much) ahead of the output.
+                    let wanted = ((num_frames - n) as f64 * self.step).ceil();
+                    let wanted = (wanted as usize).clamp(1, MIX_CHUNK_FRAMES);
+                    self.in_buf.resize(wanted * num_channels, 0.0);
+                    mix(&mut self.in_buf);
+                    self.in_buf_pos = 0;
+                }
+                self.prev_and_next.copy_within(num_channels.., 0);
+                self.prev_and_next[num_channels..].copy_from_slice(
+                    &self.in_buf
+                        [self.in_buf_pos..self.in_buf_pos + num_channels],
+                );
+                self.in_buf_pos += num_channels;
+                self.frac -= 1.0;
+            }
+            let frac = self.frac as f32;
+            let (prev, next) = self.prev_and_next.split_at(num_channels);
+            for n in 0..num_channels {
+                out_frame[n] = prev[n] + (next[n] - prev[n]) * frac;
+            }
+            self.frac += self.step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resampled_tone_is_continuous() {
+        const IN_RATE: f64 = 44100.0;
+        const FREQUENCY: f64 = 441.0;
+        let step = IN_RATE / 48000.0;
+        let mut resampler = OutputResampler::new(
+            2,
+            PosFloat::new_clamped(IN_RATE as f32),
+            PosFloat::new_clamped(48000.0),
+        );
+        // a sine on the left, a cosine on the right
+        let tone = |frame: f64| {
+            let phase = frame / IN_RATE * FREQUENCY * std::f64::consts::TAU;
+            [phase.sin() as f32, phase.cos() as f32]
+        };
+        let mut mixed = 0;
+        let mut out_frames = 0;
+        for (n, chunk_frames) in
+            [1, 7, 1000, 333, 2048, 5, 4000].into_iter().enumerate()
+        {
+            let mut out = vec![f32::NAN; chunk_frames * 2];
+            resampler.resample(&mut out, |buf| {
+                for frame in buf.chunks_exact_mut(2) {
+                    frame.copy_from_slice(&tone(mixed as f64));
+                    mixed += 1;
+                }
+            });
+            // every output sample frame lands where it should on the tone,
+            // with no seams between calls
+            for (m, frame) in out.chunks_exact(2).enumerate() {
+                let expected = tone((out_frames + m) as f64 * step);
+                for (got, expected) in frame.iter().zip(expected) {
+                    assert!(
+                        (got - expected).abs() < 0.001,
+                        "chunk {n}, frame {m}: {got} vs. {expected}"
+                    );
+                }
+            }
+            out_frames += chunk_frames;
+            // and it doesn't mix much more than it needs to
+            // (the last output sample frame is between these two)
+            let needed = ((out_frames - 1) as f64 * step) as usize + 2;
+            assert!(mixed >= needed, "{mixed} < {needed}");
+            assert!(mixed <= needed + n + 1, "{mixed} > {needed} + {n}");
+        }
+    }
+}
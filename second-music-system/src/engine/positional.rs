@@ -0,0 +1,135 @@
+//! Works out how loud a positional sound should be on each speaker, given
+//! where it is relative to the listener. Only distance and horizontal
+//! direction are taken into account; a sound directly above the listener
+//! sounds the same as one directly in front.
+
+use super::*;
+
+/// The horizontal direction of each full-range speaker of a surround layout,
+/// in degrees clockwise from straight ahead, sorted by direction. (The
+/// subwoofer doesn't take part in panning.)
+const QUADRAPHONIC_SPEAKERS: &[(usize, f32)] =
+    &[(2, -135.0), (0, -45.0), (1, 45.0), (3, 135.0)];
+const SURROUND51_SPEAKERS: &[(usize, f32)] =
+    &[(4, -110.0), (0, -30.0), (2, 0.0), (1, 30.0), (5, 110.0)];
+const SURROUND71_SPEAKERS: &[(usize, f32)] = &[
+    (4, -150.0),
+    (6, -90.0),
+    (0, -30.0),
+    (2, 0.0),
+    (1, 30.0),
+    (7, 90.0),
+    (5, 150.0),
+];
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    if length == 0.0 {
+        a
+    } else {
+        [a[0] / length, a[1] / length, a[2] / length]
+    }
+}
+
+/// Fill `gains` (one per speaker channel of `layout`) with the gain of a
+/// sound at `position`, as heard by `listener`.
+pub(crate) fn positional_gains(
+    layout: SpeakerLayout,
+    listener: &Listener,
+    position: [f32; 3],
+    gains: &mut [f32],
+) {
+    let offset = sub(position, listener.position);
+    let distance = dot(offset, offset).sqrt();
+    let reference_distance = *listener.reference_distance;
+    let attenuation = if distance <= reference_distance {
+        1.0
+    } else {
+        reference_distance / distance
+    };
+    // (These have to be normalized, since they come out of the cross
+    // products with different lengths unless `up` is a unit vector, and
+    // that would skew the azimuth.)
+    let right = normalize(cross(listener.forward, listener.up));
+    let forward = normalize(cross(listener.up, right));
+    // Straight ahead is zero, clockwise (seen from above) is positive. A
+    // sound directly above, below, or on top of the listener comes out
+    // straight ahead.
+    let azimuth = dot(offset, right).atan2(dot(offset, forward));
+    gains.fill(0.0);
+    let speakers = match layout {
+        SpeakerLayout::Mono => {
+            gains[0] = attenuation;
+            return;
+        }
+        SpeakerLayout::Stereo | SpeakerLayout::Headphones => {
+            // Sounds behind the listener are panned as if they were in
+            // front.
+            let [left, right] = pan_gains(azimuth.sin());
+            gains[0] = left * attenuation;
+            gains[1] = right * attenuation;
+            return;
+        }
+        SpeakerLayout::Quadraphonic => QUADRAPHONIC_SPEAKERS,
+        SpeakerLayout::Surround51 => SURROUND51_SPEAKERS,
+        SpeakerLayout::Surround71 => SURROUND71_SPEAKERS,
+    };
+    // Pan between the two speakers on either side of the sound, with a
+    // constant-power pan law.
+    let azimuth = azimuth.to_degrees();
+    let next = speakers.iter().position(|(_, x)| *x > azimuth).unwrap_or(0);
+    let prev = next.checked_sub(1).unwrap_or(speakers.len() - 1);
+    let (prev_channel, prev_azimuth) = speakers[prev];
+    let (next_channel, next_azimuth) = speakers[next];
+    let span = (next_azimuth - prev_azimuth).rem_euclid(360.0);
+    let frac = (azimuth - prev_azimuth).rem_euclid(360.0) / span;
+    let (sin, cos) = (frac * std::f32::consts::FRAC_PI_2).sin_cos();
+    gains[prev_channel] = cos * attenuation;
+    gains[next_channel] = sin * attenuation;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn up_needs_no_normalizing() {
+        let unit = Listener::default();
+        let long = Listener {
+            up: [0.0, 5.0, 0.0],
+            ..unit
+        };
+        for layout in [SpeakerLayout::Stereo, SpeakerLayout::Surround71] {
+            for position in
+                [[1.0, 0.0, -1.0], [-3.0, 2.0, 0.5], [0.2, 0.0, 4.0]]
+            {
+                let mut expected = [0.0; 8];
+                let mut got = [0.0; 8];
+                positional_gains(layout, &unit, position, &mut expected);
+                positional_gains(layout, &long, position, &mut got);
+                for (expected, got) in expected.iter().zip(got.iter()) {
+                    assert!(
+                        (expected - got).abs() < 1e-6,
+                        "{layout:?} {position:?}: {expected:?} != {got:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Routed outputs: mixing some MixControls to their own output buffers
+//! instead of the main output, e.g. for a platform with more than one
+//! hardware output bus.
+
+use super::*;
+
+/// A MixControl that's being mixed to its own output, during a
+/// `turn_handle_routed`.
+pub(crate) struct RoutedOutput {
+    pub channel: CompactString,
+    /// What it got from the mixer so far, in the `Engine`'s speaker layout.
+    output: Vec<f32>,
+}
+
+impl Engine {
+    /// As `turn_handle`, but with some MixControls mixed to their own output
+    /// buffers instead of to `out`. Each entry in `routes` is the name of a
+    /// MixControl and the buffer it goes to. Every sound playing on that
+    /// MixControl is mixed into that buffer, and not into `out`; the
+    /// MixControls that aren't listed are mixed into `out` as usual. (A sound
+    /// that `send`s to a routed MixControl is mixed into that MixControl's
+    /// buffer at the send's level, and into its own channel's buffer as
+    /// usual.)
+    ///
+    /// Every buffer must be the same length as `out`, and in the `Engine`'s
+    /// speaker layout. Any existing data in the buffers is overwritten. A
+    /// routed MixControl keeps its volume, effect, filter, and stereo width.
+    /// List each MixControl at most once; if one is listed twice, only the
+    /// first buffer gets it.
+    ///
+    /// Routing only applies to audio that's mixed during this call. Audio
+    /// that was already mixed for a tap (see `add_tap`) comes out in `out`,
+    /// with every MixControl in it. Panics if `out` doesn't hold a whole
+    /// number of sample frames, if any buffer isn't the same length as
+    /// `out`, or if the output sample rate has been changed (see
+    /// `set_output_sample_rate`).
+    pub fn turn_handle_routed(
+        &mut self,
+        out: &mut [f32],
+        routes: &mut [(&str, &mut [f32])],
+    ) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        assert_eq!(out.len() % num_channels, 0);
+        assert!(
+            routes.iter().all(|(_, buf)| buf.len() == out.len()),
+            "turn_handle_routed: every buffer must be the same length"
+        );
+        assert!(
+            self.output_resampler.is_none(),
+            "turn_handle_routed: can't be used once the output sample rate \
+             has been changed"
+        );
+        self.routed_outputs = routes
+            .iter()
+            .map(|(channel, _)| RoutedOutput {
+                channel: channel.to_compact_string(),
+                output: Vec::with_capacity(out.len()),
+            })
+            .collect();
+        self.run_for((out.len() / num_channels) as u64, Some(out));
+        let routed_outputs = std::mem::take(&mut self.routed_outputs);
+        for ((_, buf), routed) in routes.iter_mut().zip(routed_outputs) {
+            // (anything from the main backlog came before what we mixed)
+            let start = buf.len() - routed.output.len();
+            buf[..start].fill(0.0);
+            buf[start..].copy_from_slice(&routed.output);
+        }
+    }
+    /// Keep what the routed outputs got from the mixer's last `mix`.
+    pub(super) fn collect_routed_outputs(&mut self) {
+        for (n, routed) in self.routed_outputs.iter_mut().enumerate() {
+            routed.output.extend_from_slice(self.mixer.output(n));
+        }
+    }
+}
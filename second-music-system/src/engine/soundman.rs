@@ -1,4 +1,10 @@
-use std::{num::NonZeroUsize, sync::OnceLock};
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
 
 use super::*;
 
@@ -7,13 +13,90 @@ use buffer::*;
 mod stream;
 use stream::*;
 
+/// Held by whoever is waiting on a background load. When it's dropped (or
+/// `cancel` is called), the load is told to give up, so that we don't waste
+/// I/O on sounds we no longer need. A load that is already in the middle of
+/// decoding may still finish; its result is simply thrown away.
+pub(crate) struct LoadCanceller(Arc<AtomicBool>);
+
+/// Held by a background load, so that it can tell whether it's been
+/// cancelled.
+pub(crate) struct CancelToken(Arc<AtomicBool>);
+
+impl LoadCanceller {
+    pub fn new() -> (LoadCanceller, CancelToken) {
+        let flag = Arc::new(AtomicBool::new(false));
+        (LoadCanceller(flag.clone()), CancelToken(flag))
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+}
+
+impl Drop for LoadCanceller {
+    fn drop(&mut self) {
+        self.cancel()
+    }
+}
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Checks that a stream fresh from the delegate has a usable sample rate. If
+/// it doesn't, tells the delegate about it, and returns false.
+fn check_stream_format(
+    delegate: &dyn SoundDelegate,
+    name: &str,
+    stream: &FormattedSoundStream,
+) -> bool {
+    if *stream.sample_rate == 0.0 {
+        delegate.diagnostic(
+            DiagLevel::Error,
+            DiagCode::BadStreamFormat,
+            &format!(
+                "Sound file {:?} was opened with a sample rate of zero, and \
+                 will be silent",
+                name
+            ),
+        );
+        return false;
+    }
+    true
+}
+
+/// Tells the delegate that a stream didn't come out in whole sample frames,
+/// which usually means the `SpeakerLayout` it was opened with is wrong.
+fn report_partial_frame(
+    delegate: &dyn SoundDelegate,
+    name: &str,
+    speaker_layout: SpeakerLayout,
+) {
+    delegate.diagnostic(
+        DiagLevel::Error,
+        DiagCode::BadStreamFormat,
+        &format!(
+            "Sound file {:?} was opened as {:?} ({} channels), but its \
+             samples didn't come out in whole frames of that many channels",
+            name,
+            speaker_layout,
+            speaker_layout.get_num_channels()
+        ),
+    );
+}
+
 pub(crate) trait SoundManSubtype<Runtime: TaskRuntime> {
     /// Load the given sound. Recursive; call `load` N times, and you have to
-    /// call `unload` N times before it will take effect.
+    /// call `unload` N times before it will take effect. If `urgent`, the
+    /// sound is needed right away, and any background loading gets one of
+    /// the `Urgent` `TaskType`s.
     fn load(
         &mut self,
         sound: &str,
         start: PosFloat,
+        urgent: bool,
         loading_rt: &Arc<Runtime>,
     );
     /// Unload the given sound. The sound will actually stick around if it's
@@ -26,6 +109,14 @@ pub(crate) trait SoundManSubtype<Runtime: TaskRuntime> {
     /// Returns whether the given sound is *ready*, i.e. currently loaded and
     /// not awaiting.
     fn is_ready(&mut self, sound: &str, start: PosFloat) -> bool;
+    /// Returns a rough estimate of how many seconds of audio still have to be
+    /// decoded before the given sound is ready, or `None` if we can't tell
+    /// (yet). Only meaningful for a sound that's been `load`ed.
+    fn seconds_remaining(
+        &mut self,
+        sound: &str,
+        start: PosFloat,
+    ) -> Option<f32>;
     /// Request an instance of the given sound. If it's preloaded, this simply
     /// returns a reference to the preloaded sound. If it's streamed, this
     /// returns the decoder state for the given sound, and will (if background
@@ -43,6 +134,9 @@ pub(crate) trait SoundManSubtype<Runtime: TaskRuntime> {
         start: PosFloat,
         end: &OnceLock<PosFloat>,
     ) -> Option<FormattedSoundStream>;
+    /// Use the given delegate for all future loads. Loads already underway
+    /// keep using the old one.
+    fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>);
 }
 
 #[derive(Debug, PartialEq)]
@@ -54,6 +148,10 @@ enum SoundType {
 struct SoundInfo {
     load_count: NonZeroUsize,
     sound_type: SoundType,
+    /// True if the sound would have been streamed, but was buffered because
+    /// of `cache_sound`. Its loads stay buffered after `uncache_sound`, until
+    /// they're all unloaded.
+    forced: bool,
 }
 
 pub(crate) struct SoundMan<Runtime: TaskRuntime> {
@@ -61,44 +159,80 @@ pub(crate) struct SoundMan<Runtime: TaskRuntime> {
     streamman: StreamMan<Runtime>,
     delegate: Arc<dyn SoundDelegate>,
     sound_infos: HashMap<CompactString, SoundInfo>,
+    /// Sound files that have been forced into the buffer cache with
+    /// `cache_sound`, regardless of their `stream` flag. Each one holds a
+    /// load on the `BufferMan` for as long as it's in here.
+    forced_buffers: HashSet<CompactString>,
     loading_rt: Arc<Runtime>,
 }
 
 pub(crate) trait GenericSoundMan: 'static + Send {
-    fn load(&mut self, sound: &Sound);
+    /// Start loading the given sound. If `urgent`, it's needed right away
+    /// (rather than being precached), and its loading jumps the queue.
+    fn load(&mut self, sound: &Sound, urgent: bool);
     fn unload(&mut self, sound: &Sound);
     fn is_ready(&mut self, sound: &Sound) -> bool;
+    /// Roughly how many seconds of audio still have to be decoded before the
+    /// given sound is ready, or `None` if we can't tell (yet).
+    fn seconds_remaining(&mut self, sound: &Sound) -> Option<f32>;
     fn get_sound(&mut self, sound: &Sound) -> Option<FormattedSoundStream>;
+    /// Force the given sound's file to be buffered instead of streamed, and
+    /// keep it loaded until `uncache_sound` is called. Loads of the sound that
+    /// are already streaming stay streamed until they are all unloaded.
+    fn cache_sound(&mut self, sound: &Sound);
+    /// Undo a previous `cache_sound`. Later loads will go back to respecting
+    /// the sound's `stream` flag, once the loads that were buffered because
+    /// of it have all been unloaded.
+    fn uncache_sound(&mut self, sound: &Sound);
+    /// How many bytes of sample data we're keeping loaded for buffered
+    /// sounds.
+    fn cached_sound_bytes(&self) -> usize;
+    /// How many different sound files are loaded for streaming.
+    fn num_streaming_sounds(&self) -> usize;
+    /// Use the given delegate for all future loads (and diagnostics). Loads
+    /// already underway keep using the old one, and sounds that are already
+    /// loaded stay as they are.
+    fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>);
 }
 
 impl<Runtime: TaskRuntime> SoundMan<Runtime> {
     pub fn new(
         delegate: Arc<dyn SoundDelegate>,
         loading_rt: Arc<Runtime>,
+        buffer_budget: Option<usize>,
     ) -> SoundMan<Runtime> {
         SoundMan {
-            bufferman: BufferMan::new(delegate.clone()),
+            bufferman: BufferMan::new(delegate.clone(), buffer_budget),
             streamman: StreamMan::new(delegate.clone(), &loading_rt),
             delegate,
             sound_infos: HashMap::new(),
+            forced_buffers: HashSet::new(),
             loading_rt,
         }
     }
 }
 
 impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
-    fn load(&mut self, sound: &Sound) {
+    fn load(&mut self, sound: &Sound, urgent: bool) {
+        let force_buffer = self.forced_buffers.contains(&sound.path);
         if let Some(info) = self.sound_infos.get_mut(&sound.path) {
-            let target_type = if sound.stream {
+            let target_type = if sound.stream && !force_buffer {
                 SoundType::Streamed
             } else {
                 SoundType::Buffered
             };
-            if target_type != info.sound_type {
-                self.delegate.warning(&format!(
-                    "sound file {:?} is both streamed and buffered",
-                    sound.path
-                ));
+            // (a forced buffer may still have streams in flight, and an
+            // uncached one may still have buffers in flight; that's fine)
+            if target_type != info.sound_type && !force_buffer && !info.forced
+            {
+                self.delegate.diagnostic(
+                    DiagLevel::Warning,
+                    DiagCode::StreamedAndBuffered,
+                    &format!(
+                        "sound file {:?} is both streamed and buffered",
+                        sound.path
+                    ),
+                );
             }
             // already loaded
             match info.sound_type {
@@ -106,6 +240,7 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
                     self.streamman.load(
                         &sound.path,
                         sound.start,
+                        urgent,
                         &self.loading_rt,
                     );
                     info.load_count = info.load_count.checked_add(1).unwrap();
@@ -114,6 +249,7 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
                     self.bufferman.load(
                         &sound.path,
                         sound.start,
+                        urgent,
                         &self.loading_rt,
                     );
                     info.load_count = info.load_count.checked_add(1).unwrap();
@@ -121,11 +257,12 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
             }
         } else {
             // not yet loaded
-            let sound_type = if sound.stream {
+            let sound_type = if sound.stream && !force_buffer {
                 // load it as a streaming sound
                 self.streamman.load(
                     &sound.path,
                     sound.start,
+                    urgent,
                     &self.loading_rt,
                 );
                 SoundType::Streamed
@@ -133,6 +270,7 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
                 self.bufferman.load(
                     &sound.path,
                     sound.start,
+                    urgent,
                     &self.loading_rt,
                 );
                 SoundType::Buffered
@@ -142,6 +280,7 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
                 SoundInfo {
                     sound_type,
                     load_count: NonZeroUsize::new(1).unwrap(),
+                    forced: sound.stream && force_buffer,
                 },
             );
         }
@@ -149,7 +288,7 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
     fn unload(&mut self, sound: &Sound) {
         match self.sound_infos.get_mut(&sound.path) {
             None => {
-                self.delegate.warning(&format!("unbalanced unload of sound file {:?} (THIS IS A BUG IN SMS)", sound.path));
+                self.delegate.diagnostic(DiagLevel::Bug, DiagCode::UnbalancedUnload, &format!("unbalanced unload of sound file {:?} (THIS IS A BUG IN SMS)", sound.path));
             }
             Some(sound_info) => {
                 match sound_info.sound_type {
@@ -184,6 +323,16 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
             }) => self.streamman.is_ready(&sound.path, sound.start),
         }
     }
+    fn seconds_remaining(&mut self, sound: &Sound) -> Option<f32> {
+        match self.sound_infos.get(&sound.path)?.sound_type {
+            SoundType::Buffered => {
+                self.bufferman.seconds_remaining(&sound.path, sound.start)
+            }
+            SoundType::Streamed => {
+                self.streamman.seconds_remaining(&sound.path, sound.start)
+            }
+        }
+    }
     fn get_sound(&mut self, sound: &Sound) -> Option<FormattedSoundStream> {
         match self.sound_infos.get(&sound.path) {
             None => None, // not being loaded, therefore not ready
@@ -196,7 +345,11 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
                     sound.start,
                     &sound.end,
                 );
-                sound.end.get_or_init(|| todo!());
+                // (a sound that's still loading has no stream yet, and may not
+                // know where it ends yet either)
+                if result.is_some() {
+                    sound.end.get_or_init(|| todo!());
+                }
                 result
             }
             Some(SoundInfo {
@@ -208,4 +361,96 @@ impl<Runtime: TaskRuntime> GenericSoundMan for SoundMan<Runtime> {
             }
         }
     }
+    fn cache_sound(&mut self, sound: &Sound) {
+        if self.forced_buffers.insert(sound.path.clone()) {
+            self.bufferman.load(
+                &sound.path,
+                sound.start,
+                false,
+                &self.loading_rt,
+            );
+        }
+    }
+    fn uncache_sound(&mut self, sound: &Sound) {
+        if self.forced_buffers.remove(&sound.path) {
+            self.bufferman.unload(&sound.path, sound.start);
+        }
+    }
+    fn cached_sound_bytes(&self) -> usize {
+        self.bufferman.loaded_bytes()
+    }
+    fn num_streaming_sounds(&self) -> usize {
+        self.sound_infos
+            .values()
+            .filter(|x| x.sound_type == SoundType::Streamed)
+            .count()
+    }
+    fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>) {
+        self.bufferman.replace_delegate(delegate.clone());
+        self.streamman.replace_delegate(delegate.clone());
+        self.delegate = delegate;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::engine::testing::ReportingDelegate;
+
+    struct Silence(usize);
+    impl SoundReader<f32> for Silence {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            let len = buf.len().min(self.0);
+            buf[..len].fill(MaybeUninit::new(0.0));
+            self.0 -= len;
+            len
+        }
+    }
+
+    /// Every file is a tenth of a second of mono silence.
+    fn open_silence(_name: &str) -> Option<FormattedSoundStream> {
+        Some(FormattedSoundStream {
+            sample_rate: PosFloat::new_clamped(1000.0),
+            speaker_layout: SpeakerLayout::Mono,
+            reader: FormattedSoundReader::F32(Box::new(Silence(100))),
+        })
+    }
+
+    #[test]
+    fn caching_can_be_undone_and_redone() {
+        let soundtrack = Soundtrack::from_source(
+            r#"sound s
+  file s.wav
+  stream
+"#,
+        )
+        .unwrap();
+        let sound = soundtrack.sounds.get("s").unwrap();
+        let delegate = ReportingDelegate::new(open_silence);
+        let mut soundman = SoundMan::new(
+            delegate.clone(),
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        );
+        soundman.cache_sound(sound);
+        soundman.load(sound, false);
+        assert!(soundman.is_ready(sound));
+        assert_eq!(soundman.num_streaming_sounds(), 0);
+        soundman.uncache_sound(sound);
+        soundman.load(sound, false);
+        soundman.unload(sound);
+        soundman.unload(sound);
+        // (the sound is streamed again, now that nothing's holding it in the
+        // cache)
+        soundman.load(sound, false);
+        assert_eq!(soundman.num_streaming_sounds(), 1);
+        soundman.cache_sound(sound);
+        soundman.load(sound, false);
+        soundman.uncache_sound(sound);
+        soundman.unload(sound);
+        soundman.unload(sound);
+        assert_eq!(soundman.num_streaming_sounds(), 0);
+        assert_eq!(delegate.codes(), []);
+    }
 }
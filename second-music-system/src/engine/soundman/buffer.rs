@@ -2,7 +2,10 @@ use super::*;
 
 use std::{
     marker::PhantomData,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Weak,
+    },
 };
 
 use crossbeam::channel;
@@ -33,7 +36,8 @@ enum WeakFormattedVec {
 
 impl FormattedVec {
     fn default() -> FormattedVec {
-        FormattedVec::U8(Arc::new(vec![0u8; 128]))
+        // (128 is silence for unsigned 8-bit samples)
+        FormattedVec::U8(Arc::new(vec![128u8; 128]))
     }
     fn len(&self) -> usize {
         match self {
@@ -44,6 +48,16 @@ impl FormattedVec {
             FormattedVec::F32(x) => x.len(),
         }
     }
+    /// How many bytes of sample data we're holding onto.
+    fn byte_len(&self) -> usize {
+        match self {
+            FormattedVec::U8(x) => x.len() * std::mem::size_of::<u8>(),
+            FormattedVec::U16(x) => x.len() * std::mem::size_of::<u16>(),
+            FormattedVec::I8(x) => x.len() * std::mem::size_of::<i8>(),
+            FormattedVec::I16(x) => x.len() * std::mem::size_of::<i16>(),
+            FormattedVec::F32(x) => x.len() * std::mem::size_of::<f32>(),
+        }
+    }
     fn downgrade(&self) -> WeakFormattedVec {
         match self {
             FormattedVec::U8(x) => WeakFormattedVec::U8(Arc::downgrade(x)),
@@ -59,6 +73,16 @@ impl WeakFormattedVec {
     fn default() -> WeakFormattedVec {
         WeakFormattedVec::U8(Weak::default())
     }
+    /// Returns true if the underlying vec has been freed.
+    fn is_dead(&self) -> bool {
+        match self {
+            WeakFormattedVec::U8(x) => x.strong_count() == 0,
+            WeakFormattedVec::U16(x) => x.strong_count() == 0,
+            WeakFormattedVec::I8(x) => x.strong_count() == 0,
+            WeakFormattedVec::I16(x) => x.strong_count() == 0,
+            WeakFormattedVec::F32(x) => x.strong_count() == 0,
+        }
+    }
     fn upgrade(&self) -> Option<FormattedVec> {
         match self {
             WeakFormattedVec::U8(x) => x.upgrade().map(FormattedVec::U8),
@@ -79,18 +103,68 @@ impl Format {
     }
 }
 
+/// How many sample frames to decode at a time when loading a whole sound,
+/// between chances for the runtime to interrupt us.
+const READ_CHUNK_FRAMES: usize = 4096;
+
+/// How far along a background load is. Written by the loading task, read by
+/// whoever wants to know how much longer it will take.
+struct LoadProgress {
+    /// The sound's sample rate, as the bits of an `f32`, or zero if the file
+    /// hasn't been opened yet.
+    sample_rate: AtomicU32,
+    /// The decoder's estimate of how many sample frames long the sound is, or
+    /// `u64::MAX` if it hasn't given one (yet).
+    estimated_frames: AtomicU64,
+    /// How many sample frames have been decoded so far.
+    decoded_frames: AtomicU64,
+}
+
+impl LoadProgress {
+    fn new() -> LoadProgress {
+        LoadProgress {
+            sample_rate: AtomicU32::new(0),
+            estimated_frames: AtomicU64::new(u64::MAX),
+            decoded_frames: AtomicU64::new(0),
+        }
+    }
+    /// Roughly how many seconds of audio are left to decode, if we know.
+    fn seconds_remaining(&self) -> Option<f32> {
+        let sample_rate =
+            f32::from_bits(self.sample_rate.load(Ordering::Relaxed));
+        let estimated_frames = self.estimated_frames.load(Ordering::Relaxed);
+        if sample_rate <= 0.0 || estimated_frames == u64::MAX {
+            return None;
+        }
+        let decoded_frames = self.decoded_frames.load(Ordering::Relaxed);
+        // (the estimate may have been too low; we're nearly done, then)
+        Some(
+            estimated_frames.saturating_sub(decoded_frames) as f32
+                / sample_rate,
+        )
+    }
+}
+
 enum CachedSound {
     /// A sound that hasn't been loaded yet, but whose loading has been
     /// requested.
     Loading {
         rx: channel::Receiver<(Format, FormattedVec)>,
+        /// If this is zero, the load has been cancelled.
         load_count: u32,
+        canceller: LoadCanceller,
+        progress: Arc<LoadProgress>,
     },
     /// A sound that has been loaded, and is currently being actively cached.
+    /// If there is a memory budget, `load_count` may be zero, in which case
+    /// we're only holding onto it in case it's needed again soon.
     Loaded {
         format: Format,
         vec: FormattedVec,
         load_count: u32,
+        /// When this sound was last loaded or played, according to
+        /// `BufferMan::clock`.
+        last_used: u64,
     },
     /// A sound that was previously loaded, but whose load count has gone to
     /// zero, and which will get purged if no active playback requires it.
@@ -102,25 +176,31 @@ enum CachedSound {
 
 impl CachedSound {
     /// If we are a `LoadingSound`, check if we should actually become a
-    /// `LoadedSound` instead. If so, mutate.
+    /// `LoadedSound` instead. If so, mutate, and return true.
     fn check_loading(
         &mut self,
         delegate: &Arc<dyn SoundDelegate>,
         name: &str,
-    ) {
-        if let CachedSound::Loading { load_count, rx } = self {
+        now: u64,
+    ) -> bool {
+        if let CachedSound::Loading { load_count, rx, .. } = self {
             match rx.try_recv() {
                 Ok((format, vec)) if *load_count > 0 => {
                     *self = CachedSound::Loaded {
                         load_count: *load_count,
                         format,
                         vec,
+                        last_used: now,
                     };
+                    return true;
                 }
-                Ok(_) if *load_count == 0 => {
+                Ok(_) | Err(channel::TryRecvError::Disconnected)
+                    if *load_count == 0 =>
+                {
                     // Great, thanks for loading! But we don't want you anymore
                     // (it makes no sense to put it straight in as a weak vec,
-                    // because it would immediately be freed)
+                    // because it would immediately be freed) Or, we told you
+                    // to stop, and you did.
                     *self = CachedSound::Unloaded {
                         format: Format::default(),
                         vec: WeakFormattedVec::default(),
@@ -130,26 +210,41 @@ impl CachedSound {
                     // nothing to do right now
                 }
                 _ => {
-                    delegate.warning(&format!(
-                        "Background loading sound {:?} failed",
-                        name
-                    ));
+                    delegate.diagnostic(
+                        DiagLevel::Error,
+                        DiagCode::LoadFailed,
+                        &format!("Background loading sound {:?} failed", name),
+                    );
                     *self = CachedSound::Loaded {
                         load_count: *load_count,
                         format: Format::default(),
                         vec: FormattedVec::default(),
+                        last_used: now,
                     };
                 }
             }
         }
+        false
     }
 }
 
 /// Manages cached *sounds*. We load them completely, ahead of time. We use a
 /// lot of memory, but our returned streams are very simple to decode.
+///
+/// If we have a memory budget, sounds that are no longer needed are kept
+/// around until the budget is exceeded, at which point the least recently
+/// used ones are let go.
 pub struct BufferMan<Runtime: TaskRuntime> {
     delegate: Arc<dyn SoundDelegate>,
     sounds: HashMap<String, CachedSound>,
+    /// Maximum number of bytes of sample data to keep loaded, if any.
+    budget: Option<usize>,
+    /// Incremented every time a sound is loaded or played. Used to find the
+    /// least recently used sound.
+    clock: u64,
+    /// Whether we've already warned about being over budget (so that we
+    /// don't warn again until we've gotten back under it).
+    warned_over_budget: bool,
     _marker: PhantomData<Runtime>,
 }
 
@@ -158,14 +253,30 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
         &mut self,
         sound: &str,
         _start: PosFloat,
+        urgent: bool,
         loading_rt: &Arc<Runtime>,
     ) {
+        let now = self.tick();
         if let Some(ent) = self.sounds.get_mut(sound) {
-            ent.check_loading(&self.delegate, sound);
+            let became_loaded = ent.check_loading(&self.delegate, sound, now);
             match ent {
-                CachedSound::Loading { load_count, .. }
-                | CachedSound::Loaded { load_count, .. } => {
+                CachedSound::Loading { load_count, .. } if *load_count > 0 => {
+                    *load_count += 1;
+                    return;
+                }
+                CachedSound::Loading { .. } => {
+                    // We already cancelled this load. Start a new one.
+                }
+                CachedSound::Loaded {
+                    load_count,
+                    last_used,
+                    ..
+                } => {
                     *load_count += 1;
+                    *last_used = now;
+                    if became_loaded {
+                        self.enforce_budget();
+                    }
                     return;
                 }
                 CachedSound::Unloaded { format, vec } => {
@@ -174,7 +285,9 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
                             load_count: 1,
                             format: format.clone(),
                             vec,
+                            last_used: now,
                         };
+                        self.enforce_budget();
                         return;
                     }
                 }
@@ -186,32 +299,62 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
         let delegate = self.delegate.clone();
         let sound = sound.to_string();
         let sound_clone = sound.clone();
-        loading_rt.spawn_task(TaskType::BufferLoad, async move {
-            let _ = result_tx.send(load_whole_sound(&delegate, &sound_clone));
+        let (canceller, token) = LoadCanceller::new();
+        let progress = Arc::new(LoadProgress::new());
+        let progress_clone = progress.clone();
+        let kind = if urgent {
+            TaskType::UrgentBufferLoad
+        } else {
+            TaskType::BufferLoad
+        };
+        loading_rt.spawn_task(kind, async move {
+            if let Some(result) = load_whole_sound(
+                &delegate,
+                &sound_clone,
+                &token,
+                &progress_clone,
+            )
+            .await
+            {
+                let _ = result_tx.send(result);
+            }
         });
         self.sounds.insert(
             sound.to_string(),
             CachedSound::Loading {
                 load_count: 1,
                 rx: result_rx,
+                canceller,
+                progress,
             },
         );
     }
     fn unload(&mut self, sound: &str, _start: PosFloat) -> bool {
         match self.sounds.get_mut(sound) {
             None | Some(CachedSound::Unloaded { .. }) => {
-                self.delegate.warning(&format!(
+                self.delegate.diagnostic(
+                    DiagLevel::Bug,
+                    DiagCode::UnbalancedUnload,
+                    &format!(
                     "unbalanced unload of sound {:?} (THIS IS A BUG IN SMS!)",
                     sound
-                ));
+                ),
+                );
                 true
             }
             Some(x) => match x {
-                CachedSound::Loading { load_count, .. } => {
+                CachedSound::Loading {
+                    load_count,
+                    canceller,
+                    ..
+                } => {
                     if *load_count > 0 {
                         *load_count -= 1;
+                        if *load_count == 0 {
+                            canceller.cancel();
+                        }
                     } else {
-                        self.delegate.warning(&format!("unbalanced unload of sound {:?} (THIS IS A BUG IN SMS!)", sound));
+                        self.delegate.diagnostic(DiagLevel::Bug, DiagCode::UnbalancedUnload, &format!("unbalanced unload of sound {:?} (THIS IS A BUG IN SMS!)", sound));
                     }
                     *load_count == 0
                 }
@@ -219,10 +362,20 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
                     load_count,
                     format,
                     vec,
+                    ..
                 } => {
                     if *load_count > 1 {
                         *load_count -= 1;
                         false
+                    } else if *load_count == 0 {
+                        self.delegate.diagnostic(DiagLevel::Bug, DiagCode::UnbalancedUnload, &format!("unbalanced unload of sound {:?} (THIS IS A BUG IN SMS!)", sound));
+                        true
+                    } else if self.budget.is_some() {
+                        // Hang onto it for now. `enforce_budget` will let go
+                        // of it if we need the space.
+                        *load_count = 0;
+                        self.enforce_budget();
+                        true
                     } else {
                         *x = CachedSound::Unloaded {
                             format: format.clone(),
@@ -238,25 +391,52 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
     fn unload_all(&mut self) {
         self.sounds.clear();
     }
+    fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>) {
+        self.delegate = delegate;
+    }
     fn is_ready(&mut self, sound: &str, _start: PosFloat) -> bool {
+        let now = self.clock;
         if let Some(x) = self.sounds.get_mut(sound) {
-            x.check_loading(&self.delegate, sound);
+            let became_loaded = x.check_loading(&self.delegate, sound, now);
             if let CachedSound::Loaded { .. } = x {
+                if became_loaded {
+                    self.enforce_budget();
+                }
                 return true;
             }
         }
         false
     }
+    fn seconds_remaining(
+        &mut self,
+        sound: &str,
+        _start: PosFloat,
+    ) -> Option<f32> {
+        match self.sounds.get(sound) {
+            Some(CachedSound::Loading { progress, .. }) => {
+                progress.seconds_remaining()
+            }
+            Some(CachedSound::Loaded { .. }) => Some(0.0),
+            _ => None,
+        }
+    }
     fn get_sound(
         &mut self,
         sound: &str,
         start: PosFloat,
         end: &OnceLock<PosFloat>,
     ) -> Option<FormattedSoundStream> {
+        let now = self.tick();
         self.sounds.get_mut(sound).and_then(|s| {
-            s.check_loading(&self.delegate, sound);
+            s.check_loading(&self.delegate, sound, now);
             match s {
-                CachedSound::Loaded { format, vec, .. } => {
+                CachedSound::Loaded {
+                    format,
+                    vec,
+                    last_used,
+                    ..
+                } => {
+                    *last_used = now;
                     Some(new_buffer_stream(format, vec.clone(), start, end))
                 }
                 CachedSound::Unloaded { format, vec } => {
@@ -271,13 +451,84 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for BufferMan<Runtime> {
 }
 
 impl<Runtime: TaskRuntime> BufferMan<Runtime> {
-    pub fn new(delegate: Arc<dyn SoundDelegate>) -> BufferMan<Runtime> {
+    pub fn new(
+        delegate: Arc<dyn SoundDelegate>,
+        budget: Option<usize>,
+    ) -> BufferMan<Runtime> {
         BufferMan {
             delegate,
             sounds: HashMap::new(),
+            budget,
+            clock: 0,
+            warned_over_budget: false,
             _marker: PhantomData,
         }
     }
+    /// How many bytes of sample data we're keeping loaded. (Sounds that we've
+    /// let go of, but that are still being played, don't count.)
+    pub fn loaded_bytes(&self) -> usize {
+        self.sounds
+            .values()
+            .map(|sound| match sound {
+                CachedSound::Loaded { vec, .. } => vec.byte_len(),
+                _ => 0,
+            })
+            .sum()
+    }
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+    /// Forget about sounds that have been completely freed, and, if we're
+    /// over budget, let go of the least recently used sounds that nobody
+    /// currently needs.
+    fn enforce_budget(&mut self) {
+        self.sounds.retain(|_, sound| match sound {
+            CachedSound::Unloaded { vec, .. } => !vec.is_dead(),
+            _ => true,
+        });
+        let Some(budget) = self.budget else { return };
+        let mut total = self.loaded_bytes();
+        while total > budget {
+            let victim = self
+                .sounds
+                .iter()
+                .filter_map(|(name, sound)| match sound {
+                    CachedSound::Loaded {
+                        load_count: 0,
+                        last_used,
+                        ..
+                    } => Some((*last_used, name)),
+                    _ => None,
+                })
+                .min()
+                .map(|(_, name)| name.clone());
+            let Some(victim) = victim else {
+                if !self.warned_over_budget {
+                    self.delegate.diagnostic(
+                        DiagLevel::Warning,
+                        DiagCode::OverBudget,
+                        &format!(
+                        "buffered sounds are using {} bytes, which is over \
+                         the budget of {} bytes, but all of them are in use",
+                        total, budget
+                    ),
+                    );
+                    self.warned_over_budget = true;
+                }
+                return;
+            };
+            let sound = self.sounds.get_mut(&victim).unwrap();
+            if let CachedSound::Loaded { format, vec, .. } = sound {
+                total -= vec.byte_len();
+                *sound = CachedSound::Unloaded {
+                    format: format.clone(),
+                    vec: vec.downgrade(),
+                };
+            }
+        }
+        self.warned_over_budget = false;
+    }
 }
 
 #[derive(Clone)]
@@ -366,53 +617,86 @@ fn new_buffer_stream(
 }
 
 impl<T: Sample> BufferStream<T> {
-    fn read_whole_sound(stream: &mut Box<dyn SoundReader<T>>) -> Vec<T> {
+    /// Returns `None` if the load was cancelled partway through. Yields after
+    /// every `READ_CHUNK_FRAMES` sample frames.
+    async fn read_whole_sound(
+        stream: &mut Box<dyn SoundReader<T>>,
+        num_channels: usize,
+        token: &CancelToken,
+        progress: &LoadProgress,
+    ) -> Option<Vec<T>> {
         let mut ret = Vec::new();
         if let Some(len) = stream.estimate_len() {
-            if let Ok(len) = len.try_into() {
+            progress.estimated_frames.store(len, Ordering::Relaxed);
+            if let Some(len) = len
+                .checked_mul(num_channels as u64)
+                .and_then(|x| x.try_into().ok())
+            {
                 ret.resize_with(len, MaybeUninit::uninit);
             }
         }
         let mut amount_read = 0;
+        let chunk_len = READ_CHUNK_FRAMES * num_channels;
         loop {
+            if token.is_cancelled() {
+                return None;
+            }
             let rem_capacity = ret.len() - amount_read;
-            let wanted_capacity = 32768; // why not
-            if rem_capacity < wanted_capacity {
-                let grew = ret.len().checked_mul(2).unwrap().max(32768);
+            if rem_capacity < chunk_len {
+                let grew = ret
+                    .len()
+                    .checked_mul(2)
+                    .unwrap()
+                    .max(amount_read + chunk_len);
                 ret.resize_with(grew, MaybeUninit::uninit);
             }
-            let len = stream.read(&mut ret[amount_read..]);
+            let len =
+                stream.read(&mut ret[amount_read..amount_read + chunk_len]);
             if len == 0 {
                 break;
             }
             amount_read += len;
+            progress
+                .decoded_frames
+                .store((amount_read / num_channels) as u64, Ordering::Relaxed);
+            yield_now().await;
         }
         unsafe {
             ret.set_len(amount_read);
             ret.shrink_to_fit();
-            std::mem::transmute(ret)
+            Some(std::mem::transmute::<Vec<MaybeUninit<T>>, Vec<T>>(ret))
         }
     }
 }
 
-fn read_whole_sound(reader: &mut FormattedSoundReader) -> FormattedVec {
-    match reader {
-        FormattedSoundReader::U8(x) => {
-            FormattedVec::U8(Arc::new(BufferStream::read_whole_sound(x)))
-        }
-        FormattedSoundReader::U16(x) => {
-            FormattedVec::U16(Arc::new(BufferStream::read_whole_sound(x)))
-        }
-        FormattedSoundReader::I8(x) => {
-            FormattedVec::I8(Arc::new(BufferStream::read_whole_sound(x)))
-        }
-        FormattedSoundReader::I16(x) => {
-            FormattedVec::I16(Arc::new(BufferStream::read_whole_sound(x)))
-        }
-        FormattedSoundReader::F32(x) => {
-            FormattedVec::F32(Arc::new(BufferStream::read_whole_sound(x)))
-        }
-    }
+async fn read_whole_sound(
+    reader: &mut FormattedSoundReader,
+    num_channels: usize,
+    token: &CancelToken,
+    progress: &LoadProgress,
+) -> Option<FormattedVec> {
+    Some(match reader {
+        FormattedSoundReader::U8(x) => FormattedVec::U8(Arc::new(
+            BufferStream::read_whole_sound(x, num_channels, token, progress)
+                .await?,
+        )),
+        FormattedSoundReader::U16(x) => FormattedVec::U16(Arc::new(
+            BufferStream::read_whole_sound(x, num_channels, token, progress)
+                .await?,
+        )),
+        FormattedSoundReader::I8(x) => FormattedVec::I8(Arc::new(
+            BufferStream::read_whole_sound(x, num_channels, token, progress)
+                .await?,
+        )),
+        FormattedSoundReader::I16(x) => FormattedVec::I16(Arc::new(
+            BufferStream::read_whole_sound(x, num_channels, token, progress)
+                .await?,
+        )),
+        FormattedSoundReader::F32(x) => FormattedVec::F32(Arc::new(
+            BufferStream::read_whole_sound(x, num_channels, token, progress)
+                .await?,
+        )),
+    })
 }
 
 impl<T: Sample> SoundReader<T> for BufferStream<T> {
@@ -432,8 +716,14 @@ impl<T: Sample> SoundReader<T> for BufferStream<T> {
         self.cursor += len;
         len
     }
-    fn seek(&mut self, _in_pos: u64) -> Option<u64> {
-        panic!("SMS logic error: seeking a BufferStream");
+    fn seek(&mut self, pos: u64) -> Option<u64> {
+        // The whole sound is right here, so we can land exactly where we were
+        // asked to (or at the end, if that's past it).
+        let pos = pos
+            .saturating_mul(self.num_channels as u64)
+            .min(self.end as u64) as usize;
+        self.cursor = pos;
+        Some((pos / self.num_channels) as u64)
     }
     fn attempt_clone(
         &self,
@@ -452,19 +742,31 @@ impl<T: Sample> SoundReader<T> for BufferStream<T> {
     fn skip_coarse(&mut self, count: u64, _buf: &mut [MaybeUninit<T>]) -> u64 {
         let count = count.min(usize::MAX as u64) as usize;
         let old_cursor = self.cursor;
-        self.cursor = self.cursor.saturating_add(count).min(self.vec.len());
+        self.cursor = self.cursor.saturating_add(count).min(self.end);
         (self.cursor - old_cursor) as u64
     }
 }
 
-fn load_whole_sound(
+/// Returns `None` if the load was cancelled.
+async fn load_whole_sound(
     delegate: &Arc<dyn SoundDelegate>,
     name: &str,
-) -> (Format, FormattedVec) {
-    match delegate.open_file(name) {
+    token: &CancelToken,
+    progress: &LoadProgress,
+) -> Option<(Format, FormattedVec)> {
+    if token.is_cancelled() {
+        return None;
+    }
+    Some(match delegate.open_file(name) {
         None => {
-            delegate
-                .warning(&format!("Unable to open sound file: {:?}", name));
+            delegate.diagnostic(
+                DiagLevel::Error,
+                DiagCode::OpenFailed,
+                &format!("Unable to open sound file: {:?}", name),
+            );
+            (Format::default(), FormattedVec::default())
+        }
+        Some(stream) if !check_stream_format(&**delegate, name, &stream) => {
             (Format::default(), FormattedVec::default())
         }
         Some(mut stream) => {
@@ -472,8 +774,213 @@ fn load_whole_sound(
                 sample_rate: stream.sample_rate,
                 speaker_layout: stream.speaker_layout,
             };
-            let buf = read_whole_sound(&mut stream.reader);
+            progress
+                .sample_rate
+                .store(stream.sample_rate.to_bits(), Ordering::Relaxed);
+            let buf = read_whole_sound(
+                &mut stream.reader,
+                stream.speaker_layout.get_num_channels(),
+                token,
+                progress,
+            )
+            .await?;
+            if let Some(error) = stream.reader.take_error() {
+                // (better silence than a truncated sound)
+                delegate.diagnostic(
+                    DiagLevel::Error,
+                    DiagCode::DecodeFailed,
+                    &format!(
+                        "Sound file {:?} failed partway through decoding: {}",
+                        name, error
+                    ),
+                );
+                return Some((Format::default(), FormattedVec::default()));
+            }
+            if buf.len() % stream.speaker_layout.get_num_channels() != 0 {
+                // (the layout is wrong, so none of it can be trusted)
+                report_partial_frame(&**delegate, name, stream.speaker_layout);
+                return Some((Format::default(), FormattedVec::default()));
+            }
             (format, buf)
         }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::engine::testing::ReportingDelegate;
+
+    /// Full-scale DC, for as many samples as it's made with.
+    struct Dc(usize);
+    impl SoundReader<f32> for Dc {
+        fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+            let len = buf.len().min(self.0);
+            buf[..len].fill(MaybeUninit::new(1.0));
+            self.0 -= len;
+            len
+        }
+    }
+
+    /// Every file is 100 frames of mono DC.
+    fn open_dc(_name: &str) -> Option<FormattedSoundStream> {
+        Some(FormattedSoundStream {
+            sample_rate: PosFloat::new_clamped(1000.0),
+            speaker_layout: SpeakerLayout::Mono,
+            reader: FormattedSoundReader::F32(Box::new(Dc(100))),
+        })
+    }
+
+    #[test]
+    fn cancelled_loads_are_abandoned() {
+        /// As `Dc`, but counts the samples read from it, and says when it's
+        /// dropped (i.e. when the load that opened it is over).
+        struct CountingDc(usize, Arc<AtomicU64>, channel::Sender<()>);
+        impl SoundReader<f32> for CountingDc {
+            fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+                let len = buf.len().min(self.0);
+                buf[..len].fill(MaybeUninit::new(1.0));
+                self.0 -= len;
+                self.1.fetch_add(len as u64, Ordering::Relaxed);
+                len
+            }
+        }
+        impl Drop for CountingDc {
+            fn drop(&mut self) {
+                let _ = self.2.send(());
+            }
+        }
+        /// Blocks in `open_file` until it's released, so that loads can be
+        /// cancelled while they're under way.
+        struct BlockingDelegate {
+            opened: channel::Sender<()>,
+            release: channel::Receiver<()>,
+            finished: channel::Sender<()>,
+            samples_read: Arc<AtomicU64>,
+        }
+        impl SoundDelegate for BlockingDelegate {
+            fn open_file(&self, _name: &str) -> Option<FormattedSoundStream> {
+                self.opened.send(()).unwrap();
+                self.release.recv().unwrap();
+                Some(FormattedSoundStream {
+                    sample_rate: PosFloat::new_clamped(1000.0),
+                    speaker_layout: SpeakerLayout::Mono,
+                    reader: FormattedSoundReader::F32(Box::new(CountingDc(
+                        100,
+                        self.samples_read.clone(),
+                        self.finished.clone(),
+                    ))),
+                })
+            }
+        }
+        let (opened_tx, opened) = channel::unbounded();
+        let (release, release_rx) = channel::unbounded();
+        let (finished_tx, finished) = channel::unbounded();
+        let samples_read = Arc::new(AtomicU64::new(0));
+        let mut bufferman = BufferMan::new(
+            Arc::new(BlockingDelegate {
+                opened: opened_tx,
+                release: release_rx,
+                finished: finished_tx,
+                samples_read: samples_read.clone(),
+            }),
+            None,
+        );
+        let rt = Arc::new(ThreadPoolTaskRuntime::new(
+            std::num::NonZeroUsize::new(1).unwrap(),
+        ));
+        let timeout = std::time::Duration::from_secs(10);
+        // Cancel one load by unloading it, and another by dropping it
+        // outright, while each is stuck opening its file.
+        for (name, unload_all) in [("a", false), ("b", true)] {
+            bufferman.load(name, PosFloat::ZERO, false, &rt);
+            opened.recv_timeout(timeout).unwrap();
+            if unload_all {
+                bufferman.unload_all();
+            } else {
+                bufferman.unload(name, PosFloat::ZERO);
+            }
+            release.send(()).unwrap();
+            finished.recv_timeout(timeout).unwrap();
+            assert_eq!(samples_read.load(Ordering::Relaxed), 0);
+            assert!(!bufferman.is_ready(name, PosFloat::ZERO));
+            assert_eq!(bufferman.loaded_bytes(), 0);
+        }
+        // (a load that isn't cancelled reads the whole file, and sticks)
+        bufferman.load("c", PosFloat::ZERO, false, &rt);
+        opened.recv_timeout(timeout).unwrap();
+        release.send(()).unwrap();
+        finished.recv_timeout(timeout).unwrap();
+        assert_eq!(samples_read.load(Ordering::Relaxed), 100);
+        // (the result may take a moment to arrive after the reader is gone)
+        let deadline = std::time::Instant::now() + timeout;
+        while !bufferman.is_ready("c", PosFloat::ZERO) {
+            assert!(std::time::Instant::now() < deadline);
+            std::thread::yield_now();
+        }
+        assert_eq!(bufferman.loaded_bytes(), 400);
+    }
+
+    #[test]
+    fn budget_lets_go_of_least_recently_used_sounds_first() {
+        let delegate = ReportingDelegate::new(open_dc);
+        // (room for two of the 400-byte sounds, but not three)
+        let mut bufferman = BufferMan::new(delegate.clone(), Some(1000));
+        let rt = Arc::new(ForegroundTaskRuntime);
+        let end = OnceLock::new();
+        for name in ["a", "b", "c"] {
+            bufferman.load(name, PosFloat::ZERO, false, &rt);
+            assert!(bufferman.is_ready(name, PosFloat::ZERO));
+        }
+        // (over budget, but every sound is in use, so nothing can go)
+        assert_eq!(bufferman.loaded_bytes(), 1200);
+        assert_eq!(delegate.codes(), [DiagCode::OverBudget]);
+        for name in ["c", "a", "b"] {
+            assert!(bufferman.get_sound(name, PosFloat::ZERO, &end).is_some());
+        }
+        // (c is the least recently used, but it's still in use, so a goes)
+        bufferman.unload("a", PosFloat::ZERO);
+        assert_eq!(bufferman.loaded_bytes(), 800);
+        assert!(!bufferman.is_ready("a", PosFloat::ZERO));
+        // (back under budget, so these are kept around for now)
+        bufferman.unload("b", PosFloat::ZERO);
+        bufferman.unload("c", PosFloat::ZERO);
+        assert_eq!(bufferman.loaded_bytes(), 800);
+        // (making room for d lets go of c, which was used before b)
+        bufferman.load("d", PosFloat::ZERO, false, &rt);
+        assert!(bufferman.is_ready("d", PosFloat::ZERO));
+        assert_eq!(bufferman.loaded_bytes(), 800);
+        assert!(!bufferman.is_ready("c", PosFloat::ZERO));
+        assert!(bufferman.is_ready("b", PosFloat::ZERO));
+        assert_eq!(delegate.codes().len(), 1);
+    }
+
+    #[test]
+    fn seek_to_middle_of_buffer() {
+        let vec: Vec<f32> = (0..20).map(|x| x as f32).collect();
+        let format = Format {
+            sample_rate: PosFloat::new_clamped(10.0),
+            speaker_layout: SpeakerLayout::Stereo,
+        };
+        let end = OnceLock::new();
+        let stream = new_buffer_stream(
+            &format,
+            FormattedVec::F32(Arc::new(vec)),
+            PosFloat::ZERO,
+            &end,
+        );
+        let FormattedSoundReader::F32(mut reader) = stream.reader else {
+            unreachable!()
+        };
+        let mut buf = [MaybeUninit::uninit(); 4];
+        assert_eq!(reader.seek(5), Some(5));
+        assert_eq!(reader.read(&mut buf), 4);
+        let buf = buf.map(|x| unsafe { x.assume_init() });
+        assert_eq!(buf, [10.0, 11.0, 12.0, 13.0]);
+        // (past the end lands at the end)
+        assert_eq!(reader.seek(100), Some(10));
+        assert_eq!(reader.read(&mut [MaybeUninit::uninit(); 2]), 0);
+        assert_eq!(reader.seek(0), Some(0));
     }
 }
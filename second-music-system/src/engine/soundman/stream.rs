@@ -61,18 +61,161 @@ fn empty_stream() -> FormattedSoundStream {
     }
 }
 
+/// Wraps a stream we're handing out, so that if decoding fails partway
+/// through (or the stream hands back a partial sample frame), the delegate
+/// hears about it and the stream ends there for good.
+struct DecodeErrorReporter<T: Sample> {
+    inner: Box<dyn SoundReader<T>>,
+    delegate: Arc<dyn SoundDelegate>,
+    name: Arc<str>,
+    speaker_layout: SpeakerLayout,
+    failed: bool,
+}
+
+impl<T: Sample> SoundReader<T> for DecodeErrorReporter<T> {
+    fn read(&mut self, buf: &mut [MaybeUninit<T>]) -> usize {
+        if self.failed {
+            return 0;
+        }
+        let len = self.inner.read(buf);
+        let partial = len % self.speaker_layout.get_num_channels();
+        if partial != 0 {
+            report_partial_frame(
+                &*self.delegate,
+                &self.name,
+                self.speaker_layout,
+            );
+            self.failed = true;
+            return len - partial;
+        }
+        if len < buf.len() {
+            if let Some(error) = self.inner.take_error() {
+                self.delegate.diagnostic(
+                    DiagLevel::Error,
+                    DiagCode::DecodeFailed,
+                    &format!(
+                        "Sound file {:?} failed partway through decoding: {}",
+                        self.name, error
+                    ),
+                );
+                self.failed = true;
+            }
+        }
+        len
+    }
+    fn seek(&mut self, pos: u64) -> Option<u64> {
+        if self.failed {
+            // (there's nothing to loop back to)
+            return None;
+        }
+        self.inner.seek(pos)
+    }
+    fn skip_precise(
+        &mut self,
+        count: u64,
+        buf: &mut [MaybeUninit<T>],
+    ) -> bool {
+        !self.failed && self.inner.skip_precise(count, buf)
+    }
+    fn skip_coarse(&mut self, count: u64, buf: &mut [MaybeUninit<T>]) -> u64 {
+        if self.failed {
+            return 0;
+        }
+        self.inner.skip_coarse(count, buf)
+    }
+    fn can_be_cloned(&self) -> bool {
+        self.inner.can_be_cloned()
+    }
+    fn attempt_clone(
+        &self,
+        sample_rate: PosFloat,
+        speaker_layout: SpeakerLayout,
+    ) -> FormattedSoundStream {
+        report_decode_errors(
+            self.inner.attempt_clone(sample_rate, speaker_layout),
+            &self.delegate,
+            &self.name,
+        )
+    }
+    fn estimate_len(&mut self) -> Option<u64> {
+        self.inner.estimate_len()
+    }
+    fn take_error(&mut self) -> Option<String> {
+        // (we've already reported it)
+        None
+    }
+}
+
+fn wrap_reader<T: Sample>(
+    inner: Box<dyn SoundReader<T>>,
+    delegate: &Arc<dyn SoundDelegate>,
+    name: &Arc<str>,
+    speaker_layout: SpeakerLayout,
+) -> Box<dyn SoundReader<T>> {
+    Box::new(DecodeErrorReporter {
+        inner,
+        delegate: delegate.clone(),
+        name: name.clone(),
+        speaker_layout,
+        failed: false,
+    })
+}
+
+/// Wrap a stream in a `DecodeErrorReporter`.
+fn report_decode_errors(
+    stream: FormattedSoundStream,
+    delegate: &Arc<dyn SoundDelegate>,
+    name: &Arc<str>,
+) -> FormattedSoundStream {
+    let reader =
+        match stream.reader {
+            FormattedSoundReader::U8(x) => FormattedSoundReader::U8(
+                wrap_reader(x, delegate, name, stream.speaker_layout),
+            ),
+            FormattedSoundReader::U16(x) => FormattedSoundReader::U16(
+                wrap_reader(x, delegate, name, stream.speaker_layout),
+            ),
+            FormattedSoundReader::I8(x) => FormattedSoundReader::I8(
+                wrap_reader(x, delegate, name, stream.speaker_layout),
+            ),
+            FormattedSoundReader::I16(x) => FormattedSoundReader::I16(
+                wrap_reader(x, delegate, name, stream.speaker_layout),
+            ),
+            FormattedSoundReader::F32(x) => FormattedSoundReader::F32(
+                wrap_reader(x, delegate, name, stream.speaker_layout),
+            ),
+        };
+    FormattedSoundStream { reader, ..stream }
+}
+
+/// Returns `None` if the load was cancelled.
 fn load_stream(
     delegate: &dyn SoundDelegate,
     name: &str,
     start_point: PosFloat,
-) -> (FormattedSoundStream, bool) {
-    match delegate.open_file(name) {
+    token: &CancelToken,
+) -> Option<(FormattedSoundStream, bool)> {
+    if token.is_cancelled() {
+        return None;
+    }
+    Some(match delegate.open_file(name) {
         None => {
-            delegate
-                .warning(&format!("Unable to open sound file: {:?}", name));
+            delegate.diagnostic(
+                DiagLevel::Error,
+                DiagCode::OpenFailed,
+                &format!("Unable to open sound file: {:?}", name),
+            );
+            (empty_stream(), true)
+        }
+        Some(stream) if !check_stream_format(delegate, name, &stream) => {
             (empty_stream(), true)
         }
         Some(mut stream) => {
+            // Skipping to the start point may mean decoding a lot, so make
+            // sure we're still wanted first.
+            if token.is_cancelled() {
+                return None;
+            }
             let start_point =
                 start_point.seconds_to_frames(stream.sample_rate);
             let can_seek = match stream.reader.seek(start_point) {
@@ -102,7 +245,7 @@ fn load_stream(
             }
             (stream, can_seek)
         }
-    }
+    })
 }
 
 /// Tea, predicated on a question.
@@ -143,8 +286,13 @@ impl<T, F, U: Default> Default for Predicated<T, F, U> {
 /// may or may not have become available yet.
 enum CachedStream {
     /// A stream that hasn't been loaded yet, but whose loading has been
-    /// requested.
-    LoadingStream(channel::Receiver<(FormattedSoundStream, bool)>),
+    /// requested. If this is dropped before loading finishes, the load is
+    /// cancelled.
+    LoadingStream(
+        channel::Receiver<(FormattedSoundStream, bool)>,
+        #[allow(dead_code)] // (only here to be dropped)
+        LoadCanceller,
+    ),
     /// A stream that has been loaded, and is currently ready.
     LoadedStream(FormattedSoundStream, bool),
 }
@@ -154,18 +302,44 @@ impl CachedStream {
         delegate: Arc<dyn SoundDelegate>,
         name: String,
         start_point: PosFloat,
+        kind: TaskType,
         loading_runtime: &Arc<Runtime>,
     ) -> CachedStream {
         let (tx, rx) = channel::bounded(1);
-        loading_runtime.spawn_task(TaskType::StreamLoad, async move {
-            let _ = tx.send(load_stream(&*delegate, &name, start_point));
+        let (canceller, token) = LoadCanceller::new();
+        loading_runtime.spawn_task(kind, async move {
+            if let Some(result) =
+                load_stream(&*delegate, &name, start_point, &token)
+            {
+                let _ = tx.send(result);
+            }
         });
-        CachedStream::LoadingStream(rx)
+        CachedStream::LoadingStream(rx, canceller)
+    }
+    /// Finish positioning an already-open, seekable stream, by skipping the
+    /// given number of *samples* in the background. The stream won't become
+    /// ready until the skip is done.
+    fn begin_skipping<Runtime: TaskRuntime>(
+        mut stream: FormattedSoundStream,
+        residual: u64,
+        kind: TaskType,
+        loading_runtime: &Arc<Runtime>,
+    ) -> CachedStream {
+        let (tx, rx) = channel::bounded(1);
+        let (canceller, token) = LoadCanceller::new();
+        loading_runtime.spawn_task(kind, async move {
+            if token.is_cancelled() {
+                return;
+            }
+            stream.reader.skip(residual);
+            let _ = tx.send((stream, true));
+        });
+        CachedStream::LoadingStream(rx, canceller)
     }
     /// If we are a `LoadingStream`, check if we should actually become a
     /// `LoadedStream` instead. If so, mutate.
     fn check_loading(&mut self, delegate: &dyn SoundDelegate, name: &str) {
-        if let CachedStream::LoadingStream(rx) = self {
+        if let CachedStream::LoadingStream(rx, _) = self {
             match rx.try_recv() {
                 Ok((stream, can_seek)) => {
                     *self = CachedStream::LoadedStream(stream, can_seek)
@@ -174,10 +348,14 @@ impl CachedStream {
                     // nothing we can do right now
                 }
                 _ => {
-                    delegate.warning(&format!(
-                        "Background loading stream {:?} failed",
-                        name
-                    ));
+                    delegate.diagnostic(
+                        DiagLevel::Error,
+                        DiagCode::LoadFailed,
+                        &format!(
+                            "Background loading stream {:?} failed",
+                            name
+                        ),
+                    );
                     *self = CachedStream::LoadedStream(empty_stream(), true);
                 }
             }
@@ -206,6 +384,7 @@ impl AtStartPoint {
         delegate: &Arc<dyn SoundDelegate>,
         name: &str,
         start_point: PosFloat,
+        kind: TaskType,
         loading_runtime: &Arc<Runtime>,
     ) {
         self.loads += 1;
@@ -215,6 +394,7 @@ impl AtStartPoint {
                     delegate.clone(),
                     name.to_string(),
                     start_point,
+                    kind,
                     loading_runtime,
                 ));
             }
@@ -230,7 +410,7 @@ impl AtStartPoint {
         if let Predicated::Unknown(cached) = self.cloneable.as_mut() {
             cached.check_loading(&**delegate, sound);
             match cached {
-                CachedStream::LoadingStream(_) => (),
+                CachedStream::LoadingStream(..) => (),
                 CachedStream::LoadedStream(stream, can_seek) => {
                     let mut alt = FormattedSoundStream {
                         sample_rate: stream.sample_rate,
@@ -254,6 +434,7 @@ impl AtStartPoint {
                                 delegate.clone(),
                                 sound.to_string(),
                                 start_point,
+                                TaskType::StreamLoad,
                                 &loading_rt,
                             ));
                         }
@@ -303,8 +484,14 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
         &mut self,
         sound: &str,
         start: PosFloat,
+        urgent: bool,
         loading_rt: &Arc<Runtime>,
     ) {
+        let kind = if urgent {
+            TaskType::UrgentStreamLoad
+        } else {
+            TaskType::StreamLoad
+        };
         let individual_sound =
             if let Some(individual_sound) = self.sounds.get_mut(sound) {
                 individual_sound
@@ -320,6 +507,7 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
                     &self.delegate,
                     sound,
                     start,
+                    kind,
                     loading_rt,
                 );
             }
@@ -332,18 +520,22 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
                         let sought = child.reader.seek(target_point).expect(
                             "Bug in delegate: stream stopped being seekable!",
                         );
-                        if sought < target_point {
-                            // TODO: we should like to do this in a background
-                            // thread
-                            child.reader.skip(
-                                (target_point - sought)
-                                    * child.speaker_layout.get_num_channels()
-                                        as u64,
-                            );
-                        }
+                        let cloneable = if sought < target_point {
+                            // The seek landed early. Skipping the rest of the
+                            // way could mean decoding a lot, so don't do it
+                            // here in the sound thread.
+                            let residual = (target_point - sought)
+                                * child.speaker_layout.get_num_channels()
+                                    as u64;
+                            Predicated::Unknown(CachedStream::begin_skipping(
+                                child, residual, kind, loading_rt,
+                            ))
+                        } else {
+                            Predicated::Available(child)
+                        };
                         ent.insert(AtStartPoint {
                             loads: 1,
-                            cloneable: Predicated::Available(child),
+                            cloneable,
                         });
                     }
                     _ => {
@@ -354,6 +546,7 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
                                     self.delegate.clone(),
                                     sound.to_string(),
                                     start,
+                                    kind,
                                     loading_rt,
                                 ),
                             ),
@@ -368,8 +561,11 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
             if let Some(individual_sound) = self.sounds.get_mut(sound) {
                 individual_sound
             } else {
-                self.delegate
-                    .warning("SMS bug: unloaded something not loaded");
+                self.delegate.diagnostic(
+                    DiagLevel::Bug,
+                    DiagCode::UnbalancedUnload,
+                    "SMS bug: unloaded something not loaded",
+                );
                 return true;
             };
         match individual_sound.カンバン.entry(start) {
@@ -385,8 +581,11 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
                 }
             }
             VecMapEntry::Vacant(_) => {
-                self.delegate
-                    .warning("SMS bug: unloaded something not loaded");
+                self.delegate.diagnostic(
+                    DiagLevel::Bug,
+                    DiagCode::UnbalancedUnload,
+                    "SMS bug: unloaded something not loaded",
+                );
                 true
             }
         }
@@ -394,6 +593,9 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
     fn unload_all(&mut self) {
         self.sounds.clear();
     }
+    fn replace_delegate(&mut self, delegate: Arc<dyn SoundDelegate>) {
+        self.delegate = delegate;
+    }
     fn is_ready(&mut self, sound: &str, start: PosFloat) -> bool {
         let individual_sound =
             if let Some(individual_sound) = self.sounds.get_mut(sound) {
@@ -415,6 +617,15 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
             Predicated::Available(_) => true,
         }
     }
+    fn seconds_remaining(
+        &mut self,
+        _sound: &str,
+        _start: PosFloat,
+    ) -> Option<f32> {
+        // A stream is ready as soon as it's been opened and has decoded a
+        // little bit, which is next to nothing compared to a whole buffer.
+        Some(0.0)
+    }
     fn get_sound(
         &mut self,
         sound: &str,
@@ -426,7 +637,7 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
         let individual_sound = self.sounds.get_mut(sound)?;
         let カンバン = individual_sound.カンバン.get_mut(&start)?;
         カンバン.check_load(&self.delegate, sound, start, &self.loading_rt);
-        match カンバン.cloneable.as_mut() {
+        let stream = match カンバン.cloneable.as_mut() {
             Predicated::Unknown(_) => unreachable!(),
             Predicated::Unavailable(vec) => {
                 if let Some(i) = vec.iter_mut().position(|x| {
@@ -444,15 +655,17 @@ impl<Runtime: TaskRuntime> SoundManSubtype<Runtime> for StreamMan<Runtime> {
                             self.delegate.clone(),
                             sound.to_string(),
                             start,
+                            TaskType::StreamLoad,
                             &loading_rt,
                         ));
                     }
-                    Some(ret)
+                    ret
                 } else {
-                    None
+                    return None;
                 }
             }
-            Predicated::Available(parent) => Some(parent.attempt_clone()),
-        }
+            Predicated::Available(parent) => parent.attempt_clone(),
+        };
+        Some(report_decode_errors(stream, &self.delegate, &sound.into()))
     }
 }
@@ -0,0 +1,212 @@
+//! Taps: extra outputs from the same `Engine`, each with its own speaker
+//! layout, master volume, and MixControl gains. (For example, one mix per
+//! player in a split-screen game.) Every sound is only decoded once, and is
+//! mixed onto the main output and every tap in the same pass.
+
+use super::*;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// The most sample frames `turn_handle_tap` will mix at a time.
+const TAP_CHUNK_FRAMES: usize = 1024;
+/// The most sample frames of mixed output that will be kept for an output
+/// that isn't keeping up. (Beyond that, the oldest are thrown away.)
+const MAX_BACKLOG_FRAMES: usize = 65536;
+
+/// Identifies one of an `Engine`'s taps. See `Engine::add_tap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TapId(pub u32);
+
+pub(crate) struct Tap {
+    id: TapId,
+    speaker_layout: SpeakerLayout,
+    volume: PosFloat,
+    /// Gains for individual MixControls. MixControls that aren't in here are
+    /// at 1.0.
+    channel_gains: HashMap<CompactString, PosFloat>,
+    /// Mixed sample frames that haven't been picked up yet, in the
+    /// `Engine`'s speaker layout. Shared with `adapter`.
+    backlog: Arc<Mutex<VecDeque<f32>>>,
+    /// Turns the backlog into the tap's speaker layout. Made once, when the
+    /// tap is added, so that pulling from the tap doesn't allocate.
+    adapter: Box<dyn SoundReader<f32>>,
+}
+
+impl Tap {
+    /// Returns how loud the given MixControl is on this tap, on top of its
+    /// own volume, including the tap's master volume.
+    pub fn channel_gain(&self, channel: &str) -> PosFloat {
+        self.volume
+            * self
+                .channel_gains
+                .get(channel)
+                .copied()
+                .unwrap_or(PosFloat::ONE)
+    }
+}
+
+/// Hands a tap's backlog to its channel adapter.
+struct BacklogReader(Arc<Mutex<VecDeque<f32>>>);
+
+impl SoundReader<f32> for BacklogReader {
+    fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+        let mut backlog = self.0.lock().unwrap();
+        let len = buf.len().min(backlog.len());
+        for (dst, src) in buf.iter_mut().zip(backlog.drain(..len)) {
+            *dst = MaybeUninit::new(src);
+        }
+        len
+    }
+}
+
+/// Throw away the oldest sample frames in `backlog`, if there are too many.
+fn trim_backlog(backlog: &mut VecDeque<f32>, num_channels: usize) {
+    let max_len = MAX_BACKLOG_FRAMES * num_channels;
+    if backlog.len() > max_len {
+        backlog.drain(..backlog.len() - max_len);
+    }
+}
+
+impl Engine {
+    /// Adds a tap: an extra output, mixed from the same music as the main
+    /// output, but with its own speaker layout, master volume, and MixControl
+    /// gains. Get its output with `turn_handle_tap`. Taps start out at full
+    /// volume, with every MixControl gain at 1.0.
+    ///
+    /// Taps get the same sounds, at the same volumes, as the main output,
+    /// except that MixControl effects and filters only apply to the main
+    /// output. Positional sounds are heard from the same listener. Taps are
+    /// always at the `Engine`'s own sample rate; see
+    /// `set_output_sample_rate`.
+    ///
+    /// Whichever output is pulled from first mixes for all of them, and the
+    /// others keep what it mixed until they're pulled from, so you should
+    /// pull the same amount of audio from each one. (An output that isn't
+    /// pulled from at all will only keep a second or so of audio.) Time that
+    /// passes by `advance_silently` is silent on every tap.
+    pub fn add_tap(&mut self, speaker_layout: SpeakerLayout) -> TapId {
+        let id = TapId(self.next_tap_id);
+        self.next_tap_id = self.next_tap_id.wrapping_add(1);
+        let backlog = Arc::new(Mutex::new(VecDeque::new()));
+        let adapter = new_channel_adapter(
+            Box::new(BacklogReader(backlog.clone())),
+            self.sample_rate,
+            self.speaker_layout,
+            speaker_layout,
+        );
+        self.taps.push(Tap {
+            id,
+            speaker_layout,
+            volume: PosFloat::ONE,
+            channel_gains: HashMap::new(),
+            backlog,
+            adapter,
+        });
+        id
+    }
+    /// Removes a tap. Does nothing if there is no such tap.
+    pub fn remove_tap(&mut self, tap: TapId) {
+        self.taps.retain(|x| x.id != tap);
+        if self.taps.is_empty() {
+            // (nobody else is going to need it)
+            self.main_backlog.clear();
+        }
+    }
+    /// Changes a tap's master volume. Does nothing if there is no such tap.
+    pub fn set_tap_volume(&mut self, tap: TapId, volume: PosFloat) {
+        if let Some(tap) = self.taps.iter_mut().find(|x| x.id == tap) {
+            tap.volume = volume;
+        }
+    }
+    /// Changes how loud a MixControl is on a tap, on top of its own volume.
+    /// Does nothing if there is no such tap.
+    pub fn set_tap_channel_gain(
+        &mut self,
+        tap: TapId,
+        channel: CompactString,
+        gain: PosFloat,
+    ) {
+        if let Some(tap) = self.taps.iter_mut().find(|x| x.id == tap) {
+            if gain == PosFloat::ONE {
+                tap.channel_gains.remove(&channel);
+            } else {
+                tap.channel_gains.insert(channel, gain);
+            }
+        }
+    }
+    /// As `turn_handle`, but for a tap. `out` must have a number of elements
+    /// divisible by the number of speaker channels *in the tap's speaker
+    /// layout*. Any existing data in `out` is overwritten.
+    ///
+    /// Panics if there is no such tap, or if `out` doesn't hold a whole
+    /// number of sample frames.
+    pub fn turn_handle_tap(&mut self, tap: TapId, out: &mut [f32]) {
+        let index = self
+            .taps
+            .iter()
+            .position(|x| x.id == tap)
+            .expect("turn_handle_tap: no such tap");
+        let num_channels = self.speaker_layout.get_num_channels();
+        let tap_channels = self.taps[index].speaker_layout.get_num_channels();
+        assert_eq!(out.len() % tap_channels, 0);
+        let mut buf = Vec::new();
+        swap(&mut buf, &mut self.tap_buf);
+        buf.resize(TAP_CHUNK_FRAMES * tap_channels, MaybeUninit::uninit());
+        for out in out.chunks_mut(TAP_CHUNK_FRAMES * tap_channels) {
+            let num_frames = out.len() / tap_channels;
+            let backlog_frames =
+                self.taps[index].backlog.lock().unwrap().len() / num_channels;
+            if backlog_frames < num_frames {
+                self.mix_ahead((num_frames - backlog_frames) as u64);
+            }
+            let len = self.taps[index].adapter.read(&mut buf[..out.len()]);
+            debug_assert_eq!(len, out.len());
+            for (dst, src) in out.iter_mut().zip(buf[..len].iter()) {
+                *dst = unsafe { src.assume_init() };
+            }
+        }
+        self.tap_buf = buf;
+    }
+    /// Mix the given number of sample frames for every output, keeping them
+    /// all for later.
+    fn mix_ahead(&mut self, num_frames: u64) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        let mut buf = Vec::new();
+        swap(&mut buf, &mut self.mix_ahead_buf);
+        buf.clear();
+        buf.resize(num_frames as usize * num_channels, 0.0);
+        self.mix_for(num_frames, Some(&mut buf));
+        self.main_backlog.extend(buf.iter().copied());
+        trim_backlog(&mut self.main_backlog, num_channels);
+        self.mix_ahead_buf = buf;
+    }
+    /// Keep what the taps got from the mixer's last `mix`.
+    pub(super) fn collect_tap_outputs(&mut self) {
+        let num_channels = self.speaker_layout.get_num_channels();
+        for (n, tap) in self.taps.iter_mut().enumerate() {
+            let mut backlog = tap.backlog.lock().unwrap();
+            backlog.extend(self.mixer.tap_output(n));
+            trim_backlog(&mut backlog, num_channels);
+        }
+    }
+    /// Use up as much of the main output's backlog as we can, by copying it
+    /// into `out` (if it's `Some`) or throwing it away (if it's `None`).
+    /// Returns the number of sample frames used up.
+    pub(super) fn use_main_backlog(
+        &mut self,
+        num_frames: u64,
+        out: Option<&mut [f32]>,
+    ) -> u64 {
+        let num_channels = self.speaker_layout.get_num_channels();
+        let frames =
+            (self.main_backlog.len() / num_channels).min(num_frames as usize);
+        let backlog = self.main_backlog.drain(..frames * num_channels);
+        if let Some(out) = out {
+            for (dst, src) in out.iter_mut().zip(backlog) {
+                *dst = src;
+            }
+        }
+        frames as u64
+    }
+}
@@ -0,0 +1,162 @@
+//! A harness for unit-testing flows: an `Engine` that needs no sound files,
+//! whose clock is driven one sample frame at a time, and that can be asked
+//! which sounds it has started. Only available with the `testing` feature.
+
+use super::*;
+
+/// A `SoundDelegate` that can't open any files, and keeps quiet about it.
+/// Sounds "played" through it don't make any noise, but everything else
+/// (nodes, sequences, flow controls, and so on) works as usual.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullSoundDelegate;
+
+impl SoundDelegate for NullSoundDelegate {
+    fn open_file(&self, _name: &str) -> Option<FormattedSoundStream> {
+        None
+    }
+    fn diagnostic(&self, _level: DiagLevel, _code: DiagCode, _message: &str) {}
+}
+
+/// Remembers every diagnostic it's given, and every FlowControl change
+/// it's told about. Opens files with the function it's made with. (For the
+/// crate's own tests.)
+#[cfg(test)]
+pub(crate) struct ReportingDelegate {
+    open: fn(&str) -> Option<FormattedSoundStream>,
+    diagnostics: std::sync::Mutex<Vec<(DiagCode, String)>>,
+    pub(crate) flow_control_changes: std::sync::Mutex<Vec<String>>,
+}
+#[cfg(test)]
+impl ReportingDelegate {
+    pub(crate) fn new(
+        open: fn(&str) -> Option<FormattedSoundStream>,
+    ) -> Arc<ReportingDelegate> {
+        Arc::new(ReportingDelegate {
+            open,
+            diagnostics: Default::default(),
+            flow_control_changes: Default::default(),
+        })
+    }
+    /// Returns the code of every diagnostic given so far.
+    pub(crate) fn codes(&self) -> Vec<DiagCode> {
+        let diagnostics = self.diagnostics.lock().unwrap();
+        diagnostics.iter().map(|(code, _)| *code).collect()
+    }
+    /// Returns every diagnostic given so far with the given code.
+    pub(crate) fn messages(&self, code: DiagCode) -> Vec<String> {
+        let diagnostics = self.diagnostics.lock().unwrap();
+        diagnostics
+            .iter()
+            .filter(|(x, _)| *x == code)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
+#[cfg(test)]
+impl SoundDelegate for ReportingDelegate {
+    fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+        (self.open)(name)
+    }
+    fn diagnostic(&self, _level: DiagLevel, code: DiagCode, msg: &str) {
+        self.diagnostics
+            .lock()
+            .unwrap()
+            .push((code, msg.to_string()));
+    }
+    fn flow_control_changed(&self, name: &str, value: &StringOrNumber) {
+        let change = format!("{name}={value:?}");
+        self.flow_control_changes.lock().unwrap().push(change);
+    }
+}
+
+/// A sound that an `Engine` has queued or started. See
+/// `Engine::inspect_queued_sounds` and `Engine::take_started_sounds`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedSoundReport {
+    /// The sample frame it's due to start on (or did start on).
+    pub when: u64,
+    /// The flow that played it, or empty if it was a one-shot.
+    pub flow: CompactString,
+    /// `None` if it was played by the starting node (or was a one-shot),
+    /// `Some(x)` if it was played by the node named `x`.
+    pub node: Option<CompactString>,
+    /// The MixControl it plays on.
+    pub channel: CompactString,
+    /// The name of the sound.
+    pub sound: CompactString,
+    /// If it was played by a sequence, the name of the sequence and the
+    /// index of the element that played it. (See
+    /// `EngineCommands::fade_sequence_element_to`.)
+    pub element: Option<(CompactString, usize)>,
+}
+
+impl QueuedSoundReport {
+    pub(super) fn new(queued_sound: &QueuedSound) -> QueuedSoundReport {
+        QueuedSoundReport {
+            when: queued_sound.when,
+            flow: queued_sound.who.flow_name().into(),
+            node: queued_sound.who.node_name().map(CompactString::from),
+            channel: queued_sound.who.channel.clone(),
+            sound: queued_sound.who.sound.clone(),
+            element: queued_sound.who.element.clone(),
+        }
+    }
+}
+
+impl Engine {
+    /// Creates a new Engine for testing flows with. It uses a
+    /// `NullSoundDelegate`, so it needs no sound files, and it loads
+    /// everything in the foreground. Nothing happens until you call
+    /// `step_frames` (or `turn_handle`, or `advance_silently`).
+    pub fn new_for_testing(
+        speaker_layout: SpeakerLayout,
+        sample_rate: PosFloat,
+    ) -> Engine {
+        Engine::new_with_runtime(
+            Arc::new(NullSoundDelegate),
+            speaker_layout,
+            sample_rate,
+            Arc::new(ForegroundTaskRuntime),
+            None,
+        )
+    }
+    /// Advance time by exactly the given number of sample frames, without
+    /// producing any audio. (As `advance_silently`, but counting in sample
+    /// frames instead of seconds.)
+    pub fn step_frames(&mut self, num_frames: u64) {
+        self.run_for(num_frames, None);
+    }
+    /// Returns the number of the next sample frame to be mixed, i.e. how many
+    /// sample frames have passed since the `Engine` was created.
+    pub fn current_frame(&self) -> u64 {
+        self.mixer.get_next_output_sample_frame_number()
+    }
+    /// Returns every sound that is queued up to start in the future, in the
+    /// order they'll start.
+    pub fn inspect_queued_sounds(&self) -> Vec<QueuedSoundReport> {
+        let mut queued_sounds: Vec<&QueuedSound> =
+            self.queued_sounds.iter().collect();
+        queued_sounds.sort_by_key(|x| (x.when, x.seq));
+        queued_sounds
+            .into_iter()
+            .map(QueuedSoundReport::new)
+            .collect()
+    }
+    /// Returns every sound that has started since the last call, in the
+    /// order they started. (A sound "starts" even if it couldn't be opened,
+    /// or was dropped because of a voice limit.)
+    pub fn take_started_sounds(&mut self) -> Vec<QueuedSoundReport> {
+        std::mem::take(&mut self.started_sounds)
+    }
+    /// Returns every flow node that's currently playing. (As
+    /// `get_active_nodes`, but answered immediately.)
+    pub fn inspect_active_nodes(&self) -> Vec<ActiveNodeReport> {
+        self.active_flow_nodes
+            .iter()
+            .map(|x| ActiveNodeReport {
+                flow: x.flow.name.clone(),
+                node: x.node.name.clone(),
+            })
+            .collect()
+    }
+}
@@ -6,7 +6,8 @@ use crate::PosFloat;
 /// unit time. Linear fades will seem to speed up or slow down over the course
 /// of the fade, and should be used when "intermixing" related tracks.
 /// Exponential fades will have the variable-speed "problem" even worse, but
-/// may sound the best of the three.
+/// may sound the best of the three. S-curve fades ease in and out, which suits
+/// things like volume sliders in a settings menu.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum FadeType {
     /// Fades between the given volumes on a logarithmic curve, such that any
@@ -21,13 +22,64 @@ pub enum FadeType {
     /// aesthetically pleasing than a logarithmic fade.
     #[default]
     Exponential,
+    /// Fades between the given amplification factors on a "smoothstep"
+    /// curve (3t²−2t³), which starts and ends gently, with no abrupt
+    /// change in speed at either end.
+    SCurve,
+    /// Fades between the given amplification factors on a quarter of a sine
+    /// (or cosine) wave, such that a fade up and a fade down of the same
+    /// length, played over each other, keep the same total power throughout.
+    /// This is the usual crossfade for uncorrelated signals.
+    EqualPower,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum FadeCurve {
-    Logarithmic { pos: f32, step: f32 },
-    Exponential { pos: f32, step: f32 },
-    Linear { pos: f32, step: f32 },
+    Logarithmic {
+        pos: f32,
+        step: f32,
+    },
+    Exponential {
+        pos: f32,
+        step: f32,
+    },
+    Linear {
+        pos: f32,
+        step: f32,
+    },
+    /// `pos` goes from 0 to 1 over the course of the fade, and is mapped
+    /// through the smoothstep curve onto `from..from+delta`.
+    SCurve {
+        from: f32,
+        delta: f32,
+        pos: f32,
+        step: f32,
+    },
+    /// As `SCurve`, but mapped through `equal_power`.
+    EqualPower {
+        from: f32,
+        delta: f32,
+        pos: f32,
+        step: f32,
+    },
+}
+
+/// The smoothstep function, with `t` clamped to 0..=1.
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// The equal-power curve, with `t` clamped to 0..=1: a quarter of a sine wave
+/// when fading up, and one minus a quarter of a cosine wave when fading down,
+/// so that a fade from 0 to 1 and a fade from 1 to 0 square-sum to 1.
+fn equal_power(t: f32, rising: bool) -> f32 {
+    let angle = t.clamp(0.0, 1.0) * std::f32::consts::FRAC_PI_2;
+    if rising {
+        angle.sin()
+    } else {
+        1.0 - angle.cos()
+    }
 }
 
 /// Natural logarithm of the quietest amplitude we consider audible.
@@ -66,6 +118,18 @@ impl FadeCurve {
                 let step = (*to - *from) / (*length + 1.0);
                 FadeCurve::Linear { pos: *from, step }
             }
+            FadeType::SCurve => FadeCurve::SCurve {
+                from: *from,
+                delta: *to - *from,
+                pos: 0.0,
+                step: 1.0 / (*length + 1.0),
+            },
+            FadeType::EqualPower => FadeCurve::EqualPower {
+                from: *from,
+                delta: *to - *from,
+                pos: 0.0,
+                step: 1.0 / (*length + 1.0),
+            },
         }
     }
     /// Evaluate the current state of the fader.
@@ -74,6 +138,12 @@ impl FadeCurve {
             Self::Exponential { pos, .. } => pos.ln(),
             Self::Logarithmic { pos, .. } => pos.exp(),
             Self::Linear { pos, .. } => *pos,
+            Self::SCurve {
+                from, delta, pos, ..
+            } => from + delta * smoothstep(*pos),
+            Self::EqualPower {
+                from, delta, pos, ..
+            } => from + delta * equal_power(*pos, *delta >= 0.0),
         })
     }
     /// Evaluate the state of the fader t steps into the future.
@@ -82,6 +152,18 @@ impl FadeCurve {
             Self::Exponential { pos, step } => (pos + step * *t).ln(),
             Self::Logarithmic { pos, step } => (pos + step * *t).exp(),
             Self::Linear { pos, step } => *pos + step * *t,
+            Self::SCurve {
+                from,
+                delta,
+                pos,
+                step,
+            } => from + delta * smoothstep(pos + step * *t),
+            Self::EqualPower {
+                from,
+                delta,
+                pos,
+                step,
+            } => from + delta * equal_power(pos + step * *t, *delta >= 0.0),
         })
     }
     /// Step by a single sample frame
@@ -89,7 +171,9 @@ impl FadeCurve {
         match self {
             Self::Logarithmic { pos, step }
             | Self::Exponential { pos, step }
-            | Self::Linear { pos, step } => *pos += *step,
+            | Self::Linear { pos, step }
+            | Self::SCurve { pos, step, .. }
+            | Self::EqualPower { pos, step, .. } => *pos += *step,
         }
     }
     /// Step by a given number of sample frames
@@ -97,7 +181,9 @@ impl FadeCurve {
         match self {
             Self::Logarithmic { pos, step }
             | Self::Exponential { pos, step }
-            | Self::Linear { pos, step } => *pos += *step * *count,
+            | Self::Linear { pos, step }
+            | Self::SCurve { pos, step, .. }
+            | Self::EqualPower { pos, step, .. } => *pos += *step * *count,
         }
     }
 }
@@ -162,6 +248,10 @@ impl Fader {
     pub fn complete(&self) -> bool {
         self.pos >= self.length
     }
+    /// Returns the volume the fade ends up at.
+    pub fn target(&self) -> PosFloat {
+        self.to
+    }
     /// Evaluate the current volume.
     pub fn evaluate(&self) -> PosFloat {
         if self.complete() {
@@ -214,3 +304,60 @@ impl Iterator for Fader {
         (self.length.ceil() - *self.pos) as usize
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn s_curve_is_monotonic() {
+        const LENGTH: usize = 1000;
+        for (from, to) in [(0.0, 1.0), (1.0, 0.0), (0.25, 0.75)] {
+            let from = PosFloat::new_clamped(from);
+            let to = PosFloat::new_clamped(to);
+            let values: Vec<f32> = Fader::start(
+                FadeType::SCurve,
+                from,
+                to,
+                PosFloat::from(LENGTH),
+            )
+            .map(|x| *x)
+            .collect();
+            assert_eq!(values.len(), LENGTH);
+            assert_eq!(values[0], *from);
+            for pair in values.windows(2) {
+                if to > from {
+                    assert!(pair[0] <= pair[1], "not monotonic: {pair:?}");
+                } else {
+                    assert!(pair[0] >= pair[1], "not monotonic: {pair:?}");
+                }
+            }
+            // The speed of the fade should be near zero at both ends, and
+            // greatest in the middle.
+            let delta = |n: usize| (values[n + 1] - values[n]).abs();
+            let middle = delta(LENGTH / 2);
+            assert!(delta(0) < middle * 0.05);
+            assert!(delta(LENGTH - 2) < middle * 0.05);
+        }
+    }
+    #[test]
+    fn equal_power_keeps_power() {
+        let length = PosFloat::from(100usize);
+        let up = Fader::start(
+            FadeType::EqualPower,
+            PosFloat::ZERO,
+            PosFloat::ONE,
+            length,
+        );
+        let down = Fader::start(
+            FadeType::EqualPower,
+            PosFloat::ONE,
+            PosFloat::ZERO,
+            length,
+        );
+        for (up, down) in up.zip(down) {
+            let power = *up * *up + *down * *down;
+            assert!((power - 1.0).abs() < 0.001, "power was {power}");
+        }
+    }
+}
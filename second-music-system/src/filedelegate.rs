@@ -0,0 +1,128 @@
+//! A ready-made `SoundDelegate` that opens sound files from a directory on
+//! disk. Only available if one or more of the built-in decoder features is
+//! enabled.
+
+use crate::*;
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+#[cfg(feature = "ogg")]
+mod ogg;
+#[cfg(feature = "builtin-wav")]
+mod pcm;
+
+/// A `SoundDelegate` that opens sound files relative to a given root
+/// directory, and decodes them using SMS's built-in decoders. Which formats
+/// are supported depends on which features are enabled:
+///
+/// - `builtin-wav`: uncompressed PCM WAV, AIFF, and AIFF-C files.
+/// - `ogg`: Ogg Vorbis files.
+///
+/// This is meant to get you up and running quickly. If your engine has its
+/// own archive format, or its own opinions about decoding, you should
+/// implement `SoundDelegate` yourself instead.
+pub struct FileSoundDelegate {
+    root: PathBuf,
+}
+
+impl FileSoundDelegate {
+    /// Create a new `FileSoundDelegate` that will look for sound files
+    /// relative to the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> FileSoundDelegate {
+        FileSoundDelegate { root: root.into() }
+    }
+    fn open_path(&self, name: &str) -> Result<FormattedSoundStream, String> {
+        let path = self.root.join(name);
+        let mut file =
+            BufReader::new(File::open(&path).map_err(|x| format!("{}", x))?);
+        let mut magic = [0u8; 12];
+        file.read_exact(&mut magic)
+            .map_err(|x| format!("unable to read header: {}", x))?;
+        file.seek(SeekFrom::Start(0))
+            .map_err(|x| format!("{}", x))?;
+        #[cfg(feature = "builtin-wav")]
+        {
+            if pcm::is_wav(&magic) {
+                return pcm::open_wav(file);
+            } else if pcm::is_aiff(&magic) {
+                return pcm::open_aiff(file);
+            }
+        }
+        #[cfg(feature = "ogg")]
+        {
+            if ogg::is_ogg(&magic) {
+                return ogg::open_ogg(file);
+            }
+        }
+        Err("unknown or unsupported file format".to_string())
+    }
+}
+
+impl SoundDelegate for FileSoundDelegate {
+    fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+        match self.open_path(name) {
+            Ok(x) => Some(x),
+            Err(x) => {
+                self.warning(&format!("{:?}: {}", name, x));
+                None
+            }
+        }
+    }
+}
+
+/// Picks the `SpeakerLayout` that SMS assumes for a file with the given
+/// number of channels, if there is one.
+fn speaker_layout_for_channels(num_channels: u32) -> Option<SpeakerLayout> {
+    match num_channels {
+        1 => Some(SpeakerLayout::Mono),
+        2 => Some(SpeakerLayout::Stereo),
+        4 => Some(SpeakerLayout::Quadraphonic),
+        6 => Some(SpeakerLayout::Surround51),
+        8 => Some(SpeakerLayout::Surround71),
+        _ => None,
+    }
+}
+
+/// Checks that the sample rate given in a file's header is one we can use.
+fn valid_sample_rate(sample_rate: f64) -> Result<PosFloat, String> {
+    PosFloat::new(sample_rate as f32)
+        .ok()
+        .filter(|x| *x > PosFloat::ZERO)
+        .ok_or_else(|| format!("invalid sample rate: {}", sample_rate))
+}
+
+/// A directory holding one file, for a test to open. Deleted when dropped.
+#[cfg(test)]
+struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Writes the given data to a file with the given name, in a new
+    /// directory of its own. (Named after the process and the file, so that
+    /// tests running at the same time don't step on each other.)
+    fn with_file(name: &str, data: &[u8]) -> TempDir {
+        let dir = std::env::temp_dir().join(format!(
+            "sms-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ret = TempDir(dir);
+        std::fs::write(ret.0.join(name), data).unwrap();
+        ret
+    }
+    fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
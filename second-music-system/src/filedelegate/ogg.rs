@@ -0,0 +1,480 @@
+//! Decoding of Ogg Vorbis files, using the `lewton` crate.
+
+use super::*;
+
+use std::mem::MaybeUninit;
+
+use lewton::inside_ogg::OggStreamReader;
+
+/// For each SMS channel, which Vorbis channel it comes from. Vorbis and SMS
+/// agree on the order of mono, stereo, and quadraphonic channels, but not on
+/// the order of surround channels.
+fn channel_map(num_channels: u32) -> Option<&'static [usize]> {
+    match num_channels {
+        1 => Some(&[0]),
+        2 => Some(&[0, 1]),
+        4 => Some(&[0, 1, 2, 3]),
+        6 => Some(&[0, 2, 1, 5, 3, 4]),
+        8 => Some(&[0, 2, 1, 7, 5, 6, 3, 4]),
+        _ => None,
+    }
+}
+
+struct OggReader {
+    /// Only `None` if a rewind failed, in which case the stream is over.
+    stream: Option<OggStreamReader<BufReader<File>>>,
+    channel_map: &'static [usize],
+    /// Decoded samples, in SMS channel order, that haven't been read yet.
+    buffer: Vec<i16>,
+    buffer_pos: usize,
+    /// How many samples (not frames) into the sound the next one read is.
+    sample_pos: u64,
+}
+
+impl OggReader {
+    /// Decodes packets until at least one sample has been appended to the
+    /// buffer. Returns false if the stream has ended (or broken).
+    fn decode_packet(&mut self) -> bool {
+        let Some(stream) = self.stream.as_mut() else {
+            return false;
+        };
+        if self.buffer_pos >= self.buffer.len() {
+            self.buffer.clear();
+            self.buffer_pos = 0;
+        }
+        loop {
+            let samples = match stream.read_dec_packet_itl() {
+                Ok(Some(samples)) => samples,
+                Ok(None) | Err(_) => return false,
+            };
+            if samples.is_empty() {
+                // the first packet after a seek decodes to nothing
+                continue;
+            }
+            if self.channel_map.iter().enumerate().all(|(a, b)| a == *b) {
+                self.buffer.extend_from_slice(&samples);
+            } else {
+                for frame in samples.chunks_exact(self.channel_map.len()) {
+                    self.buffer
+                        .extend(self.channel_map.iter().map(|x| frame[*x]));
+                }
+            }
+            return true;
+        }
+    }
+    /// Decodes and throws away the given number of samples (or as many as
+    /// there are left, if the stream ends first).
+    fn discard(&mut self, count: u64) {
+        let mut discarded = 0;
+        loop {
+            let available = (self.buffer.len() - self.buffer_pos) as u64;
+            let amount = available.min(count - discarded);
+            self.buffer_pos += amount as usize;
+            self.sample_pos += amount;
+            discarded += amount;
+            if discarded == count || !self.decode_packet() {
+                return;
+            }
+        }
+    }
+    /// Go back to the very beginning of the file. The absgp-based seek can't
+    /// do this, since the header pages also have an absgp of zero.
+    fn rewind(&mut self) -> Option<()> {
+        let mut file = self.stream.take()?.into_inner().into_inner();
+        file.seek(SeekFrom::Start(0)).ok()?;
+        self.stream = Some(OggStreamReader::new(file).ok()?);
+        Some(())
+    }
+}
+
+impl SoundReader<i16> for OggReader {
+    fn read(&mut self, buf: &mut [MaybeUninit<i16>]) -> usize {
+        let mut count = 0;
+        while count < buf.len() {
+            if self.buffer_pos >= self.buffer.len() && !self.decode_packet() {
+                break;
+            }
+            let available = &self.buffer[self.buffer_pos..];
+            let amount = available.len().min(buf.len() - count);
+            for (dst, src) in
+                buf[count..count + amount].iter_mut().zip(available.iter())
+            {
+                dst.write(*src);
+            }
+            count += amount;
+            self.buffer_pos += amount;
+        }
+        self.sample_pos += count as u64;
+        count
+    }
+    fn seek(&mut self, pos: u64) -> Option<u64> {
+        self.buffer.clear();
+        self.buffer_pos = 0;
+        let stream = self.stream.as_mut()?;
+        // The first packet we decode after seeking only primes the decoder,
+        // so aim at least one packet's worth early. (A packet decodes to, at
+        // most, half a long block.)
+        let target =
+            pos.saturating_sub(1 << (stream.ident_hdr.blocksize_1 - 1));
+        // A seek that runs off the end of the stream leaves behind whatever
+        // packets were already read, and we can't tell that from a seek that
+        // worked. Start from scratch, so there aren't any.
+        self.rewind()?;
+        self.sample_pos = 0;
+        if target == 0 {
+            return Some(0);
+        }
+        // This will land us on a page boundary at or before the target, but
+        // we won't know *which* page boundary until we've decoded up to the
+        // end of a page.
+        self.stream.as_mut()?.seek_absgp_pg(target).ok()?;
+        let end = loop {
+            if !self.decode_packet() {
+                // We may have landed among the headers, which don't decode
+                // as audio. Start from the very beginning instead.
+                self.buffer.clear();
+                self.buffer_pos = 0;
+                self.rewind()?;
+                self.sample_pos = 0;
+                return Some(0);
+            }
+            if let Some(absgp) = self.stream.as_ref()?.get_last_absgp() {
+                break absgp;
+            }
+        };
+        let num_channels = self.channel_map.len() as u64;
+        let buffered_frames = self.buffer.len() as u64 / num_channels;
+        let here = end.checked_sub(buffered_frames)?;
+        if here > pos {
+            // We overshot. Let SMS reopen the file instead.
+            return None;
+        }
+        // Throw away as much of the residual as we already have decoded. SMS
+        // will skip the rest, if any.
+        let discard = (pos - here).min(buffered_frames);
+        self.buffer_pos = (discard * num_channels) as usize;
+        self.sample_pos = (here + discard) * num_channels;
+        Some(here + discard)
+    }
+    fn skip_coarse(
+        &mut self,
+        count: u64,
+        _buf: &mut [MaybeUninit<i16>],
+    ) -> u64 {
+        let num_channels = self.channel_map.len() as u64;
+        // Whatever has already been decoded can be skipped for free.
+        let available = (self.buffer.len() - self.buffer_pos) as u64;
+        let skipped = available.min(count);
+        self.buffer_pos += skipped as usize;
+        self.sample_pos += skipped;
+        let Some(stream) = self.stream.as_ref() else {
+            return skipped;
+        };
+        // Seeking means decoding a packet or two to find out where we
+        // landed, so it's only worth it for longer skips. (It also only
+        // works in whole frames.)
+        let frames = (count - skipped) / num_channels;
+        if frames < 1 << stream.ident_hdr.blocksize_1
+            || !self.sample_pos.is_multiple_of(num_channels)
+        {
+            return skipped;
+        }
+        let here = self.sample_pos / num_channels;
+        match self.seek(here + frames) {
+            Some(landed) if landed >= here => {
+                skipped + (landed - here) * num_channels
+            }
+            Some(landed) => {
+                // We landed behind where we started. Catch back up.
+                self.discard((here - landed) * num_channels);
+                skipped
+            }
+            None => {
+                // We don't know where we are anymore. Start over, and catch
+                // back up from the beginning. (This should be rare.)
+                self.buffer.clear();
+                self.buffer_pos = 0;
+                if self.rewind().is_some() {
+                    self.sample_pos = 0;
+                    self.discard(here * num_channels);
+                }
+                skipped
+            }
+        }
+    }
+}
+
+pub(super) fn is_ogg(magic: &[u8; 12]) -> bool {
+    &magic[0..4] == b"OggS"
+}
+
+pub(super) fn open_ogg(
+    file: BufReader<File>,
+) -> Result<FormattedSoundStream, String> {
+    let stream = OggStreamReader::new(file).map_err(|x| format!("{}", x))?;
+    let num_channels = stream.ident_hdr.audio_channels as u32;
+    let (Some(speaker_layout), Some(channel_map)) = (
+        speaker_layout_for_channels(num_channels),
+        channel_map(num_channels),
+    ) else {
+        return Err(format!(
+            "unsupported number of channels: {}",
+            num_channels
+        ));
+    };
+    let sample_rate =
+        valid_sample_rate(stream.ident_hdr.audio_sample_rate as f64)?;
+    Ok(FormattedSoundStream {
+        sample_rate,
+        speaker_layout,
+        reader: FormattedSoundReader::I16(Box::new(OggReader {
+            stream: Some(stream),
+            channel_map,
+            buffer: Vec::new(),
+            buffer_pos: 0,
+            sample_pos: 0,
+        })),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Packs values into bytes the way Vorbis does, least significant bit
+    /// first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        num_bits: usize,
+    }
+    impl BitWriter {
+        /// Starts a Vorbis header packet of the given type.
+        fn header(packet_type: u32) -> BitWriter {
+            let mut ret = BitWriter::default();
+            ret.put(packet_type, 8);
+            for byte in b"vorbis" {
+                ret.put(*byte as u32, 8);
+            }
+            ret
+        }
+        fn put(&mut self, value: u32, bits: u32) {
+            for n in 0..bits {
+                if self.num_bits == self.bytes.len() * 8 {
+                    self.bytes.push(0);
+                }
+                if (value >> n) & 1 != 0 {
+                    *self.bytes.last_mut().unwrap() |=
+                        1 << (self.num_bits % 8);
+                }
+                self.num_bits += 1;
+            }
+        }
+    }
+
+    /// Appends an Ogg page containing the given (whole) packets.
+    fn write_page(
+        out: &mut Vec<u8>,
+        header_type: u8,
+        granule: u64,
+        sequence: u32,
+        packets: &[Vec<u8>],
+    ) {
+        let start = out.len();
+        out.extend_from_slice(b"OggS\0");
+        out.push(header_type);
+        out.extend_from_slice(&granule.to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes()); // serial number
+        out.extend_from_slice(&sequence.to_le_bytes());
+        out.extend_from_slice(&[0; 4]); // checksum, filled in below
+        let mut lacing = vec![];
+        for packet in packets {
+            lacing.resize(lacing.len() + packet.len() / 255, 255);
+            lacing.push((packet.len() % 255) as u8);
+        }
+        out.push(lacing.len() as u8);
+        out.extend_from_slice(&lacing);
+        for packet in packets {
+            out.extend_from_slice(packet);
+        }
+        let mut crc = 0u32;
+        for byte in &out[start..] {
+            crc ^= (*byte as u32) << 24;
+            for _ in 0..8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04C1_1DB7
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    const NUM_PACKETS: u64 = 41;
+    const PACKETS_PER_PAGE: u64 = 4;
+    /// How many sample frames each packet (but the first) decodes to. (Every
+    /// block is 256 samples long, and overlaps half of the one before.)
+    const FRAMES_PER_PACKET: u64 = 128;
+
+    /// Makes a mono Ogg Vorbis file of noise, about 5000 sample frames long,
+    /// using the simplest setup the format allows.
+    fn make_ogg() -> Vec<u8> {
+        let mut ident = BitWriter::header(1);
+        ident.put(0, 32); // version
+        ident.put(1, 8); // channels
+        ident.put(8000, 32); // sample rate
+        ident.put(0, 32); // maximum bitrate
+        ident.put(0, 32); // nominal bitrate
+        ident.put(0, 32); // minimum bitrate
+        ident.put(8, 4); // short blocks are 256 samples...
+        ident.put(8, 4); // ...and so are long ones
+        ident.put(1, 8); // framing
+        let mut comment = BitWriter::header(3);
+        comment.put(0, 32); // vendor string length
+        comment.put(0, 32); // number of comments
+        comment.put(1, 8); // framing
+        let mut setup = BitWriter::header(5);
+        setup.put(1, 8); // two codebooks:
+        for lookup in [false, true] {
+            setup.put(0x564342, 24); // sync
+            setup.put(1, 16); // dimensions
+            setup.put(2, 24); // entries
+            setup.put(0, 1); // not ordered
+            setup.put(0, 1); // not sparse
+            setup.put(0, 5); // both codewords are one bit long
+            setup.put(0, 5);
+            if lookup {
+                // entry 0 is 1.0, entry 1 is -1.0
+                setup.put(1, 4); // lookup type
+                setup.put(0x80000000 | (788 << 21) | 1, 32); // minimum
+                setup.put((789 << 21) | 1, 32); // delta
+                setup.put(0, 4); // one bit per value
+                setup.put(0, 1); // not a sequence
+                setup.put(1, 1);
+                setup.put(0, 1);
+            } else {
+                setup.put(0, 4); // no lookup
+            }
+        }
+        setup.put(0, 6); // one time domain transform
+        setup.put(0, 16);
+        setup.put(0, 6); // one floor:
+        setup.put(1, 16); // type 1
+        setup.put(0, 5); // no partitions
+        setup.put(0, 2); // multiplier of 1
+        setup.put(7, 4); // range of 128
+        setup.put(0, 6); // one residue:
+        setup.put(1, 16); // type 1
+        setup.put(0, 24); // begin
+        setup.put(128, 24); // end
+        setup.put(15, 24); // partitions of 16
+        setup.put(1, 6); // two classifications, from codebook 0
+        setup.put(0, 8);
+        setup.put(0, 4); // class 0 is silent
+        setup.put(1, 4); // class 1 uses codebook 1
+        setup.put(1, 8);
+        setup.put(0, 6); // one mapping
+        setup.put(0, 16);
+        setup.put(0, 4); // no submaps, no coupling
+        setup.put(0, 24); // floor 0, residue 0
+        setup.put(0, 6); // one mode: short blocks, mapping 0
+        setup.put(0, 1);
+        setup.put(0, 16);
+        setup.put(0, 16);
+        setup.put(0, 8);
+        setup.put(1, 1); // framing
+        let mut ogg = vec![];
+        write_page(&mut ogg, 2, 0, 0, &[ident.bytes]);
+        write_page(&mut ogg, 0, 0, 1, &[comment.bytes, setup.bytes]);
+        let mut rng = 0x1234_5678u32;
+        let mut random_bit = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 17;
+            rng ^= rng << 5;
+            rng & 1
+        };
+        let mut packets = vec![];
+        for index in 0..NUM_PACKETS {
+            let mut packet = BitWriter::default();
+            packet.put(0, 1); // audio
+            packet.put(1, 1); // floor is used
+            packet.put(200, 8);
+            packet.put(180, 8);
+            for _ in 0..8 {
+                let class = random_bit();
+                packet.put(class, 1);
+                if class == 1 {
+                    for _ in 0..16 {
+                        packet.put(random_bit(), 1);
+                    }
+                }
+            }
+            packets.push(packet.bytes);
+            if packets.len() as u64 == PACKETS_PER_PAGE
+                || index + 1 == NUM_PACKETS
+            {
+                let last = index + 1 == NUM_PACKETS;
+                write_page(
+                    &mut ogg,
+                    if last { 4 } else { 0 },
+                    index * FRAMES_PER_PACKET,
+                    (2 + index / PACKETS_PER_PAGE) as u32,
+                    &packets,
+                );
+                packets.clear();
+            }
+        }
+        ogg
+    }
+
+    fn read_rest(reader: &mut dyn SoundReader<i16>) -> Vec<i16> {
+        let mut ret = vec![];
+        let mut buf = [MaybeUninit::uninit(); 100];
+        loop {
+            let count = reader.read(&mut buf);
+            if count == 0 {
+                return ret;
+            }
+            ret.extend(
+                buf[..count].iter().map(|x| unsafe { x.assume_init() }),
+            );
+        }
+    }
+
+    #[test]
+    fn seeking_matches_decoding() {
+        let dir = TempDir::with_file("noise.ogg", &make_ogg());
+        let delegate = FileSoundDelegate::new(dir.path());
+        let open = || match delegate.open_file("noise.ogg").unwrap().reader {
+            FormattedSoundReader::I16(reader) => reader,
+            _ => panic!("expected 16-bit reader"),
+        };
+        let all = read_rest(&mut *open());
+        assert_eq!(all.len() as u64, (NUM_PACKETS - 1) * FRAMES_PER_PACKET);
+        assert!(all.iter().any(|x| *x != 0));
+        for pos in [0, 1, 300, 1000, 2500, 4000, 5000] {
+            let mut reader = open();
+            let here = reader.seek(pos).unwrap();
+            assert!(here <= pos, "seek to {pos} landed on {here}");
+            let mut rest = read_rest(&mut *reader);
+            rest.drain(..(pos - here) as usize);
+            assert!(rest == all[pos as usize..], "seek to {pos} went wrong");
+        }
+        // (the longer skips seek, and the longest runs off the end)
+        for start in [10, 600] {
+            for count in [1, 100, 300, 1000, 2000, 4000, 10000] {
+                let mut reader = open();
+                let mut buf = [MaybeUninit::uninit(); 10];
+                reader.skip_precise(start, &mut buf);
+                let skipped = reader.skip_coarse(count, &mut buf);
+                assert!(skipped <= count);
+                let rest = read_rest(&mut *reader);
+                assert!(
+                    rest == all[(start + skipped) as usize..],
+                    "skip of {count} from {start} went wrong"
+                );
+            }
+        }
+    }
+}
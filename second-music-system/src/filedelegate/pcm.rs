@@ -0,0 +1,536 @@
+//! Decoding of uncompressed PCM audio in WAV and AIFF containers.
+
+use super::*;
+
+use std::mem::MaybeUninit;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// The in-file encoding of a single sample.
+#[derive(Clone, Copy, Debug)]
+enum PcmEncoding {
+    U8,
+    I8,
+    I16Le,
+    I16Be,
+    I24Le,
+    I24Be,
+    I32Le,
+    I32Be,
+    F32Le,
+    F32Be,
+}
+
+impl PcmEncoding {
+    fn bytes_per_sample(&self) -> usize {
+        match self {
+            PcmEncoding::U8 | PcmEncoding::I8 => 1,
+            PcmEncoding::I16Le | PcmEncoding::I16Be => 2,
+            PcmEncoding::I24Le | PcmEncoding::I24Be => 3,
+            PcmEncoding::I32Le
+            | PcmEncoding::I32Be
+            | PcmEncoding::F32Le
+            | PcmEncoding::F32Be => 4,
+        }
+    }
+}
+
+/// Everything we need to know about a PCM file to start reading it.
+struct PcmInfo {
+    encoding: PcmEncoding,
+    num_channels: u32,
+    sample_rate: f64,
+    /// Offset of the first sample, in bytes, from the beginning of the file.
+    data_start: u64,
+    /// Number of *sample frames* in the file.
+    num_frames: u64,
+}
+
+/// Reads samples straight out of the data chunk of a WAV or AIFF file.
+/// Seeking is exact, since every sample frame is the same size.
+struct PcmReader<T: Sample> {
+    file: BufReader<File>,
+    decode: fn(&[u8]) -> T,
+    bytes_per_sample: usize,
+    num_channels: u64,
+    data_start: u64,
+    /// Total number of *samples* in the data chunk.
+    num_samples: u64,
+    /// Current position, in *samples*, from the beginning of the data chunk.
+    position: u64,
+    scratch: Vec<u8>,
+}
+
+impl<T: Sample> SoundReader<T> for PcmReader<T> {
+    fn read(&mut self, buf: &mut [MaybeUninit<T>]) -> usize {
+        let wanted =
+            (buf.len() as u64).min(self.num_samples - self.position) as usize;
+        self.scratch.clear();
+        let result = (&mut self.file)
+            .take((wanted * self.bytes_per_sample) as u64)
+            .read_to_end(&mut self.scratch);
+        let mut count = self.scratch.len() / self.bytes_per_sample;
+        if result.is_err() || count < wanted {
+            // Truncated or unreadable file. End the stream here, on a frame
+            // boundary.
+            count -= count % self.num_channels as usize;
+            self.num_samples = self.position + count as u64;
+        }
+        for (src, dst) in self
+            .scratch
+            .chunks_exact(self.bytes_per_sample)
+            .zip(buf[..count].iter_mut())
+        {
+            dst.write((self.decode)(src));
+        }
+        self.position += count as u64;
+        count
+    }
+    fn seek(&mut self, pos: u64) -> Option<u64> {
+        let pos = pos.min(self.num_samples / self.num_channels);
+        let byte_pos = self.data_start
+            + pos * self.num_channels * self.bytes_per_sample as u64;
+        self.file.seek(SeekFrom::Start(byte_pos)).ok()?;
+        self.position = pos * self.num_channels;
+        Some(pos)
+    }
+    fn skip_coarse(&mut self, count: u64, _buf: &mut [MaybeUninit<T>]) -> u64 {
+        let count = count.min(self.num_samples - self.position);
+        // only skip whole sample frames
+        let count = count - count % self.num_channels;
+        if count == 0 {
+            return 0;
+        }
+        let offset = (count * self.bytes_per_sample as u64) as i64;
+        match self.file.seek_relative(offset) {
+            Ok(_) => {
+                self.position += count;
+                count
+            }
+            Err(_) => 0,
+        }
+    }
+    fn estimate_len(&mut self) -> Option<u64> {
+        Some(self.num_samples / self.num_channels)
+    }
+}
+
+fn make_stream(
+    mut file: BufReader<File>,
+    info: PcmInfo,
+) -> Result<FormattedSoundStream, String> {
+    let speaker_layout = speaker_layout_for_channels(info.num_channels)
+        .ok_or_else(|| {
+            format!("unsupported number of channels: {}", info.num_channels)
+        })?;
+    let sample_rate = valid_sample_rate(info.sample_rate)?;
+    file.seek(SeekFrom::Start(info.data_start))
+        .map_err(|x| format!("{}", x))?;
+    let bytes_per_sample = info.encoding.bytes_per_sample();
+    let num_channels = info.num_channels as u64;
+    let num_samples = info.num_frames * num_channels;
+    macro_rules! reader {
+        ($enum:ident, $type:ty, $decode:expr) => {
+            FormattedSoundReader::$enum(Box::new(PcmReader::<$type> {
+                file,
+                decode: $decode,
+                bytes_per_sample,
+                num_channels,
+                data_start: info.data_start,
+                num_samples,
+                position: 0,
+                scratch: Vec::new(),
+            }))
+        };
+    }
+    let reader = match info.encoding {
+        PcmEncoding::U8 => reader!(U8, u8, |b| b[0]),
+        PcmEncoding::I8 => reader!(I8, i8, |b| b[0] as i8),
+        PcmEncoding::I16Le => {
+            reader!(I16, i16, |b| i16::from_le_bytes([b[0], b[1]]))
+        }
+        PcmEncoding::I16Be => {
+            reader!(I16, i16, |b| i16::from_be_bytes([b[0], b[1]]))
+        }
+        PcmEncoding::I24Le => reader!(F32, f32, |b| {
+            i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32
+                * (1.0 / 2147483648.0)
+        }),
+        PcmEncoding::I24Be => reader!(F32, f32, |b| {
+            i32::from_be_bytes([b[0], b[1], b[2], 0]) as f32
+                * (1.0 / 2147483648.0)
+        }),
+        PcmEncoding::I32Le => reader!(F32, f32, |b| {
+            i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32
+                * (1.0 / 2147483648.0)
+        }),
+        PcmEncoding::I32Be => reader!(F32, f32, |b| {
+            i32::from_be_bytes([b[0], b[1], b[2], b[3]]) as f32
+                * (1.0 / 2147483648.0)
+        }),
+        PcmEncoding::F32Le => {
+            reader!(F32, f32, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        }
+        PcmEncoding::F32Be => {
+            reader!(F32, f32, |b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        }
+    };
+    Ok(FormattedSoundStream {
+        sample_rate,
+        speaker_layout,
+        reader,
+    })
+}
+
+/// Reads a four-character chunk ID and a 32-bit chunk length. Returns `None`
+/// at the end of the file.
+fn read_chunk_header(
+    file: &mut BufReader<File>,
+    big_endian: bool,
+) -> Result<Option<([u8; 4], u32)>, String> {
+    let mut header = [0u8; 8];
+    match file.read_exact(&mut header) {
+        Ok(_) => (),
+        Err(x) if x.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Ok(None)
+        }
+        Err(x) => return Err(format!("{}", x)),
+    }
+    let id = [header[0], header[1], header[2], header[3]];
+    let len = [header[4], header[5], header[6], header[7]];
+    let len = if big_endian {
+        u32::from_be_bytes(len)
+    } else {
+        u32::from_le_bytes(len)
+    };
+    Ok(Some((id, len)))
+}
+
+/// Reads the body of a small chunk into memory, skipping the pad byte if
+/// there is one.
+fn read_chunk_body(
+    file: &mut BufReader<File>,
+    len: u32,
+) -> Result<Vec<u8>, String> {
+    if len > 65536 {
+        return Err("header chunk is implausibly large".to_string());
+    }
+    let mut ret = vec![0u8; len as usize];
+    file.read_exact(&mut ret[..])
+        .map_err(|x| format!("unable to read header: {}", x))?;
+    if !len.is_multiple_of(2) {
+        skip_chunk_body(file, 1)?;
+    }
+    Ok(ret)
+}
+
+/// Skips over the body of a chunk, including the pad byte if there is one.
+fn skip_chunk_body(
+    file: &mut BufReader<File>,
+    len: u32,
+) -> Result<(), String> {
+    let len = len as i64 + (len % 2) as i64;
+    file.seek_relative(len).map_err(|x| format!("{}", x))
+}
+
+pub(super) fn is_wav(magic: &[u8; 12]) -> bool {
+    &magic[0..4] == b"RIFF" && &magic[8..12] == b"WAVE"
+}
+
+pub(super) fn open_wav(
+    mut file: BufReader<File>,
+) -> Result<FormattedSoundStream, String> {
+    file.seek(SeekFrom::Start(12))
+        .map_err(|x| format!("{}", x))?;
+    let mut format = None;
+    let mut data = None;
+    while format.is_none() || data.is_none() {
+        let Some((id, len)) = read_chunk_header(&mut file, false)? else {
+            break;
+        };
+        match &id {
+            b"fmt " => {
+                let body = read_chunk_body(&mut file, len)?;
+                if body.len() < 16 {
+                    return Err("fmt chunk is too short".to_string());
+                }
+                let u16_at =
+                    |n: usize| u16::from_le_bytes([body[n], body[n + 1]]);
+                let mut format_tag = u16_at(0);
+                let num_channels = u16_at(2) as u32;
+                let sample_rate =
+                    u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let block_align = u16_at(12) as u32;
+                if format_tag == WAVE_FORMAT_EXTENSIBLE {
+                    if body.len() < 40 {
+                        return Err(
+                            "WAVE_FORMAT_EXTENSIBLE fmt chunk is too short"
+                                .to_string(),
+                        );
+                    }
+                    // the first two bytes of the subformat GUID are the
+                    // "real" format tag
+                    format_tag = u16_at(24);
+                }
+                if num_channels == 0
+                    || !block_align.is_multiple_of(num_channels)
+                {
+                    return Err("invalid block alignment".to_string());
+                }
+                let encoding = match (format_tag, block_align / num_channels) {
+                    (WAVE_FORMAT_PCM, 1) => PcmEncoding::U8,
+                    (WAVE_FORMAT_PCM, 2) => PcmEncoding::I16Le,
+                    (WAVE_FORMAT_PCM, 3) => PcmEncoding::I24Le,
+                    (WAVE_FORMAT_PCM, 4) => PcmEncoding::I32Le,
+                    (WAVE_FORMAT_IEEE_FLOAT, 4) => PcmEncoding::F32Le,
+                    (tag, size) => {
+                        return Err(format!(
+                            "unsupported WAV format: tag 0x{:04X}, {} bytes \
+                             per sample",
+                            tag, size
+                        ))
+                    }
+                };
+                format = Some((encoding, num_channels, sample_rate));
+            }
+            b"data" => {
+                let data_start =
+                    file.stream_position().map_err(|x| format!("{}", x))?;
+                data = Some((data_start, len));
+                skip_chunk_body(&mut file, len)?;
+            }
+            _ => skip_chunk_body(&mut file, len)?,
+        }
+    }
+    let (encoding, num_channels, sample_rate) =
+        format.ok_or_else(|| "no fmt chunk".to_string())?;
+    let (data_start, data_len) =
+        data.ok_or_else(|| "no data chunk".to_string())?;
+    let frame_size = encoding.bytes_per_sample() as u64 * num_channels as u64;
+    make_stream(
+        file,
+        PcmInfo {
+            encoding,
+            num_channels,
+            sample_rate: sample_rate as f64,
+            data_start,
+            num_frames: data_len as u64 / frame_size,
+        },
+    )
+}
+
+pub(super) fn is_aiff(magic: &[u8; 12]) -> bool {
+    &magic[0..4] == b"FORM"
+        && (&magic[8..12] == b"AIFF" || &magic[8..12] == b"AIFC")
+}
+
+/// Converts an 80-bit IEEE 754 extended precision float, as used for the
+/// sample rate in AIFF files, into an `f64`.
+fn extended_to_f64(bytes: &[u8]) -> f64 {
+    let sign_and_exponent = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let mut mantissa = [0u8; 8];
+    mantissa.copy_from_slice(&bytes[2..10]);
+    let mantissa = u64::from_be_bytes(mantissa);
+    let exponent = (sign_and_exponent & 0x7FFF) as i32;
+    if exponent == 0 && mantissa == 0 {
+        return 0.0;
+    }
+    let magnitude = mantissa as f64 * 2f64.powi(exponent - 16383 - 63);
+    if sign_and_exponent & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+pub(super) fn open_aiff(
+    mut file: BufReader<File>,
+) -> Result<FormattedSoundStream, String> {
+    let mut magic = [0u8; 12];
+    file.read_exact(&mut magic).map_err(|x| format!("{}", x))?;
+    let is_aifc = &magic[8..12] == b"AIFC";
+    let mut format = None;
+    let mut data = None;
+    while format.is_none() || data.is_none() {
+        let Some((id, len)) = read_chunk_header(&mut file, true)? else {
+            break;
+        };
+        match &id {
+            b"COMM" => {
+                let body = read_chunk_body(&mut file, len)?;
+                if body.len() < 18 || (is_aifc && body.len() < 22) {
+                    return Err("COMM chunk is too short".to_string());
+                }
+                let num_channels =
+                    u16::from_be_bytes([body[0], body[1]]) as u32;
+                let num_frames =
+                    u32::from_be_bytes([body[2], body[3], body[4], body[5]])
+                        as u64;
+                let sample_size = u16::from_be_bytes([body[6], body[7]]);
+                let sample_rate = extended_to_f64(&body[8..18]);
+                let compression = if is_aifc {
+                    [body[18], body[19], body[20], body[21]]
+                } else {
+                    *b"NONE"
+                };
+                let encoding = match (&compression, sample_size.div_ceil(8)) {
+                    (b"NONE" | b"twos", 1) => PcmEncoding::I8,
+                    (b"NONE" | b"twos", 2) => PcmEncoding::I16Be,
+                    (b"NONE" | b"twos", 3) => PcmEncoding::I24Be,
+                    (b"NONE" | b"twos", 4) => PcmEncoding::I32Be,
+                    (b"sowt", 2) => PcmEncoding::I16Le,
+                    (b"fl32" | b"FL32", _) => PcmEncoding::F32Be,
+                    (compression, size) => {
+                        return Err(format!(
+                            "unsupported AIFF format: compression {:?}, {} \
+                             bytes per sample",
+                            String::from_utf8_lossy(compression),
+                            size
+                        ))
+                    }
+                };
+                format =
+                    Some((encoding, num_channels, sample_rate, num_frames));
+            }
+            b"SSND" => {
+                let body_start =
+                    file.stream_position().map_err(|x| format!("{}", x))?;
+                let mut offset = [0u8; 4];
+                file.read_exact(&mut offset).map_err(|x| format!("{}", x))?;
+                // the offset is followed by a block size, which we ignore
+                data =
+                    Some(body_start + 8 + u32::from_be_bytes(offset) as u64);
+                file.seek(SeekFrom::Start(body_start))
+                    .map_err(|x| format!("{}", x))?;
+                skip_chunk_body(&mut file, len)?;
+            }
+            _ => skip_chunk_body(&mut file, len)?,
+        }
+    }
+    let (encoding, num_channels, sample_rate, num_frames) =
+        format.ok_or_else(|| "no COMM chunk".to_string())?;
+    let data_start = data.ok_or_else(|| "no SSND chunk".to_string())?;
+    make_stream(
+        file,
+        PcmInfo {
+            encoding,
+            num_channels,
+            sample_rate,
+            data_start,
+            num_frames,
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn read_all_i16(stream: &mut FormattedSoundStream) -> Vec<i16> {
+        let FormattedSoundReader::I16(reader) = &mut stream.reader else {
+            panic!("expected 16-bit reader");
+        };
+        let mut buf = [MaybeUninit::uninit(); 64];
+        let count = reader.read(&mut buf[..]);
+        buf[..count]
+            .iter()
+            .map(|x| unsafe { x.assume_init() })
+            .collect()
+    }
+    const SAMPLES: [i16; 6] = [0, 1000, -1000, 32767, -32768, 5];
+    #[test]
+    fn wav_round_trip() {
+        let mut wav = vec![];
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36u32 + 12).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&22050u32.to_le_bytes());
+        wav.extend_from_slice(&(22050u32 * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&12u32.to_le_bytes());
+        for sample in SAMPLES {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        let dir = TempDir::with_file("test.wav", &wav);
+        let delegate = FileSoundDelegate::new(dir.path());
+        let mut stream = delegate.open_file("test.wav").unwrap();
+        assert_eq!(*stream.sample_rate, 22050.0);
+        assert_eq!(stream.speaker_layout, SpeakerLayout::Stereo);
+        assert_eq!(read_all_i16(&mut stream), SAMPLES);
+        assert_eq!(stream.reader.seek(1), Some(1));
+        assert_eq!(read_all_i16(&mut stream), SAMPLES[2..]);
+    }
+    #[test]
+    fn wav_u8_round_trip() {
+        const SAMPLES: [u8; 5] = [128, 0, 255, 64, 192];
+        let mut wav = vec![];
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36u32 + 5).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&8u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&5u32.to_le_bytes());
+        wav.extend_from_slice(&SAMPLES);
+        let dir = TempDir::with_file("test.wav", &wav);
+        let delegate = FileSoundDelegate::new(dir.path());
+        let mut stream = delegate.open_file("test.wav").unwrap();
+        assert_eq!(*stream.sample_rate, 8000.0);
+        assert_eq!(stream.speaker_layout, SpeakerLayout::Mono);
+        let FormattedSoundReader::U8(reader) = &mut stream.reader else {
+            panic!("expected 8-bit reader");
+        };
+        let mut buf = [MaybeUninit::uninit(); 8];
+        assert_eq!(reader.read(&mut buf[..]), SAMPLES.len());
+        let samples: Vec<u8> = buf[..SAMPLES.len()]
+            .iter()
+            .map(|x| unsafe { x.assume_init() })
+            .collect();
+        assert_eq!(samples, SAMPLES);
+        // (unsigned 8-bit samples are centered on 128)
+        let floats: Vec<f32> =
+            samples.iter().map(Sample::to_float_sample).collect();
+        assert_eq!(floats, [0.0, -1.0, 127.0 / 128.0, -0.5, 0.5]);
+    }
+    #[test]
+    fn aiff_round_trip() {
+        let mut aiff = vec![];
+        aiff.extend_from_slice(b"FORM");
+        aiff.extend_from_slice(&(4u32 + 26 + 28).to_be_bytes());
+        aiff.extend_from_slice(b"AIFFCOMM");
+        aiff.extend_from_slice(&18u32.to_be_bytes());
+        aiff.extend_from_slice(&1u16.to_be_bytes());
+        aiff.extend_from_slice(&6u32.to_be_bytes());
+        aiff.extend_from_slice(&16u16.to_be_bytes());
+        // 44100 as an 80-bit extended float
+        aiff.extend_from_slice(&[
+            0x40, 0x0E, 0xAC, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        aiff.extend_from_slice(b"SSND");
+        aiff.extend_from_slice(&20u32.to_be_bytes());
+        aiff.extend_from_slice(&0u32.to_be_bytes());
+        aiff.extend_from_slice(&0u32.to_be_bytes());
+        for sample in SAMPLES {
+            aiff.extend_from_slice(&sample.to_be_bytes());
+        }
+        let dir = TempDir::with_file("test.aiff", &aiff);
+        let delegate = FileSoundDelegate::new(dir.path());
+        let mut stream = delegate.open_file("test.aiff").unwrap();
+        assert_eq!(*stream.sample_rate, 44100.0);
+        assert_eq!(stream.speaker_layout, SpeakerLayout::Mono);
+        assert_eq!(read_all_i16(&mut stream), SAMPLES);
+        assert_eq!(stream.reader.seek(4), Some(4));
+        assert_eq!(read_all_i16(&mut stream), SAMPLES[4..]);
+    }
+}
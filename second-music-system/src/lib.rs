@@ -1,6 +1,6 @@
 use std::{
     cmp::{Ordering, PartialOrd},
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     sync::Arc,
 };
 
@@ -13,12 +13,17 @@ pub(crate) mod din;
 
 mod data;
 mod delegate;
+mod diff;
 mod engine;
 mod fader;
+#[cfg(any(feature = "builtin-wav", feature = "ogg"))]
+mod filedelegate;
+mod lint;
 mod posfloat;
 pub mod query;
 mod reader;
 mod runtime;
+mod synthetic;
 
 #[doc(inline)]
 pub use data::StringOrNumber;
@@ -26,15 +31,24 @@ use data::*;
 #[doc(inline)]
 pub use delegate::*;
 #[doc(inline)]
+pub use diff::*;
+#[doc(inline)]
 pub use engine::*;
 #[doc(inline)]
 pub use fader::*;
+#[cfg(any(feature = "builtin-wav", feature = "ogg"))]
+#[doc(inline)]
+pub use filedelegate::*;
+#[doc(inline)]
+pub use lint::*;
 #[doc(inline)]
 pub use posfloat::*;
 #[doc(inline)]
 pub use reader::*;
 #[doc(inline)]
 pub use runtime::*;
+#[doc(inline)]
+pub use synthetic::*;
 
 /// Encapsulates all the information about a soundtrack: what files to play,
 /// how to play them, etc. This is purely inert data. It can be built up
@@ -44,6 +58,15 @@ pub struct Soundtrack {
     flows: Arcow<HashMap<CompactString, Arc<Flow>>>,
     sequences: Arcow<HashMap<CompactString, Arc<Sequence>>>,
     sounds: Arcow<HashMap<CompactString, Arc<Sound>>>,
+    presets: Arcow<HashMap<CompactString, Arc<Preset>>>,
+    /// The channel sounds play on when the soundtrack doesn't specify one.
+    default_channel: CompactString,
+    /// The highest volume each MixControl can be faded to, for those that
+    /// have a `maxgain`.
+    channel_max_volumes: Arcow<HashMap<CompactString, PosFloat>>,
+    /// Named groups of MixControls, which can be faded all at once. See
+    /// `EngineCommands::fade_group_to`.
+    channel_groups: Arcow<HashMap<CompactString, Vec<CompactString>>>,
 }
 
 impl Soundtrack {
@@ -52,11 +75,190 @@ impl Soundtrack {
             flows: Arcow::new(HashMap::new()),
             sequences: Arcow::new(HashMap::new()),
             sounds: Arcow::new(HashMap::new()),
+            presets: Arcow::new(HashMap::new()),
+            default_channel: DEFAULT_CHANNEL.to_compact_string(),
+            channel_max_volumes: Arcow::new(HashMap::new()),
+            channel_groups: Arcow::new(HashMap::new()),
         }
     }
     pub fn from_source(source: &str) -> Result<Soundtrack, String> {
         Soundtrack::new().parse_source(source)
     }
+    /// Returns the name of the channel that sounds play on when the
+    /// soundtrack doesn't say otherwise. See `set_default_channel`.
+    pub fn default_channel(&self) -> &str {
+        &self.default_channel
+    }
+    /// Changes the name of the channel that sounds play on when the
+    /// soundtrack doesn't say otherwise, from `main` (or whatever it was
+    /// changed to before) to `channel`. This affects sounds played directly
+    /// by nodes right away, but sequence elements get their channel when
+    /// they're parsed, so call this *before* `parse_source` for it to apply
+    /// to them.
+    ///
+    /// The `Engine` has its own idea of which MixControl is the default; see
+    /// `Engine::set_default_channel`. You will usually want to give both the
+    /// same name.
+    pub fn set_default_channel(&mut self, channel: CompactString) {
+        self.default_channel = channel;
+    }
+    /// Returns the highest volume the given MixControl can be faded to, as
+    /// set by a `channel NAME maxgain VOLUME` element, or `None` if the
+    /// soundtrack doesn't limit it. The `Engine` caps the target volume of
+    /// every MixControl fade at this. (A MixControl that's already louder
+    /// than its new `maxgain`, after a soundtrack is replaced, stays that way
+    /// until it's next faded.)
+    pub fn channel_max_volume(&self, channel: &str) -> Option<PosFloat> {
+        self.channel_max_volumes.get(channel).copied()
+    }
+    /// Returns the names of the MixControls in the given group, as declared
+    /// by a `group NAME includes CHANNEL...` element, or `None` if there's no
+    /// such group.
+    pub fn channel_group(&self, group_name: &str) -> Option<&[CompactString]> {
+        self.channel_groups.get(group_name).map(Vec::as_slice)
+    }
+    /// Returns `volume`, or the given MixControl's `maxgain`, whichever is
+    /// lower.
+    fn clamp_channel_volume(
+        &self,
+        channel: &str,
+        volume: PosFloat,
+    ) -> PosFloat {
+        match self.channel_max_volume(channel) {
+            Some(max_volume) => volume.min(max_volume),
+            None => volume,
+        }
+    }
+    /// Returns the names of all the flows in this soundtrack, in no
+    /// particular order.
+    pub fn flow_names(&self) -> impl Iterator<Item = &str> {
+        self.flows.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sequences in this soundtrack, in no
+    /// particular order.
+    pub fn sequence_names(&self) -> impl Iterator<Item = &str> {
+        self.sequences.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the sounds in this soundtrack, in no
+    /// particular order.
+    pub fn sound_names(&self) -> impl Iterator<Item = &str> {
+        self.sounds.keys().map(CompactString::as_str)
+    }
+    /// Returns the names of all the presets in this soundtrack, in no
+    /// particular order.
+    pub fn preset_names(&self) -> impl Iterator<Item = &str> {
+        self.presets.keys().map(CompactString::as_str)
+    }
+    /// Returns true if this soundtrack has a flow with the given name.
+    pub fn has_flow(&self, name: &str) -> bool {
+        self.flows.contains_key(name)
+    }
+    /// Returns true if this soundtrack has a sequence with the given name.
+    pub fn has_sequence(&self, name: &str) -> bool {
+        self.sequences.contains_key(name)
+    }
+    /// Returns true if this soundtrack has a sound with the given name.
+    pub fn has_sound(&self, name: &str) -> bool {
+        self.sounds.contains_key(name)
+    }
+    /// Returns true if this soundtrack has a preset with the given name.
+    pub fn has_preset(&self, name: &str) -> bool {
+        self.presets.contains_key(name)
+    }
+    /// Returns the number of flows in this soundtrack.
+    pub fn flow_count(&self) -> usize {
+        self.flows.len()
+    }
+    /// Returns the number of sequences in this soundtrack.
+    pub fn sequence_count(&self) -> usize {
+        self.sequences.len()
+    }
+    /// Returns the number of sounds in this soundtrack.
+    pub fn sound_count(&self) -> usize {
+        self.sounds.len()
+    }
+    /// Returns a human-readable listing of the commands in every node of the
+    /// given flow, as they will actually be executed: `if`/`else` chains are
+    /// shown as the conditional gotos they were turned into, with each goto
+    /// target labeled. Returns `None` if there's no such flow. The format is
+    /// meant for debugging, and may change at any time.
+    pub fn dump_flow(&self, flow_name: &str) -> Option<String> {
+        self.flows.get(flow_name).map(|flow| flow.dump())
+    }
+    /// Returns how long the given sequence lasts, in seconds, or `None` if
+    /// there's no such sequence.
+    pub fn sequence_duration(&self, sequence_name: &str) -> Option<PosFloat> {
+        self.sequences
+            .get(sequence_name)
+            .map(|sequence| sequence.duration())
+    }
+    /// Estimates the longest the given flow can run for, in seconds, taking
+    /// the longest way through every `if`. Returns `None` if there's no such
+    /// flow, or if there's no telling how long it will run (because it loops,
+    /// waits for a condition, or waits for a sound whose length isn't known).
+    /// It counts `wait`s, `play ... and wait`s, and nodes started by other
+    /// nodes, but not sounds left playing after the last node finishes. This
+    /// is a best-effort estimate, meant for things like drawing timelines.
+    pub fn flow_max_duration(&self, flow_name: &str) -> Option<PosFloat> {
+        self.flows
+            .get(flow_name)
+            .and_then(|flow| flow.max_duration(self))
+    }
+    /// Returns the names of every channel (i.e. MixControl) the given flow's
+    /// sounds play on or are sent to, directly or by way of sequences, or
+    /// `None` if there's no such flow. Sounds that don't specify a channel
+    /// play on `main`. Useful for setting up a flow's channels before it
+    /// starts.
+    pub fn flow_channels(
+        &self,
+        flow_name: &str,
+    ) -> Option<HashSet<CompactString>> {
+        self.flows
+            .get(flow_name)
+            .map(|flow| flow.referenced_channels(self))
+    }
+    /// Returns the editor metadata of the given flow, or `None` if there's
+    /// no such flow. Always empty unless the soundtrack was parsed with
+    /// `parse_source_with_metadata`.
+    pub fn flow_metadata(
+        &self,
+        flow_name: &str,
+    ) -> Option<&HashMap<String, String>> {
+        self.flows.get(flow_name).map(|flow| &flow.metadata)
+    }
+    /// Returns the editor metadata of the given node of the given flow, or
+    /// `None` if there's no such flow or node. Always empty unless the
+    /// soundtrack was parsed with `parse_source_with_metadata`.
+    pub fn node_metadata(
+        &self,
+        flow_name: &str,
+        node_name: &str,
+    ) -> Option<&HashMap<String, String>> {
+        self.flows
+            .get(flow_name)
+            .and_then(|flow| flow.nodes.get(node_name))
+            .map(|node| &node.metadata)
+    }
+    /// Returns the editor metadata of the given sequence, or `None` if
+    /// there's no such sequence. Always empty unless the soundtrack was
+    /// parsed with `parse_source_with_metadata`.
+    pub fn sequence_metadata(
+        &self,
+        sequence_name: &str,
+    ) -> Option<&HashMap<String, String>> {
+        self.sequences
+            .get(sequence_name)
+            .map(|sequence| &sequence.metadata)
+    }
+    /// Returns the editor metadata of the given sound, or `None` if there's
+    /// no such sound. Always empty unless the soundtrack was parsed with
+    /// `parse_source_with_metadata`.
+    pub fn sound_metadata(
+        &self,
+        sound_name: &str,
+    ) -> Option<&HashMap<String, String>> {
+        self.sounds.get(sound_name).map(|sound| &sound.metadata)
+    }
 }
 
 impl Default for Soundtrack {
@@ -81,7 +283,7 @@ pub trait Sample:
 impl private::Sealed for u8 {}
 impl Sample for u8 {
     fn to_float_sample(&self) -> f32 {
-        (*self - 128) as f32 * (1.0 / 128.0)
+        (*self as i16 - 128) as f32 * (1.0 / 128.0)
     }
     fn make_formatted_sound_reader_from(
         value: Box<dyn SoundReader<u8>>,
@@ -93,7 +295,7 @@ impl Sample for u8 {
 impl private::Sealed for u16 {}
 impl Sample for u16 {
     fn to_float_sample(&self) -> f32 {
-        (*self - 32768) as f32 * (1.0 / 32768.0)
+        (*self as i32 - 32768) as f32 * (1.0 / 32768.0)
     }
     fn make_formatted_sound_reader_from(
         value: Box<dyn SoundReader<u16>>,
@@ -0,0 +1,291 @@
+//! Checking a soundtrack's source code for every problem at once, instead of
+//! stopping at the first one, e.g. for a CI step or a pre-commit hook.
+
+use super::*;
+
+/// How serious a problem found by `Soundtrack::lint` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintSeverity {
+    /// Probably a mistake, but the soundtrack will still load and play.
+    Warning,
+    /// The soundtrack won't load (`parse_source` would fail), or refers to
+    /// something that doesn't exist.
+    Error,
+}
+
+/// A problem found by `Soundtrack::lint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintMessage {
+    pub severity: LintSeverity,
+    /// The line the problem is on, if it's on any particular line.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        match self.severity {
+            LintSeverity::Warning => write!(f, "warning: ")?,
+            LintSeverity::Error => write!(f, "error: ")?,
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Makes errors out of one of the parser's error messages, taking the line
+/// number back off the front of it. `lineno` is the line the element with the
+/// problem started on, for messages that don't have one.
+fn parse_error_messages(
+    lineno: usize,
+    mut message: &str,
+    out: &mut Vec<LintMessage>,
+) {
+    if let Some(rest) =
+        message.strip_prefix("the following nodes were not understood:\n")
+    {
+        // one error for each of them, on its own line
+        for line in rest.lines() {
+            let (line, message) = match line
+                .trim_start()
+                .strip_prefix("line ")
+                .and_then(|x| x.split_once(": "))
+                .and_then(|(line, rest)| Some((line.parse().ok()?, rest)))
+            {
+                Some((line, item)) => {
+                    (line, format!("{item} was not understood"))
+                }
+                None => (lineno, line.trim_start().to_string()),
+            };
+            out.push(LintMessage {
+                severity: LintSeverity::Error,
+                line: Some(line),
+                message,
+            });
+        }
+        return;
+    }
+    let mut line = lineno;
+    // (some errors get the line number put on them more than once)
+    while let Some((lineno, rest)) = message
+        .strip_prefix("line ")
+        .and_then(|x| x.split_once(": "))
+        .and_then(|(lineno, rest)| Some((lineno.parse().ok()?, rest)))
+    {
+        line = lineno;
+        message = rest;
+    }
+    out.push(LintMessage {
+        severity: LintSeverity::Error,
+        line: Some(line),
+        message: message.to_string(),
+    });
+}
+
+impl Soundtrack {
+    /// Checks a soundtrack's source code for problems, without stopping at
+    /// the first one. Returns every problem found, in line order. If this
+    /// returns no `LintSeverity::Error`s, `parse_source` will succeed.
+    ///
+    /// As well as everything `parse_source` would reject, this finds flows
+    /// that refer to sounds or sequences that don't exist, and sounds that
+    /// aren't used by any flow. (The latter are only warnings, since they
+    /// might be meant for `play_one_shot`.) It also warns about commands
+    /// that can never run, because they come after a `done` (or after an
+    /// `if` whose every branch ends with one). An element that can't be parsed
+    /// is left out, so only its own problems are reported, not every
+    /// reference to it. If the source has bad indentation, only that is
+    /// reported.
+    pub fn lint(source: &str) -> Vec<LintMessage> {
+        let parse = Soundtrack::new().parse_source_leniently(source);
+        let mut messages = vec![];
+        for (lineno, error) in parse.errors.iter() {
+            parse_error_messages(*lineno, error, &mut messages);
+        }
+        let soundtrack = &parse.soundtrack;
+        let line_of = |kind: &str, name: &str| {
+            parse
+                .definition_lines
+                .get(&(kind.into(), name.into()))
+                .copied()
+        };
+        let is_broken = |kind: &str, name: &str| {
+            parse.broken_names.iter().any(|(broken_kind, broken_name)| {
+                broken_kind.as_str() == kind && broken_name.as_str() == name
+            })
+        };
+        let mut used_sounds = HashSet::new();
+        for flow in soundtrack.flows.values() {
+            let line = line_of("flow", &flow.name);
+            let mut missing_sounds = vec![];
+            let mut missing_sequences = vec![];
+            let sounds = flow.find_all_sounds(
+                soundtrack,
+                |sound_name| {
+                    if !is_broken("sound", sound_name) {
+                        missing_sounds.push(sound_name.to_compact_string());
+                    }
+                },
+                |sequence_name| {
+                    if !is_broken("sequence", sequence_name) {
+                        missing_sequences
+                            .push(sequence_name.to_compact_string());
+                    }
+                },
+            );
+            used_sounds.extend(sounds.into_iter().map(|x| x.name.clone()));
+            for node in Some(&flow.start_node).into_iter().chain(
+                // (sorted, so the messages come out in the same order
+                // every time)
+                flow.nodes.iter().collect::<BTreeMap<_, _>>().into_values(),
+            ) {
+                let unreachable = node.unreachable_commands().len();
+                if unreachable == 0 {
+                    continue;
+                }
+                let node_name = match node.name.as_ref() {
+                    Some(name) => format!("node {name:?}"),
+                    None => "the starting node".to_string(),
+                };
+                messages.push(LintMessage {
+                    severity: LintSeverity::Warning,
+                    line,
+                    message: format!(
+                        "in flow {:?}, {unreachable} command{} in \
+                         {node_name} can never run, because of an earlier \
+                         \"done\"",
+                        flow.name,
+                        if unreachable == 1 { "" } else { "s" },
+                    ),
+                });
+            }
+            let missing = missing_sounds
+                .into_iter()
+                .map(|x| ("sound", x))
+                .chain(missing_sequences.into_iter().map(|x| ("sequence", x)));
+            messages.extend(missing.map(|(kind, name)| LintMessage {
+                severity: LintSeverity::Error,
+                line,
+                message: format!(
+                    "flow {:?} uses {kind} {name:?}, which doesn't exist",
+                    flow.name
+                ),
+            }));
+        }
+        // (playing a region of a sound counts as using the sound, and the
+        // regions themselves don't need to be used)
+        let mut region_sounds = HashSet::new();
+        for sound in soundtrack.sounds.values() {
+            for region in sound.regions.keys() {
+                let region_name = region_sound_name(&sound.name, region);
+                if used_sounds.contains(&region_name) {
+                    used_sounds.insert(sound.name.clone());
+                }
+                region_sounds.insert(region_name);
+            }
+        }
+        for name in soundtrack.sounds.keys() {
+            if !used_sounds.contains(name) && !region_sounds.contains(name) {
+                messages.push(LintMessage {
+                    severity: LintSeverity::Warning,
+                    line: line_of("sound", name),
+                    message: format!("sound {name:?} isn't used by any flow"),
+                });
+            }
+        }
+        // (stable, so messages about the same line stay in the order they
+        // were found)
+        messages.sort_by_key(|x| x.line);
+        messages
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lint_finds_every_problem() {
+        let messages = Soundtrack::lint(
+            r#"sound used
+  file used.wav
+sound spare
+  file spare.wav
+sound broken
+  file broken.wav
+  bogus
+flow test
+  play sound used
+  play sound broken
+  play sound missing
+flow other
+  play sequence nowhere
+flow worse
+  wibble
+"#,
+        );
+        let messages: Vec<String> =
+            messages.iter().map(ToString::to_string).collect();
+        assert_eq!(messages.len(), 5, "{messages:#?}");
+        assert_eq!(
+            messages[0],
+            "line 3: warning: sound \"spare\" isn't used by any flow"
+        );
+        assert_eq!(messages[1], "line 7: error: \"bogus\" was not understood");
+        assert_eq!(
+            messages[2],
+            "line 8: error: flow \"test\" uses sound \"missing\", which \
+             doesn't exist"
+        );
+        assert_eq!(
+            messages[3],
+            "line 12: error: flow \"other\" uses sequence \"nowhere\", which \
+             doesn't exist"
+        );
+        assert!(
+            messages[4].starts_with("line 15: error: "),
+            "{}",
+            messages[4]
+        );
+        assert!(Soundtrack::lint("flow test\n  play sound missing\n")
+            .iter()
+            .all(|x| x.line == Some(1)));
+    }
+
+    #[test]
+    fn lint_finds_unreachable_commands() {
+        let messages = Soundtrack::lint(
+            r#"sound used
+  file used.wav
+flow test
+  play sound used
+  done
+  play sound used
+  node ending
+    if $x then
+      done
+    else
+      done
+    play sound used
+    wait 1
+  node fine
+    if $x then
+      done
+    play sound used
+"#,
+        );
+        let messages: Vec<String> =
+            messages.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "line 3: warning: in flow \"test\", 1 command in the \
+                 starting node can never run, because of an earlier \"done\"",
+                "line 3: warning: in flow \"test\", 2 commands in node \
+                 \"ending\" can never run, because of an earlier \"done\"",
+            ]
+        );
+    }
+}
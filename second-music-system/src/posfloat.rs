@@ -5,6 +5,7 @@ use std::{
     num::ParseFloatError,
     ops::{Add, Deref, Div, Mul},
     str::FromStr,
+    time::Duration,
 };
 
 use super::SpeakerLayout;
@@ -15,6 +16,12 @@ use super::SpeakerLayout;
 /// This is used in many places in SMS where a negative or infinite f32 would
 /// break things: timestamps, sample rates, etc. Zero would break some of these
 /// too, but less Interestingly.
+///
+/// PosFloats can be added, multiplied, and divided with the usual operators
+/// (see `saturating_add` and friends if the result might not be finite), and,
+/// since they're `Ord`, compared and `min`ed and `max`ed. They convert to and
+/// from `f32`s (fallibly, with `new` or `try_from`, or infallibly, with
+/// `new_clamped`) and `Duration`s (as a number of seconds).
 pub struct PosFloat(f32);
 
 impl PosFloat {
@@ -51,6 +58,12 @@ impl PosFloat {
         */
         PosFloat(x)
     }
+    /// Converts a gain in decibels to a (linear) PosFloat. -6dB is about 0.5,
+    /// 0dB is 1.0, and `-inf`dB is exactly zero. If the result would be too
+    /// large to be finite, or `db` is NaN, returns zero (as `new_clamped`).
+    pub fn from_db(db: f32) -> PosFloat {
+        PosFloat::new_clamped(10f32.powf(db / 20.0))
+    }
     /// Create a new PosFloat from an f32. If it is non-finite or negative,
     /// return zero.
     pub fn new_clamped(x: f32) -> PosFloat {
@@ -91,6 +104,32 @@ impl PosFloat {
             PosFloat(ret)
         }
     }
+    /// Add `addend` to ourselves and return the result. If the result would
+    /// have been too large to be finite, return the largest finite value
+    /// instead. (Plain `+` doesn't check for this.)
+    pub fn saturating_add(&self, addend: PosFloat) -> PosFloat {
+        let ret = self.0 + addend.0;
+        if ret.is_finite() {
+            PosFloat(ret)
+        } else {
+            PosFloat(f32::MAX)
+        }
+    }
+    /// As `saturating_add`, but for multiplication.
+    pub fn saturating_mul(&self, factor: PosFloat) -> PosFloat {
+        let ret = self.0 * factor.0;
+        if ret.is_finite() {
+            PosFloat(ret)
+        } else {
+            PosFloat(f32::MAX)
+        }
+    }
+    /// Interprets this `PosFloat` as a time in seconds, and converts it to a
+    /// `Duration`. (Same as `Duration::from(x)`.) Times too long for a
+    /// `Duration` become `Duration::MAX`.
+    pub fn to_duration(&self) -> Duration {
+        Duration::try_from_secs_f32(self.0).unwrap_or(Duration::MAX)
+    }
 }
 
 impl Display for PosFloat {
@@ -164,6 +203,35 @@ impl From<usize> for PosFloat {
     }
 }
 
+/// The number of seconds in the `Duration`. (Rounded to the nearest `f32`.)
+impl From<Duration> for PosFloat {
+    fn from(value: Duration) -> PosFloat {
+        PosFloat(value.as_secs_f32())
+    }
+}
+
+/// Fails (with the same errors as `PosFloat::new`) if the `f32` is negative
+/// or not finite.
+impl TryFrom<f32> for PosFloat {
+    type Error = &'static str;
+    fn try_from(value: f32) -> Result<PosFloat, &'static str> {
+        PosFloat::new(value)
+    }
+}
+
+impl From<PosFloat> for f32 {
+    fn from(value: PosFloat) -> f32 {
+        value.0
+    }
+}
+
+/// Interprets the `PosFloat` as a time in seconds.
+impl From<PosFloat> for Duration {
+    fn from(value: PosFloat) -> Duration {
+        value.to_duration()
+    }
+}
+
 pub enum TimePointFromStrError {
     ParseFloatError(ParseFloatError),
     NewTimePointError(&'static str),
@@ -208,3 +276,28 @@ impl Mul<PosFloat> for PosFloat {
         PosFloat(self.0 * rhs.0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions_and_saturation() {
+        let half = Duration::from_millis(500);
+        assert_eq!(PosFloat::from(half), PosFloat::HALF);
+        assert_eq!(Duration::from(PosFloat::HALF), half);
+        assert_eq!(PosFloat::try_from(0.5), Ok(PosFloat::HALF));
+        assert!(PosFloat::try_from(-1.0).is_err());
+        assert!(PosFloat::try_from(f32::NAN).is_err());
+        assert_eq!(f32::from(PosFloat::ONE), 1.0);
+        let big = PosFloat::new_clamped(f32::MAX);
+        assert_eq!(big.saturating_add(big), big);
+        assert_eq!(big.saturating_mul(PosFloat::THOUSAND), big);
+        assert_eq!(
+            PosFloat::ONE.saturating_add(PosFloat::ONE),
+            PosFloat::from(2u8)
+        );
+        assert_eq!(PosFloat::ONE.max(PosFloat::HALF), PosFloat::ONE);
+        assert_eq!(Duration::from(big), Duration::MAX);
+    }
+}
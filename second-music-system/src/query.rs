@@ -2,6 +2,16 @@
 //! boundaries. A query is a question that can only be answered by one thread,
 //! and only be answered to one thread. In essence, this is a one-shot
 //! single-producer-single-consumer channel.
+//!
+//! You don't usually make queries yourself. Instead, the question-asking
+//! methods of `EngineCommands` (such as `is_flow_active`, `is_flow_ready`, and
+//! `get_flow_control`) each return a `Response`. Called on a `Commander`,
+//! they can be asked from any thread without ever blocking the thread that
+//! owns the `Engine`; the questions are answered there, in order with any
+//! other commands, the next time the `Engine` processes commands (usually
+//! during `turn_handle`). Check on a `Response` with `poll`, `get`, or
+//! `take`, or `.await` it. With the `async` feature, a `ResponseStream` can
+//! gather up several `Response`s and give their answers back as a `Stream`.
 
 use std::{
     cell::UnsafeCell,
@@ -164,3 +174,60 @@ impl<T: Send + Sized> Future for Response<T> {
         }
     }
 }
+
+/// Several `Response`s, as a `Stream` of their answers, in the order they were
+/// `push`ed. Only available with the `async` feature.
+#[cfg(feature = "async")]
+pub struct ResponseStream<T: Send + Sized>(
+    futures::stream::FuturesOrdered<Response<T>>,
+);
+
+#[cfg(feature = "async")]
+impl<T: Send + Sized> ResponseStream<T> {
+    /// Creates a new, empty `ResponseStream`.
+    pub fn new() -> ResponseStream<T> {
+        ResponseStream(futures::stream::FuturesOrdered::new())
+    }
+    /// Adds a `Response` to the end of the stream.
+    pub fn push(&mut self, response: Response<T>) {
+        self.0.push_back(response)
+    }
+    /// Returns the number of answers still to come.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Returns true if there are no answers still to come.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + Sized> Default for ResponseStream<T> {
+    fn default() -> ResponseStream<T> {
+        ResponseStream::new()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + Sized> FromIterator<Response<T>> for ResponseStream<T> {
+    fn from_iter<I: IntoIterator<Item = Response<T>>>(
+        iter: I,
+    ) -> ResponseStream<T> {
+        ResponseStream(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + Sized> futures::Stream for ResponseStream<T> {
+    type Item = T;
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<T>> {
+        std::pin::Pin::new(&mut self.0).poll_next(cx)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
@@ -93,6 +93,9 @@ pub trait SoundReader<T: Sample>: Send {
     /// to buf. If this is not *exactly* equal to the size of the buf, then the
     /// stream is assumed to have been ended; either it will be disposed of,
     /// or `seek` will be called.
+    ///
+    /// If decoding fails partway through (e.g. the file is corrupt), return
+    /// whatever you could decode, and have `take_error` say what went wrong.
     fn read(&mut self, buf: &mut [MaybeUninit<T>]) -> usize;
     /// Attempt to seek to the given *sample frame count* from the beginning of
     /// the file. Imprecision is permitted in one direction only: seeking is
@@ -201,6 +204,18 @@ pub trait SoundReader<T: Sample>: Send {
     fn estimate_len(&mut self) -> Option<u64> {
         None
     }
+    /// If the last `read` came up short because decoding *failed*, rather
+    /// than because the sound ended, return a description of what went wrong.
+    /// SMS calls this whenever a `read` comes up short. If it returns `Some`,
+    /// SMS tells the `SoundDelegate` (with `DiagCode::DecodeFailed` and the
+    /// sound's name) and stops using the stream, instead of playing or
+    /// looping a truncated sound. A buffered sound that fails to decode is
+    /// replaced with silence.
+    ///
+    /// Default implementation returns `None`, i.e. decoding never fails.
+    fn take_error(&mut self) -> Option<String> {
+        None
+    }
 }
 
 impl FormattedSoundReader {
@@ -260,6 +275,17 @@ impl FormattedSoundReader {
             }
         }
     }
+    /// If the last read came up short because decoding failed, returns a
+    /// description of what went wrong. See `SoundReader::take_error`.
+    pub fn take_error(&mut self) -> Option<String> {
+        match self {
+            FormattedSoundReader::U8(x) => x.take_error(),
+            FormattedSoundReader::U16(x) => x.take_error(),
+            FormattedSoundReader::I8(x) => x.take_error(),
+            FormattedSoundReader::I16(x) => x.take_error(),
+            FormattedSoundReader::F32(x) => x.take_error(),
+        }
+    }
     /// Attempt to skip exactly the given number of *samples*. Failure is not
     /// an option. Returns true if there is more sound data to come, false if
     /// we have reached the end of the sound.
@@ -1,35 +1,90 @@
-use std::future::Future;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 /// Something where we can put in `async` functions and have them execute
 /// eventually.
 ///
 /// All of our task functions are either of the type "perform some computation
 /// and return" or of the type "perform some blocking IO and computation and
-/// return". Regular blocking IO is used, not any kind of async IO.
+/// return". Regular blocking IO is used, not any kind of async IO. Long
+/// computations (such as decoding a whole buffered sound) yield every so
+/// often, so that a runtime can spread them out if it wants to.
 pub trait TaskRuntime: 'static + Send + Sync {
     fn spawn_task(
         &self,
         kind: TaskType,
         task: impl Future<Output = ()> + Send + 'static,
     );
+    /// Called by the `Engine` every time it mixes (or silently advances),
+    /// from whichever thread is driving it. Runtimes with threads of their
+    /// own can ignore this, and the default implementation does nothing. A
+    /// runtime with no threads, such as `CooperativeTaskRuntime`, can do a
+    /// bounded amount of work on its tasks here.
+    fn turn(&self) {}
 }
 
-/// Types of background loading tasks.
+/// Types of background loading tasks, in order of priority. More types may
+/// be added in the future.
 #[derive(Debug, PartialOrd, Ord, PartialEq, Eq, Copy, Clone)]
+#[non_exhaustive]
 pub enum TaskType {
-    /// Background loading of a buffered sound. Should have the lowest
+    /// Background loading of a buffered sound, which isn't needed yet (e.g.
+    /// for a flow that's only being precached). Should have the lowest
     /// priority.
     BufferLoad,
-    /// Background loading of a streamed sound.
+    /// Background loading of a streamed sound, which isn't needed yet.
     StreamLoad,
+    /// Background loading of a buffered sound that's needed right away, for
+    /// a flow that's been started. Should jump ahead of `BufferLoad`s and
+    /// `StreamLoad`s.
+    UrgentBufferLoad,
+    /// Background loading of a streamed sound that's needed right away, for
+    /// a flow that's been started.
+    UrgentStreamLoad,
     /// Background decoding of a streamed sound. Should have the highest
     /// priority.
     StreamDecode,
 }
 
+/// A task, boxed up so that tasks of different types can wait in the same
+/// queue.
+type BoxedTask = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// How many different `TaskType`s there are.
+const NUM_TASK_TYPES: usize = TaskType::StreamDecode as usize + 1;
+
+/// Returns `Pending` once (asking to be polled again right away), and then
+/// `Ready`. Long tasks await this every so often, so that a runtime can
+/// interrupt them.
+pub(crate) async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|cx: &mut Context| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 mod fg;
 pub use fg::*;
 
+mod cooperative;
+pub use cooperative::*;
+
+mod threadpool;
+pub use threadpool::*;
+
+mod swappable;
+pub(crate) use swappable::*;
+
 #[cfg(feature = "switchyard")]
 mod switchyard;
 #[cfg(feature = "switchyard")]
@@ -0,0 +1,68 @@
+use std::{
+    collections::VecDeque, num::NonZeroUsize, sync::Mutex, task::Context,
+};
+
+use super::*;
+
+/// A `TaskRuntime` for platforms with no threads, such as WebAssembly. Rather
+/// than running tasks as soon as they're spawned (like
+/// `ForegroundTaskRuntime`, which would freeze the only thread while a whole
+/// sound decodes), it works on them a little at a time, each time the
+/// `Engine` mixes.
+///
+/// Each `turn_handle` (or `advance_silently`) polls tasks up to
+/// `polls_per_turn` times. Each poll does a bounded amount of work: opening a
+/// sound, or decoding a chunk of a buffered one. Higher priority `TaskType`s
+/// are worked on first, and a task is worked on until it's finished before
+/// the next task of the same `TaskType` is started.
+///
+/// Loading is slower this way, so precache flows well before you need them.
+/// Turn `polls_per_turn` up if loading is too slow, or down if mixing takes
+/// too long.
+pub struct CooperativeTaskRuntime {
+    polls_per_turn: NonZeroUsize,
+    /// One queue for each `TaskType`, lowest priority first.
+    tasks: Mutex<[VecDeque<BoxedTask>; NUM_TASK_TYPES]>,
+}
+
+impl CooperativeTaskRuntime {
+    /// Makes a `CooperativeTaskRuntime` that polls tasks up to
+    /// `polls_per_turn` times each time the `Engine` mixes.
+    pub fn new(polls_per_turn: NonZeroUsize) -> CooperativeTaskRuntime {
+        CooperativeTaskRuntime {
+            polls_per_turn,
+            tasks: Mutex::new(Default::default()),
+        }
+    }
+}
+
+impl TaskRuntime for CooperativeTaskRuntime {
+    fn spawn_task(
+        &self,
+        kind: TaskType,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) {
+        self.tasks.lock().unwrap()[kind as usize].push_back(Box::pin(task));
+    }
+    fn turn(&self) {
+        // (nothing needs to wake our tasks; we poll them until they finish)
+        let mut context = Context::from_waker(futures::task::noop_waker_ref());
+        for _ in 0..self.polls_per_turn.get() {
+            let Some((kind, mut task)) = self
+                .tasks
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .enumerate()
+                .rev()
+                .find_map(|(kind, tasks)| Some((kind, tasks.pop_front()?)))
+            else {
+                return;
+            };
+            // (not holding the lock, in case the task spawns another)
+            if task.as_mut().poll(&mut context).is_pending() {
+                self.tasks.lock().unwrap()[kind].push_front(task);
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use super::*;
+
+/// An object-safe version of `TaskRuntime`, so that runtimes of different
+/// types can be kept in the same place.
+trait DynTaskRuntime: 'static + Send + Sync {
+    fn spawn_boxed(
+        &self,
+        kind: TaskType,
+        task: Pin<Box<dyn Future<Output = ()> + Send>>,
+    );
+    fn turn_dyn(&self);
+}
+
+impl<T: TaskRuntime> DynTaskRuntime for T {
+    fn spawn_boxed(
+        &self,
+        kind: TaskType,
+        task: Pin<Box<dyn Future<Output = ()> + Send>>,
+    ) {
+        self.spawn_task(kind, task)
+    }
+    fn turn_dyn(&self) {
+        self.turn()
+    }
+}
+
+/// A runtime, and a way to tell whether any of the tasks we gave it are still
+/// around.
+struct RuntimeSlot {
+    runtime: Arc<dyn DynTaskRuntime>,
+    /// Every task we spawn holds a clone of this until it finishes (or is
+    /// dropped).
+    in_flight: Arc<()>,
+}
+
+impl RuntimeSlot {
+    fn new<Runtime: TaskRuntime>(runtime: Arc<Runtime>) -> RuntimeSlot {
+        RuntimeSlot {
+            runtime,
+            in_flight: Arc::new(()),
+        }
+    }
+    fn is_idle(&self) -> bool {
+        Arc::strong_count(&self.in_flight) == 1
+    }
+}
+
+/// A `TaskRuntime` that hands tasks to another runtime, which can be replaced
+/// at any time. See `Engine::replace_runtime`.
+///
+/// Tasks that were already given to a replaced runtime are left to finish
+/// there; we keep that runtime alive until they have.
+pub(crate) struct SwappableRuntime {
+    current: Mutex<RuntimeSlot>,
+    retired: Mutex<Vec<RuntimeSlot>>,
+}
+
+impl SwappableRuntime {
+    pub fn new<Runtime: TaskRuntime>(
+        runtime: Arc<Runtime>,
+    ) -> SwappableRuntime {
+        SwappableRuntime {
+            current: Mutex::new(RuntimeSlot::new(runtime)),
+            retired: Mutex::new(vec![]),
+        }
+    }
+    /// Send all future tasks to `runtime` instead.
+    pub fn replace<Runtime: TaskRuntime>(&self, runtime: Arc<Runtime>) {
+        let old = std::mem::replace(
+            &mut *self.current.lock().unwrap(),
+            RuntimeSlot::new(runtime),
+        );
+        let mut retired = self.retired.lock().unwrap();
+        retired.push(old);
+        retired.retain(|x| !x.is_idle());
+    }
+}
+
+impl TaskRuntime for SwappableRuntime {
+    fn spawn_task(
+        &self,
+        kind: TaskType,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) {
+        {
+            let mut retired = self.retired.lock().unwrap();
+            if !retired.is_empty() {
+                retired.retain(|x| !x.is_idle());
+            }
+        }
+        let (runtime, in_flight) = {
+            let current = self.current.lock().unwrap();
+            (current.runtime.clone(), current.in_flight.clone())
+        };
+        // (not holding the lock, in case the runtime runs the task right
+        // away)
+        runtime.spawn_boxed(
+            kind,
+            Box::pin(async move {
+                task.await;
+                drop(in_flight);
+            }),
+        );
+    }
+    fn turn(&self) {
+        // (a replaced runtime may still need turns to finish its tasks)
+        for slot in self.retired.lock().unwrap().iter() {
+            slot.runtime.turn_dyn();
+        }
+        let runtime = self.current.lock().unwrap().runtime.clone();
+        runtime.turn_dyn();
+    }
+}
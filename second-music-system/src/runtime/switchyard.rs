@@ -11,8 +11,65 @@ impl<T: Send + 'static> TaskRuntime for Switchyard<T> {
         let priority = match kind {
             TaskType::BufferLoad => 0,
             TaskType::StreamLoad => 1,
-            TaskType::StreamDecode => 2,
+            TaskType::UrgentBufferLoad => 2,
+            TaskType::UrgentStreamLoad => 3,
+            TaskType::StreamDecode => 4,
         };
         self.spawn(priority, task);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::switchyard::threads::ThreadAllocationOutput;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn urgent_loads_jump_the_queue() {
+        let runtime = Switchyard::new(
+            [ThreadAllocationOutput {
+                name: Some("SMStest".to_string()),
+                ident: 0,
+                stack_size: None,
+                affinity: None,
+            }],
+            || (),
+        )
+        .unwrap();
+        // Keep the only thread busy until everything else is queued up.
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        runtime.spawn_task(TaskType::StreamDecode, async move {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        });
+        started_rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        let (done_tx, done_rx) = mpsc::channel();
+        for kind in [
+            TaskType::BufferLoad,
+            TaskType::StreamLoad,
+            TaskType::UrgentBufferLoad,
+            TaskType::UrgentStreamLoad,
+        ] {
+            let done_tx = done_tx.clone();
+            runtime.spawn_task(kind, async move {
+                done_tx.send(kind).unwrap();
+            });
+        }
+        release_tx.send(()).unwrap();
+        let order: Vec<TaskType> = (0..4)
+            .map(|_| done_rx.recv_timeout(Duration::from_secs(10)).unwrap())
+            .collect();
+        assert_eq!(
+            order,
+            [
+                TaskType::UrgentStreamLoad,
+                TaskType::UrgentBufferLoad,
+                TaskType::StreamLoad,
+                TaskType::BufferLoad,
+            ]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::{Arc, Condvar, Mutex},
+};
+
+use super::*;
+
+struct Queue {
+    /// One queue for each `TaskType`, lowest priority first.
+    tasks: [VecDeque<BoxedTask>; NUM_TASK_TYPES],
+    /// Set when the `ThreadPoolTaskRuntime` is dropped.
+    shutting_down: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Notified whenever a task is queued, or when we're shutting down.
+    wakeup: Condvar,
+}
+
+/// A `TaskRuntime` that runs tasks on a fixed number of plain `std::thread`s,
+/// taking them from a shared queue. Higher priority `TaskType`s are always
+/// started ahead of lower priority ones; tasks of the same `TaskType` are
+/// started in the order they were spawned.
+///
+/// This is what an `Engine` uses, if it isn't given a runtime, when the
+/// `switchyard` feature is disabled. It has no dependencies, and doesn't
+/// touch thread affinity, so it's a good choice for platforms where
+/// Switchyard doesn't work well.
+///
+/// When it's dropped, its threads finish whatever task they're on and then
+/// exit. Tasks that haven't been started yet are thrown away.
+pub struct ThreadPoolTaskRuntime {
+    shared: Arc<Shared>,
+}
+
+impl ThreadPoolTaskRuntime {
+    /// Starts up a `ThreadPoolTaskRuntime` with the given number of threads.
+    ///
+    /// Panics if a thread can't be spawned.
+    pub fn new(num_threads: NonZeroUsize) -> ThreadPoolTaskRuntime {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                tasks: Default::default(),
+                shutting_down: false,
+            }),
+            wakeup: Condvar::new(),
+        });
+        for i in 0..num_threads.get() {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name(format!("SMSworker{i}"))
+                .stack_size(1024 * 1024)
+                .spawn(move || worker(&shared))
+                .expect("Unable to spawn SMS worker thread");
+        }
+        ThreadPoolTaskRuntime { shared }
+    }
+}
+
+fn worker(shared: &Shared) {
+    loop {
+        let task = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.shutting_down {
+                    return;
+                }
+                if let Some(task) =
+                    queue.tasks.iter_mut().rev().find_map(VecDeque::pop_front)
+                {
+                    break task;
+                }
+                queue = shared.wakeup.wait(queue).unwrap();
+            }
+        };
+        futures::executor::block_on(task);
+    }
+}
+
+impl TaskRuntime for ThreadPoolTaskRuntime {
+    fn spawn_task(
+        &self,
+        kind: TaskType,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.tasks[kind as usize].push_back(Box::pin(task));
+        drop(queue);
+        self.shared.wakeup.notify_one();
+    }
+}
+
+impl Drop for ThreadPoolTaskRuntime {
+    fn drop(&mut self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.shutting_down = true;
+        // (dropped after the lock is released)
+        let unstarted = std::mem::take(&mut queue.tasks);
+        drop(queue);
+        self.shared.wakeup.notify_all();
+        drop(unstarted);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thread_pool_runs_higher_priorities_first() {
+        let runtime = ThreadPoolTaskRuntime::new(NonZeroUsize::MIN);
+        let (started_tx, started_rx) = crossbeam::channel::bounded(0);
+        let (gate_tx, gate_rx) = crossbeam::channel::bounded(0);
+        let (done_tx, done_rx) = crossbeam::channel::unbounded();
+        // (keeps the only thread busy until everything else is queued)
+        runtime.spawn_task(TaskType::BufferLoad, async move {
+            started_tx.send(()).unwrap();
+            gate_rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+        let kinds = [
+            TaskType::StreamLoad,
+            TaskType::BufferLoad,
+            TaskType::StreamDecode,
+            TaskType::UrgentBufferLoad,
+            TaskType::StreamLoad,
+            TaskType::UrgentStreamLoad,
+        ];
+        for (n, kind) in kinds.into_iter().enumerate() {
+            let done_tx = done_tx.clone();
+            runtime.spawn_task(kind, async move {
+                done_tx.send(n).unwrap();
+            });
+        }
+        gate_tx.send(()).unwrap();
+        let done: Vec<_> = (0..6).map(|_| done_rx.recv().unwrap()).collect();
+        assert_eq!(done, [2, 5, 3, 0, 4, 1]);
+    }
+}
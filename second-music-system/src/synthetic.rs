@@ -0,0 +1,264 @@
+//! A ready-made `SoundDelegate` that makes up its sounds instead of opening
+//! files, for examples, tests, and trying SMS out without any assets.
+
+use crate::*;
+
+use std::mem::MaybeUninit;
+
+/// How long a sine or noise sound is, in seconds, if its name doesn't say.
+const DEFAULT_SECONDS: f32 = 1.0;
+/// How loud sines and noise are. (About -6 dB, so that a couple of them
+/// playing at once won't clip.)
+const AMPLITUDE: f32 = 0.5;
+
+/// What a `SyntheticReader` makes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Waveform {
+    /// A sine wave, at the given frequency in Hz.
+    Sine(f32),
+    /// White noise.
+    WhiteNoise,
+    /// Nothing at all.
+    Silence,
+}
+
+/// A `SoundDelegate` that doesn't open any files. Instead, it makes up
+/// sounds based on their names:
+///
+/// - `sine:440` is a sine wave at 440 Hz.
+/// - `noise:white` is white noise.
+/// - `silence:2.0` is two seconds of silence.
+///
+/// Sines and noise last for one second, unless another number of seconds is
+/// put on the end (e.g. `sine:440:2.5`). Every sound is mono, at about -6 dB,
+/// and at the sample rate the delegate was made with. The same name always
+/// makes exactly the same sound, noise included, so the output of an `Engine`
+/// that uses this delegate can be checked by tests.
+///
+/// The sounds can be seeked and cheaply cloned, and know their own lengths,
+/// so they work equally well whether the soundtrack streams them or buffers
+/// them. This also makes `SyntheticSoundDelegate` a handy example of how to
+/// write a `SoundDelegate` of your own.
+#[derive(Debug, Clone, Copy)]
+pub struct SyntheticSoundDelegate {
+    sample_rate: PosFloat,
+}
+
+impl SyntheticSoundDelegate {
+    /// Create a new `SyntheticSoundDelegate`, whose sounds will all be at the
+    /// given sample rate.
+    pub fn new(sample_rate: PosFloat) -> SyntheticSoundDelegate {
+        SyntheticSoundDelegate { sample_rate }
+    }
+    fn parse_name(&self, name: &str) -> Result<SyntheticReader, String> {
+        let mut parts = name.split(':');
+        let kind = parts.next().unwrap_or("");
+        let parameter = parts.next();
+        let seconds = parts.next();
+        if parts.next().is_some() {
+            return Err("too many parts".to_string());
+        }
+        let parse_seconds = |seconds: &str| {
+            seconds
+                .parse::<f32>()
+                .ok()
+                .and_then(|x| PosFloat::new(x).ok())
+                .filter(|x| x.is_finite())
+                .ok_or_else(|| {
+                    format!("{seconds:?} isn't a number of seconds")
+                })
+        };
+        let (waveform, seconds) =
+            match (kind, parameter, seconds) {
+                ("sine", Some(frequency), seconds) => {
+                    let frequency = frequency
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|x| x.is_finite() && *x >= 0.0)
+                        .ok_or_else(|| {
+                            format!("{frequency:?} isn't a frequency in Hz")
+                        })?;
+                    (Waveform::Sine(frequency), seconds)
+                }
+                ("noise", Some("white"), seconds) => {
+                    (Waveform::WhiteNoise, seconds)
+                }
+                ("noise", Some(color), _) => {
+                    return Err(format!("unknown kind of noise {color:?}"))
+                }
+                ("silence", Some(seconds), None) => {
+                    (Waveform::Silence, Some(seconds))
+                }
+                _ => return Err(
+                    "expected something like \"sine:440\", \"noise:white\", \
+                     or \"silence:2.0\""
+                        .to_string(),
+                ),
+            };
+        let seconds = match seconds {
+            Some(seconds) => parse_seconds(seconds)?,
+            None => PosFloat::new_clamped(DEFAULT_SECONDS),
+        };
+        Ok(SyntheticReader {
+            waveform,
+            sample_rate: self.sample_rate,
+            len: (seconds * self.sample_rate).round() as u64,
+            position: 0,
+        })
+    }
+}
+
+impl Default for SyntheticSoundDelegate {
+    /// A `SyntheticSoundDelegate` at 48000 Hz.
+    fn default() -> SyntheticSoundDelegate {
+        SyntheticSoundDelegate::new(PosFloat::new_clamped(48000.0))
+    }
+}
+
+impl SoundDelegate for SyntheticSoundDelegate {
+    fn open_file(&self, name: &str) -> Option<FormattedSoundStream> {
+        match self.parse_name(name) {
+            Ok(reader) => Some(FormattedSoundStream {
+                sample_rate: self.sample_rate,
+                speaker_layout: SpeakerLayout::Mono,
+                reader: FormattedSoundReader::F32(Box::new(reader)),
+            }),
+            Err(x) => {
+                self.warning(&format!("{:?}: {}", name, x));
+                None
+            }
+        }
+    }
+}
+
+/// Makes one of a `SyntheticSoundDelegate`'s sounds. Every sample is worked
+/// out from its position alone, so seeking and cloning are free.
+#[derive(Debug, Clone)]
+struct SyntheticReader {
+    waveform: Waveform,
+    sample_rate: PosFloat,
+    /// How long the sound is, in sample frames.
+    len: u64,
+    /// Where we are, in sample frames.
+    position: u64,
+}
+
+impl SyntheticReader {
+    fn sample_at(&self, position: u64) -> f32 {
+        match self.waveform {
+            Waveform::Sine(frequency) => {
+                // (in f64, so that long sounds don't lose their phase)
+                let phase = (position as f64 * frequency as f64
+                    / *self.sample_rate as f64)
+                    .fract();
+                (phase * std::f64::consts::TAU).sin() as f32 * AMPLITUDE
+            }
+            Waveform::WhiteNoise => {
+                // SplitMix64, as a hash of the position
+                let mut x = position.wrapping_add(0x9E3779B97F4A7C15);
+                x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+                x ^= x >> 31;
+                // top 24 bits, to -1.0..1.0
+                ((x >> 40) as f32 / (1 << 23) as f32 - 1.0) * AMPLITUDE
+            }
+            Waveform::Silence => 0.0,
+        }
+    }
+}
+
+impl SoundReader<f32> for SyntheticReader {
+    fn read(&mut self, buf: &mut [MaybeUninit<f32>]) -> usize {
+        let count = (buf.len() as u64).min(self.len - self.position) as usize;
+        for (n, dst) in buf[..count].iter_mut().enumerate() {
+            dst.write(self.sample_at(self.position + n as u64));
+        }
+        self.position += count as u64;
+        count
+    }
+    fn seek(&mut self, pos: u64) -> Option<u64> {
+        self.position = pos.min(self.len);
+        Some(self.position)
+    }
+    fn skip_coarse(
+        &mut self,
+        count: u64,
+        _buf: &mut [MaybeUninit<f32>],
+    ) -> u64 {
+        let count = count.min(self.len - self.position);
+        self.position += count;
+        count
+    }
+    fn can_be_cloned(&self) -> bool {
+        true
+    }
+    fn attempt_clone(
+        &self,
+        sample_rate: PosFloat,
+        speaker_layout: SpeakerLayout,
+    ) -> FormattedSoundStream {
+        FormattedSoundStream {
+            sample_rate,
+            speaker_layout,
+            reader: FormattedSoundReader::F32(Box::new(self.clone())),
+        }
+    }
+    fn estimate_len(&mut self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn synthetic_sounds_stream_and_buffer_alike() {
+        let delegate =
+            SyntheticSoundDelegate::new(PosFloat::new_clamped(1000.0));
+        for bad in ["sine", "sine:loud", "noise:pink", "silence", "wibble:1"] {
+            assert!(delegate.parse_name(bad).is_err(), "{bad:?}");
+        }
+        let mut reader = delegate.parse_name("noise:white:0.1").unwrap();
+        assert_eq!(reader.estimate_len(), Some(100));
+        let mut first = [MaybeUninit::uninit(); 150];
+        assert_eq!(reader.read(&mut first), 100);
+        assert_eq!(reader.seek(40), Some(40));
+        let mut again = [MaybeUninit::uninit(); 10];
+        assert_eq!(reader.read(&mut again), 10);
+        for (a, b) in first[40..50].iter().zip(again.iter()) {
+            assert_eq!(unsafe { a.assume_init() }, unsafe { b.assume_init() });
+        }
+        // the same soundtrack, once streamed and once buffered, comes out the
+        // same
+        let mut outputs = vec![];
+        for stream in ["", "  stream\n"] {
+            let mut engine = Engine::new_with_runtime(
+                Arc::new(delegate),
+                SpeakerLayout::Mono,
+                PosFloat::new_clamped(1000.0),
+                Arc::new(ForegroundTaskRuntime),
+                None,
+            );
+            engine.replace_soundtrack(
+                Soundtrack::from_source(&format!(
+                    "sound tone\n  file sine:250:0.5\n{stream}flow test\n  \
+                     play sound tone\n"
+                ))
+                .unwrap(),
+            );
+            engine.start_flow(
+                "test".into(),
+                PosFloat::ONE,
+                PosFloat::ZERO,
+                FadeType::Linear,
+            );
+            let mut out = vec![0.0; 600];
+            engine.turn_handle(&mut out);
+            outputs.push(out);
+        }
+        assert!((outputs[0][1] - AMPLITUDE).abs() < 0.001);
+        assert_eq!(outputs[0][500..], [0.0; 100]);
+        assert_eq!(outputs[0], outputs[1]);
+    }
+}